@@ -0,0 +1,191 @@
+//! Periodic persistence for the API server
+//!
+//! The server otherwise only writes its state on a clean shutdown, so a
+//! crash would lose everything since startup. The autosave task writes the
+//! blockchain, mempool and managers every interval, bounding the loss to
+//! one interval's worth of data.
+
+use crate::api::handlers::ApiState;
+use crate::core::Blockchain;
+use crate::core::Transaction;
+use crate::mining::Mempool;
+use crate::storage::StorageError;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// Default time between automatic saves
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// File (inside the data directory) holding pending transactions
+pub const MEMPOOL_FILE: &str = "mempool.json";
+
+/// Serializes [`save_state`] calls: the autosave task and the shutdown
+/// handler write the same `.tmp` files and must not interleave
+static SAVE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Write `value` as JSON via a temporary file so a crash mid-write never
+/// leaves a truncated file behind
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), StorageError> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Persist the blockchain, mempool, contracts, multisig wallets and tokens.
+/// Concurrent calls run one at a time.
+pub async fn save_state(state: &ApiState, data_dir: &Path) -> Result<(), StorageError> {
+    let _guard = SAVE_LOCK.lock().await;
+    write_json(
+        &data_dir.join("contracts.json"),
+        &*state.contract_manager.read().await,
+    )?;
+    write_json(
+        &data_dir.join("multisig.json"),
+        &*state.multisig_manager.read().await,
+    )?;
+    write_json(
+        &data_dir.join("tokens.json"),
+        &*state.token_manager.read().await,
+    )?;
+
+    let pending = {
+        let mempool = state.mempool.read().await;
+        mempool.get_transactions(mempool.len())
+    };
+    write_json(&data_dir.join(MEMPOOL_FILE), &pending)?;
+
+    let blockchain = state.blockchain.read().await;
+    state.storage.save(&blockchain)
+}
+
+/// Re-add transactions saved by [`save_state`], returning how many were
+/// accepted. Ones that no longer apply (already mined, double spends) are
+/// dropped.
+pub fn restore_mempool(
+    mempool: &mut Mempool,
+    blockchain: &Blockchain,
+    data_dir: &Path,
+) -> Result<usize, StorageError> {
+    let path = data_dir.join(MEMPOOL_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let pending: Vec<Transaction> = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    let mut restored = 0;
+    for tx in pending {
+        let accepted = if tx.token_data.is_some() || tx.contract_data.is_some() {
+            mempool.add_token_transaction(tx, blockchain).is_ok()
+        } else {
+            mempool.add_transaction(tx, blockchain).is_ok()
+        };
+        if accepted {
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+/// Spawn a task that calls [`save_state`] every `interval`
+pub fn spawn_autosave(state: ApiState, data_dir: PathBuf, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately; nothing has changed yet
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            match save_state(&state, &data_dir).await {
+                Ok(()) => log::debug!("Autosaved state to {:?}", data_dir),
+                Err(e) => log::error!("Autosave failed: {}", e),
+            }
+        }
+    })
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::WsBroadcaster;
+    use crate::contract::ContractManager;
+    use crate::core::EventBus;
+    use crate::multisig::MultisigManager;
+    use crate::storage::{Storage, StorageConfig};
+    use crate::token::TokenManager;
+    use crate::wallet::WalletManager;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_autosave_persists_mined_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("data");
+        let storage = Storage::new(StorageConfig {
+            data_dir: data_dir.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let state = ApiState {
+            blockchain: Arc::new(RwLock::new(Blockchain::with_difficulty(1))),
+            mempool: Arc::new(RwLock::new(Mempool::new())),
+            storage: Arc::new(storage),
+            wallet_manager: Arc::new(RwLock::new(
+                WalletManager::new(&dir.path().join("wallets")).unwrap(),
+            )),
+            contract_manager: Arc::new(RwLock::new(ContractManager::new())),
+            ws_broadcaster: Arc::new(WsBroadcaster::new()),
+            events: Arc::new(EventBus::new()),
+            multisig_manager: Arc::new(RwLock::new(MultisigManager::new())),
+            token_manager: Arc::new(RwLock::new(TokenManager::new())),
+            peer_manager: None,
+            dev_mode: false,
+        };
+
+        let task = spawn_autosave(state.clone(), data_dir.clone(), Duration::from_millis(50));
+        state
+            .blockchain
+            .write()
+            .await
+            .mine_block(vec![], "miner")
+            .unwrap();
+
+        let mut saved_height = 0;
+        for _ in 0..40 {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+            if let Ok(chain) = state.storage.load() {
+                saved_height = chain.height();
+                if saved_height == 1 {
+                    break;
+                }
+            }
+        }
+        task.abort();
+
+        assert_eq!(saved_height, 1);
+        assert!(data_dir.join(MEMPOOL_FILE).exists());
+        assert!(data_dir.join("tokens.json").exists());
+
+        // Overlapping saves (autosave racing the shutdown save) must not
+        // trip over each other's temporary files
+        let saves: Vec<_> = (0..8)
+            .map(|_| {
+                let state = state.clone();
+                let data_dir = data_dir.clone();
+                tokio::spawn(async move { save_state(&state, &data_dir).await })
+            })
+            .collect();
+        for save in saves {
+            save.await.unwrap().unwrap();
+        }
+        assert!(!data_dir.join("blockchain.tmp").exists());
+    }
+}
@@ -1,14 +1,19 @@
 //! REST API handlers for blockchain operations
 
 use crate::api::websocket::WsBroadcaster;
-use crate::contract::{Compiler, ContractManager};
+use crate::contract::{
+    BalanceLookup, CallOptions, Compiler, ContractManager, GasDestination, TraceStep,
+    GAS_BURN_ADDRESS,
+};
 use crate::core::{
-    Blockchain, ContractOperationType, TokenOperationType, Transaction, TransactionInput,
-    SEQUENCE_FINAL,
+    BatchMerkleProof, Block, Blockchain, ChainEvent, ContractOperationType, EventBus, MerkleProof,
+    ReorgEvent, TokenOperationType, Transaction, TransactionInput, DIFFICULTY_ADJUSTMENT_INTERVAL,
+    MAX_BLOCK_WEIGHT, MAX_REORG_EVENTS, SEQUENCE_FINAL, UTXO_GROWTH_WINDOW,
 };
-use crate::mining::{Mempool, Miner};
+use crate::mining::{Mempool, Miner, BLOCK_WEIGHT_RESERVED};
 use crate::multisig::{MultisigConfig, MultisigManager, MultisigSignature};
-use crate::network::peer::PeerManager;
+use crate::network::peer::{PeerManager, MAX_PEERS};
+use crate::network::{Message, LOCAL_SERVICES, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
 use crate::storage::Storage;
 use crate::token::TokenManager;
 use crate::wallet::WalletManager;
@@ -30,10 +35,16 @@ pub struct ApiState {
     pub wallet_manager: Arc<RwLock<WalletManager>>,
     pub contract_manager: Arc<RwLock<ContractManager>>,
     pub ws_broadcaster: Arc<WsBroadcaster>,
+    /// Domain events (blocks, transactions, reorgs) for subscribers such as
+    /// the websocket broadcaster
+    pub events: Arc<EventBus>,
     pub multisig_manager: Arc<RwLock<MultisigManager>>,
     pub token_manager: Arc<RwLock<TokenManager>>,
     /// Optional P2P peer manager for broadcasting blocks/transactions
     pub peer_manager: Option<Arc<PeerManager>>,
+    /// Enables endpoints that rewrite chain state for testing, such as
+    /// reorg simulation. Never set on a node holding real funds.
+    pub dev_mode: bool,
 }
 
 // ============================================================================
@@ -47,7 +58,16 @@ pub struct ChainInfo {
     pub total_blocks: usize,
     pub total_transactions: usize,
     pub total_coins: u64,
+    /// `total_coins` formatted with the chain's coin decimals
+    pub total_coins_display: String,
+    pub coin_decimals: u8,
     pub latest_hash: String,
+    /// Height of the best connected peer (our own height without peers)
+    pub best_peer_height: u64,
+    /// Fraction (0.0 to 1.0) of the best peer's chain verified so far
+    pub verification_progress: f64,
+    /// Still catching up with peers or holding a stale tip
+    pub initial_block_download: bool,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -60,6 +80,27 @@ pub struct BlockInfo {
     pub difficulty: u32,
     pub nonce: u64,
     pub transactions: usize,
+    pub size: usize,   // Serialized size in bytes (header + transactions)
+    pub weight: usize, // Block weight (size * 4, no witness discount yet)
+    pub coinbase_message: Option<String>,
+}
+
+impl From<&Block> for BlockInfo {
+    fn from(block: &Block) -> Self {
+        Self {
+            index: block.index,
+            hash: block.hash.clone(),
+            previous_hash: block.header.previous_hash.clone(),
+            merkle_root: block.header.merkle_root.clone(),
+            timestamp: block.header.timestamp.to_rfc3339(),
+            difficulty: block.header.difficulty,
+            nonce: block.header.nonce,
+            transactions: block.transactions.len(),
+            size: block.size(),
+            weight: block.weight(),
+            coinbase_message: block.coinbase_tx().and_then(|tx| tx.coinbase_message()),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -70,12 +111,27 @@ pub struct MineResponse {
     pub attempts: u64,
 }
 
+#[derive(Serialize)]
+pub struct MineBatchResponse {
+    pub blocks: Vec<MineResponse>,
+    pub total_reward: u64,
+}
+
 #[derive(Serialize)]
 pub struct BalanceResponse {
     pub address: String,
     pub balance: u64,
+    /// `balance` formatted with the chain's coin decimals
+    pub balance_display: String,
     pub spendable_balance: u64,
+    /// Spendable outputs with at least `min_confirmations` confirmations,
+    /// excluding those already spent by a mempool transaction
+    pub confirmed_balance: u64,
+    /// Unspent outputs below `min_confirmations` plus pending mempool outputs
+    pub unconfirmed_balance: u64,
+    /// Coinbase outputs that have not reached maturity yet
     pub immature_balance: u64,
+    pub min_confirmations: u64,
     pub utxo_count: usize,
 }
 
@@ -86,6 +142,9 @@ pub struct TransactionResponse {
     pub inputs: usize,
     pub outputs: usize,
     pub total_output: u64,
+    pub size: usize,  // Estimated serialized size in bytes
+    pub vsize: usize, // Virtual size used for fee rates
+    pub coinbase_message: Option<String>,
 }
 
 impl From<&Transaction> for TransactionResponse {
@@ -96,10 +155,29 @@ impl From<&Transaction> for TransactionResponse {
             inputs: tx.inputs.len(),
             outputs: tx.outputs.len(),
             total_output: tx.total_output(),
+            size: tx.estimated_size(),
+            vsize: tx.virtual_size(),
+            coinbase_message: tx.coinbase_message(),
         }
     }
 }
 
+/// A transaction looked up by ID, with where it was confirmed
+#[derive(Serialize)]
+pub struct TransactionDetailResponse {
+    #[serde(flatten)]
+    pub transaction: TransactionResponse,
+    pub block_height: Option<u64>,
+    pub block_hash: Option<String>,
+    pub confirmations: u64,
+    /// "confirmed", "pending" or "replaced"
+    pub status: String,
+    /// Transaction that replaced this one (RBF), if any
+    pub replaced_by: Option<String>,
+    /// Transactions this one replaced while in the mempool
+    pub replaces: Vec<String>,
+}
+
 #[derive(Serialize)]
 pub struct WalletResponse {
     pub address: String,
@@ -120,7 +198,7 @@ pub struct ValidationResponse {
     pub message: String,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct ApiError {
     pub error: String,
 }
@@ -134,6 +212,23 @@ pub struct MineRequest {
     pub miner_address: String,
 }
 
+/// Maximum number of blocks mined by one batch request
+pub const MAX_BATCH_MINE_COUNT: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct MineBatchRequest {
+    pub count: u32,
+    pub miner_address: String,
+}
+
+/// Default confirmations before a balance counts as confirmed
+pub const DEFAULT_MIN_CONFIRMATIONS: u64 = 6;
+
+#[derive(Deserialize)]
+pub struct BalanceQuery {
+    pub min_confirmations: Option<u64>,
+}
+
 #[derive(Deserialize)]
 pub struct CreateWalletRequest {
     pub label: Option<String>,
@@ -142,6 +237,7 @@ pub struct CreateWalletRequest {
 #[derive(Deserialize)]
 pub struct DeployContractRequest {
     pub source: String,
+    pub gas_limit: Option<u64>, // Gas limit for the init run (default: 100000)
 }
 
 #[derive(Deserialize)]
@@ -150,6 +246,12 @@ pub struct CallContractRequest {
     pub gas_limit: Option<u64>,
     pub gas_price: Option<u64>,         // Price per gas unit (default: 1)
     pub caller_address: Option<String>, // Who pays for gas (required for gas payment)
+    pub value: Option<u64>,             // Coins sent to the contract (requires caller_address)
+}
+
+#[derive(Deserialize)]
+pub struct CallContractQuery {
+    pub trace: Option<bool>, // Record an instruction trace (default: false)
 }
 
 #[derive(Serialize)]
@@ -164,6 +266,7 @@ pub struct ContractInfo {
 pub struct DeployResponse {
     pub address: String,
     pub code_size: usize,
+    pub init_gas_used: u64, // Gas charged to the deployer for the init run
 }
 
 #[derive(Serialize)]
@@ -171,8 +274,11 @@ pub struct CallResponse {
     pub success: bool,
     pub return_value: Option<u64>,
     pub gas_used: u64,
-    pub gas_cost: u64,               // Total cost in coins (gas_used * gas_price)
+    pub gas_refunded: u64,           // Capped refund for cleared storage slots
+    pub gas_cost: u64,               // Total cost in coins ((gas_used - gas_refunded) * gas_price)
     pub caller_balance: Option<u64>, // Remaining balance after gas payment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<TraceStep>>, // Present only when ?trace=true
 }
 
 // ============================================================================
@@ -181,7 +287,12 @@ pub struct CallResponse {
 
 /// GET /api/chain - Get blockchain info
 pub async fn get_chain_info(State(state): State<ApiState>) -> Json<ChainInfo> {
+    let best_peer_height = match state.peer_manager {
+        Some(ref peer_manager) => peer_manager.get_best_peer().await.map(|(_, h)| h),
+        None => None,
+    };
     let chain = state.blockchain.read().await;
+    let best_peer_height = best_peer_height.unwrap_or(0).max(chain.height());
 
     let total_tx: usize = chain.blocks.iter().map(|b| b.transactions.len()).sum();
     let total_coins: u64 = chain
@@ -198,7 +309,12 @@ pub async fn get_chain_info(State(state): State<ApiState>) -> Json<ChainInfo> {
         total_blocks: chain.blocks.len(),
         total_transactions: total_tx,
         total_coins,
+        total_coins_display: chain.params.format_amount(total_coins),
+        coin_decimals: chain.params.coin_decimals,
         latest_hash: chain.latest_block().hash.clone(),
+        best_peer_height,
+        verification_progress: chain.verification_progress(best_peer_height),
+        initial_block_download: chain.is_initial_block_download(best_peer_height),
     })
 }
 
@@ -210,47 +326,158 @@ pub async fn get_blocks(State(state): State<ApiState>) -> Json<Vec<BlockInfo>> {
         .iter()
         .rev()
         .take(10)
-        .map(|block| BlockInfo {
-            index: block.index,
-            hash: block.hash.clone(),
-            previous_hash: block.header.previous_hash.clone(),
-            merkle_root: block.header.merkle_root.clone(),
-            timestamp: block.header.timestamp.to_rfc3339(),
-            difficulty: block.header.difficulty,
-            nonce: block.header.nonce,
-            transactions: block.transactions.len(),
-        })
+        .map(BlockInfo::from)
         .collect();
 
     Json(blocks)
 }
 
+/// Block range query parameters (inclusive heights)
+#[derive(Deserialize)]
+pub struct BlockRangeQuery {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// GET /api/chain/blocks/range - Blocks at heights `from..=to`, in order
+///
+/// The range is clamped to the chain tip and to `MAX_BLOCK_RANGE` blocks.
+pub async fn get_block_range(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<BlockRangeQuery>,
+) -> Result<Json<Vec<BlockInfo>>, (StatusCode, Json<ApiError>)> {
+    if query.from > query.to {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: format!(
+                    "Invalid block range: from ({}) is greater than to ({})",
+                    query.from, query.to
+                ),
+            }),
+        ));
+    }
+
+    let chain = state.blockchain.read().await;
+    let blocks = chain.get_block_range(query.from, query.to);
+    Ok(Json(blocks.iter().map(BlockInfo::from).collect()))
+}
+
+/// Full block at `height`: 404 past the tip, 410 once its body was pruned
+fn block_with_body(
+    chain: &Blockchain,
+    height: u64,
+) -> Result<&Block, (StatusCode, Json<ApiError>)> {
+    match chain.get_block(height) {
+        Some(block) if chain.has_block_data(height) => Ok(block),
+        Some(_) => Err((
+            StatusCode::GONE,
+            Json(ApiError {
+                error: format!("Block at height {} has been pruned", height),
+            }),
+        )),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: format!("Block at height {} not found", height),
+            }),
+        )),
+    }
+}
+
 /// GET /api/chain/blocks/:height - Get block by height
 pub async fn get_block_by_height(
     State(state): State<ApiState>,
     Path(height): Path<u64>,
 ) -> Result<Json<BlockInfo>, (StatusCode, Json<ApiError>)> {
     let chain = state.blockchain.read().await;
+    let block = block_with_body(&chain, height)?;
+    Ok(Json(BlockInfo::from(block)))
+}
 
-    if let Some(block) = chain.get_block(height) {
-        Ok(Json(BlockInfo {
-            index: block.index,
-            hash: block.hash.clone(),
-            previous_hash: block.header.previous_hash.clone(),
-            merkle_root: block.header.merkle_root.clone(),
-            timestamp: block.header.timestamp.to_rfc3339(),
-            difficulty: block.header.difficulty,
-            nonce: block.header.nonce,
-            transactions: block.transactions.len(),
-        }))
-    } else {
-        Err((
-            StatusCode::NOT_FOUND,
+/// GET /api/chain/blocks/:height/undo - Outputs spent and transactions added by a block
+pub async fn get_block_undo(
+    State(state): State<ApiState>,
+    Path(height): Path<u64>,
+) -> Result<Json<UndoDataResponse>, (StatusCode, Json<ApiError>)> {
+    let chain = state.blockchain.read().await;
+
+    let undo = chain
+        .get_block(height)
+        .and_then(|block| chain.get_undo_data(&block.hash))
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: format!("No undo data for block at height {}", height),
+                }),
+            )
+        })?;
+
+    Ok(Json(UndoDataResponse {
+        height,
+        block_hash: undo.block_hash.clone(),
+        spent_outputs: undo
+            .spent_outputs
+            .iter()
+            .map(|(outpoint, output)| SpentOutputResponse {
+                outpoint: outpoint.clone(),
+                amount: output.amount,
+                recipient: output.recipient.clone(),
+            })
+            .collect(),
+        added_tx_ids: undo.added_tx_ids.clone(),
+    }))
+}
+
+/// GET /api/chain/blocks/:height/coinbase - Coinbase reward and its maturity
+pub async fn get_block_coinbase(
+    State(state): State<ApiState>,
+    Path(height): Path<u64>,
+) -> Result<Json<CoinbaseMaturityResponse>, (StatusCode, Json<ApiError>)> {
+    let chain = state.blockchain.read().await;
+
+    let coinbase = block_with_body(&chain, height)?
+        .transactions
+        .iter()
+        .find(|tx| tx.is_coinbase)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: format!("No coinbase for block at height {}", height),
+                }),
+            )
+        })?;
+
+    Ok(Json(CoinbaseMaturityResponse {
+        height,
+        txid: coinbase.id.clone(),
+        reward: coinbase.total_output(),
+        is_mature: chain.is_coinbase_mature(&coinbase.id),
+        blocks_until_mature: chain.coinbase_blocks_until_mature(&coinbase.id),
+    }))
+}
+
+/// POST /api/chain/blocks/:height/proofs - One Merkle proof for several
+/// transactions in a block
+pub async fn get_block_proofs(
+    State(state): State<ApiState>,
+    Path(height): Path<u64>,
+    Json(req): Json<BatchProofRequest>,
+) -> Result<Json<BatchMerkleProof>, (StatusCode, Json<ApiError>)> {
+    let chain = state.blockchain.read().await;
+    let block = block_with_body(&chain, height)?;
+
+    let proof = MerkleProof::build_batch(block, &req.txids).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
             Json(ApiError {
-                error: format!("Block at height {} not found", height),
+                error: format!("Not all transactions are in block {}", height),
             }),
-        ))
-    }
+        )
+    })?;
+    Ok(Json(proof))
 }
 
 /// GET /api/chain/validate - Validate blockchain
@@ -270,13 +497,138 @@ pub async fn validate_chain(State(state): State<ApiState>) -> Json<ValidationRes
     })
 }
 
+/// Hashrate estimator query parameters
+#[derive(Deserialize)]
+pub struct HashrateQuery {
+    /// Number of recent blocks to average over
+    pub window: Option<u64>,
+}
+
+/// Hashrate estimate response
+#[derive(Serialize)]
+pub struct HashrateResponse {
+    pub height: u64,
+    pub difficulty: u32,
+    pub window_blocks: u64,
+    pub hashes_per_second: f64,
+}
+
+/// GET /api/chain/hashrate - Estimate network hashrate
+pub async fn get_hashrate(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<HashrateQuery>,
+) -> Json<HashrateResponse> {
+    let chain = state.blockchain.read().await;
+    let window = query
+        .window
+        .unwrap_or(DIFFICULTY_ADJUSTMENT_INTERVAL)
+        .min(chain.height());
+
+    Json(HashrateResponse {
+        height: chain.height(),
+        difficulty: chain.difficulty,
+        window_blocks: window,
+        hashes_per_second: chain.estimated_hashrate(window),
+    })
+}
+
+/// An output spent by a block, restored if the block is disconnected
+#[derive(Serialize)]
+pub struct SpentOutputResponse {
+    /// Spent outpoint as `tx_id:index`
+    pub outpoint: String,
+    pub amount: u64,
+    pub recipient: String,
+}
+
+/// UTXO changes made by a block
+#[derive(Serialize)]
+pub struct UndoDataResponse {
+    pub height: u64,
+    pub block_hash: String,
+    pub spent_outputs: Vec<SpentOutputResponse>,
+    pub added_tx_ids: Vec<String>,
+}
+
+/// Whether a block's coinbase reward can be spent yet
+#[derive(Serialize)]
+pub struct CoinbaseMaturityResponse {
+    pub height: u64,
+    pub txid: String,
+    pub reward: u64,
+    pub is_mature: bool,
+    pub blocks_until_mature: u64,
+}
+
+/// Transactions to prove inclusion of in one batched proof
+#[derive(Deserialize)]
+pub struct BatchProofRequest {
+    pub txids: Vec<String>,
+}
+
+/// Reorg log query parameters
+#[derive(Deserialize)]
+pub struct ReorgQuery {
+    /// Number of most recent reorgs to return (default 10)
+    pub limit: Option<usize>,
+}
+
+/// A chain reorganization, as recorded by the node
+#[derive(Serialize)]
+pub struct ReorgEventResponse {
+    pub time: u64,
+    pub old_tip_hash: String,
+    pub old_height: u64,
+    pub old_work: String,
+    pub new_tip_hash: String,
+    pub new_height: u64,
+    pub new_work: String,
+    pub disconnected: u64,
+    pub connected: u64,
+    pub returned_txs: Vec<String>,
+}
+
+impl From<&ReorgEvent> for ReorgEventResponse {
+    fn from(event: &ReorgEvent) -> Self {
+        Self {
+            time: event.time,
+            old_tip_hash: event.old_tip_hash.clone(),
+            old_height: event.old_height,
+            old_work: event.old_work.to_string(),
+            new_tip_hash: event.new_tip_hash.clone(),
+            new_height: event.new_height,
+            new_work: event.new_work.to_string(),
+            disconnected: event.disconnected,
+            connected: event.connected,
+            returned_txs: event.returned_txs.clone(),
+        }
+    }
+}
+
+/// GET /api/chain/reorgs - Most recent chain reorganizations, newest first
+pub async fn get_reorgs(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<ReorgQuery>,
+) -> Json<Vec<ReorgEventResponse>> {
+    let chain = state.blockchain.read().await;
+    let limit = query.limit.unwrap_or(10).min(MAX_REORG_EVENTS);
+
+    Json(
+        chain
+            .recent_reorgs(limit)
+            .into_iter()
+            .map(ReorgEventResponse::from)
+            .collect(),
+    )
+}
+
 /// POST /api/mine - Mine a new block
 pub async fn mine_block(
     State(state): State<ApiState>,
     Json(req): Json<MineRequest>,
 ) -> Result<Json<MineResponse>, (StatusCode, Json<ApiError>)> {
     // Step 1: Get transactions from mempool and snapshot chain state (quick reads)
-    let (transactions, current_height, previous_hash, difficulty) = {
+    let (transactions, current_height, previous_hash, difficulty, reward, fees, pow) = {
         let chain = state.blockchain.read().await;
         let mempool = state.mempool.read().await;
 
@@ -284,8 +636,17 @@ pub async fn mine_block(
         let height = chain.height();
         let prev_hash = chain.latest_block().hash.clone();
         let diff = chain.difficulty;
+        let fees = chain.transaction_fees(&transactions);
 
-        (transactions, height, prev_hash, diff)
+        (
+            transactions,
+            height,
+            prev_hash,
+            diff,
+            chain.next_block_reward(),
+            fees,
+            chain.params.pow_algorithm,
+        )
     };
     // Locks are now released!
 
@@ -295,7 +656,13 @@ pub async fn mine_block(
 
     // Step 2: Run CPU-intensive mining in a blocking task (NO LOCKS HELD)
     let mining_result = tokio::task::spawn_blocking(move || {
-        let miner = Miner::new(&miner_address);
+        // Fees (including contract gas left for the miner) are claimed in
+        // their own coinbase output
+        let miner = Miner::new(&miner_address)
+            .with_reward(reward)
+            .with_fee_split(&miner_address)
+            .with_fees(fees)
+            .with_pow(pow);
         miner.mine_block_detached(current_height, previous_hash, difficulty, transactions)
     })
     .await
@@ -324,34 +691,34 @@ pub async fn mine_block(
             ));
         }
 
-        if let Err(e) = chain.add_block(block.clone()) {
-            return Err((
+        let returned_txs = chain.add_block(block.clone()).map_err(|e| {
+            (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ApiError {
                     error: format!("Failed to add block: {}", e),
                 }),
-            ));
-        }
+            )
+        })?;
+        // Expire stale entries, drop what the block confirmed or
+        // double-spent, then return transactions taken out by reorgs through
+        // connected orphans
+        let mut mempool = state.mempool.write().await;
+        mempool.expire();
+        mempool.remove_conflicting(&chain);
+        mempool.add_reorged_transactions(returned_txs, &chain);
     }
     // Write lock released!
 
     // Create block info for response and WebSocket
-    let block_info = BlockInfo {
-        index: block.index,
-        hash: block.hash.clone(),
-        previous_hash: block.header.previous_hash.clone(),
-        merkle_root: block.header.merkle_root.clone(),
-        timestamp: block.header.timestamp.to_rfc3339(),
-        difficulty: block.header.difficulty,
-        nonce: block.header.nonce,
-        transactions: block.transactions.len(),
-    };
+    let block_info = BlockInfo::from(&block);
     let reward = block.mining_reward();
 
     // Step 4: Cleanup and notify (quick operations)
     {
+        let chain = state.blockchain.read().await;
         let mut mempool = state.mempool.write().await;
         mempool.remove_transactions(&tx_ids);
+        mempool.refresh_priorities(&chain);
     }
 
     // Save blockchain
@@ -362,21 +729,16 @@ pub async fn mine_block(
         }
     }
 
-    // Broadcast BlockMined event to WebSocket clients
-    state
-        .ws_broadcaster
-        .broadcast(crate::api::websocket::WsEvent::BlockMined {
-            block: block_info.clone(),
-            reward,
-        });
+    state.events.publish(ChainEvent::BlockConnected {
+        block: block.clone(),
+        reward,
+    });
 
     // Broadcast new block to P2P network (if connected)
     if let Some(ref peer_manager) = state.peer_manager {
-        use crate::network::message::Message;
-        log::info!("Broadcasting mined block {} to P2P network", block.index);
-        peer_manager
-            .broadcast(Message::NewBlock(block.clone()))
-            .await;
+        if peer_manager.announce_mined_block(block.clone()).await {
+            log::info!("Broadcast mined block {} to P2P network", block.index);
+        }
     }
 
     Ok(Json(MineResponse {
@@ -387,13 +749,160 @@ pub async fn mine_block(
     }))
 }
 
-/// GET /api/mempool - Get pending transactions
-pub async fn get_mempool(State(state): State<ApiState>) -> Json<MempoolResponse> {
+/// Request to simulate a reorg
+#[derive(Deserialize)]
+pub struct ReorgSimulateRequest {
+    /// Number of blocks to replace
+    pub depth: u64,
+    /// Recipient of the competing block's coinbase
+    pub miner_address: Option<String>,
+}
+
+/// Outcome of a simulated reorg
+#[derive(Serialize)]
+pub struct ReorgSimulateResponse {
+    pub old_tip_hash: String,
+    pub new_tip_hash: String,
+    pub new_height: u64,
+    pub disconnected: u64,
+    pub connected: u64,
+    pub returned_txs: Vec<String>,
+}
+
+/// POST /api/chain/reorg-simulate - Replace the last `depth` blocks with a
+/// heavier competing block (dev mode only)
+pub async fn simulate_reorg(
+    State(state): State<ApiState>,
+    Json(req): Json<ReorgSimulateRequest>,
+) -> Result<Json<ReorgSimulateResponse>, (StatusCode, Json<ApiError>)> {
+    let error = |status: StatusCode, error: String| (status, Json(ApiError { error }));
+    if !state.dev_mode {
+        return Err(error(
+            StatusCode::FORBIDDEN,
+            "Reorg simulation is only available in dev mode (--dev-mode)".to_string(),
+        ));
+    }
+
+    // Mine on a snapshot so the chain stays readable meanwhile
+    let snapshot = state.blockchain.read().await.clone();
+    let old_tip = snapshot.latest_block().hash.clone();
+    let miner = req
+        .miner_address
+        .unwrap_or_else(|| "reorg-simulate".to_string());
+    let fork =
+        tokio::task::spawn_blocking(move || snapshot.mine_competing_block(req.depth, &miner))
+            .await
+            .map_err(|e| {
+                error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Mining task failed: {}", e),
+                )
+            })?
+            .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let event = {
+        let mut chain = state.blockchain.write().await;
+        if chain.latest_block().hash != old_tip {
+            return Err(error(
+                StatusCode::CONFLICT,
+                "Chain changed during mining, please retry".to_string(),
+            ));
+        }
+        let result = chain
+            .apply_block(fork)
+            .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let mut mempool = state.mempool.write().await;
+        mempool.expire();
+        mempool.remove_conflicting(&chain);
+        mempool.add_reorged_transactions(result.returned_txs, &chain);
+        drop(mempool);
+        let event = chain
+            .recent_reorgs(1)
+            .first()
+            .filter(|event| event.old_tip_hash == old_tip)
+            .map(|event| (*event).clone())
+            .ok_or_else(|| {
+                error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Competing block did not cause a reorg".to_string(),
+                )
+            })?;
+        if let Err(e) = state.storage.save(&chain) {
+            log::error!("Failed to save blockchain: {}", e);
+        }
+        event
+    };
+
+    state.events.publish(ChainEvent::Reorg(event.clone()));
+
+    Ok(Json(ReorgSimulateResponse {
+        old_tip_hash: event.old_tip_hash,
+        new_tip_hash: event.new_tip_hash,
+        new_height: event.new_height,
+        disconnected: event.disconnected,
+        connected: event.connected,
+        returned_txs: event.returned_txs,
+    }))
+}
+
+/// POST /api/mine/batch - Mine several blocks in a row
+///
+/// Each block drains the mempool the same way `POST /api/mine` does.
+pub async fn mine_batch(
+    State(state): State<ApiState>,
+    Json(req): Json<MineBatchRequest>,
+) -> Result<Json<MineBatchResponse>, (StatusCode, Json<ApiError>)> {
+    if req.count == 0 || req.count > MAX_BATCH_MINE_COUNT {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: format!("count must be between 1 and {}", MAX_BATCH_MINE_COUNT),
+            }),
+        ));
+    }
+
+    let mut blocks = Vec::with_capacity(req.count as usize);
+    for _ in 0..req.count {
+        let Json(mined) = mine_block(
+            State(state.clone()),
+            Json(MineRequest {
+                miner_address: req.miner_address.clone(),
+            }),
+        )
+        .await?;
+        blocks.push(mined);
+    }
+
+    let total_reward = blocks.iter().map(|b| b.reward).sum();
+    Ok(Json(MineBatchResponse {
+        blocks,
+        total_reward,
+    }))
+}
+
+/// Mempool query parameters
+#[derive(Deserialize)]
+pub struct MempoolQuery {
+    /// Lowest fee rate to include (inclusive)
+    pub min_feerate: Option<u64>,
+    /// Highest fee rate to include (inclusive)
+    pub max_feerate: Option<u64>,
+}
+
+/// GET /api/mempool - Get pending transactions, optionally within a fee-rate band
+pub async fn get_mempool(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<MempoolQuery>,
+) -> Json<MempoolResponse> {
     let mempool = state.mempool.read().await;
     let transactions: Vec<TransactionResponse> = mempool
-        .get_transactions(100)
-        .iter()
-        .map(TransactionResponse::from)
+        .transactions_in_feerate_range(
+            query.min_feerate.unwrap_or(0),
+            query.max_feerate.unwrap_or(u64::MAX),
+        )
+        .into_iter()
+        .take(100)
+        .map(|entry| TransactionResponse::from(&entry.tx))
         .collect();
 
     Json(MempoolResponse {
@@ -402,28 +911,162 @@ pub async fn get_mempool(State(state): State<ApiState>) -> Json<MempoolResponse>
     })
 }
 
+/// A pending transaction's place in the mining queue
+#[derive(Serialize)]
+pub struct MempoolPositionResponse {
+    pub tx_id: String,
+    /// 1 = next to be mined
+    pub position: usize,
+    pub total: usize,
+    pub fee_rate: u64,
+    /// Weight of this and all higher-paying transactions
+    pub weight_ahead: usize,
+    /// Blocks until it would be mined if nothing better arrives (1 = next block)
+    pub estimated_blocks: usize,
+}
+
+/// GET /api/mempool/:id/position - Queue position and estimated block
+pub async fn get_mempool_position(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<MempoolPositionResponse>, (StatusCode, Json<ApiError>)> {
+    let mempool = state.mempool.read().await;
+    let (position, total) = mempool.fee_rank(&id).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: format!("Transaction {} is not in the mempool", id),
+            }),
+        )
+    })?;
+    let fee_rate = mempool.get_entry(&id).map(|e| e.fee_rate).unwrap_or(0);
+
+    // Same per-transaction weight and capacity used when assembling blocks
+    let weight_ahead: usize = mempool
+        .get_transactions(position)
+        .iter()
+        .map(|tx| tx.serialized_size() * 4)
+        .sum();
+    let capacity = MAX_BLOCK_WEIGHT - BLOCK_WEIGHT_RESERVED;
+
+    Ok(Json(MempoolPositionResponse {
+        tx_id: id,
+        position,
+        total,
+        fee_rate,
+        weight_ahead,
+        estimated_blocks: weight_ahead.div_ceil(capacity).max(1),
+    }))
+}
+
+/// Full detail on one pending transaction
+#[derive(Serialize)]
+pub struct MempoolEntryResponse {
+    pub tx_id: String,
+    pub fee: u64,
+    pub fee_rate: u64,
+    /// Fee rate of the transaction together with its in-pool ancestors
+    pub ancestor_fee_rate: u64,
+    /// Serialized size in bytes
+    pub size: usize,
+    pub vsize: usize,
+    /// Seconds since the transaction entered the pool
+    pub time_in_pool: u64,
+    pub ancestor_count: usize,
+    pub ancestor_size: usize,
+    pub descendant_count: usize,
+    pub descendant_size: usize,
+    pub signals_rbf: bool,
+    /// In-pool transactions this one spends from
+    pub depends: Vec<String>,
+    /// In-pool transactions spending this one
+    pub spent_by: Vec<String>,
+}
+
+/// GET /api/mempool/:id - Detailed mempool entry
+pub async fn get_mempool_entry(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<MempoolEntryResponse>, (StatusCode, Json<ApiError>)> {
+    let mempool = state.mempool.read().await;
+    let entry = mempool.get_entry(&id).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: format!("Transaction {} is not in the mempool", id),
+            }),
+        )
+    })?;
+
+    let (ancestor_count, ancestor_size) =
+        mempool.calculate_ancestors(&entry.tx).unwrap_or_default();
+    let (descendant_count, descendant_size) =
+        mempool.calculate_descendants(&id).unwrap_or_default();
+
+    Ok(Json(MempoolEntryResponse {
+        tx_id: id.clone(),
+        fee: entry.tx.fee,
+        fee_rate: entry.fee_rate,
+        ancestor_fee_rate: entry.ancestor_fee_rate,
+        size: entry.tx.serialized_size(),
+        vsize: entry.tx.virtual_size(),
+        time_in_pool: mempool.time_in_pool(&id).unwrap_or(0),
+        ancestor_count,
+        ancestor_size,
+        descendant_count,
+        descendant_size,
+        signals_rbf: entry.tx.signals_rbf(),
+        depends: mempool.depends(&id),
+        spent_by: mempool.spent_by(&id),
+    }))
+}
+
 /// GET /api/transactions/:id - Get transaction by ID
 pub async fn get_transaction(
     State(state): State<ApiState>,
     Path(id): Path<String>,
-) -> Result<Json<TransactionResponse>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<TransactionDetailResponse>, (StatusCode, Json<ApiError>)> {
     let chain = state.blockchain.read().await;
 
+    let mempool = state.mempool.read().await;
+
     // Search in blockchain
-    for block in &chain.blocks {
-        for tx in &block.transactions {
-            if tx.id == id {
-                return Ok(Json(TransactionResponse::from(tx)));
-            }
-        }
+    if let Some((tx, height, hash, _)) = chain.get_tx_with_location(&id) {
+        return Ok(Json(TransactionDetailResponse {
+            transaction: TransactionResponse::from(tx),
+            block_height: Some(height),
+            block_hash: Some(hash.to_string()),
+            confirmations: chain.confirmations(&id),
+            status: "confirmed".to_string(),
+            replaced_by: None,
+            replaces: mempool.replaces(&id).to_vec(),
+        }));
     }
 
     // Search in mempool
-    let mempool = state.mempool.read().await;
-    for tx in mempool.get_transactions(1000) {
-        if tx.id == id {
-            return Ok(Json(TransactionResponse::from(&tx)));
-        }
+    if let Some(tx) = mempool.get_transaction(&id) {
+        return Ok(Json(TransactionDetailResponse {
+            transaction: TransactionResponse::from(tx),
+            block_height: None,
+            block_hash: None,
+            confirmations: 0,
+            status: "pending".to_string(),
+            replaced_by: None,
+            replaces: mempool.replaces(&id).to_vec(),
+        }));
+    }
+
+    // Evicted by a replacement: point the wallet at its successor
+    if let Some(record) = mempool.get_replaced(&id) {
+        return Ok(Json(TransactionDetailResponse {
+            transaction: TransactionResponse::from(&record.tx),
+            block_height: None,
+            block_hash: None,
+            confirmations: 0,
+            status: "replaced".to_string(),
+            replaced_by: Some(record.replaced_by.clone()),
+            replaces: mempool.replaces(&id).to_vec(),
+        }));
     }
 
     Err((
@@ -434,6 +1077,52 @@ pub async fn get_transaction(
     ))
 }
 
+/// How far a transaction we broadcast has spread among our peers
+#[derive(Serialize)]
+pub struct TxPropagationResponse {
+    pub tx_id: String,
+    /// Connected peers that announced the transaction back to us
+    pub peers_with_tx: usize,
+    /// Connected peers that rejected it
+    pub peers_rejected: usize,
+    pub connected_peers: usize,
+    pub seconds_since_broadcast: u64,
+}
+
+/// GET /api/transactions/:id/propagation - Peers that picked up a
+/// transaction this node broadcast
+pub async fn get_tx_propagation(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<TxPropagationResponse>, (StatusCode, Json<ApiError>)> {
+    let not_tracked = |reason: &str| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: reason.to_string(),
+            }),
+        )
+    };
+    let peer_manager = state
+        .peer_manager
+        .as_ref()
+        .ok_or_else(|| not_tracked("P2P networking is not enabled"))?;
+    let propagation = peer_manager.tx_propagation(&id).await.ok_or_else(|| {
+        not_tracked(&format!(
+            "Transaction {} was not broadcast by this node",
+            id
+        ))
+    })?;
+
+    Ok(Json(TxPropagationResponse {
+        tx_id: id,
+        peers_with_tx: propagation.announced_by.len(),
+        peers_rejected: propagation.rejected_by.len(),
+        connected_peers: peer_manager.peer_count().await,
+        seconds_since_broadcast: propagation.broadcast_at.elapsed().as_secs(),
+    }))
+}
+
 /// POST /api/wallets - Create new wallet
 pub async fn create_wallet(
     State(state): State<ApiState>,
@@ -489,8 +1178,11 @@ pub async fn list_wallets(
 pub async fn get_wallet_balance(
     State(state): State<ApiState>,
     Path(address): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<BalanceQuery>,
 ) -> Json<BalanceResponse> {
     let chain = state.blockchain.read().await;
+    let mempool = state.mempool.read().await;
+    let min_confirmations = query.min_confirmations.unwrap_or(DEFAULT_MIN_CONFIRMATIONS);
 
     // Get all UTXOs (including immature coinbase)
     let utxos = chain.get_utxos_for_address(&address);
@@ -500,18 +1192,203 @@ pub async fn get_wallet_balance(
     let spendable_balance = chain.get_spendable_balance(&address);
     let immature_balance = chain.get_immature_balance(&address);
 
+    // Split spendable funds by confirmation depth
+    let (confirmed_balance, unconfirmed_balance) =
+        split_spendable_balance(&chain, &mempool, &address, min_confirmations);
+
     Json(BalanceResponse {
         address,
         balance,
+        balance_display: chain.params.format_amount(balance),
         spendable_balance,
+        confirmed_balance,
+        unconfirmed_balance,
         immature_balance,
+        min_confirmations,
         utxo_count: utxos.len(),
     })
 }
 
-/// Health check endpoint
-pub async fn health_check() -> &'static str {
-    "OK"
+/// Split an address's spendable funds into `(confirmed, unconfirmed)`.
+/// Outputs a mempool transaction already spends count towards neither;
+/// pending mempool outputs paying the address count as unconfirmed.
+fn split_spendable_balance(
+    chain: &Blockchain,
+    mempool: &Mempool,
+    address: &str,
+    min_confirmations: u64,
+) -> (u64, u64) {
+    let spent = mempool.spent_outpoints();
+    let (mut confirmed, mut unconfirmed) = (0, mempool.pending_balance(address));
+    for utxo in chain.get_spendable_utxos_for_address(address) {
+        if spent.contains(&utxo.outpoint()) {
+            continue;
+        }
+        if chain.confirmations(&utxo.tx_id) >= min_confirmations {
+            confirmed += utxo.output.amount;
+        } else {
+            unconfirmed += utxo.output.amount;
+        }
+    }
+    (confirmed, unconfirmed)
+}
+
+/// Query for address history
+#[derive(Deserialize)]
+pub struct AddressHistoryQuery {
+    /// First block height (default 0)
+    pub from: Option<u64>,
+    /// Last block height (default the tip)
+    pub to: Option<u64>,
+}
+
+/// One transaction in an address's history
+#[derive(Serialize)]
+pub struct AddressHistoryEntry {
+    pub tx_id: String,
+    pub block_height: u64,
+    /// "in", "out", or "self" when nothing moved in or out on net
+    pub direction: String,
+    pub received: u64,
+    pub sent: u64,
+    /// Absolute net change; `direction` gives the sign
+    pub net_amount: u64,
+}
+
+/// Inclusive block-height range
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct HeightRange {
+    pub from: u64,
+    pub to: u64,
+}
+
+#[derive(Serialize)]
+pub struct AddressHistoryResponse {
+    pub address: String,
+    pub from: u64,
+    pub to: u64,
+    /// Part of the requested range whose block bodies were pruned; no
+    /// history is available for it
+    pub pruned: Option<HeightRange>,
+    pub transactions: Vec<AddressHistoryEntry>,
+}
+
+/// GET /api/address/:addr/history - Confirmed transactions touching an
+/// address within a block-height range
+pub async fn get_address_history(
+    State(state): State<ApiState>,
+    Path(address): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<AddressHistoryQuery>,
+) -> Result<Json<AddressHistoryResponse>, (StatusCode, Json<ApiError>)> {
+    let chain = state.blockchain.read().await;
+    let from = query.from.unwrap_or(0);
+    let to = query.to.unwrap_or(chain.height()).min(chain.height());
+    if from > to {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: format!("Invalid height range: {}..{}", from, to),
+            }),
+        ));
+    }
+
+    let transactions = chain
+        .address_history(&address, from, to)
+        .into_iter()
+        .map(|entry| {
+            let direction = match entry.received.cmp(&entry.sent) {
+                std::cmp::Ordering::Greater => "in",
+                std::cmp::Ordering::Less => "out",
+                std::cmp::Ordering::Equal => "self",
+            };
+            AddressHistoryEntry {
+                tx_id: entry.tx_id,
+                block_height: entry.block_height,
+                direction: direction.to_string(),
+                received: entry.received,
+                sent: entry.sent,
+                net_amount: entry.received.abs_diff(entry.sent),
+            }
+        })
+        .collect();
+
+    Ok(Json(AddressHistoryResponse {
+        pruned: chain
+            .pruned_heights(from, to)
+            .map(|(from, to)| HeightRange { from, to }),
+        address,
+        from,
+        to,
+        transactions,
+    }))
+}
+
+/// Signed message verification request
+#[derive(Deserialize)]
+pub struct VerifyMessageRequest {
+    pub address: String,
+    pub message: String,
+    pub signature: String,
+}
+
+/// Signed message verification response
+#[derive(Serialize)]
+pub struct VerifyMessageResponse {
+    pub address: String,
+    pub valid: bool,
+}
+
+/// POST /api/verifymessage - Verify an address ownership proof
+pub async fn verify_message(Json(req): Json<VerifyMessageRequest>) -> Json<VerifyMessageResponse> {
+    let valid = crate::crypto::verify_address_message(&req.address, &req.message, &req.signature);
+
+    Json(VerifyMessageResponse {
+        address: req.address,
+        valid,
+    })
+}
+
+/// Node health, for load balancers and monitoring
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// `"ok"`, or `"syncing"` while a peer is ahead of us
+    pub status: String,
+    pub height: u64,
+    pub best_hash: String,
+    /// Connected peers
+    pub peers: usize,
+    pub is_syncing: bool,
+    pub blocks_behind_best_peer: u64,
+    pub mempool_size: usize,
+}
+
+/// GET /health - Health check with sync status
+pub async fn health_check(State(state): State<ApiState>) -> Json<HealthResponse> {
+    let (height, best_hash) = {
+        let chain = state.blockchain.read().await;
+        (chain.height(), chain.latest_block().hash.clone())
+    };
+    let mempool_size = state.mempool.read().await.len();
+
+    let (peers, best_peer_height) = match state.peer_manager {
+        Some(ref peer_manager) => (
+            peer_manager.stats().await.connected_peers,
+            peer_manager.get_best_peer().await.map(|(_, height)| height),
+        ),
+        None => (0, None),
+    };
+    let blocks_behind_best_peer = best_peer_height.map_or(0, |best| best.saturating_sub(height));
+    let is_syncing = blocks_behind_best_peer > 0;
+
+    Json(HealthResponse {
+        status: if is_syncing { "syncing" } else { "ok" }.to_string(),
+        height,
+        best_hash,
+        peers,
+        is_syncing,
+        blocks_behind_best_peer,
+        mempool_size,
+    })
 }
 
 // ============================================================================
@@ -559,8 +1436,14 @@ pub async fn deploy_contract(
     let chain = state.blockchain.read().await;
     let mut manager = state.contract_manager.write().await;
 
-    match manager.deploy(bytecode.clone(), "web-deployer", chain.height()) {
+    match manager.deploy_with_gas_limit(
+        bytecode.clone(),
+        "web-deployer",
+        chain.height(),
+        req.gas_limit,
+    ) {
         Ok(address) => {
+            let init_gas_used = manager.get(&address).map(|c| c.init_gas_used).unwrap_or(0);
             drop(manager);
             drop(chain);
 
@@ -581,13 +1464,15 @@ pub async fn deploy_contract(
             let tx = Transaction::with_contract_data(vec![input], vec![], contract_op);
 
             // Add to mempool for on-chain recording
+            let chain = state.blockchain.read().await;
             let mut mempool = state.mempool.write().await;
-            let _ = mempool.add_contract_transaction(tx);
+            let _ = mempool.add_contract_transaction(tx, &chain);
             drop(mempool);
 
             Ok(Json(DeployResponse {
                 address,
                 code_size: bytecode.len(),
+                init_gas_used,
             }))
         }
         Err(e) => Err((
@@ -622,15 +1507,110 @@ pub async fn get_contract(
     }
 }
 
+/// Sign a payment from a wallet held by this server and queue it in the
+/// mempool. Only mature outputs that no pending transaction spends are
+/// used, so back-to-back payments don't conflict.
+async fn queue_wallet_payment(
+    state: &ApiState,
+    from: &str,
+    outputs: Vec<(String, u64)>,
+    fee: u64,
+    chain: &Blockchain,
+) -> Result<Transaction, String> {
+    let wallet = state
+        .wallet_manager
+        .read()
+        .await
+        .load_wallet(from)
+        .map_err(|e| e.to_string())?;
+    let needed = outputs
+        .iter()
+        .try_fold(fee, |sum, (_, amount)| sum.checked_add(*amount))
+        .ok_or_else(|| "Payment amount overflows".to_string())?;
+
+    let mut mempool = state.mempool.write().await;
+    let mut selected = Vec::new();
+    let mut selected_amount = 0u64;
+    for utxo in wallet.utxos(chain) {
+        if selected_amount >= needed {
+            break;
+        }
+        if mempool.is_utxo_spent(&utxo.tx_id, utxo.output_index)
+            || !chain.is_coinbase_mature(&utxo.tx_id)
+        {
+            continue;
+        }
+        selected_amount += utxo.output.amount;
+        selected.push(utxo);
+    }
+
+    let tx = wallet
+        .create_transaction_with_inputs(selected, outputs, fee, chain)
+        .map_err(|e| e.to_string())?;
+    mempool
+        .add_transaction(tx.clone(), chain)
+        .map_err(|e| e.to_string())?;
+    Ok(tx)
+}
+
+/// Announce a transaction accepted into the mempool to P2P peers (if
+/// connected), tracking it for `GET /api/transactions/:id/propagation`
+async fn relay_to_peers(state: &ApiState, tx: &Transaction) {
+    if let Some(ref peer_manager) = state.peer_manager {
+        peer_manager.track_broadcast(&tx.id).await;
+        peer_manager
+            .broadcast_transaction(Message::NewTransaction(tx.clone()), None)
+            .await;
+    }
+}
+
 /// POST /api/contracts/:address/call - Call a contract
 pub async fn call_contract(
     State(state): State<ApiState>,
     Path(address): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<CallContractQuery>,
     Json(req): Json<CallContractRequest>,
 ) -> Result<Json<CallResponse>, (StatusCode, Json<ApiError>)> {
     let gas_price = req.gas_price.unwrap_or(1);
     let gas_limit = req.gas_limit.unwrap_or(1_000); // Reasonable default for simple contracts
-    let max_cost = gas_limit * gas_price;
+
+    // Enforce the server's gas ceiling before anything else
+    if let Some(requested) = req.gas_limit {
+        if let Err(e) = state
+            .contract_manager
+            .read()
+            .await
+            .check_gas_limit(requested)
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApiError {
+                    error: e.to_string(),
+                }),
+            ));
+        }
+    }
+    let value = req.value.unwrap_or(0);
+    let Some(total_cost) = gas_limit
+        .checked_mul(gas_price)
+        .and_then(|max_cost| max_cost.checked_add(value))
+    else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Gas limit × gas price + value overflows".to_string(),
+            }),
+        ));
+    };
+
+    if value > 0 && req.caller_address.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Sending value requires caller_address".to_string(),
+            }),
+        ));
+    }
 
     // If caller provided, check balance first
     let caller_address = req
@@ -639,17 +1619,17 @@ pub async fn call_contract(
         .unwrap_or_else(|| "anonymous".to_string());
     let mut caller_balance: Option<u64> = None;
 
-    if req.caller_address.is_some() && gas_price > 0 {
+    if req.caller_address.is_some() && (gas_price > 0 || value > 0) {
         let chain = state.blockchain.read().await;
         let balance = chain.get_balance(&caller_address);
 
-        if balance < max_cost {
+        if balance < total_cost {
             return Err((
                 StatusCode::BAD_REQUEST,
                 Json(ApiError {
                     error: format!(
-                        "Insufficient balance for gas. Need {} coins (gas_limit {} × gas_price {}), have {}",
-                        max_cost, gas_limit, gas_price, balance
+                        "Insufficient balance for gas. Need {} coins (gas_limit {} × gas_price {} + value {}), have {}",
+                        total_cost, gas_limit, gas_price, value, balance
                     ),
                 }),
             ));
@@ -657,10 +1637,10 @@ pub async fn call_contract(
         caller_balance = Some(balance);
     }
 
-    // Execute the contract
-    // Execute the contract - need write access if charging gas
-    let mut chain = state.blockchain.write().await;
+    // Execute the contract, keeping a snapshot to restore if it can't be paid for
+    let chain = state.blockchain.read().await;
     let mut manager = state.contract_manager.write().await;
+    let snapshot = manager.get(&address).cloned();
     let timestamp = chrono::Utc::now().timestamp() as u64;
     let height = chain.height();
 
@@ -668,76 +1648,78 @@ pub async fn call_contract(
     let args_for_tx = req.args.clone();
     let gas_limit_for_tx = req.gas_limit;
 
-    match manager.call(
+    // Resolve BALANCE through the chain's address index so real accounts are
+    // visible without walking the UTXO set on every call
+    let balances = chain.address_balances();
+    let balance_lookup = BalanceLookup::by_hash(Arc::new(move |hash| balances.get_by_hash(hash)));
+
+    let trace = query.trace.unwrap_or(false);
+    let options = CallOptions {
+        gas_limit: req.gas_limit,
+        value,
+        trace,
+        balance_lookup,
+    };
+
+    match manager.call_with_options(
         &address,
         &caller_address,
         req.args,
         timestamp,
         height,
-        req.gas_limit,
+        options,
     ) {
         Ok(result) => {
-            let gas_cost = result.gas_used * gas_price;
-
-            // Process gas payment if applicable
-            let mut new_balance = caller_balance;
-
-            if req.caller_address.is_some() && gas_price > 0 && gas_cost > 0 {
-                // Drop write locks before getting wallet (to avoid potential deadlocks)
-                drop(chain);
-                drop(manager);
-
-                let wallet_manager = state.wallet_manager.read().await;
-                if let Ok(wallet) = wallet_manager.load_wallet(&caller_address) {
-                    // Re-acquire chain read lock to create transaction
-                    let chain = state.blockchain.read().await;
-
-                    // Create transaction to "burn" address to pay for gas
-                    // Using a burn address ensures coins are removed from circulation (simulating burnt fee)
-                    // Or we could send to a "miner" address. "0x0000..." is simpler for burning.
-                    match wallet.create_transaction(
-                        "0x0000000000000000000000000000000000000000",
-                        gas_cost,
-                        &chain,
-                    ) {
-                        Ok(tx) => {
-                            drop(chain); // Drop read lock
-
-                            // Re-acquire chain write lock to mine
-                            let mut chain = state.blockchain.write().await;
-
-                            // Mine block with this transaction
-                            // We use a system address for mining this "maintenance" block
-                            match chain.mine_block(vec![tx], "network_gas_miner") {
-                                Ok(_) => {
-                                    log::info!(
-                                        "Gas paid: {} coins persisted via mined block",
-                                        gas_cost
-                                    );
-                                    new_balance = Some(chain.get_balance(&caller_address));
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to mine gas block: {}", e);
-                                    // Fallback to speculative balance (won't persist but shows correct in UI now)
-                                    new_balance =
-                                        caller_balance.map(|b| b.saturating_sub(gas_cost));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Failed to create gas tx: {}", e);
-                            new_balance = caller_balance.map(|b| b.saturating_sub(gas_cost));
+            let gas_cost = (result.gas_used - result.gas_refunded) * gas_price;
+            let gas_destination = manager.gas_destination();
+
+            // Pay the value and gas in one mempool tx: the value goes to the
+            // contract, the gas is burned or left as a fee for the next
+            // block's miner. The call only stands if the payment is accepted.
+            let mut outputs = Vec::new();
+            if value > 0 {
+                outputs.push((address.clone(), value));
+            }
+            let mut fee = 0;
+            let mut payment = None;
+            if req.caller_address.is_some() && gas_cost > 0 {
+                match gas_destination {
+                    GasDestination::Burn => outputs.push((GAS_BURN_ADDRESS.to_string(), gas_cost)),
+                    GasDestination::Miner => fee = gas_cost,
+                }
+            }
+            if !outputs.is_empty() || fee > 0 {
+                match queue_wallet_payment(&state, &caller_address, outputs, fee, &chain).await {
+                    Ok(tx) => {
+                        log::info!(
+                            "Call paid: {} coins value and {} coins gas queued in mempool",
+                            value,
+                            gas_cost
+                        );
+                        state
+                            .events
+                            .publish(ChainEvent::TxAccepted { tx: tx.clone() });
+                        payment = Some(tx);
+                        caller_balance = caller_balance
+                            .map(|b| b.saturating_sub(value.saturating_add(gas_cost)));
+                    }
+                    Err(e) => {
+                        if let Some(contract) = snapshot {
+                            manager.restore(contract);
                         }
+                        return Err((
+                            StatusCode::BAD_REQUEST,
+                            Json(ApiError {
+                                error: format!("Call payment failed: {}", e),
+                            }),
+                        ));
                     }
-                } else {
-                    log::warn!(
-                        "Wallet {} not found locally, cannot sign gas tx",
-                        caller_address
-                    );
-                    new_balance = caller_balance.map(|b| b.saturating_sub(gas_cost));
                 }
-            } else {
-                new_balance = caller_balance.map(|b| b.saturating_sub(gas_cost));
+            }
+            drop(chain);
+            drop(manager);
+            if let Some(tx) = payment {
+                relay_to_peers(&state, &tx).await;
             }
 
             // Create on-chain transaction to record the contract call
@@ -758,16 +1740,21 @@ pub async fn call_contract(
             let tx = Transaction::with_contract_data(vec![input], vec![], contract_op);
 
             // Add to mempool for on-chain recording
+            let chain = state.blockchain.read().await;
             let mut mempool = state.mempool.write().await;
-            let _ = mempool.add_contract_transaction(tx);
+            if mempool.add_contract_transaction(tx.clone(), &chain).is_ok() {
+                state.events.publish(ChainEvent::TxAccepted { tx });
+            }
             drop(mempool);
 
             Ok(Json(CallResponse {
                 success: result.success,
                 return_value: result.return_value,
                 gas_used: result.gas_used,
+                gas_refunded: result.gas_refunded,
                 gas_cost,
-                caller_balance: new_balance,
+                caller_balance,
+                trace: trace.then_some(result.trace),
             }))
         }
         Err(e) => Err((
@@ -920,17 +1907,24 @@ pub async fn get_multisig_balance(
 ) -> Result<Json<BalanceResponse>, (StatusCode, Json<ApiError>)> {
     let manager = state.multisig_manager.read().await;
     let blockchain = state.blockchain.read().await;
+    let mempool = state.mempool.read().await;
 
     match manager.get_balance(&address, &blockchain) {
         Some(balance) => {
             let utxos = blockchain.get_utxos_for_address(&address);
             let spendable_balance = blockchain.get_spendable_balance(&address);
             let immature_balance = blockchain.get_immature_balance(&address);
+            let (confirmed_balance, unconfirmed_balance) =
+                split_spendable_balance(&blockchain, &mempool, &address, DEFAULT_MIN_CONFIRMATIONS);
             Ok(Json(BalanceResponse {
                 address,
                 balance,
+                balance_display: blockchain.params.format_amount(balance),
                 spendable_balance,
+                confirmed_balance,
+                unconfirmed_balance,
                 immature_balance,
+                min_confirmations: DEFAULT_MIN_CONFIRMATIONS,
                 utxo_count: utxos.len(),
             }))
         }
@@ -1180,12 +2174,19 @@ pub async fn broadcast_multisig_tx(
     let tx_id = transaction.id.clone();
 
     // Add to mempool
-    {
+    let accepted = {
         let blockchain = state.blockchain.read().await;
         let mut mempool = state.mempool.write().await;
-        if let Err(e) = mempool.add_transaction(transaction, &blockchain) {
-            log::warn!("Failed to add to mempool (may be already there): {}", e);
+        match mempool.add_transaction(transaction.clone(), &blockchain) {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!("Failed to add to mempool (may be already there): {}", e);
+                false
+            }
         }
+    };
+    if accepted {
+        relay_to_peers(&state, &transaction).await;
     }
 
     // Mark as broadcast and remove from pending
@@ -1345,8 +2346,9 @@ pub async fn create_token(
     let tx = Transaction::with_token_data(vec![input], vec![], token_op);
 
     // Add to mempool for on-chain recording
+    let chain = state.blockchain.read().await;
     let mut mempool = state.mempool.write().await;
-    let _ = mempool.add_token_transaction(tx.clone());
+    let _ = mempool.add_token_transaction(tx.clone(), &chain);
     drop(mempool);
 
     Ok(Json(TokenInfo {
@@ -1487,8 +2489,9 @@ pub async fn transfer_tokens(
     let tx = Transaction::with_token_data(vec![input], vec![], token_op);
 
     // Add to mempool for on-chain recording
+    let chain = state.blockchain.read().await;
     let mut mempool = state.mempool.write().await;
-    let _ = mempool.add_token_transaction(tx);
+    let _ = mempool.add_token_transaction(tx, &chain);
     drop(mempool);
 
     Ok(Json(TransferResponse {
@@ -1544,8 +2547,9 @@ pub async fn approve_tokens(
 
     let tx = Transaction::with_token_data(vec![input], vec![], token_op);
 
+    let chain = state.blockchain.read().await;
     let mut mempool = state.mempool.write().await;
-    let _ = mempool.add_token_transaction(tx);
+    let _ = mempool.add_token_transaction(tx, &chain);
     drop(mempool);
 
     Ok(Json(serde_json::json!({
@@ -1626,8 +2630,9 @@ pub async fn transfer_from_tokens(
 
     let tx = Transaction::with_token_data(vec![input], vec![], token_op);
 
+    let chain = state.blockchain.read().await;
     let mut mempool = state.mempool.write().await;
-    let _ = mempool.add_token_transaction(tx);
+    let _ = mempool.add_token_transaction(tx, &chain);
     drop(mempool);
 
     Ok(Json(TransferResponse {
@@ -1687,8 +2692,9 @@ pub async fn burn_tokens(
 
     let tx = Transaction::with_token_data(vec![input], vec![], token_op);
 
+    let chain = state.blockchain.read().await;
     let mut mempool = state.mempool.write().await;
-    let _ = mempool.add_token_transaction(tx);
+    let _ = mempool.add_token_transaction(tx, &chain);
     drop(mempool);
 
     Ok(Json(serde_json::json!({
@@ -1752,8 +2758,9 @@ pub async fn mint_tokens(
 
     let tx = Transaction::with_token_data(vec![input], vec![], token_op);
 
+    let chain = state.blockchain.read().await;
     let mut mempool = state.mempool.write().await;
-    let _ = mempool.add_token_transaction(tx);
+    let _ = mempool.add_token_transaction(tx, &chain);
     drop(mempool);
 
     Ok(Json(serde_json::json!({
@@ -1802,6 +2809,67 @@ pub async fn get_token_history(
     Ok(Json(entries))
 }
 
+/// Token distribution query parameters
+#[derive(Deserialize)]
+pub struct TokenDistributionQuery {
+    /// Number of largest holders to return (default 10)
+    pub limit: Option<usize>,
+}
+
+/// A holder's share of a token
+#[derive(Serialize)]
+pub struct TokenHolderShare {
+    pub address: String,
+    pub balance: String,
+    pub percent: f64,
+}
+
+/// Token supply and holder distribution
+#[derive(Serialize)]
+pub struct TokenDistributionResponse {
+    pub token: String,
+    pub total_supply: String,
+    pub circulating_supply: String,
+    pub holder_count: usize,
+    pub top_holders: Vec<TokenHolderShare>,
+}
+
+/// GET /api/tokens/{address}/distribution - Supply and top holders
+pub async fn get_token_distribution(
+    State(state): State<ApiState>,
+    Path(address): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<TokenDistributionQuery>,
+) -> Result<Json<TokenDistributionResponse>, (StatusCode, Json<ApiError>)> {
+    let manager = state.token_manager.read().await;
+
+    let dist = manager
+        .distribution(&address, query.limit.unwrap_or(10).min(100))
+        .map_err(|e| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: format!("{}", e),
+                }),
+            )
+        })?;
+
+    Ok(Json(TokenDistributionResponse {
+        token: dist.token,
+        total_supply: dist.total_supply.to_string(),
+        circulating_supply: dist.circulating_supply.to_string(),
+        holder_count: dist.holder_count,
+        top_holders: dist
+            .top_holders
+            .into_iter()
+            .map(|h| TokenHolderShare {
+                address: h.address,
+                balance: h.balance.to_string(),
+                percent: h.percent,
+            })
+            .collect(),
+    }))
+}
+
 // ============================================================================
 // Search Endpoints
 // ============================================================================
@@ -1852,16 +2920,7 @@ pub async fn search(
         // Try parsing as block height
         if let Ok(height) = q.parse::<u64>() {
             if let Some(block) = chain.get_block(height) {
-                result.blocks.push(BlockInfo {
-                    index: block.index,
-                    hash: block.hash.clone(),
-                    previous_hash: block.header.previous_hash.clone(),
-                    merkle_root: block.header.merkle_root.clone(),
-                    timestamp: block.header.timestamp.to_rfc3339(),
-                    difficulty: block.header.difficulty,
-                    nonce: block.header.nonce,
-                    transactions: block.transactions.len(),
-                });
+                result.blocks.push(BlockInfo::from(block));
             }
         }
 
@@ -1870,16 +2929,7 @@ pub async fn search(
             if block.hash.to_lowercase().starts_with(&q)
                 && result.blocks.iter().all(|b| b.index != block.index)
             {
-                result.blocks.push(BlockInfo {
-                    index: block.index,
-                    hash: block.hash.clone(),
-                    previous_hash: block.header.previous_hash.clone(),
-                    merkle_root: block.header.merkle_root.clone(),
-                    timestamp: block.header.timestamp.to_rfc3339(),
-                    difficulty: block.header.difficulty,
-                    nonce: block.header.nonce,
-                    transactions: block.transactions.len(),
-                });
+                result.blocks.push(BlockInfo::from(block));
                 if result.blocks.len() >= 10 {
                     break;
                 }
@@ -2026,6 +3076,8 @@ pub struct FeeEstimateResponse {
     pub normal: u64,
     pub low_priority: u64,
     pub economy: u64,
+    /// Current dynamic minimum fee rate to enter the mempool
+    pub mempool_min_fee_rate: u64,
     pub unit: String,
 }
 
@@ -2044,11 +3096,15 @@ pub async fn get_fee_estimates(State(state): State<ApiState>) -> Json<FeeEstimat
         _ => (base_fee * 20, base_fee * 10, base_fee * 5, base_fee * 3),
     };
 
+    // Never suggest a fee below what the mempool currently accepts
+    let min_fee = mempool.mempool_min_feerate();
+
     Json(FeeEstimateResponse {
-        high_priority: high,
-        normal,
-        low_priority: low,
-        economy,
+        high_priority: high.max(min_fee),
+        normal: normal.max(min_fee),
+        low_priority: low.max(min_fee),
+        economy: economy.max(min_fee),
+        mempool_min_fee_rate: min_fee,
         unit: "sat/byte".to_string(),
     })
 }
@@ -2073,6 +3129,11 @@ pub struct StorageStatsResponse {
     pub block_count: usize,
     pub transaction_count: usize,
     pub utxo_count: usize,
+    /// Average UTXO set growth per block over [`UTXO_GROWTH_WINDOW`] blocks
+    pub utxo_growth_per_block: f64,
+    /// UTXO set size above which consolidating transactions get a discount
+    pub max_utxo_set_size: usize,
+    pub consolidation_discount_percent: u64,
     pub difficulty: u32,
     pub chain_work: String,
 }
@@ -2095,23 +3156,34 @@ pub async fn get_advanced_stats(State(state): State<ApiState>) -> Json<AdvancedS
     let tx_count: usize = chain.blocks.iter().map(|b| b.transactions.len()).sum();
 
     // Get UTXO count
-    let utxo_count = chain.utxo_set.len();
+    let utxo_count = chain.utxo_count();
 
     // Estimate mempool size in bytes (rough estimate)
     let mempool_bytes = mempool.len() * 300; // ~300 bytes per tx average
 
+    let (peer_count, banned_count) = match state.peer_manager {
+        Some(ref peer_manager) => {
+            let stats = peer_manager.stats().await;
+            (stats.connected_peers, stats.banned_count)
+        }
+        None => (0, 0),
+    };
+
     Json(AdvancedStatsResponse {
         network: NetworkStatsResponse {
-            protocol_version: 70001,
-            min_protocol_version: 70000,
-            peer_count: 0, // Would need peer manager access
-            max_peers: 125,
-            banned_count: 0,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            peer_count,
+            max_peers: MAX_PEERS,
+            banned_count,
         },
         storage: StorageStatsResponse {
             block_count: chain.blocks.len(),
             transaction_count: tx_count,
             utxo_count,
+            utxo_growth_per_block: chain.utxo_growth_per_block(UTXO_GROWTH_WINDOW),
+            max_utxo_set_size: mempool.max_utxo_set_size(),
+            consolidation_discount_percent: mempool.consolidation_discount_percent(),
             difficulty: chain.difficulty,
             chain_work: format!(
                 "{}",
@@ -2122,3 +3194,1330 @@ pub async fn get_advanced_stats(State(state): State<ApiState>) -> Json<AdvancedS
         mempool_bytes,
     })
 }
+
+/// Network info response
+#[derive(Serialize)]
+pub struct NetworkInfoResponse {
+    pub protocol_version: u32,
+    pub min_protocol_version: u32,
+    pub services: u64,
+    pub service_names: Vec<String>,
+    /// Whether the P2P node is enabled
+    pub network_active: bool,
+    pub listen_port: Option<u16>,
+    pub connections: usize,
+    pub connections_in: usize,
+    pub connections_out: usize,
+    pub upnp_active: bool,
+    pub upnp_port: Option<u16>,
+}
+
+/// GET /api/network - Protocol, services and connection details
+pub async fn get_network_info(State(state): State<ApiState>) -> Json<NetworkInfoResponse> {
+    let mut info = NetworkInfoResponse {
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        services: LOCAL_SERVICES.0,
+        service_names: LOCAL_SERVICES
+            .names()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        network_active: false,
+        listen_port: None,
+        connections: 0,
+        connections_in: 0,
+        connections_out: 0,
+        upnp_active: false,
+        upnp_port: None,
+    };
+
+    if let Some(ref peer_manager) = state.peer_manager {
+        let stats = peer_manager.stats().await;
+        let upnp_port = peer_manager.upnp_mapping().await;
+
+        info.network_active = true;
+        info.listen_port = Some(peer_manager.listen_port());
+        info.connections = stats.total_peers;
+        info.connections_in = stats.inbound_peers;
+        info.connections_out = stats.outbound_peers;
+        info.upnp_active = upnp_port.is_some();
+        info.upnp_port = upnp_port;
+    }
+
+    Json(info)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::MAX_REFUND_QUOTIENT;
+    use crate::core::{TransactionBuilder, BLOCK_REWARD};
+    use crate::crypto::KeyPair;
+    use axum::extract::Query;
+
+    fn test_state(dir: &std::path::Path) -> ApiState {
+        let storage = Storage::new(crate::storage::StorageConfig {
+            data_dir: dir.join("data"),
+            ..Default::default()
+        })
+        .unwrap();
+
+        ApiState {
+            blockchain: Arc::new(RwLock::new(Blockchain::with_difficulty(4))),
+            mempool: Arc::new(RwLock::new(Mempool::new())),
+            storage: Arc::new(storage),
+            wallet_manager: Arc::new(RwLock::new(
+                WalletManager::new(&dir.join("wallets")).unwrap(),
+            )),
+            contract_manager: Arc::new(RwLock::new(ContractManager::new())),
+            ws_broadcaster: Arc::new(WsBroadcaster::new()),
+            events: Arc::new(EventBus::new()),
+            multisig_manager: Arc::new(RwLock::new(MultisigManager::new())),
+            token_manager: Arc::new(RwLock::new(TokenManager::new())),
+            peer_manager: None,
+            dev_mode: false,
+        }
+    }
+
+    async fn balance_of(state: &ApiState, address: &str) -> BalanceResponse {
+        get_wallet_balance(
+            State(state.clone()),
+            Path(address.to_string()),
+            Query(BalanceQuery {
+                min_confirmations: None,
+            }),
+        )
+        .await
+        .0
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_sync_status() {
+        use crate::network::{Handshake, PeerHandle};
+        use std::net::SocketAddr;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(dir.path());
+        let peer_manager = Arc::new(PeerManager::new(8333));
+        state.peer_manager = Some(peer_manager.clone());
+        state
+            .blockchain
+            .write()
+            .await
+            .mine_block(vec![], "miner")
+            .unwrap();
+
+        let addr: SocketAddr = "10.1.0.1:8333".parse().unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        peer_manager
+            .add_peer(addr, PeerHandle { addr, tx }, true)
+            .await
+            .unwrap();
+        let peer_at = |height: u64| Handshake::new(height, "peer_tip".to_string(), 8333);
+
+        // At the best peer's height
+        peer_manager.update_peer(&addr, &peer_at(1)).await;
+        let health = health_check(State(state.clone())).await.0;
+        assert_eq!(health.status, "ok");
+        assert_eq!(health.height, 1);
+        assert_eq!(
+            health.best_hash,
+            state.blockchain.read().await.latest_block().hash
+        );
+        assert_eq!(health.peers, 1);
+        assert!(!health.is_syncing);
+        assert_eq!(health.blocks_behind_best_peer, 0);
+        assert_eq!(health.mempool_size, 0);
+
+        // A peer pulls ahead
+        peer_manager.update_peer(&addr, &peer_at(6)).await;
+        let health = health_check(State(state)).await.0;
+        assert_eq!(health.status, "syncing");
+        assert!(health.is_syncing);
+        assert_eq!(health.blocks_behind_best_peer, 5);
+    }
+
+    #[tokio::test]
+    async fn test_wallet_balance_confirmation_split() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        let key_pair = KeyPair::generate();
+        let miner = key_pair.address();
+        let recipient_key = KeyPair::generate();
+        let recipient = recipient_key.address();
+        state.blockchain.write().await.params.coinbase_maturity = 1;
+
+        // Freshly mined reward is immature
+        let block = state
+            .blockchain
+            .write()
+            .await
+            .mine_block(vec![], &miner)
+            .unwrap();
+        let balance = balance_of(&state, &miner).await;
+        assert_eq!(balance.immature_balance, 50);
+        assert_eq!(balance.confirmed_balance, 0);
+        assert_eq!(balance.unconfirmed_balance, 0);
+        state
+            .blockchain
+            .write()
+            .await
+            .mine_block(vec![], "other")
+            .unwrap();
+
+        // A payment waiting in the mempool is unconfirmed
+        let utxo = state
+            .blockchain
+            .read()
+            .await
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let tx = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&recipient, 30)
+            .add_output(&miner, 20)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        {
+            let chain = state.blockchain.read().await;
+            let mut mempool = state.mempool.write().await;
+            mempool.add_transaction(tx.clone(), &chain).unwrap();
+        }
+        let balance = balance_of(&state, &recipient).await;
+        assert_eq!(balance.unconfirmed_balance, 30);
+        assert_eq!(balance.confirmed_balance, 0);
+        // The sender's spent coinbase no longer counts, only its change
+        let balance = balance_of(&state, &miner).await;
+        assert_eq!(balance.unconfirmed_balance, 20);
+
+        // Once buried under enough blocks it is confirmed
+        state.mempool.write().await.remove_transaction(&tx.id);
+        {
+            let mut chain = state.blockchain.write().await;
+            chain.mine_block(vec![tx], &miner).unwrap();
+            for _ in 1..DEFAULT_MIN_CONFIRMATIONS {
+                chain.mine_block(vec![], &miner).unwrap();
+            }
+        }
+        let balance = balance_of(&state, &recipient).await;
+        assert_eq!(balance.confirmed_balance, 30);
+        assert_eq!(balance.unconfirmed_balance, 0);
+        assert_eq!(balance.immature_balance, 0);
+
+        // Spending it from the mempool moves it out of the confirmed balance
+        let utxo = state
+            .blockchain
+            .read()
+            .await
+            .get_spendable_utxos_for_address(&recipient)[0]
+            .clone();
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&miner, 20)
+            .add_output(&recipient, 10)
+            .build_and_sign(&recipient_key)
+            .unwrap();
+        {
+            let chain = state.blockchain.read().await;
+            let mut mempool = state.mempool.write().await;
+            mempool.add_transaction(spend, &chain).unwrap();
+        }
+        let balance = balance_of(&state, &recipient).await;
+        assert_eq!(balance.confirmed_balance, 0);
+        assert_eq!(balance.unconfirmed_balance, 10);
+    }
+
+    #[tokio::test]
+    async fn test_block_and_transaction_sizes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let miner = key_pair.address();
+
+        // Mine a block containing a payment alongside the coinbase
+        let block = {
+            let mut chain = state.blockchain.write().await;
+            let reward = chain.mine_block(vec![], &miner).unwrap();
+            let utxo = chain
+                .find_utxo(&reward.transactions[0].id, 0)
+                .unwrap()
+                .clone();
+            let tx = TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output("recipient", 30)
+                .add_output(&miner, 20)
+                .build_and_sign(&key_pair)
+                .unwrap();
+            chain.mine_block(vec![tx], &miner).unwrap()
+        };
+
+        let info = get_block_by_height(State(state.clone()), Path(block.index))
+            .await
+            .unwrap()
+            .0;
+        assert!(info.size > 0);
+        assert_eq!(info.weight, info.size * 4);
+
+        let mut tx_total = 0;
+        for tx in &block.transactions {
+            let tx_info = get_transaction(State(state.clone()), Path(tx.id.clone()))
+                .await
+                .unwrap()
+                .0;
+            assert!(tx_info.transaction.size > 0);
+            assert!(tx_info.transaction.vsize > 0);
+            tx_total += tx_info.transaction.size;
+        }
+        assert_eq!(info.size, crate::core::BLOCK_HEADER_SIZE + tx_total);
+    }
+
+    #[tokio::test]
+    async fn test_replaced_transaction_points_to_successor() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        let key_pair = KeyPair::generate();
+
+        let utxo = {
+            let mut chain = state.blockchain.write().await;
+            *chain = Blockchain::with_params(crate::core::ChainParams {
+                genesis_difficulty: 4,
+                block_reward: 1_000_000,
+                coinbase_maturity: 0,
+                ..Default::default()
+            });
+            let block = chain.mine_block(vec![], &key_pair.address()).unwrap();
+            chain
+                .find_utxo(&block.transactions[0].id, 0)
+                .unwrap()
+                .clone()
+        };
+        let paying_fee = |recipient: &str, fee: u64| {
+            TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output(recipient, utxo.output.amount - fee)
+                .with_rbf()
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        // Bump the fee of a signaling transaction
+        let original = paying_fee("alice", 1_000);
+        let replacement = paying_fee("alice", 2_000);
+        {
+            let chain = state.blockchain.read().await;
+            let mut mempool = state.mempool.write().await;
+            mempool.add_transaction(original.clone(), &chain).unwrap();
+            mempool
+                .add_transaction(replacement.clone(), &chain)
+                .unwrap();
+        }
+
+        let old = get_transaction(State(state.clone()), Path(original.id.clone()))
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(old.status, "replaced");
+        assert_eq!(old.replaced_by, Some(replacement.id.clone()));
+        assert_eq!(old.transaction.id, original.id);
+
+        let new = get_transaction(State(state.clone()), Path(replacement.id.clone()))
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(new.status, "pending");
+        assert_eq!(new.replaced_by, None);
+        assert_eq!(new.replaces, vec![original.id]);
+    }
+
+    #[tokio::test]
+    async fn test_block_range() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        {
+            let mut chain = state.blockchain.write().await;
+            for _ in 0..6 {
+                chain.mine_block(vec![], "miner").unwrap();
+            }
+        }
+        let range = |from, to| {
+            get_block_range(
+                State(state.clone()),
+                axum::extract::Query(BlockRangeQuery { from, to }),
+            )
+        };
+
+        let blocks = range(2, 5).await.unwrap().0;
+        let heights: Vec<u64> = blocks.iter().map(|b| b.index).collect();
+        assert_eq!(heights, vec![2, 3, 4, 5]);
+        assert_eq!(blocks[1].previous_hash, blocks[0].hash);
+
+        // Clamped to the tip, empty past it, rejected when inverted
+        assert_eq!(range(5, 1_000).await.unwrap().0.len(), 2);
+        assert!(range(10, 20).await.unwrap().0.is_empty());
+        assert!(matches!(
+            range(5, 2).await,
+            Err((StatusCode::BAD_REQUEST, _))
+        ));
+
+        let chain = state.blockchain.read().await;
+        assert_eq!(chain.get_block_range(0, u64::MAX).len(), 7);
+        assert_eq!(chain.get_block_range(0, 0)[0].index, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pruned_block_bodies_are_gone() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        {
+            let mut chain = state.blockchain.write().await;
+            for _ in 0..6 {
+                chain.mine_block(vec![], "miner").unwrap();
+            }
+            chain.convert_to_pruned(2);
+        }
+        let block = |height| get_block_by_height(State(state.clone()), Path(height));
+
+        assert!(matches!(block(2).await, Err((StatusCode::GONE, _))));
+        assert!(matches!(
+            get_block_coinbase(State(state.clone()), Path(3)).await,
+            Err((StatusCode::GONE, _))
+        ));
+        assert_eq!(block(4).await.unwrap().0.index, 4);
+        assert!(matches!(block(7).await, Err((StatusCode::NOT_FOUND, _))));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_location() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let miner = key_pair.address();
+
+        let (block, pending) = {
+            let mut chain = state.blockchain.write().await;
+            let block = chain.mine_block(vec![], &miner).unwrap();
+            chain.mine_block(vec![], &miner).unwrap();
+            let utxo = chain
+                .find_utxo(&block.transactions[0].id, 0)
+                .unwrap()
+                .clone();
+            let tx = TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output("recipient", 30)
+                .build_and_sign(&key_pair)
+                .unwrap();
+            state
+                .mempool
+                .write()
+                .await
+                .add_transaction(tx.clone(), &chain)
+                .unwrap();
+            (block, tx)
+        };
+
+        let confirmed =
+            get_transaction(State(state.clone()), Path(block.transactions[0].id.clone()))
+                .await
+                .unwrap()
+                .0;
+        assert_eq!(confirmed.block_height, Some(block.index));
+        assert_eq!(confirmed.block_hash, Some(block.hash.clone()));
+        assert_eq!(confirmed.confirmations, 2);
+
+        let unconfirmed = get_transaction(State(state.clone()), Path(pending.id.clone()))
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(unconfirmed.transaction.id, pending.id);
+        assert_eq!(unconfirmed.block_height, None);
+        assert_eq!(unconfirmed.block_hash, None);
+        assert_eq!(unconfirmed.confirmations, 0);
+    }
+
+    #[tokio::test]
+    async fn test_address_history_height_range() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let address = key_pair.address();
+
+        // Block 1 pays the address; blocks 2-4 each send 10 of it onward
+        let mut sends = Vec::new();
+        {
+            let mut chain = state.blockchain.write().await;
+            let reward = chain.mine_block(vec![], &address).unwrap();
+            let mut utxo = chain
+                .find_utxo(&reward.transactions[0].id, 0)
+                .unwrap()
+                .clone();
+            for _ in 0..3 {
+                let tx = TransactionBuilder::new()
+                    .add_input(&utxo)
+                    .add_output("recipient", 10)
+                    .add_output(&address, utxo.output.amount - 10)
+                    .build_and_sign(&key_pair)
+                    .unwrap();
+                chain.mine_block(vec![tx.clone()], "miner").unwrap();
+                utxo = chain.find_utxo(&tx.id, 1).unwrap().clone();
+                sends.push(tx);
+            }
+        }
+
+        let history = |from, to| {
+            get_address_history(
+                State(state.clone()),
+                Path(address.clone()),
+                Query(AddressHistoryQuery { from, to }),
+            )
+        };
+
+        let all = history(None, None).await.unwrap().0;
+        assert_eq!(all.to, 4);
+        assert_eq!(all.transactions.len(), 4);
+        assert_eq!(all.transactions[0].direction, "in");
+
+        let range = history(Some(2), Some(3)).await.unwrap().0;
+        let ids: Vec<&str> = range
+            .transactions
+            .iter()
+            .map(|t| t.tx_id.as_str())
+            .collect();
+        assert_eq!(ids, vec![sends[0].id.as_str(), sends[1].id.as_str()]);
+        for entry in &range.transactions {
+            assert!((2..=3).contains(&entry.block_height));
+            assert_eq!(entry.direction, "out");
+            assert_eq!(entry.net_amount, 10);
+        }
+
+        assert!(matches!(
+            history(Some(3), Some(2)).await,
+            Err((StatusCode::BAD_REQUEST, _))
+        ));
+        assert_eq!(all.pruned, None);
+
+        // Pruned blocks are reported instead of silently missing
+        state.blockchain.write().await.convert_to_pruned(1);
+        let lowest = state
+            .blockchain
+            .read()
+            .await
+            .prune_state()
+            .unwrap()
+            .lowest_block;
+        let after_prune = history(None, None).await.unwrap().0;
+        assert_eq!(
+            after_prune.pruned,
+            Some(HeightRange {
+                from: 0,
+                to: lowest - 1
+            })
+        );
+        let kept: Vec<&AddressHistoryEntry> = all
+            .transactions
+            .iter()
+            .filter(|t| t.block_height >= lowest)
+            .collect();
+        assert!(!kept.is_empty());
+        assert_eq!(after_prune.transactions.len(), kept.len());
+        for (entry, expected) in after_prune.transactions.iter().zip(kept) {
+            assert_eq!(entry.tx_id, expected.tx_id);
+            assert_eq!(entry.direction, "out");
+            assert_eq!(entry.net_amount, 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mempool_entry_package_detail() {
+        use crate::core::UTXO;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let mut chain = state.blockchain.write().await;
+        let block = chain.mine_block(vec![], &key_pair.address()).unwrap();
+        let mut utxo = chain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        // grandparent <- parent <- child, each paying a fee of 10
+        let mut package = Vec::new();
+        for _ in 0..3 {
+            let tx = TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output(&key_pair.address(), utxo.output.amount - 10)
+                .build_and_sign(&key_pair)
+                .unwrap();
+            state
+                .mempool
+                .write()
+                .await
+                .add_transaction(tx.clone(), &chain)
+                .unwrap();
+            utxo = UTXO {
+                tx_id: tx.id.clone(),
+                output_index: 0,
+                output: tx.outputs[0].clone(),
+            };
+            package.push(tx);
+        }
+        drop(chain);
+
+        let middle = &package[1];
+        let entry = get_mempool_entry(State(state.clone()), Path(middle.id.clone()))
+            .await
+            .unwrap()
+            .0;
+        // The pool fills in the fee, so sizes come from its copies
+        let mempool = state.mempool.read().await;
+        let pooled = |tx: &Transaction| mempool.get_transaction(&tx.id).unwrap().clone();
+        assert_eq!(entry.fee, 10);
+        assert_eq!(entry.vsize, pooled(middle).virtual_size());
+        assert_eq!(entry.size, pooled(middle).serialized_size());
+        assert_eq!(entry.ancestor_count, 1);
+        assert_eq!(entry.ancestor_size, pooled(&package[0]).estimated_size());
+        assert_eq!(entry.descendant_count, 1);
+        assert_eq!(entry.descendant_size, pooled(&package[2]).estimated_size());
+        assert_eq!(entry.depends, vec![package[0].id.clone()]);
+        assert_eq!(entry.spent_by, vec![package[2].id.clone()]);
+        assert_eq!(entry.signals_rbf, middle.signals_rbf());
+        drop(mempool);
+
+        let missing = get_mempool_entry(State(state), Path("missing".to_string())).await;
+        assert!(matches!(missing, Err((StatusCode::NOT_FOUND, _))));
+    }
+
+    #[tokio::test]
+    async fn test_block_coinbase_maturity() {
+        use crate::core::{Block, Transaction, BLOCK_REWARD, COINBASE_MATURITY};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+
+        // Blocks one target interval apart keep the difficulty steady
+        let extend = |chain: &mut Blockchain| {
+            let latest = chain.latest_block();
+            let height = latest.index + 1;
+            let mut block = Block::new(
+                height,
+                latest.hash.clone(),
+                vec![Transaction::coinbase("miner", BLOCK_REWARD, height)],
+                chain.difficulty,
+            );
+            block.header.timestamp =
+                latest.header.timestamp + chrono::Duration::seconds(chain.params.target_block_time);
+            block.mine();
+            chain.add_block(block).unwrap();
+        };
+
+        extend(&mut *state.blockchain.write().await);
+        let coinbase = |state: ApiState| async move {
+            get_block_coinbase(State(state), Path(1)).await.unwrap().0
+        };
+
+        let fresh = coinbase(state.clone()).await;
+        let block = state.blockchain.read().await.get_block(1).unwrap().clone();
+        assert_eq!(fresh.txid, block.transactions[0].id);
+        assert_eq!(fresh.reward, BLOCK_REWARD);
+        assert!(!fresh.is_mature);
+        assert_eq!(fresh.blocks_until_mature, COINBASE_MATURITY);
+
+        {
+            let mut chain = state.blockchain.write().await;
+            for _ in 1..COINBASE_MATURITY {
+                extend(&mut chain);
+            }
+        }
+        let almost = coinbase(state.clone()).await;
+        assert!(!almost.is_mature);
+        assert_eq!(almost.blocks_until_mature, 1);
+
+        extend(&mut *state.blockchain.write().await);
+        let mature = coinbase(state.clone()).await;
+        assert!(mature.is_mature);
+        assert_eq!(mature.blocks_until_mature, 0);
+
+        assert!(get_block_coinbase(State(state), Path(500)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_block_undo_lists_spent_outputs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+
+        let (reward, block) = {
+            let mut chain = state.blockchain.write().await;
+            let reward = chain.mine_block(vec![], &key_pair.address()).unwrap();
+            let utxo = chain
+                .find_utxo(&reward.transactions[0].id, 0)
+                .unwrap()
+                .clone();
+            let spend = TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output("recipient", 30)
+                .build_and_sign(&key_pair)
+                .unwrap();
+            let block = chain.mine_block(vec![spend], "miner").unwrap();
+            (reward, block)
+        };
+
+        let undo = get_block_undo(State(state.clone()), Path(block.index))
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(undo.height, block.index);
+        assert_eq!(undo.block_hash, block.hash);
+        assert_eq!(undo.spent_outputs.len(), 1);
+        assert_eq!(
+            undo.spent_outputs[0].outpoint,
+            format!("{}:0", reward.transactions[0].id)
+        );
+        assert_eq!(
+            undo.spent_outputs[0].amount,
+            reward.transactions[0].outputs[0].amount
+        );
+        assert_eq!(undo.spent_outputs[0].recipient, key_pair.address());
+        let added: Vec<String> = block.transactions.iter().map(|tx| tx.id.clone()).collect();
+        assert_eq!(undo.added_tx_ids, added);
+
+        let missing = get_block_undo(State(state), Path(99)).await;
+        assert!(matches!(missing, Err((StatusCode::NOT_FOUND, _))));
+    }
+
+    #[tokio::test]
+    async fn test_contract_gas_goes_to_mempool() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let caller = state
+            .wallet_manager
+            .read()
+            .await
+            .create_wallet(None)
+            .unwrap()
+            .address();
+        let contract = {
+            let mut chain = state.blockchain.write().await;
+            chain.mine_block(vec![], &caller).unwrap();
+            let code = Compiler::new().compile("PUSH 42\nRETURN").unwrap();
+            state
+                .contract_manager
+                .write()
+                .await
+                .deploy(code, "deployer", chain.height())
+                .unwrap()
+        };
+
+        for destination in [GasDestination::Miner, GasDestination::Burn] {
+            state.mempool.write().await.clear();
+            state
+                .contract_manager
+                .write()
+                .await
+                .set_gas_destination(destination);
+            let height = state.blockchain.read().await.height();
+
+            let response = call_contract(
+                State(state.clone()),
+                Path(contract.clone()),
+                Query(CallContractQuery { trace: None }),
+                Json(CallContractRequest {
+                    args: vec![],
+                    gas_limit: Some(40),
+                    gas_price: Some(1),
+                    caller_address: Some(caller.clone()),
+                    value: None,
+                }),
+            )
+            .await
+            .unwrap()
+            .0;
+            assert!(response.gas_cost > 0);
+            assert_eq!(response.caller_balance, Some(50 - response.gas_cost));
+
+            // No maintenance block is mined for the gas payment
+            assert_eq!(state.blockchain.read().await.height(), height);
+
+            let mempool = state.mempool.read().await;
+            let gas_tx = mempool
+                .get_transactions(mempool.len())
+                .into_iter()
+                .find(|tx| tx.contract_data.is_none())
+                .unwrap();
+            match destination {
+                GasDestination::Miner => {
+                    assert_eq!(gas_tx.fee, response.gas_cost);
+                    assert_eq!(gas_tx.total_output(), 50 - response.gas_cost);
+                }
+                GasDestination::Burn => {
+                    assert_eq!(gas_tx.fee, 0);
+                    assert!(gas_tx
+                        .outputs
+                        .iter()
+                        .any(|o| o.recipient == GAS_BURN_ADDRESS && o.amount == response.gas_cost));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_contract_call_value_is_paid_or_rolled_back() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let caller = state
+            .wallet_manager
+            .read()
+            .await
+            .create_wallet(None)
+            .unwrap()
+            .address();
+        let contract = {
+            let mut chain = state.blockchain.write().await;
+            chain.mine_block(vec![], &caller).unwrap();
+            let code = Compiler::new().compile("SELFBALANCE\nRETURN").unwrap();
+            state
+                .contract_manager
+                .write()
+                .await
+                .deploy(code, "deployer", chain.height())
+                .unwrap()
+        };
+        let call = || {
+            call_contract(
+                State(state.clone()),
+                Path(contract.clone()),
+                Query(CallContractQuery { trace: None }),
+                Json(CallContractRequest {
+                    args: vec![],
+                    gas_limit: None,
+                    gas_price: Some(0),
+                    caller_address: Some(caller.clone()),
+                    value: Some(10),
+                }),
+            )
+        };
+        let contract_balance = || async {
+            state
+                .contract_manager
+                .read()
+                .await
+                .get(&contract)
+                .unwrap()
+                .balance
+        };
+
+        // The value is paid by a mempool tx, not a freshly mined block
+        let paid = call().await.unwrap().0;
+        assert_eq!(paid.return_value, Some(10));
+        assert_eq!(paid.caller_balance, Some(40));
+        assert_eq!(state.blockchain.read().await.height(), 1);
+        let pending = state.mempool.read().await.get_transactions(10);
+        assert!(pending.iter().any(|tx| tx
+            .outputs
+            .iter()
+            .any(|o| o.recipient == contract && o.amount == 10)));
+        assert_eq!(contract_balance().await, 10);
+
+        // The caller's only output is now pending, so the next call can't be
+        // paid for and leaves the contract untouched
+        match call().await {
+            Err((StatusCode::BAD_REQUEST, Json(err))) => {
+                assert!(err.error.starts_with("Call payment failed"));
+            }
+            _ => panic!("unpaid call was accepted"),
+        }
+        assert_eq!(contract_balance().await, 10);
+
+        // Overflowing costs are rejected up front
+        let overflow = call_contract(
+            State(state.clone()),
+            Path(contract.clone()),
+            Query(CallContractQuery { trace: None }),
+            Json(CallContractRequest {
+                args: vec![],
+                gas_limit: Some(1_000),
+                gas_price: Some(u64::MAX),
+                caller_address: Some(caller.clone()),
+                value: None,
+            }),
+        )
+        .await;
+        assert!(matches!(overflow, Err((StatusCode::BAD_REQUEST, _))));
+    }
+
+    #[tokio::test]
+    async fn test_submitted_payment_is_relayed_and_tracked() {
+        use crate::network::PeerHandle;
+        use std::net::SocketAddr;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(temp_dir.path());
+        let peer_manager = Arc::new(PeerManager::new(8333));
+        state.peer_manager = Some(peer_manager.clone());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+
+        let mut peers = Vec::new();
+        for i in 1..=2 {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = tokio::sync::mpsc::channel(10);
+            peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            peers.push((addr, rx));
+        }
+
+        let caller = state
+            .wallet_manager
+            .read()
+            .await
+            .create_wallet(None)
+            .unwrap()
+            .address();
+        let contract = {
+            let mut chain = state.blockchain.write().await;
+            chain.mine_block(vec![], &caller).unwrap();
+            let code = Compiler::new().compile("SELFBALANCE\nRETURN").unwrap();
+            state
+                .contract_manager
+                .write()
+                .await
+                .deploy(code, "deployer", chain.height())
+                .unwrap()
+        };
+        let _ = call_contract(
+            State(state.clone()),
+            Path(contract.clone()),
+            Query(CallContractQuery { trace: None }),
+            Json(CallContractRequest {
+                args: vec![],
+                gas_limit: None,
+                gas_price: Some(0),
+                caller_address: Some(caller.clone()),
+                value: Some(10),
+            }),
+        )
+        .await
+        .unwrap();
+        let payment = state.mempool.read().await.get_transactions(1)[0].clone();
+
+        // Every peer is sent the payment, and it is tracked from the start
+        let propagation = |id: String| get_tx_propagation(State(state.clone()), Path(id));
+        assert_eq!(
+            propagation(payment.id.clone()).await.unwrap().peers_with_tx,
+            0
+        );
+        for (addr, rx) in peers.iter_mut() {
+            match rx.try_recv() {
+                Ok(Message::NewTransaction(relayed)) => assert_eq!(relayed.id, payment.id),
+                other => panic!("expected the payment, got {:?}", other),
+            }
+            peer_manager.record_tx_announcement(&payment.id, addr).await;
+        }
+
+        let tracked = propagation(payment.id.clone()).await.unwrap();
+        assert_eq!(tracked.peers_with_tx, 2);
+        assert_eq!(tracked.connected_peers, 2);
+    }
+
+    #[tokio::test]
+    async fn test_contract_gas_reserves_inputs_and_pays_miner() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let caller = state
+            .wallet_manager
+            .read()
+            .await
+            .create_wallet(None)
+            .unwrap()
+            .address();
+        let contract = {
+            let mut chain = state.blockchain.write().await;
+            chain.mine_block(vec![], &caller).unwrap();
+            let code = Compiler::new().compile("PUSH 42\nRETURN").unwrap();
+            let mut manager = state.contract_manager.write().await;
+            manager.set_gas_destination(GasDestination::Miner);
+            manager.deploy(code, "deployer", chain.height()).unwrap()
+        };
+        let call = || {
+            call_contract(
+                State(state.clone()),
+                Path(contract.clone()),
+                Query(CallContractQuery { trace: None }),
+                Json(CallContractRequest {
+                    args: vec![],
+                    gas_limit: Some(40),
+                    gas_price: Some(1),
+                    caller_address: Some(caller.clone()),
+                    value: None,
+                }),
+            )
+        };
+
+        let paid = call().await.unwrap().0;
+        assert!(paid.gas_cost > 0);
+
+        // The caller's only output is reserved by the first gas payment, so
+        // a second call fails instead of running unpaid
+        assert!(matches!(call().await, Err((StatusCode::BAD_REQUEST, _))));
+        let gas_txs = state
+            .mempool
+            .read()
+            .await
+            .get_transactions(10)
+            .into_iter()
+            .filter(|tx| tx.contract_data.is_none())
+            .count();
+        assert_eq!(gas_txs, 1);
+
+        // The next block's miner collects the gas
+        let mined = mine_block(
+            State(state.clone()),
+            Json(MineRequest {
+                miner_address: "gasminer".to_string(),
+            }),
+        )
+        .await
+        .unwrap()
+        .0;
+        assert_eq!(mined.reward, BLOCK_REWARD + paid.gas_cost);
+    }
+
+    #[tokio::test]
+    async fn test_contract_call_refunds_cleared_storage() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+
+        // The constructor (no args) sets slot 1; any call with args clears it
+        let source = "ARGCOUNT\nJUMPI clear\nPUSH 1\nPUSH 42\nSSTORE\nHALT\n\
+                      :clear\nPUSH 1\nPUSH 0\nSSTORE\nHALT";
+        let contract = {
+            let code = Compiler::new().compile(source).unwrap();
+            state
+                .contract_manager
+                .write()
+                .await
+                .deploy(code, "deployer", 0)
+                .unwrap()
+        };
+        let call = || {
+            call_contract(
+                State(state.clone()),
+                Path(contract.clone()),
+                Query(CallContractQuery { trace: None }),
+                Json(CallContractRequest {
+                    args: vec![1],
+                    gas_limit: None,
+                    gas_price: Some(2),
+                    caller_address: None,
+                    value: None,
+                }),
+            )
+        };
+
+        let cleared = call().await.unwrap().0;
+        assert!(cleared.gas_refunded > 0);
+        assert!(cleared.gas_refunded <= cleared.gas_used / MAX_REFUND_QUOTIENT);
+        assert_eq!(
+            cleared.gas_cost,
+            (cleared.gas_used - cleared.gas_refunded) * 2
+        );
+        assert!(cleared.gas_cost < cleared.gas_used * 2);
+
+        // Clearing an already empty slot earns nothing
+        let again = call().await.unwrap().0;
+        assert_eq!(again.gas_used, cleared.gas_used);
+        assert_eq!(again.gas_refunded, 0);
+        assert_eq!(again.gas_cost, again.gas_used * 2);
+    }
+
+    #[tokio::test]
+    async fn test_contract_gas_limit_ceiling() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        let contract = {
+            let code = Compiler::new().compile("PUSH 42\nRETURN").unwrap();
+            let mut manager = state.contract_manager.write().await;
+            manager.set_max_gas_limit(500);
+            manager
+                .deploy_with_gas_limit(code, "deployer", 0, Some(500))
+                .unwrap()
+        };
+        let call = |gas_limit: u64| {
+            call_contract(
+                State(state.clone()),
+                Path(contract.clone()),
+                Query(CallContractQuery { trace: None }),
+                Json(CallContractRequest {
+                    args: vec![],
+                    gas_limit: Some(gas_limit),
+                    gas_price: Some(0),
+                    caller_address: None,
+                    value: None,
+                }),
+            )
+        };
+
+        match call(501).await {
+            Err((StatusCode::BAD_REQUEST, Json(err))) => {
+                assert_eq!(err.error, "Gas limit too high: 501 (max: 500)");
+            }
+            _ => panic!("gas limit above the ceiling was accepted"),
+        }
+
+        let response = call(500).await.unwrap().0;
+        assert_eq!(response.return_value, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_reorg_in_dev_mode() {
+        use crate::api::websocket::{spawn_event_forwarder, WsEvent};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(temp_dir.path());
+        {
+            let mut chain = state.blockchain.write().await;
+            for _ in 0..3 {
+                chain.mine_block(vec![], "miner").unwrap();
+            }
+        }
+        let old_tip = state.blockchain.read().await.latest_block().hash.clone();
+        let request = || {
+            Json(ReorgSimulateRequest {
+                depth: 2,
+                miner_address: Some("rival".to_string()),
+            })
+        };
+
+        let disabled = simulate_reorg(State(state.clone()), request()).await;
+        assert!(matches!(disabled, Err((StatusCode::FORBIDDEN, _))));
+
+        state.dev_mode = true;
+        spawn_event_forwarder(&state.events, state.ws_broadcaster.clone());
+        let mut ws = state.ws_broadcaster.subscribe();
+
+        let result = simulate_reorg(State(state.clone()), request())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(result.old_tip_hash, old_tip);
+        assert_eq!(result.disconnected, 2);
+        assert_eq!(result.new_height, 2);
+
+        let chain = state.blockchain.read().await;
+        assert_eq!(chain.latest_block().hash, result.new_tip_hash);
+        assert_ne!(chain.latest_block().hash, old_tip);
+        assert_eq!(chain.get_balance("rival"), chain.params.block_reward_at(2));
+        drop(chain);
+
+        match ws.recv().await.unwrap() {
+            WsEvent::Reorg { new_tip_hash, .. } => assert_eq!(new_tip_hash, result.new_tip_hash),
+            other => panic!("expected a reorg event, got {:?}", other),
+        }
+
+        // Deeper than the chain
+        let too_deep = simulate_reorg(
+            State(state),
+            Json(ReorgSimulateRequest {
+                depth: 10,
+                miner_address: None,
+            }),
+        )
+        .await;
+        assert!(matches!(too_deep, Err((StatusCode::BAD_REQUEST, _))));
+    }
+
+    #[tokio::test]
+    async fn test_mine_batch() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        let height = state.blockchain.read().await.height();
+
+        let response = mine_batch(
+            State(state.clone()),
+            Json(MineBatchRequest {
+                count: 5,
+                miner_address: "miner".to_string(),
+            }),
+        )
+        .await
+        .unwrap()
+        .0;
+
+        assert_eq!(state.blockchain.read().await.height(), height + 5);
+        assert_eq!(response.blocks.len(), 5);
+        assert_eq!(response.blocks[4].block.index, height + 5);
+        assert_eq!(response.total_reward, 5 * 50);
+
+        for count in [0, MAX_BATCH_MINE_COUNT + 1] {
+            let result = mine_batch(
+                State(state.clone()),
+                Json(MineBatchRequest {
+                    count,
+                    miner_address: "miner".to_string(),
+                }),
+            )
+            .await;
+            assert!(matches!(result, Err((StatusCode::BAD_REQUEST, _))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mining_expires_stale_orphans() {
+        use crate::core::{MockClock, TransactionOutput, UTXO};
+        use crate::mining::ORPHAN_TX_EXPIRE_SECS;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        let clock = Arc::new(MockClock::new(chrono::Utc::now().timestamp()));
+        state.mempool.write().await.set_clock(clock.clone());
+
+        // Spends an output nobody has seen, so it waits as an orphan
+        let key_pair = KeyPair::generate();
+        let orphan = TransactionBuilder::new()
+            .add_input(&UTXO {
+                tx_id: "f".repeat(64),
+                output_index: 0,
+                output: TransactionOutput {
+                    amount: 100,
+                    recipient: key_pair.address(),
+                },
+            })
+            .add_output("recipient", 50)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        {
+            let chain = state.blockchain.read().await;
+            let mut mempool = state.mempool.write().await;
+            assert!(mempool.add_transaction(orphan, &chain).is_err());
+            assert_eq!(mempool.orphan_count(), 1);
+        }
+
+        // Connecting a block is when stale entries are cleared out
+        clock.advance(ORPHAN_TX_EXPIRE_SECS as i64 + 1);
+        let Json(mined) = mine_block(
+            State(state.clone()),
+            Json(MineRequest {
+                miner_address: "miner".to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(mined.block.index, 1);
+        assert_eq!(state.mempool.read().await.orphan_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_verify_message() {
+        let wallet = crate::wallet::Wallet::new();
+        let signature = wallet.sign_message("hello");
+
+        let request = |address: String, message: &str| VerifyMessageRequest {
+            address,
+            message: message.to_string(),
+            signature: signature.clone(),
+        };
+
+        assert!(
+            verify_message(Json(request(wallet.address(), "hello")))
+                .await
+                .valid
+        );
+        assert!(
+            !verify_message(Json(request(wallet.address(), "hell0")))
+                .await
+                .valid
+        );
+        assert!(
+            !verify_message(Json(request(KeyPair::generate().address(), "hello")))
+                .await
+                .valid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_network_info() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(temp_dir.path());
+
+        let Json(info) = get_network_info(State(state.clone())).await;
+        assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(info.min_protocol_version, MIN_PROTOCOL_VERSION);
+        assert!(!info.network_active);
+        assert_eq!(info.listen_port, None);
+
+        let peer_manager = Arc::new(PeerManager::new(8333));
+        peer_manager.set_upnp_mapping(Some(8333)).await;
+        state.peer_manager = Some(peer_manager);
+
+        let Json(info) = get_network_info(State(state)).await;
+        assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(info.services, LOCAL_SERVICES.0);
+        assert_eq!(info.service_names, vec!["NETWORK", "SMART_TX"]);
+        assert!(info.network_active);
+        assert_eq!(info.listen_port, Some(8333));
+        assert_eq!(info.connections, 0);
+        assert!(info.upnp_active);
+    }
+
+    #[tokio::test]
+    async fn test_fee_tiers_respect_mempool_floor() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = test_state(temp_dir.path());
+        state.blockchain.write().await.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let miner = key_pair.address();
+
+        let Json(fees) = get_fee_estimates(State(state.clone())).await;
+        assert_eq!(fees.mempool_min_fee_rate, 0);
+        assert_eq!((fees.high_priority, fees.economy), (2, 1));
+
+        // Fund a spend, then shrink the pool so that one spend fills it
+        let mined = {
+            let mut chain = state.blockchain.write().await;
+            chain.mine_block(vec![], &miner).unwrap()
+        };
+        {
+            let chain = state.blockchain.read().await;
+            let utxo = chain
+                .find_utxo(&mined.transactions[0].id, 0)
+                .unwrap()
+                .clone();
+            let spend = TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output("recipient", 30)
+                .add_output(&miner, 10)
+                .build_and_sign(&key_pair)
+                .unwrap();
+            let mut mempool = state.mempool.write().await;
+            mempool.add_transaction(spend, &chain).unwrap();
+            let full = mempool.total_mempool_size();
+            mempool.set_max_bytes(full);
+        }
+
+        let Json(fees) = get_fee_estimates(State(state.clone())).await;
+        assert!(fees.mempool_min_fee_rate > 2);
+        for tier in [
+            fees.high_priority,
+            fees.normal,
+            fees.low_priority,
+            fees.economy,
+        ] {
+            assert_eq!(tier, fees.mempool_min_fee_rate);
+        }
+    }
+}
@@ -5,30 +5,58 @@
 //! # Endpoints
 //!
 //! ## Chain
-//! - `GET /api/chain` - Blockchain info
+//! - `GET /api/chain` - Blockchain info and sync progress
 //! - `GET /api/chain/blocks` - List recent blocks
+//! - `GET /api/chain/blocks/range?from=&to=` - Blocks in an inclusive height range (at most 100)
 //! - `GET /api/chain/blocks/:height` - Get block by height
+//! - `GET /api/chain/blocks/:height/undo` - Outputs spent and transactions added by a block
+//! - `GET /api/chain/blocks/:height/coinbase` - Coinbase reward and whether it is spendable yet
+//! - `POST /api/chain/blocks/:height/proofs` - One Merkle proof for several transactions in a block
 //! - `GET /api/chain/validate` - Validate chain
+//! - `GET /api/chain/hashrate` - Estimated network hashrate
+//! - `GET /api/chain/reorgs` - Recent chain reorganizations
+//! - `POST /api/chain/reorg-simulate` - Replace the last `depth` blocks with a heavier fork
+//!   (only with `--dev-mode`)
 //!
 //! ## Mining
 //! - `POST /api/mine` - Mine new block
+//! - `POST /api/mine/batch` - Mine several blocks (`{count, miner_address}`)
 //!
 //! ## Transactions
 //! - `GET /api/transactions/:id` - Get transaction
-//! - `GET /api/mempool` - List pending transactions
+//! - `GET /api/transactions/:id/propagation` - Peers that picked up a transaction we broadcast
+//! - `GET /api/mempool` - List pending transactions (`?min_feerate=&max_feerate=` filters by fee rate)
+//! - `GET /api/mempool/:id` - Fees, sizes, package counts and in-pool parents/children of a pending transaction
+//! - `GET /api/mempool/:id/position` - Fee-rate rank and the block a pending transaction would land in
 //!
 //! ## Wallets
 //! - `GET /api/wallets` - List wallets
 //! - `POST /api/wallets` - Create wallet
 //! - `GET /api/wallets/:address/balance` - Get balance
+//! - `GET /api/address/:addr/history` - Transactions touching an address (`?from=&to=` heights)
+//! - `POST /api/verifymessage` - Verify a signed message (address ownership)
+//!
+//! ## Network
+//! - `GET /api/network` - Protocol version, services, connections and UPnP state
+//! - `GET /health` - Height, peers and sync lag behind the best peer (JSON)
 //!
 //! ## WebSocket
-//! - `GET /ws` - Real-time updates (BlockMined, TransactionAdded, ChainUpdated)
+//! - `GET /ws` - Real-time updates (BlockMined, TransactionAdded, Reorg, ChainUpdated),
+//!   forwarded from the [`EventBus`](crate::core::EventBus). Send `{"watch_tx":"<id>"}`
+//!   to receive `TxConfirmed` updates as that transaction gains confirmations
+//!
+//! ## Persistence
+//! State is saved on shutdown and every `--autosave-interval` seconds (see
+//! [`autosave`]), so a crash loses at most one interval of data.
 
+pub mod autosave;
 pub mod handlers;
 pub mod routes;
 pub mod websocket;
 
+pub use autosave::{
+    restore_mempool, save_state, spawn_autosave, DEFAULT_AUTOSAVE_INTERVAL, MEMPOOL_FILE,
+};
 pub use handlers::ApiState;
 pub use routes::create_router;
-pub use websocket::WsBroadcaster;
+pub use websocket::{spawn_event_forwarder, TxWatcher, WsBroadcaster, WsEvent};
@@ -83,16 +83,42 @@ pub fn create_router(state: ApiState) -> Router {
         // Chain endpoints
         .route("/api/chain", get(handlers::get_chain_info))
         .route("/api/chain/blocks", get(handlers::get_blocks))
+        .route("/api/chain/blocks/range", get(handlers::get_block_range))
         .route(
             "/api/chain/blocks/{height}",
             get(handlers::get_block_by_height),
         )
+        .route(
+            "/api/chain/blocks/{height}/undo",
+            get(handlers::get_block_undo),
+        )
+        .route(
+            "/api/chain/blocks/{height}/coinbase",
+            get(handlers::get_block_coinbase),
+        )
+        .route(
+            "/api/chain/blocks/{height}/proofs",
+            post(handlers::get_block_proofs),
+        )
         .route("/api/chain/validate", get(handlers::validate_chain))
+        .route("/api/chain/hashrate", get(handlers::get_hashrate))
+        .route("/api/chain/reorgs", get(handlers::get_reorgs))
+        .route("/api/chain/reorg-simulate", post(handlers::simulate_reorg))
         // Mining
         .route("/api/mine", post(handlers::mine_block))
+        .route("/api/mine/batch", post(handlers::mine_batch))
         // Transactions
         .route("/api/transactions/{id}", get(handlers::get_transaction))
+        .route(
+            "/api/transactions/{id}/propagation",
+            get(handlers::get_tx_propagation),
+        )
         .route("/api/mempool", get(handlers::get_mempool))
+        .route("/api/mempool/{id}", get(handlers::get_mempool_entry))
+        .route(
+            "/api/mempool/{id}/position",
+            get(handlers::get_mempool_position),
+        )
         // Wallets
         .route("/api/wallets", get(handlers::list_wallets))
         .route("/api/wallets", post(handlers::create_wallet))
@@ -100,6 +126,11 @@ pub fn create_router(state: ApiState) -> Router {
             "/api/wallets/{address}/balance",
             get(handlers::get_wallet_balance),
         )
+        .route(
+            "/api/address/{address}/history",
+            get(handlers::get_address_history),
+        )
+        .route("/api/verifymessage", post(handlers::verify_message))
         // Contracts
         .route("/api/contracts", get(handlers::list_contracts))
         .route("/api/contracts", post(handlers::deploy_contract))
@@ -166,10 +197,16 @@ pub fn create_router(state: ApiState) -> Router {
             "/api/tokens/{address}/history",
             get(handlers::get_token_history),
         )
+        .route(
+            "/api/tokens/{address}/distribution",
+            get(handlers::get_token_distribution),
+        )
         // Fee Estimation
         .route("/api/fees", get(handlers::get_fee_estimates))
         // Advanced Stats
         .route("/api/stats", get(handlers::get_advanced_stats))
+        // Network
+        .route("/api/network", get(handlers::get_network_info))
         // Search
         .route("/api/search", get(handlers::search))
         // Static files (Web UI)
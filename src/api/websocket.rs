@@ -3,6 +3,7 @@
 //! Provides a broadcast channel for pushing events to connected clients.
 
 use crate::api::handlers::{BlockInfo, TransactionResponse};
+use crate::core::{Blockchain, ChainEvent, EventBus};
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
@@ -11,12 +12,21 @@ use axum::{
     response::IntoResponse,
 };
 use futures::{SinkExt, StreamExt};
-use serde::Serialize;
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, RwLock};
 
-/// Maximum number of events to buffer per subscriber
-const BROADCAST_CAPACITY: usize = 100;
+/// Events buffered per client; a client that falls this far behind is
+/// dropped rather than buffered without bound
+pub const CLIENT_BUFFER_CAPACITY: usize = 100;
+
+/// Most transactions a single client may watch at once
+pub const MAX_WATCHED_TXS: usize = 100;
+
+/// Confirmations after which a watched transaction is no longer tracked
+pub const TX_WATCH_DEPTH: u64 = 6;
 
 /// WebSocket events that can be broadcast to clients
 #[derive(Clone, Debug, Serialize)]
@@ -26,6 +36,20 @@ pub enum WsEvent {
     BlockMined { block: BlockInfo, reward: u64 },
     /// A new transaction was added to the mempool
     TransactionAdded { transaction: TransactionResponse },
+    /// The active chain switched branches
+    Reorg {
+        old_tip_hash: String,
+        new_tip_hash: String,
+        new_height: u64,
+        disconnected: u64,
+        connected: u64,
+    },
+    /// A watched transaction is in a block with this many confirmations
+    TxConfirmed {
+        id: String,
+        block_height: u64,
+        confirmations: u64,
+    },
     /// Chain state was updated
     ChainUpdated {
         height: u64,
@@ -39,32 +63,55 @@ pub enum WsEvent {
 }
 
 /// Broadcaster for WebSocket events
+///
+/// Each client gets its own bounded queue. Broadcasting never waits on a
+/// client: one whose queue is full is disconnected.
 #[derive(Debug)]
 pub struct WsBroadcaster {
-    sender: broadcast::Sender<WsEvent>,
+    clients: Mutex<Vec<mpsc::Sender<WsEvent>>>,
 }
 
 impl WsBroadcaster {
     /// Create a new broadcaster
     pub fn new() -> Self {
-        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
-        Self { sender }
+        Self {
+            clients: Mutex::new(Vec::new()),
+        }
     }
 
-    /// Broadcast an event to all connected clients
+    fn clients(&self) -> MutexGuard<'_, Vec<mpsc::Sender<WsEvent>>> {
+        self.clients.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Broadcast an event to all connected clients, dropping any that
+    /// can't keep up
     pub fn broadcast(&self, event: WsEvent) {
-        // Ignore send errors (no subscribers)
-        let _ = self.sender.send(event);
+        self.clients()
+            .retain(|client| match client.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    log::warn!(
+                        "Dropping slow WebSocket client ({} events unread)",
+                        CLIENT_BUFFER_CAPACITY
+                    );
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
     }
 
-    /// Subscribe to events
-    pub fn subscribe(&self) -> broadcast::Receiver<WsEvent> {
-        self.sender.subscribe()
+    /// Subscribe to events; the receiver ends once the client is dropped
+    pub fn subscribe(&self) -> mpsc::Receiver<WsEvent> {
+        let (sender, receiver) = mpsc::channel(CLIENT_BUFFER_CAPACITY);
+        self.clients().push(sender);
+        receiver
     }
 
     /// Get the number of active subscribers
     pub fn subscriber_count(&self) -> usize {
-        self.sender.receiver_count()
+        let mut clients = self.clients();
+        clients.retain(|client| !client.is_closed());
+        clients.len()
     }
 }
 
@@ -74,18 +121,132 @@ impl Default for WsBroadcaster {
     }
 }
 
+impl From<&ChainEvent> for WsEvent {
+    fn from(event: &ChainEvent) -> Self {
+        match event {
+            ChainEvent::BlockConnected { block, reward } => WsEvent::BlockMined {
+                block: BlockInfo::from(block),
+                reward: *reward,
+            },
+            ChainEvent::TxAccepted { tx } => WsEvent::TransactionAdded {
+                transaction: TransactionResponse::from(tx),
+            },
+            ChainEvent::Reorg(reorg) => WsEvent::Reorg {
+                old_tip_hash: reorg.old_tip_hash.clone(),
+                new_tip_hash: reorg.new_tip_hash.clone(),
+                new_height: reorg.new_height,
+                disconnected: reorg.disconnected,
+                connected: reorg.connected,
+            },
+        }
+    }
+}
+
+/// Request sent by a client over the socket
+#[derive(Debug, Deserialize)]
+struct WsRequest {
+    /// Transaction to report confirmations for
+    watch_tx: Option<String>,
+}
+
+/// Confirmation tracking for the transactions one client watches
+#[derive(Debug, Default)]
+pub struct TxWatcher {
+    /// Watched txid -> confirmations last reported
+    watched: BTreeMap<String, u64>,
+}
+
+impl TxWatcher {
+    /// Create an empty watcher
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching a transaction; false once the watch limit is reached
+    pub fn watch(&mut self, id: String) -> bool {
+        if self.watched.len() >= MAX_WATCHED_TXS && !self.watched.contains_key(&id) {
+            return false;
+        }
+        self.watched.entry(id).or_insert(0);
+        true
+    }
+
+    /// Number of transactions being watched
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+
+    /// Whether no transactions are being watched
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    /// Report watched transactions whose confirmation count changed.
+    ///
+    /// Transactions reaching [`TX_WATCH_DEPTH`] are reported one last time
+    /// and then dropped. One that leaves the chain in a reorg is reported
+    /// again once it is reconfirmed.
+    pub fn poll(&mut self, blockchain: &Blockchain) -> Vec<WsEvent> {
+        let mut events = Vec::new();
+
+        self.watched.retain(|id, reported| {
+            let confirmations = blockchain.confirmations(id);
+            if confirmations == *reported {
+                return true;
+            }
+            *reported = confirmations;
+
+            if let Some((_, block_height, _, _)) = blockchain.get_tx_with_location(id) {
+                events.push(WsEvent::TxConfirmed {
+                    id: id.clone(),
+                    block_height,
+                    confirmations,
+                });
+            }
+            confirmations < TX_WATCH_DEPTH
+        });
+
+        events
+    }
+}
+
+/// Forward events from the bus to websocket clients until the bus closes
+pub fn spawn_event_forwarder(
+    events: &EventBus,
+    broadcaster: Arc<WsBroadcaster>,
+) -> tokio::task::JoinHandle<()> {
+    let mut rx = events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => broadcaster.broadcast(WsEvent::from(&event)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("WebSocket forwarder skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
 /// WebSocket upgrade handler
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<crate::api::handlers::ApiState>,
 ) -> impl IntoResponse {
     let broadcaster = state.ws_broadcaster.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, broadcaster))
+    let blockchain = state.blockchain.clone();
+    ws.on_upgrade(move |socket| handle_socket(socket, broadcaster, blockchain))
 }
 
 /// Handle a WebSocket connection
-async fn handle_socket(socket: WebSocket, broadcaster: Arc<WsBroadcaster>) {
+async fn handle_socket(
+    socket: WebSocket,
+    broadcaster: Arc<WsBroadcaster>,
+    blockchain: Arc<RwLock<Blockchain>>,
+) {
     let (mut sender, mut receiver) = socket.split();
+    let (watch_sender, mut watch_requests) = mpsc::channel::<String>(16);
 
     // Subscribe to broadcast events
     let mut rx = broadcaster.subscribe();
@@ -98,12 +259,36 @@ async fn handle_socket(socket: WebSocket, broadcaster: Arc<WsBroadcaster>) {
         let _ = sender.send(Message::Text(json.into())).await;
     }
 
-    // Spawn task to forward broadcast events to this client
+    // Spawn task to forward broadcast events (and watched-tx updates) to this client
     let mut send_task = tokio::spawn(async move {
-        while let Ok(event) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&event) {
-                if sender.send(Message::Text(json.into())).await.is_err() {
-                    break;
+        let mut watcher = TxWatcher::new();
+        loop {
+            let events = tokio::select! {
+                event = rx.recv() => {
+                    // Ends when the broadcaster dropped this client
+                    let Some(event) = event else { break };
+                    let chain_changed =
+                        matches!(event, WsEvent::BlockMined { .. } | WsEvent::Reorg { .. });
+                    let mut events = vec![event];
+                    if chain_changed && !watcher.is_empty() {
+                        events.extend(watcher.poll(&*blockchain.read().await));
+                    }
+                    events
+                }
+                Some(id) = watch_requests.recv() => {
+                    if !watcher.watch(id) {
+                        log::debug!("WebSocket client reached the watch limit");
+                    }
+                    // Report right away if the transaction is already confirmed
+                    watcher.poll(&*blockchain.read().await)
+                }
+            };
+
+            for event in events {
+                if let Ok(json) = serde_json::to_string(&event) {
+                    if sender.send(Message::Text(json.into())).await.is_err() {
+                        return;
+                    }
                 }
             }
         }
@@ -120,6 +305,11 @@ async fn handle_socket(socket: WebSocket, broadcaster: Arc<WsBroadcaster>) {
                 }
                 Ok(Message::Text(text)) => {
                     log::debug!("Received text message: {}", text);
+                    if let Ok(WsRequest { watch_tx: Some(id) }) = serde_json::from_str(&text) {
+                        if watch_sender.send(id).await.is_err() {
+                            break;
+                        }
+                    }
                 }
                 Err(e) => {
                     log::warn!("WebSocket error: {}", e);
@@ -160,6 +350,88 @@ mod tests {
         broadcaster.broadcast(WsEvent::Ping);
     }
 
+    #[test]
+    fn test_slow_client_is_dropped() {
+        let broadcaster = WsBroadcaster::new();
+        let mut stalled = broadcaster.subscribe();
+        let mut live = broadcaster.subscribe();
+        assert_eq!(broadcaster.subscriber_count(), 2);
+
+        for _ in 0..CLIENT_BUFFER_CAPACITY * 3 {
+            broadcaster.broadcast(WsEvent::Ping);
+            // The live client keeps up
+            assert!(matches!(live.try_recv(), Ok(WsEvent::Ping)));
+        }
+        assert_eq!(broadcaster.subscriber_count(), 1);
+
+        // The stalled client holds at most a full buffer, then is closed
+        let mut buffered = 0;
+        while stalled.try_recv().is_ok() {
+            buffered += 1;
+        }
+        assert_eq!(buffered, CLIENT_BUFFER_CAPACITY);
+        assert!(matches!(
+            stalled.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+
+        // Clients that went away are forgotten too
+        drop(live);
+        assert_eq!(broadcaster.subscriber_count(), 0);
+    }
+
+    #[test]
+    fn test_watched_tx_confirmations() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let tx = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 30)
+            .build_and_sign(&key_pair)
+            .unwrap();
+
+        let mut watcher = TxWatcher::new();
+        assert!(watcher.watch(tx.id.clone()));
+        assert!(watcher.poll(&blockchain).is_empty());
+
+        let confirmed = |events: Vec<WsEvent>| match events.as_slice() {
+            [WsEvent::TxConfirmed {
+                id,
+                block_height,
+                confirmations,
+            }] => {
+                assert_eq!(id, &tx.id);
+                assert_eq!(*block_height, 2);
+                *confirmations
+            }
+            other => panic!("unexpected events: {:?}", other),
+        };
+
+        blockchain.mine_block(vec![tx.clone()], "miner").unwrap();
+        assert_eq!(confirmed(watcher.poll(&blockchain)), 1);
+        assert!(watcher.poll(&blockchain).is_empty());
+
+        blockchain.mine_block(vec![], "miner").unwrap();
+        assert_eq!(confirmed(watcher.poll(&blockchain)), 2);
+
+        // Dropped once buried deep enough
+        for _ in 2..TX_WATCH_DEPTH {
+            blockchain.mine_block(vec![], "miner").unwrap();
+        }
+        assert_eq!(confirmed(watcher.poll(&blockchain)), TX_WATCH_DEPTH);
+        assert!(watcher.is_empty());
+    }
+
     #[test]
     fn test_event_serialization() {
         let event = WsEvent::BlockMined {
@@ -172,6 +444,9 @@ mod tests {
                 difficulty: 16,
                 nonce: 12345,
                 transactions: 1,
+                size: 250,
+                weight: 1000,
+                coinbase_message: None,
             },
             reward: 50,
         };
@@ -2,8 +2,8 @@
 //!
 //! Implements all command handlers for the CLI interface.
 
-use crate::core::Blockchain;
-use crate::mining::{Mempool, Miner};
+use crate::core::{Blockchain, ChainParams, Transaction, MAX_BLOCK_WEIGHT, MAX_COIN_DECIMALS};
+use crate::mining::{BlockAssembly, Mempool, Miner, BLOCK_WEIGHT_RESERVED};
 use crate::storage::{Storage, StorageConfig};
 use crate::wallet::WalletManager;
 use std::path::PathBuf;
@@ -60,7 +60,27 @@ impl AppState {
 }
 
 /// Initialize a new blockchain
-pub fn cmd_init(data_dir: &PathBuf, difficulty: Option<u32>) -> CliResult<()> {
+pub fn cmd_init(data_dir: &PathBuf, params: ChainParams) -> CliResult<()> {
+    if params.adjustment_interval == 0 {
+        return Err("Adjustment interval must be at least 1 block".into());
+    }
+    if params.target_block_time <= 0 {
+        return Err("Target block time must be positive".into());
+    }
+    if params.min_difficulty == 0 || params.min_difficulty > params.max_difficulty {
+        return Err("Difficulty range must satisfy 1 <= min <= max".into());
+    }
+    if params.coin_decimals > MAX_COIN_DECIMALS {
+        return Err(format!("Coin decimals must be at most {}", MAX_COIN_DECIMALS).into());
+    }
+    if !(params.min_difficulty..=params.max_difficulty).contains(&params.genesis_difficulty) {
+        return Err(format!(
+            "Difficulty {} outside allowed range [{}, {}]",
+            params.genesis_difficulty, params.min_difficulty, params.max_difficulty
+        )
+        .into());
+    }
+
     let storage_config = StorageConfig {
         data_dir: data_dir.clone(),
         ..Default::default()
@@ -74,16 +94,38 @@ pub fn cmd_init(data_dir: &PathBuf, difficulty: Option<u32>) -> CliResult<()> {
         return Ok(());
     }
 
-    let blockchain = match difficulty {
-        Some(d) => Blockchain::with_difficulty(d),
-        None => Blockchain::new(),
-    };
+    let blockchain = Blockchain::with_params(params);
+    let params = &blockchain.params;
 
     storage.save(&blockchain)?;
 
     println!("✅ Blockchain initialized!");
     println!("   📁 Data directory: {:?}", data_dir);
     println!("   🔧 Difficulty: {}", blockchain.difficulty);
+    println!("   ⏱️  Target block time: {}s", params.target_block_time);
+    println!(
+        "   🔁 Adjustment interval: {} blocks",
+        params.adjustment_interval
+    );
+    println!(
+        "   💰 Block reward: {}",
+        params.format_amount(params.block_reward)
+    );
+    if params.coin_decimals > 0 {
+        println!("   🔢 Coin decimals: {}", params.coin_decimals);
+    }
+    if !params.pow_algorithm.is_sha256d() {
+        println!("   ⛏️  Proof of work: {}", params.pow_algorithm);
+    }
+    if params.halving_interval > 0 {
+        println!(
+            "   ✂️  Halving interval: {} blocks",
+            params.halving_interval
+        );
+    }
+    if let Some(ref hash) = params.assume_valid {
+        println!("   ⏩ Assume valid: {}", hash);
+    }
     println!(
         "   🧱 Genesis block hash: {}",
         blockchain.latest_block().hash
@@ -93,15 +135,26 @@ pub fn cmd_init(data_dir: &PathBuf, difficulty: Option<u32>) -> CliResult<()> {
 }
 
 /// Mine a new block
-pub fn cmd_mine(state: &mut AppState, address: &str, count: u32) -> CliResult<()> {
-    let miner = Miner::new(address);
+pub fn cmd_mine(
+    state: &mut AppState,
+    address: &str,
+    count: u32,
+    assembly: BlockAssembly,
+    tag: Option<&str>,
+) -> CliResult<()> {
+    let miner = match tag {
+        Some(tag) => Miner::new_with_tag(address, tag.as_bytes()),
+        None => Miner::new(address),
+    };
 
     println!("⛏️  Mining {} block(s) for address: {}", count, address);
     println!("   Current difficulty: {}", state.blockchain.difficulty);
 
     for _ in 0..count {
         // Get transactions from mempool
-        let transactions = state.mempool.get_transactions(100);
+        let transactions = state
+            .mempool
+            .select_for_block(MAX_BLOCK_WEIGHT - BLOCK_WEIGHT_RESERVED, assembly);
         let tx_count = transactions.len();
 
         // Get transaction IDs before mining
@@ -124,7 +177,10 @@ pub fn cmd_mine(state: &mut AppState, address: &str, count: u32) -> CliResult<()
     }
 
     let balance = state.blockchain.get_balance(address);
-    println!("\n💰 New balance for miner: {} coins", balance);
+    println!(
+        "\n💰 New balance for miner: {} coins",
+        state.blockchain.params.format_amount(balance)
+    );
 
     Ok(())
 }
@@ -159,7 +215,12 @@ pub fn cmd_wallet_list(state: &mut AppState) -> CliResult<()> {
         let balance = state.blockchain.get_balance(address);
         let wallet = state.wallet_manager.load_wallet(address)?;
         let label = wallet.label.as_deref().unwrap_or("-");
-        println!("   {} ({}) - {} coins", address, label, balance);
+        println!(
+            "   {} ({}) - {} coins",
+            address,
+            label,
+            state.blockchain.params.format_amount(balance)
+        );
     }
 
     Ok(())
@@ -169,9 +230,10 @@ pub fn cmd_wallet_list(state: &mut AppState) -> CliResult<()> {
 pub fn cmd_wallet_balance(state: &AppState, address: &str) -> CliResult<()> {
     let balance = state.blockchain.get_balance(address);
     let utxos = state.blockchain.get_utxos_for_address(address);
+    let params = &state.blockchain.params;
 
     println!("💰 Balance for {}", address);
-    println!("   Total: {} coins", balance);
+    println!("   Total: {} coins", params.format_amount(balance));
     println!("   UTXOs: {}", utxos.len());
 
     if !utxos.is_empty() {
@@ -181,7 +243,7 @@ pub fn cmd_wallet_balance(state: &AppState, address: &str) -> CliResult<()> {
                 "   └─ {}:{} = {} coins",
                 &utxo.tx_id[..8],
                 utxo.output_index,
-                utxo.output.amount
+                params.format_amount(utxo.output.amount)
             );
         }
         if utxos.len() > 10 {
@@ -192,14 +254,37 @@ pub fn cmd_wallet_balance(state: &AppState, address: &str) -> CliResult<()> {
     Ok(())
 }
 
+/// Import a wallet from a WIF-encoded private key
+pub fn cmd_wallet_import(state: &AppState, key: &str, label: Option<&str>) -> CliResult<()> {
+    let wallet = state.wallet_manager.import_wallet(key, label)?;
+
+    println!("🔐 Wallet imported!");
+    println!("   📍 Address: {}", wallet.address());
+    if let Some(l) = &wallet.label {
+        println!("   🏷️  Label: {}", l);
+    }
+
+    Ok(())
+}
+
+/// Print a wallet's private key in WIF format
+pub fn cmd_wallet_export(state: &AppState, address: &str) -> CliResult<()> {
+    let wallet = state.wallet_manager.load_wallet(address)?;
+
+    println!("🔑 Private key for {}:", address);
+    println!("   {}", wallet.export_key());
+    println!("\n   ⚠️  Anyone with this key can spend the wallet's funds. Keep it secret!");
+
+    Ok(())
+}
+
 /// Send coins
 pub fn cmd_send(state: &mut AppState, from: &str, to: &str, amount: u64) -> CliResult<()> {
     // Load sender wallet
     let wallet = state.wallet_manager.load_wallet(from)?;
-    let balance = wallet.balance(&state.blockchain);
 
-    if balance < amount {
-        println!("❌ Insufficient funds: have {}, need {}", balance, amount);
+    if let Some(reason) = send_shortfall(&state.blockchain, &wallet.address(), amount) {
+        println!("❌ {}", reason);
         return Ok(());
     }
 
@@ -210,7 +295,10 @@ pub fn cmd_send(state: &mut AppState, from: &str, to: &str, amount: u64) -> CliR
     println!("   ID: {}", tx.id);
     println!("   From: {}", from);
     println!("   To: {}", to);
-    println!("   Amount: {} coins", amount);
+    println!(
+        "   Amount: {} coins",
+        state.blockchain.params.format_amount(amount)
+    );
 
     // Add to mempool
     state.mempool.add_transaction(tx, &state.blockchain)?;
@@ -221,6 +309,55 @@ pub fn cmd_send(state: &mut AppState, from: &str, to: &str, amount: u64) -> CliR
     Ok(())
 }
 
+/// Why `address` can't send `amount` right now, if it can't.
+///
+/// Distinguishes an empty wallet from one whose funds are still locked as
+/// immature coinbase rewards, and says when enough of them unlock.
+fn send_shortfall(blockchain: &Blockchain, address: &str, amount: u64) -> Option<String> {
+    let balance = blockchain.get_balance(address);
+    if balance < amount {
+        return Some(format!(
+            "Insufficient funds: have {}, need {}",
+            balance, amount
+        ));
+    }
+
+    let spendable = blockchain.get_spendable_balance(address);
+    if spendable >= amount {
+        return None;
+    }
+
+    // Unlock order of the immature rewards, soonest first
+    let mut immature: Vec<(u64, u64)> = blockchain
+        .get_utxos_for_address(address)
+        .iter()
+        .filter(|utxo| !blockchain.is_coinbase_mature(&utxo.tx_id))
+        .map(|utxo| {
+            (
+                blockchain.coinbase_blocks_until_mature(&utxo.tx_id),
+                utxo.output.amount,
+            )
+        })
+        .collect();
+    immature.sort_unstable();
+
+    let mut available = spendable;
+    let mut blocks = 0;
+    for (until_mature, amount_unlocked) in immature {
+        if available >= amount {
+            break;
+        }
+        available += amount_unlocked;
+        blocks = until_mature;
+    }
+
+    Some(format!(
+        "Only {} of {} coins are spendable; the rest are immature coinbase rewards. \
+         Enough will mature to send {} in {} more block(s)",
+        spendable, balance, amount, blocks
+    ))
+}
+
 /// Display blockchain info
 pub fn cmd_chain_info(state: &AppState) -> CliResult<()> {
     let stats = state.blockchain.stats();
@@ -229,7 +366,10 @@ pub fn cmd_chain_info(state: &AppState) -> CliResult<()> {
     println!("   ├─ Height: {}", stats.height);
     println!("   ├─ Total blocks: {}", stats.total_blocks);
     println!("   ├─ Total transactions: {}", stats.total_transactions);
-    println!("   ├─ Total coins: {}", stats.total_coins);
+    println!(
+        "   ├─ Total coins: {}",
+        state.blockchain.params.format_amount(stats.total_coins)
+    );
     println!("   ├─ Difficulty: {}", stats.difficulty);
     println!("   └─ Latest hash: {}...", &stats.latest_hash[..32]);
 
@@ -261,6 +401,42 @@ pub fn cmd_chain_blocks(state: &AppState, count: u32) -> CliResult<()> {
     Ok(())
 }
 
+/// Drop block bodies older than the most recent `keep` blocks
+pub fn cmd_chain_prune(state: &mut AppState, keep: u64) -> CliResult<()> {
+    let pruned = state.blockchain.convert_to_pruned(keep);
+    state.save()?;
+
+    match state.blockchain.prune_state() {
+        Some(prune) => {
+            println!("✂️  Pruned {} block bodies", pruned);
+            println!("   ├─ Pruned up to height: {}", prune.pruned_to);
+            println!("   ├─ Bytes saved: {}", prune.bytes_saved);
+            println!("   └─ UTXOs kept: {}", state.blockchain.utxo_set.len());
+        }
+        None => println!("Nothing to prune while keeping the last {} blocks", keep),
+    }
+
+    Ok(())
+}
+
+/// Summarize the UTXO set
+pub fn cmd_utxoset_info(state: &AppState) -> CliResult<()> {
+    let info = state.blockchain.utxo_set_info();
+
+    println!("💰 UTXO Set");
+    println!("   ├─ Height: {}", info.height);
+    println!("   ├─ Best hash: {}...", &info.best_hash[..32]);
+    println!("   ├─ Unspent outputs: {}", info.utxos);
+    println!(
+        "   ├─ Total amount: {}",
+        state.blockchain.params.format_amount(info.total_amount)
+    );
+    println!("   ├─ Addresses: {}", info.addresses);
+    println!("   └─ UTXO set hash: {}", info.hash);
+
+    Ok(())
+}
+
 /// Validate the blockchain
 pub fn cmd_validate(state: &AppState) -> CliResult<()> {
     println!("🔍 Validating blockchain...");
@@ -293,9 +469,173 @@ pub fn cmd_mempool(state: &AppState) -> CliResult<()> {
     Ok(())
 }
 
+/// Look up a transaction in the chain and print it as JSON or raw hex
+pub fn cmd_tx_get(state: &AppState, id: &str, raw_hex: bool) -> CliResult<()> {
+    let tx = state
+        .blockchain
+        .tx_heights
+        .get(id)
+        .and_then(|height| state.blockchain.get_block(*height))
+        .and_then(|block| block.transactions.iter().find(|tx| tx.id == id))
+        .or_else(|| state.mempool.get_transaction(id))
+        .ok_or_else(|| format!("Transaction not found: {}", id))?;
+
+    if raw_hex {
+        println!("{}", tx.to_raw_hex()?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(tx)?);
+    }
+
+    Ok(())
+}
+
+/// Decode a raw hex transaction
+pub fn decode_raw_transaction(raw_hex: &str) -> CliResult<Transaction> {
+    Ok(Transaction::from_raw_hex(raw_hex)?)
+}
+
+/// Decode a raw hex transaction and print its structure
+pub fn cmd_tx_decode(raw_hex: &str) -> CliResult<()> {
+    let tx = decode_raw_transaction(raw_hex)?;
+    println!("{}", serde_json::to_string_pretty(&tx)?);
+    Ok(())
+}
+
+/// File format for `export`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Full chain as JSON (can be re-imported)
+    #[default]
+    Json,
+    /// Flat CSV rows for spreadsheets and analysis
+    Csv,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            other => Err(format!(
+                "Unknown export format '{}' (expected json or csv)",
+                other
+            )),
+        }
+    }
+}
+
+/// Which records a CSV export contains
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportKind {
+    #[default]
+    Transactions,
+    Blocks,
+    Utxos,
+}
+
+impl std::str::FromStr for ExportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "transactions" | "txs" => Ok(Self::Transactions),
+            "blocks" => Ok(Self::Blocks),
+            "utxos" => Ok(Self::Utxos),
+            other => Err(format!(
+                "Unknown export kind '{}' (expected transactions, blocks or utxos)",
+                other
+            )),
+        }
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render chain data as CSV with a header row
+pub fn export_csv(blockchain: &Blockchain, what: ExportKind) -> String {
+    let mut out = String::new();
+
+    match what {
+        ExportKind::Transactions => {
+            out.push_str("txid,block_height,input_count,output_count,total_out,fee\n");
+            for block in &blockchain.blocks {
+                for tx in &block.transactions {
+                    let fee = if tx.is_coinbase {
+                        0
+                    } else {
+                        tx.inputs
+                            .iter()
+                            .filter_map(|i| blockchain.input_value(&i.tx_id, i.output_index))
+                            .sum::<u64>()
+                            .saturating_sub(tx.total_output())
+                    };
+                    out.push_str(&format!(
+                        "{},{},{},{},{},{}\n",
+                        tx.id,
+                        block.index,
+                        tx.inputs.len(),
+                        tx.outputs.len(),
+                        tx.total_output(),
+                        fee
+                    ));
+                }
+            }
+        }
+        ExportKind::Blocks => {
+            out.push_str("height,hash,previous_hash,timestamp,difficulty,nonce,tx_count,size\n");
+            for block in &blockchain.blocks {
+                out.push_str(&format!(
+                    "{},{},{},{},{},{},{},{}\n",
+                    block.index,
+                    block.hash,
+                    block.header.previous_hash,
+                    block.header.timestamp.timestamp(),
+                    block.header.difficulty,
+                    block.header.nonce,
+                    block.transactions.len(),
+                    block.size()
+                ));
+            }
+        }
+        ExportKind::Utxos => {
+            out.push_str("txid,output_index,recipient,amount\n");
+            let mut utxos: Vec<_> = blockchain.utxo_set.values().collect();
+            utxos.sort_by(|a, b| (&a.tx_id, a.output_index).cmp(&(&b.tx_id, b.output_index)));
+            for utxo in utxos {
+                out.push_str(&format!(
+                    "{},{},{},{}\n",
+                    utxo.tx_id,
+                    utxo.output_index,
+                    csv_field(&utxo.output.recipient),
+                    utxo.output.amount
+                ));
+            }
+        }
+    }
+
+    out
+}
+
 /// Export blockchain to file
-pub fn cmd_export(state: &AppState, path: &PathBuf) -> CliResult<()> {
-    crate::storage::save_to_file(&state.blockchain, path)?;
+pub fn cmd_export(
+    state: &AppState,
+    path: &PathBuf,
+    format: ExportFormat,
+    what: ExportKind,
+) -> CliResult<()> {
+    match format {
+        ExportFormat::Json => crate::storage::save_to_file(&state.blockchain, path)?,
+        ExportFormat::Csv => std::fs::write(path, export_csv(&state.blockchain, what))?,
+    }
     println!("📦 Blockchain exported to {:?}", path);
     Ok(())
 }
@@ -317,3 +657,199 @@ pub fn cmd_import(state: &mut AppState, path: &PathBuf) -> CliResult<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{TransactionBuilder, COINBASE_MATURITY};
+    use crate::crypto::KeyPair;
+
+    #[test]
+    fn test_raw_transaction_roundtrip() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let tx = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 30)
+            .with_rbf()
+            .build_and_sign(&key_pair)
+            .unwrap();
+
+        let decoded = decode_raw_transaction(&tx.to_raw_hex().unwrap()).unwrap();
+
+        assert_eq!(decoded.id, tx.id);
+        assert_eq!(decoded.inputs, tx.inputs);
+        assert_eq!(decoded.outputs, tx.outputs);
+        assert_eq!(decoded.fee, tx.fee);
+        assert_eq!(decoded.locktime, tx.locktime);
+        assert_eq!(decoded.timestamp, tx.timestamp);
+        assert!(decoded.verify_signatures().unwrap());
+
+        assert!(cmd_tx_decode(&tx.to_raw_hex().unwrap()).is_ok());
+        assert!(decode_raw_transaction("not hex").is_err());
+        assert!(decode_raw_transaction("deadbeef").is_err());
+    }
+
+    #[test]
+    fn test_send_reports_immature_coinbase() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::new(temp_dir.path().to_path_buf()).unwrap();
+        let wallet = state.wallet_manager.create_wallet(None).unwrap();
+        let address = wallet.address();
+        cmd_mine(&mut state, &address, 1, BlockAssembly::Greedy, None).unwrap();
+
+        let reason = send_shortfall(&state.blockchain, &address, 10).unwrap();
+        assert!(reason.contains("immature coinbase"));
+        assert!(!reason.contains("Insufficient funds"));
+        assert!(reason.contains(&format!("in {} more block(s)", COINBASE_MATURITY)));
+
+        // The send is refused without touching the mempool
+        cmd_send(&mut state, &address, "recipient", 10).unwrap();
+        assert!(state.mempool.is_empty());
+
+        let reason = send_shortfall(&state.blockchain, &address, 1_000_000).unwrap();
+        assert!(reason.starts_with("Insufficient funds"));
+    }
+
+    #[test]
+    fn test_utxoset_info() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::new(temp_dir.path().to_path_buf()).unwrap();
+        state.blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let genesis = state.blockchain.utxo_set_info();
+
+        let block = state
+            .blockchain
+            .mine_block(vec![], &key_pair.address())
+            .unwrap();
+        state.blockchain.mine_block(vec![], "miner").unwrap();
+        let utxo = state
+            .blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("alice", 20)
+            .add_output("bob", 20)
+            .add_output(&key_pair.address(), 5)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        state.blockchain.mine_block(vec![spend], "miner").unwrap();
+
+        // Three coinbases minus the 5-coin fee the third block didn't claim;
+        // the spent coinbase is gone, its three outputs took its place
+        let info = state.blockchain.utxo_set_info();
+        assert_eq!(info.height, 3);
+        assert_eq!(info.utxos, genesis.utxos + 5);
+        assert_eq!(info.total_amount, genesis.total_amount + 3 * 50 - 5);
+        assert_eq!(info.addresses, genesis.addresses + 4);
+        assert_eq!(info.hash, state.blockchain.utxo_set_hash());
+        assert!(cmd_utxoset_info(&state).is_ok());
+
+        // The commitment changes with the set
+        state.blockchain.mine_block(vec![], "miner").unwrap();
+        assert_ne!(state.blockchain.utxo_set_hash(), info.hash);
+    }
+
+    #[test]
+    fn test_init_with_custom_params_survives_reload() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+        let params = ChainParams {
+            genesis_difficulty: 3,
+            target_block_time: 60,
+            adjustment_interval: 20,
+            block_reward: 25,
+            halving_interval: 1,
+            ..ChainParams::default()
+        };
+
+        cmd_init(&data_dir, params.clone()).unwrap();
+
+        let mut state = AppState::new(data_dir).unwrap();
+        assert_eq!(state.blockchain.params, params);
+        assert_eq!(state.blockchain.difficulty, 3);
+
+        cmd_mine(&mut state, "miner", 1, BlockAssembly::Greedy, None).unwrap();
+        assert_eq!(state.blockchain.get_balance("miner"), 12);
+
+        let other_dir = temp_dir.path().join("other");
+        let invalid = ChainParams {
+            adjustment_interval: 0,
+            ..params.clone()
+        };
+        assert!(cmd_init(&other_dir, invalid).is_err());
+        let below_floor = ChainParams {
+            min_difficulty: 4,
+            ..params
+        };
+        assert!(cmd_init(&other_dir, below_floor).is_err());
+    }
+
+    #[test]
+    fn test_export_transactions_csv() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+        let mut state = AppState::new(data_dir.clone()).unwrap();
+        state.blockchain.params.coinbase_maturity = 0;
+
+        let key_pair = KeyPair::generate();
+        let block = state
+            .blockchain
+            .mine_block(vec![], &key_pair.address())
+            .unwrap();
+        let utxo = state
+            .blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 30)
+            .add_output(&key_pair.address(), 12)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        state
+            .blockchain
+            .mine_block(vec![spend.clone()], "miner")
+            .unwrap();
+
+        let path = data_dir.join("txs.csv");
+        cmd_export(&state, &path, ExportFormat::Csv, ExportKind::Transactions).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "txid,block_height,input_count,output_count,total_out,fee"
+        );
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+        let tx_count: usize = state
+            .blockchain
+            .blocks
+            .iter()
+            .map(|b| b.transactions.len())
+            .sum();
+        assert_eq!(rows.len(), tx_count);
+        assert!(rows.iter().all(|row| row.len() == 6));
+
+        let row = rows.iter().find(|row| row[0] == spend.id).unwrap();
+        assert_eq!(row[1], "2");
+        assert_eq!(row[2], "1");
+        assert_eq!(row[3], "2");
+        assert_eq!(row[4], "42");
+        assert_eq!(row[5].parse::<u64>().unwrap(), utxo.output.amount - 42);
+
+        let blocks = export_csv(&state.blockchain, ExportKind::Blocks);
+        assert_eq!(blocks.lines().count(), state.blockchain.blocks.len() + 1);
+        let utxos = export_csv(&state.blockchain, ExportKind::Utxos);
+        assert_eq!(utxos.lines().count(), state.blockchain.utxo_set.len() + 1);
+    }
+}
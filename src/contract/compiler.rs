@@ -142,6 +142,7 @@ impl Compiler {
             "HALT" => self.code.push(OpCode::Halt as u8),
             "RETURN" => self.code.push(OpCode::Return as u8),
             "REVERT" => self.code.push(OpCode::Revert as u8),
+            "SELFDESTRUCT" => self.code.push(OpCode::SelfDestruct as u8),
 
             // Storage
             "SSTORE" => self.code.push(OpCode::SStore as u8),
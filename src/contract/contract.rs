@@ -2,12 +2,24 @@
 //!
 //! Handles contract deployment, storage, and invocation.
 
-use crate::contract::vm::{ExecutionContext, ExecutionResult, VmError, DEFAULT_GAS_LIMIT, VM};
+use crate::contract::vm::{
+    BalanceLookup, ExecutionContext, ExecutionResult, GasSchedule, VmError, DEFAULT_GAS_LIMIT, VM,
+};
 use crate::crypto::hash::sha256;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
+/// Maximum deployed bytecode size in bytes (EIP-170: 24KB)
+pub const MAX_CONTRACT_CODE_SIZE: usize = 24_576;
+
+/// Largest gas limit a single call may request, unless configured otherwise
+pub const DEFAULT_MAX_GAS_LIMIT: u64 = 1_000_000;
+
+/// Address that burned gas is sent to
+pub const GAS_BURN_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
 /// Contract errors
 #[derive(Error, Debug)]
 pub enum ContractError {
@@ -19,8 +31,51 @@ pub enum ContractError {
     VmError(#[from] VmError),
     #[error("Invalid bytecode")]
     InvalidBytecode,
+    #[error("Contract code too large: {0} bytes (max: {1})")]
+    CodeTooLarge(usize, usize),
     #[error("Deployment failed: {0}")]
     DeploymentFailed(String),
+    #[error("Gas limit too high: {0} (max: {1})")]
+    GasLimitTooHigh(u64, u64),
+}
+
+/// Where coins paid for gas end up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GasDestination {
+    /// Sent to `GAS_BURN_ADDRESS`, removing them from circulation
+    #[default]
+    Burn,
+    /// Left as a transaction fee for the miner of the next block
+    Miner,
+}
+
+impl std::str::FromStr for GasDestination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "burn" => Ok(Self::Burn),
+            "miner" => Ok(Self::Miner),
+            other => Err(format!(
+                "Unknown gas destination '{}' (expected burn or miner)",
+                other
+            )),
+        }
+    }
+}
+
+/// Optional parameters for a contract call
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// Gas limit (defaults to `DEFAULT_GAS_LIMIT`)
+    pub gas_limit: Option<u64>,
+    /// Coins sent from the caller to the contract with the call
+    pub value: u64,
+    /// Record an instruction trace in the result
+    pub trace: bool,
+    /// Address balances visible to `BALANCE`
+    pub balance_lookup: BalanceLookup,
 }
 
 /// A deployed smart contract
@@ -34,8 +89,14 @@ pub struct Contract {
     pub storage: HashMap<String, u64>,
     /// Deployer address
     pub deployer: String,
+    /// Coin balance held by the contract
+    #[serde(default)]
+    pub balance: u64,
     /// Block number when deployed
     pub deployed_at: u64,
+    /// Gas consumed by the init run at deploy time (charged to the deployer)
+    #[serde(default)]
+    pub init_gas_used: u64,
 }
 
 impl Contract {
@@ -46,7 +107,56 @@ impl Contract {
             code,
             storage: HashMap::new(),
             deployer,
+            balance: 0,
             deployed_at: block_number,
+            init_gas_used: 0,
+        }
+    }
+
+    /// Run the contract code once as its constructor.
+    ///
+    /// The bytecode is executed with an empty stack and no arguments; any
+    /// storage it writes becomes the contract's initial storage. An explicit
+    /// `REVERT` or running out of `gas_limit` aborts the deployment, while
+    /// code that faults for other reasons (e.g. it expects call arguments)
+    /// is treated as having no constructor and leaves storage empty.
+    pub fn initialize(
+        &mut self,
+        block_number: u64,
+        gas_limit: u64,
+        gas_schedule: &GasSchedule,
+    ) -> Result<(), ContractError> {
+        let deployer = self.deployer.clone();
+        let options = CallOptions {
+            gas_limit: Some(gas_limit),
+            ..CallOptions::default()
+        };
+        match self.execute_with_options(
+            &deployer,
+            Vec::new(),
+            0,
+            block_number,
+            &options,
+            gas_schedule,
+        ) {
+            Ok(result) if result.self_destruct.is_some() => Err(ContractError::DeploymentFailed(
+                "constructor self-destructed".to_string(),
+            )),
+            Ok(result) => {
+                self.init_gas_used = result.gas_used;
+                Ok(())
+            }
+            Err(VmError::Reverted) => Err(ContractError::DeploymentFailed(
+                "constructor reverted".to_string(),
+            )),
+            Err(VmError::OutOfGas) => Err(ContractError::DeploymentFailed(format!(
+                "constructor ran out of gas (limit {})",
+                gas_limit
+            ))),
+            Err(e) => {
+                log::debug!("Contract {} has no runnable init code: {}", self.address, e);
+                Ok(())
+            }
         }
     }
 
@@ -59,34 +169,100 @@ impl Contract {
         block_number: u64,
         gas_limit: Option<u64>,
     ) -> Result<ExecutionResult, VmError> {
+        let options = CallOptions {
+            gas_limit,
+            ..CallOptions::default()
+        };
+        self.execute_with_options(
+            caller,
+            args,
+            timestamp,
+            block_number,
+            &options,
+            &GasSchedule::default(),
+        )
+    }
+
+    /// Execute the contract under a gas schedule with the given call options.
+    ///
+    /// Any call value is visible to the code via `SELFBALANCE` and is only
+    /// credited to the contract if execution succeeds.
+    pub fn execute_with_options(
+        &mut self,
+        caller: &str,
+        args: Vec<u64>,
+        timestamp: u64,
+        block_number: u64,
+        options: &CallOptions,
+        gas_schedule: &GasSchedule,
+    ) -> Result<ExecutionResult, VmError> {
+        let balance = self.balance.saturating_add(options.value);
         let context = ExecutionContext {
             caller: caller.to_string(),
             contract_address: self.address.clone(),
             timestamp,
             block_number,
             args,
-            gas_limit: gas_limit.unwrap_or(DEFAULT_GAS_LIMIT),
+            gas_limit: options.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT),
+            balance,
         };
 
         let mut vm = VM::new(self.code.clone(), self.storage.clone(), context);
+        vm.set_gas_schedule(gas_schedule.clone());
+        vm.set_balance_lookup(options.balance_lookup.clone());
+        if options.trace {
+            vm.enable_trace();
+        }
         let result = vm.execute()?;
 
         // Apply storage changes
         for (key, value) in &result.storage_changes {
             self.storage.insert(key.clone(), *value);
         }
+        self.balance = balance;
+
+        // The balance leaves with the beneficiary transfer
+        if result.self_destruct.is_some() {
+            self.balance = 0;
+        }
 
         Ok(result)
     }
 }
 
 /// Manages all deployed contracts
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContractManager {
     /// All deployed contracts
     contracts: HashMap<String, Contract>,
     /// Deployment counter for address generation
     nonce: u64,
+    /// Gas costs applied to deploys and calls (runtime config, not persisted)
+    #[serde(skip, default)]
+    gas_schedule: GasSchedule,
+    /// Maximum bytecode size accepted by `deploy` (runtime config, not persisted)
+    #[serde(skip, default = "default_max_code_size")]
+    max_code_size: usize,
+    /// Where gas payments go (runtime config, not persisted)
+    #[serde(skip, default)]
+    gas_destination: GasDestination,
+    /// Largest gas limit a call may request (runtime config, not persisted)
+    #[serde(skip, default = "default_max_gas_limit")]
+    max_gas_limit: u64,
+}
+
+fn default_max_code_size() -> usize {
+    MAX_CONTRACT_CODE_SIZE
+}
+
+fn default_max_gas_limit() -> u64 {
+    DEFAULT_MAX_GAS_LIMIT
+}
+
+impl Default for ContractManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ContractManager {
@@ -95,9 +271,59 @@ impl ContractManager {
         Self {
             contracts: HashMap::new(),
             nonce: 0,
+            gas_schedule: GasSchedule::default(),
+            max_code_size: MAX_CONTRACT_CODE_SIZE,
+            gas_destination: GasDestination::default(),
+            max_gas_limit: DEFAULT_MAX_GAS_LIMIT,
         }
     }
 
+    /// Set the maximum bytecode size accepted by `deploy`
+    pub fn set_max_code_size(&mut self, max_code_size: usize) {
+        self.max_code_size = max_code_size;
+    }
+
+    /// Set the gas schedule used for subsequent deploys and calls
+    pub fn set_gas_schedule(&mut self, schedule: GasSchedule) {
+        self.gas_schedule = schedule;
+    }
+
+    /// Get the active gas schedule
+    pub fn gas_schedule(&self) -> &GasSchedule {
+        &self.gas_schedule
+    }
+
+    /// Set where gas payments for calls are sent
+    pub fn set_gas_destination(&mut self, destination: GasDestination) {
+        self.gas_destination = destination;
+    }
+
+    /// Get where gas payments for calls are sent
+    pub fn gas_destination(&self) -> GasDestination {
+        self.gas_destination
+    }
+
+    /// Set the largest gas limit a call may request
+    pub fn set_max_gas_limit(&mut self, max_gas_limit: u64) {
+        self.max_gas_limit = max_gas_limit;
+    }
+
+    /// Get the largest gas limit a call may request
+    pub fn max_gas_limit(&self) -> u64 {
+        self.max_gas_limit
+    }
+
+    /// Reject a requested gas limit above the configured ceiling
+    pub fn check_gas_limit(&self, gas_limit: u64) -> Result<(), ContractError> {
+        if gas_limit > self.max_gas_limit {
+            return Err(ContractError::GasLimitTooHigh(
+                gas_limit,
+                self.max_gas_limit,
+            ));
+        }
+        Ok(())
+    }
+
     /// Deploy a new contract
     pub fn deploy(
         &mut self,
@@ -105,9 +331,26 @@ impl ContractManager {
         deployer: &str,
         block_number: u64,
     ) -> Result<String, ContractError> {
+        self.deploy_with_gas_limit(code, deployer, block_number, None)
+    }
+
+    /// Deploy a new contract, running its init code under `gas_limit`
+    /// (default [`DEFAULT_GAS_LIMIT`]) with the same ceiling as calls
+    pub fn deploy_with_gas_limit(
+        &mut self,
+        code: Vec<u8>,
+        deployer: &str,
+        block_number: u64,
+        gas_limit: Option<u64>,
+    ) -> Result<String, ContractError> {
+        let gas_limit = gas_limit.unwrap_or(DEFAULT_GAS_LIMIT);
+        self.check_gas_limit(gas_limit)?;
         if code.is_empty() {
             return Err(ContractError::InvalidBytecode);
         }
+        if code.len() > self.max_code_size {
+            return Err(ContractError::CodeTooLarge(code.len(), self.max_code_size));
+        }
 
         // Generate contract address
         let address = self.generate_address(deployer);
@@ -117,10 +360,15 @@ impl ContractManager {
             return Err(ContractError::AlreadyExists(address));
         }
 
-        let contract = Contract::new(address.clone(), code, deployer.to_string(), block_number);
-        self.contracts.insert(address.clone(), contract);
+        let mut contract = Contract::new(address.clone(), code, deployer.to_string(), block_number);
+        contract.initialize(block_number, gas_limit, &self.gas_schedule)?;
 
-        log::info!("Contract deployed at {}", address);
+        log::info!(
+            "Contract deployed at {} (init gas: {})",
+            address,
+            contract.init_gas_used
+        );
+        self.contracts.insert(address.clone(), contract);
         Ok(address)
     }
 
@@ -134,15 +382,82 @@ impl ContractManager {
         block_number: u64,
         gas_limit: Option<u64>,
     ) -> Result<ExecutionResult, ContractError> {
+        let options = CallOptions {
+            gas_limit,
+            ..CallOptions::default()
+        };
+        self.call_with_options(address, caller, args, timestamp, block_number, options)
+    }
+
+    /// Call a contract with a value transfer and/or instruction trace
+    pub fn call_with_options(
+        &mut self,
+        address: &str,
+        caller: &str,
+        args: Vec<u64>,
+        timestamp: u64,
+        block_number: u64,
+        mut options: CallOptions,
+    ) -> Result<ExecutionResult, ContractError> {
+        if !self.contracts.contains_key(address) {
+            return Err(ContractError::NotFound(address.to_string()));
+        }
+        self.check_gas_limit(options.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT))?;
+        self.add_contract_balances(&mut options.balance_lookup);
+
         let contract = self
             .contracts
             .get_mut(address)
             .ok_or_else(|| ContractError::NotFound(address.to_string()))?;
 
-        let result = contract.execute(caller, args, timestamp, block_number, gas_limit)?;
+        let result = contract.execute_with_options(
+            caller,
+            args,
+            timestamp,
+            block_number,
+            &options,
+            &self.gas_schedule,
+        )?;
+
+        if let Some(beneficiary) = &result.self_destruct {
+            self.contracts.remove(address);
+            log::info!(
+                "Contract {} self-destructed (beneficiary: {})",
+                address,
+                beneficiary
+            );
+        }
+
         Ok(result)
     }
 
+    /// Resolve contract addresses from their tracked balances, falling back
+    /// to the lookup's own resolver for everything else
+    fn add_contract_balances(&self, lookup: &mut BalanceLookup) {
+        let balances: HashMap<String, u64> = self
+            .contracts
+            .iter()
+            .map(|(address, contract)| (address.clone(), contract.balance))
+            .collect();
+        for address in balances.keys() {
+            lookup.add_address(address);
+        }
+
+        let fallback = lookup.resolver().cloned();
+        lookup.set_resolver(Arc::new(move |address: &str| {
+            balances
+                .get(address)
+                .copied()
+                .or_else(|| fallback.as_ref().map(|resolve| resolve(address)))
+                .unwrap_or(0)
+        }));
+    }
+
+    /// Put back a contract as it was before a call that could not be paid for
+    pub fn restore(&mut self, contract: Contract) {
+        self.contracts.insert(contract.address.clone(), contract);
+    }
+
     /// Get a contract by address
     pub fn get(&self, address: &str) -> Option<&Contract> {
         self.contracts.get(address)
@@ -172,6 +487,7 @@ impl ContractManager {
 mod tests {
     use super::*;
     use crate::contract::opcodes::OpCode;
+    use crate::contract::vm::address_hash;
 
     fn make_push(value: u64) -> Vec<u8> {
         let mut bytes = vec![OpCode::Push as u8];
@@ -235,4 +551,229 @@ mod tests {
         let contract = manager.get(&address).unwrap();
         assert!(contract.storage.values().any(|&v| v == 100));
     }
+
+    #[test]
+    fn test_contract_init_sets_storage() {
+        let mut manager = ContractManager::new();
+
+        // Init: store 42 at slot 0, then return slot 0
+        let mut code = make_push(0);
+        code.extend(make_push(42));
+        code.push(OpCode::SStore as u8);
+        code.extend(make_push(0));
+        code.push(OpCode::SLoad as u8);
+        code.push(OpCode::Return as u8);
+
+        let address = manager.deploy(code, "deployer", 1).unwrap();
+
+        let contract = manager.get(&address).unwrap();
+        assert_eq!(contract.storage.get(&format!("{:016x}", 0)), Some(&42));
+        assert!(contract.init_gas_used > 0);
+
+        let result = manager
+            .call(&address, "caller", vec![], 0, 2, None)
+            .unwrap();
+        assert_eq!(result.return_value, Some(42));
+    }
+
+    #[test]
+    fn test_contract_init_respects_gas_limit() {
+        let mut manager = ContractManager::new();
+
+        // Init loops forever: JUMP back to offset 0
+        let code = vec![OpCode::Jump as u8, 0, 0, 0, 0];
+
+        let err = manager.deploy_with_gas_limit(code.clone(), "deployer", 1, Some(500));
+        assert!(matches!(err, Err(ContractError::DeploymentFailed(_))));
+        assert_eq!(manager.count(), 0);
+
+        manager.set_max_gas_limit(1_000);
+        let err = manager.deploy_with_gas_limit(code, "deployer", 1, Some(5_000));
+        assert!(matches!(
+            err,
+            Err(ContractError::GasLimitTooHigh(5_000, 1_000))
+        ));
+    }
+
+    #[test]
+    fn test_contract_self_destruct() {
+        let mut manager = ContractManager::new();
+
+        // Self-destruct to the beneficiary passed as arg 0
+        let code = vec![OpCode::Arg as u8, 0, OpCode::SelfDestruct as u8];
+        let address = manager.deploy(code, "deployer", 1).unwrap();
+        manager.contracts.get_mut(&address).unwrap().balance = 750;
+
+        let result = manager
+            .call(&address, "caller", vec![0xbeef], 0, 2, None)
+            .unwrap();
+
+        assert!(!manager.list().contains(&address));
+        assert!(manager.get(&address).is_none());
+        assert_eq!(result.transfers, vec![(format!("{:016x}", 0xbeef), 750)]);
+    }
+
+    #[test]
+    fn test_custom_gas_schedule() {
+        // Store arg 0 at key 1
+        let mut code = make_push(1);
+        code.push(OpCode::Arg as u8);
+        code.push(0);
+        code.push(OpCode::SStore as u8);
+        code.push(OpCode::Halt as u8);
+
+        let mut manager = ContractManager::new();
+        let address = manager.deploy(code.clone(), "deployer", 1).unwrap();
+        let base = manager
+            .call(&address, "caller", vec![7], 0, 1, None)
+            .unwrap()
+            .gas_used;
+
+        let sstore_cost = GasSchedule::default().cost(&OpCode::SStore);
+        let mut schedule = GasSchedule::default();
+        schedule.set_cost(OpCode::SStore, sstore_cost * 2);
+
+        let mut manager = ContractManager::new();
+        manager.set_gas_schedule(schedule);
+        let address = manager.deploy(code, "deployer", 1).unwrap();
+        let doubled = manager
+            .call(&address, "caller", vec![7], 0, 1, None)
+            .unwrap()
+            .gas_used;
+
+        // Only the SStore share of the gas doubles
+        assert_eq!(doubled - base, sstore_cost);
+    }
+
+    #[test]
+    fn test_max_code_size() {
+        let mut manager = ContractManager::new();
+
+        let at_limit = vec![OpCode::Nop as u8; MAX_CONTRACT_CODE_SIZE];
+        assert!(manager.deploy(at_limit, "deployer", 1).is_ok());
+
+        let over_limit = vec![OpCode::Nop as u8; MAX_CONTRACT_CODE_SIZE + 1];
+        assert!(matches!(
+            manager.deploy(over_limit, "deployer", 1),
+            Err(ContractError::CodeTooLarge(n, MAX_CONTRACT_CODE_SIZE)) if n == MAX_CONTRACT_CODE_SIZE + 1
+        ));
+
+        manager.set_max_code_size(16);
+        assert!(manager
+            .deploy(vec![OpCode::Nop as u8; 16], "deployer", 1)
+            .is_ok());
+        assert!(manager
+            .deploy(vec![OpCode::Nop as u8; 17], "deployer", 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_payable_call() {
+        let mut manager = ContractManager::new();
+
+        let code = vec![OpCode::SelfBalance as u8, OpCode::Return as u8];
+        let address = manager.deploy(code, "deployer", 1).unwrap();
+
+        let options = CallOptions {
+            value: 300,
+            ..CallOptions::default()
+        };
+        let result = manager
+            .call_with_options(&address, "caller", vec![], 0, 2, options.clone())
+            .unwrap();
+        assert_eq!(result.return_value, Some(300));
+        assert_eq!(manager.get(&address).unwrap().balance, 300);
+
+        let result = manager
+            .call_with_options(&address, "caller", vec![], 0, 3, options)
+            .unwrap();
+        assert_eq!(result.return_value, Some(600));
+    }
+
+    #[test]
+    fn test_self_balance_after_value_sent() {
+        let mut manager = ContractManager::new();
+
+        let code = vec![OpCode::SelfBalance as u8, OpCode::Return as u8];
+        let address = manager.deploy(code, "deployer", 1).unwrap();
+
+        let options = CallOptions {
+            value: 250,
+            ..CallOptions::default()
+        };
+        manager
+            .call_with_options(&address, "caller", vec![], 0, 2, options)
+            .unwrap();
+
+        // A later plain call sees the stored balance
+        let result = manager
+            .call(&address, "caller", vec![], 0, 3, None)
+            .unwrap();
+        assert_eq!(result.return_value, Some(250));
+    }
+
+    #[test]
+    fn test_balance_of_other_contract_and_account() {
+        let mut manager = ContractManager::new();
+
+        let vault = manager
+            .deploy(vec![OpCode::Halt as u8], "deployer", 1)
+            .unwrap();
+        manager.contracts.get_mut(&vault).unwrap().balance = 900;
+
+        // Return BALANCE(arg 0) + BALANCE(arg 1)
+        let code = vec![
+            OpCode::Arg as u8,
+            0,
+            OpCode::Balance as u8,
+            OpCode::Arg as u8,
+            1,
+            OpCode::Balance as u8,
+            OpCode::Add as u8,
+            OpCode::Return as u8,
+        ];
+        let reader = manager.deploy(code, "deployer", 1).unwrap();
+
+        let mut lookup =
+            BalanceLookup::new(Arc::new(|addr: &str| if addr == "alice" { 40 } else { 0 }));
+        lookup.add_address("alice");
+        let options = CallOptions {
+            balance_lookup: lookup,
+            ..CallOptions::default()
+        };
+        let args = vec![address_hash(&vault), address_hash("alice")];
+        let result = manager
+            .call_with_options(&reader, "caller", args, 0, 2, options)
+            .unwrap();
+
+        assert_eq!(result.return_value, Some(940));
+    }
+
+    #[test]
+    fn test_payable_call_reverted_keeps_balance() {
+        let mut manager = ContractManager::new();
+
+        let code = vec![OpCode::Arg as u8, 0, OpCode::Revert as u8];
+        let address = manager.deploy(code, "deployer", 1).unwrap();
+
+        let options = CallOptions {
+            value: 300,
+            ..CallOptions::default()
+        };
+        assert!(manager
+            .call_with_options(&address, "caller", vec![1], 0, 2, options)
+            .is_err());
+        assert_eq!(manager.get(&address).unwrap().balance, 0);
+    }
+
+    #[test]
+    fn test_contract_init_revert_fails_deploy() {
+        let mut manager = ContractManager::new();
+
+        let code = vec![OpCode::Revert as u8];
+        let result = manager.deploy(code, "deployer", 1);
+
+        assert!(matches!(result, Err(ContractError::DeploymentFailed(_))));
+        assert_eq!(manager.count(), 0);
+    }
 }
@@ -37,6 +37,12 @@ pub mod opcodes;
 pub mod vm;
 
 pub use compiler::{disassemble, Compiler, CompilerError};
-pub use contract::{Contract, ContractError, ContractManager};
+pub use contract::{
+    CallOptions, Contract, ContractError, ContractManager, GasDestination, DEFAULT_MAX_GAS_LIMIT,
+    GAS_BURN_ADDRESS, MAX_CONTRACT_CODE_SIZE,
+};
 pub use opcodes::OpCode;
-pub use vm::{ExecutionContext, ExecutionResult, VmError, DEFAULT_GAS_LIMIT, VM};
+pub use vm::{
+    address_hash, BalanceLookup, BalanceResolver, ExecutionContext, ExecutionResult, GasSchedule,
+    HashBalanceResolver, TraceStep, VmError, DEFAULT_GAS_LIMIT, MAX_REFUND_QUOTIENT, VM,
+};
@@ -5,7 +5,7 @@
 use serde::{Deserialize, Serialize};
 
 /// Opcodes for the smart contract VM
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum OpCode {
     // Stack operations (0x00 - 0x0F)
@@ -65,6 +65,8 @@ pub enum OpCode {
     Return = 0x43,
     /// Revert execution
     Revert = 0x44,
+    /// Destroy the contract, sending its balance to a beneficiary: beneficiary ->
+    SelfDestruct = 0x45,
 
     // Storage (0x50 - 0x5F)
     /// Store value: key, value -> storage
@@ -126,6 +128,7 @@ impl OpCode {
             0x42 => Some(OpCode::Halt),
             0x43 => Some(OpCode::Return),
             0x44 => Some(OpCode::Revert),
+            0x45 => Some(OpCode::SelfDestruct),
             0x50 => Some(OpCode::SStore),
             0x51 => Some(OpCode::SLoad),
             0x60 => Some(OpCode::Balance),
@@ -180,6 +183,7 @@ impl OpCode {
             OpCode::Halt => "HALT",
             OpCode::Return => "RETURN",
             OpCode::Revert => "REVERT",
+            OpCode::SelfDestruct => "SELFDESTRUCT",
             OpCode::SStore => "SSTORE",
             OpCode::SLoad => "SLOAD",
             OpCode::Balance => "BALANCE",
@@ -8,8 +8,12 @@
 //! - Reentrancy detection
 
 use crate::contract::opcodes::OpCode;
+pub use crate::crypto::address_hash;
+use crate::crypto::double_sha256_hex;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
 
 // =============================================================================
@@ -40,9 +44,149 @@ pub const SSTORE_GAS: u64 = 5000;
 /// Gas refund for storage clear
 pub const SSTORE_REFUND: u64 = 4800;
 
+/// Refunds are capped at `gas_used / MAX_REFUND_QUOTIENT` (EIP-3529), so
+/// clearing storage can discount a call but never make it free
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+
 /// Gas cost for storage read
 pub const SLOAD_GAS: u64 = 200;
 
+// =============================================================================
+// Gas Schedule
+// =============================================================================
+
+/// Per-opcode gas costs charged by the VM
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasSchedule {
+    costs: HashMap<OpCode, u64>,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        let costs = (0..=u8::MAX)
+            .filter_map(OpCode::from_byte)
+            .map(|op| (op, Self::default_cost(&op)))
+            .collect();
+        Self { costs }
+    }
+}
+
+impl GasSchedule {
+    /// Get the gas cost for an opcode
+    pub fn cost(&self, opcode: &OpCode) -> u64 {
+        self.costs
+            .get(opcode)
+            .copied()
+            .unwrap_or_else(|| Self::default_cost(opcode))
+    }
+
+    /// Override the gas cost for an opcode
+    pub fn set_cost(&mut self, opcode: OpCode, cost: u64) {
+        self.costs.insert(opcode, cost);
+    }
+
+    /// Built-in cost for an opcode
+    fn default_cost(opcode: &OpCode) -> u64 {
+        match opcode {
+            OpCode::Push | OpCode::Pop | OpCode::Dup | OpCode::Swap => 2,
+            OpCode::Add | OpCode::Sub | OpCode::Mul => 3,
+            OpCode::Div | OpCode::Mod => 5,
+            OpCode::Eq | OpCode::Lt | OpCode::Gt | OpCode::Le | OpCode::Ge | OpCode::Neq => 3,
+            OpCode::And | OpCode::Or | OpCode::Not | OpCode::IsZero => 3,
+            OpCode::Jump | OpCode::JumpIf => 8,
+            OpCode::SStore => 20,
+            OpCode::SLoad => 5,
+            OpCode::Balance | OpCode::SelfBalance => 10,
+            OpCode::Transfer | OpCode::SelfDestruct => 50,
+            OpCode::Caller | OpCode::Self_ | OpCode::Timestamp | OpCode::BlockNumber => 2,
+            OpCode::Arg | OpCode::ArgCount => 2,
+            OpCode::Halt | OpCode::Return | OpCode::Revert => 0,
+            OpCode::Nop => 1,
+        }
+    }
+}
+
+// =============================================================================
+// Balance Lookup
+// =============================================================================
+
+/// Resolves an address to its coin balance
+pub type BalanceResolver = Arc<dyn Fn(&str) -> u64 + Send + Sync>;
+
+/// Resolves an address hash to its coin balance, for addresses that were
+/// not registered by name
+pub type HashBalanceResolver = Arc<dyn Fn(u64) -> u64 + Send + Sync>;
+
+/// Maps address hashes back to addresses so `BALANCE` can query real balances
+#[derive(Clone, Default)]
+pub struct BalanceLookup {
+    /// Address hash -> address
+    addresses: HashMap<u64, String>,
+    /// Balance source (unset: every balance reads as 0)
+    resolver: Option<BalanceResolver>,
+    /// Fallback for hashes not in `addresses`
+    hash_resolver: Option<HashBalanceResolver>,
+}
+
+impl fmt::Debug for BalanceLookup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BalanceLookup")
+            .field("addresses", &self.addresses.len())
+            .field("resolver", &self.resolver.is_some())
+            .field("hash_resolver", &self.hash_resolver.is_some())
+            .finish()
+    }
+}
+
+impl BalanceLookup {
+    /// Create a lookup backed by a balance resolver
+    pub fn new(resolver: BalanceResolver) -> Self {
+        Self {
+            addresses: HashMap::new(),
+            resolver: Some(resolver),
+            hash_resolver: None,
+        }
+    }
+
+    /// Create a lookup that resolves address hashes directly (e.g. from the
+    /// chain's address index), so no addresses need registering up front
+    pub fn by_hash(hash_resolver: HashBalanceResolver) -> Self {
+        Self {
+            hash_resolver: Some(hash_resolver),
+            ..Self::default()
+        }
+    }
+
+    /// Register an address so its hash can be resolved
+    pub fn add_address(&mut self, address: &str) {
+        self.addresses
+            .insert(address_hash(address), address.to_string());
+    }
+
+    /// Get the resolver, if any
+    pub fn resolver(&self) -> Option<&BalanceResolver> {
+        self.resolver.as_ref()
+    }
+
+    /// Replace the resolver, keeping the registered addresses
+    pub fn set_resolver(&mut self, resolver: BalanceResolver) {
+        self.resolver = Some(resolver);
+    }
+
+    /// Balance of the address with the given hash (0 if unknown)
+    pub fn balance_of(&self, hash: u64) -> u64 {
+        match (
+            self.addresses.get(&hash),
+            &self.resolver,
+            &self.hash_resolver,
+        ) {
+            (Some(address), Some(resolver), _) => resolver(address),
+            (None, _, Some(hash_resolver)) => hash_resolver(hash),
+            _ => 0,
+        }
+    }
+}
+
 // =============================================================================
 // VM Errors
 // =============================================================================
@@ -93,6 +237,8 @@ pub struct ExecutionContext {
     pub args: Vec<u64>,
     /// Available gas
     pub gas_limit: u64,
+    /// Coin balance held by the contract
+    pub balance: u64,
 }
 
 impl Default for ExecutionContext {
@@ -104,6 +250,7 @@ impl Default for ExecutionContext {
             block_number: 0,
             args: Vec::new(),
             gas_limit: DEFAULT_GAS_LIMIT,
+            balance: 0,
         }
     }
 }
@@ -117,6 +264,10 @@ pub struct ExecutionResult {
     pub return_value: Option<u64>,
     /// Gas used
     pub gas_used: u64,
+    /// Gas given back for clearing storage slots, already capped; the
+    /// caller pays for `gas_used - gas_refunded`
+    #[serde(default)]
+    pub gas_refunded: u64,
     /// Storage changes: key -> value
     pub storage_changes: HashMap<String, u64>,
     /// Transfer requests: (to, amount)
@@ -125,6 +276,73 @@ pub struct ExecutionResult {
     pub error: Option<String>,
     /// Call depth reached during execution
     pub call_depth: usize,
+    /// Beneficiary address if the contract executed SELFDESTRUCT
+    #[serde(default)]
+    pub self_destruct: Option<String>,
+    /// Executed instructions (only populated when tracing is enabled)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trace: Vec<TraceStep>,
+}
+
+impl ExecutionResult {
+    /// Stable digest of the execution's observable effects, for comparing
+    /// results across nodes.
+    ///
+    /// Covers the success flag, return value, storage changes (sorted by
+    /// key), transfers (in execution order) and the SELFDESTRUCT
+    /// beneficiary. The VM emits no event logs, so there are none to cover.
+    /// Gas, call depth, error text and traces are left out.
+    pub fn result_hash(&self) -> String {
+        fn put_str(bytes: &mut Vec<u8>, s: &str) {
+            bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(s.as_bytes());
+        }
+        fn put_opt<T>(bytes: &mut Vec<u8>, value: Option<T>, put: impl FnOnce(&mut Vec<u8>, T)) {
+            match value {
+                Some(value) => {
+                    bytes.push(1);
+                    put(bytes, value);
+                }
+                None => bytes.push(0),
+            }
+        }
+
+        let mut bytes = vec![self.success as u8];
+        put_opt(&mut bytes, self.return_value, |b, v| {
+            b.extend_from_slice(&v.to_le_bytes())
+        });
+
+        let mut storage: Vec<(&String, &u64)> = self.storage_changes.iter().collect();
+        storage.sort();
+        bytes.extend_from_slice(&(storage.len() as u32).to_le_bytes());
+        for (key, value) in storage {
+            put_str(&mut bytes, key);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.transfers.len() as u32).to_le_bytes());
+        for (to, amount) in &self.transfers {
+            put_str(&mut bytes, to);
+            bytes.extend_from_slice(&amount.to_le_bytes());
+        }
+
+        put_opt(&mut bytes, self.self_destruct.as_deref(), put_str);
+
+        double_sha256_hex(&bytes)
+    }
+}
+
+/// A single executed instruction recorded in trace mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// Program counter of the instruction
+    pub pc: usize,
+    /// Opcode executed
+    pub opcode: OpCode,
+    /// Gas remaining before the instruction was charged
+    pub gas_remaining: u64,
+    /// Stack contents before the instruction ran (bottom first)
+    pub stack: Vec<u64>,
 }
 
 /// The smart contract virtual machine
@@ -139,6 +357,9 @@ pub struct VM {
     storage: HashMap<String, u64>,
     /// Pending storage changes
     storage_changes: HashMap<String, u64>,
+    /// Slots that held a non-zero value before this execution first wrote
+    /// them; clearing one earns a refund
+    refundable_slots: HashSet<String>,
     /// Pending transfers
     transfers: Vec<(String, u64)>,
     /// Bytecode being executed
@@ -149,12 +370,20 @@ pub struct VM {
     halted: bool,
     /// Return value
     return_value: Option<u64>,
+    /// Beneficiary set by SELFDESTRUCT
+    self_destruct: Option<String>,
     /// Current call depth (for nested calls)
     call_depth: usize,
     /// Current memory size in pages
     memory_pages: usize,
     /// Set of contracts currently executing (for reentrancy detection)
     executing_contracts: HashSet<String>,
+    /// Recorded instruction trace (None when tracing is off)
+    trace: Option<Vec<TraceStep>>,
+    /// Gas cost per opcode
+    gas_schedule: GasSchedule,
+    /// Address balances visible to BALANCE
+    balance_lookup: BalanceLookup,
 }
 
 impl VM {
@@ -166,14 +395,19 @@ impl VM {
             gas: context.gas_limit,
             storage,
             storage_changes: HashMap::new(),
+            refundable_slots: HashSet::new(),
             transfers: Vec::new(),
             code,
             context,
             halted: false,
             return_value: None,
+            self_destruct: None,
             call_depth: 0,
             memory_pages: 0,
             executing_contracts: HashSet::new(),
+            trace: None,
+            gas_schedule: GasSchedule::default(),
+            balance_lookup: BalanceLookup::default(),
         }
     }
 
@@ -206,17 +440,39 @@ impl VM {
             gas: context.gas_limit,
             storage,
             storage_changes: HashMap::new(),
+            refundable_slots: HashSet::new(),
             transfers: Vec::new(),
             code,
             context,
             halted: false,
             return_value: None,
+            self_destruct: None,
             call_depth,
             memory_pages: 0,
             executing_contracts: executing,
+            trace: None,
+            gas_schedule: GasSchedule::default(),
+            balance_lookup: BalanceLookup::default(),
         })
     }
 
+    /// Record every executed instruction into `ExecutionResult::trace`.
+    ///
+    /// Off by default since snapshotting the stack on each step is costly.
+    pub fn enable_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    /// Charge gas according to a custom schedule instead of the defaults
+    pub fn set_gas_schedule(&mut self, schedule: GasSchedule) {
+        self.gas_schedule = schedule;
+    }
+
+    /// Let BALANCE resolve addresses through the given lookup
+    pub fn set_balance_lookup(&mut self, lookup: BalanceLookup) {
+        self.balance_lookup = lookup;
+    }
+
     /// Get current call depth
     pub fn get_call_depth(&self) -> usize {
         self.call_depth
@@ -253,22 +509,46 @@ impl VM {
             self.step()?;
         }
 
+        let gas_used = self.context.gas_limit - self.gas;
         Ok(ExecutionResult {
             success: true,
             return_value: self.return_value,
-            gas_used: self.context.gas_limit - self.gas,
+            gas_used,
+            gas_refunded: self.storage_refund().min(gas_used / MAX_REFUND_QUOTIENT),
             storage_changes: self.storage_changes.clone(),
             transfers: self.transfers.clone(),
             error: None,
             call_depth: self.call_depth,
+            self_destruct: self.self_destruct.clone(),
+            trace: self.trace.clone().unwrap_or_default(),
         })
     }
 
+    /// Uncapped refund for slots this execution left cleared that held a
+    /// value before it ran (a slot set and cleared again earns nothing)
+    fn storage_refund(&self) -> u64 {
+        let cleared = self
+            .storage_changes
+            .iter()
+            .filter(|(key, value)| **value == 0 && self.refundable_slots.contains(*key))
+            .count() as u64;
+        cleared * SSTORE_REFUND
+    }
+
     /// Execute a single instruction
     fn step(&mut self) -> Result<(), VmError> {
         let opcode_byte = self.code[self.pc];
         let opcode = OpCode::from_byte(opcode_byte).ok_or(VmError::InvalidOpcode(opcode_byte))?;
 
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceStep {
+                pc: self.pc,
+                opcode,
+                gas_remaining: self.gas,
+                stack: self.stack.clone(),
+            });
+        }
+
         // Consume gas
         let gas_cost = self.gas_cost(&opcode);
         if self.gas < gas_cost {
@@ -403,10 +683,24 @@ impl VM {
             OpCode::Revert => {
                 return Err(VmError::Reverted);
             }
+            OpCode::SelfDestruct => {
+                let beneficiary = self.pop()?;
+                let beneficiary_addr = format!("{:016x}", beneficiary);
+                if self.context.balance > 0 {
+                    self.transfers
+                        .push((beneficiary_addr.clone(), self.context.balance));
+                }
+                self.self_destruct = Some(beneficiary_addr);
+                self.halted = true;
+            }
             OpCode::SStore => {
                 let value = self.pop()?;
                 let key = self.pop()?;
                 let key_str = format!("{:016x}", key);
+                let first_write = !self.storage_changes.contains_key(&key_str);
+                if first_write && self.storage.get(&key_str).is_some_and(|v| *v != 0) {
+                    self.refundable_slots.insert(key_str.clone());
+                }
                 self.storage.insert(key_str.clone(), value);
                 self.storage_changes.insert(key_str, value);
             }
@@ -417,8 +711,13 @@ impl VM {
                 self.push(value)?;
             }
             OpCode::Balance => {
-                // For simplicity, push 0 - real implementation would check blockchain
-                self.push(0)?;
+                let addr = self.pop()?;
+                let balance = if addr == self.hash_address(&self.context.contract_address) {
+                    self.context.balance
+                } else {
+                    self.balance_lookup.balance_of(addr)
+                };
+                self.push(balance)?;
             }
             OpCode::Transfer => {
                 let amount = self.pop()?;
@@ -443,8 +742,7 @@ impl VM {
                 self.push(self.context.block_number)?;
             }
             OpCode::SelfBalance => {
-                // For simplicity, push 0
-                self.push(0)?;
+                self.push(self.context.balance)?;
             }
             OpCode::Arg => {
                 let index = self.read_u8()?;
@@ -525,31 +823,12 @@ impl VM {
 
     /// Hash address string to u64
     fn hash_address(&self, addr: &str) -> u64 {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        let mut hasher = DefaultHasher::new();
-        addr.hash(&mut hasher);
-        hasher.finish()
+        address_hash(addr)
     }
 
     /// Get gas cost for opcode
     fn gas_cost(&self, opcode: &OpCode) -> u64 {
-        match opcode {
-            OpCode::Push | OpCode::Pop | OpCode::Dup | OpCode::Swap => 2,
-            OpCode::Add | OpCode::Sub | OpCode::Mul => 3,
-            OpCode::Div | OpCode::Mod => 5,
-            OpCode::Eq | OpCode::Lt | OpCode::Gt | OpCode::Le | OpCode::Ge | OpCode::Neq => 3,
-            OpCode::And | OpCode::Or | OpCode::Not | OpCode::IsZero => 3,
-            OpCode::Jump | OpCode::JumpIf => 8,
-            OpCode::SStore => 20,
-            OpCode::SLoad => 5,
-            OpCode::Balance | OpCode::SelfBalance => 10,
-            OpCode::Transfer => 50,
-            OpCode::Caller | OpCode::Self_ | OpCode::Timestamp | OpCode::BlockNumber => 2,
-            OpCode::Arg | OpCode::ArgCount => 2,
-            OpCode::Halt | OpCode::Return | OpCode::Revert => 0,
-            OpCode::Nop => 1,
-        }
+        self.gas_schedule.cost(opcode)
     }
 }
 
@@ -610,6 +889,121 @@ mod tests {
         assert_eq!(result.return_value, Some(42));
     }
 
+    #[test]
+    fn test_result_hash_is_deterministic() {
+        let run = |value: u64| {
+            let mut code = Vec::new();
+            for key in 1..=3 {
+                code.extend(make_push(key));
+                code.extend(make_push(value * key));
+                code.push(OpCode::SStore as u8);
+            }
+            code.extend(make_push(7));
+            code.push(OpCode::Return as u8);
+            VM::new(code, HashMap::new(), ExecutionContext::default())
+                .execute()
+                .unwrap()
+        };
+
+        // Same inputs, separate VMs (and separate HashMap iteration orders)
+        let a = run(10);
+        let b = run(10);
+        assert_eq!(a.storage_changes.len(), 3);
+        assert_eq!(a.result_hash(), b.result_hash());
+
+        // A differing storage change is visible in the hash
+        let c = run(11);
+        assert_eq!(c.return_value, a.return_value);
+        assert_ne!(c.result_hash(), a.result_hash());
+
+        // Execution metadata is not
+        let mut rerun = a.clone();
+        rerun.gas_used += 1;
+        rerun.call_depth += 1;
+        assert_eq!(rerun.result_hash(), a.result_hash());
+    }
+
+    #[test]
+    fn test_trace_records_steps() {
+        let mut code = make_push(2);
+        code.extend(make_push(3));
+        code.push(OpCode::Mul as u8);
+        code.push(OpCode::Return as u8);
+
+        let mut vm = VM::new(code, HashMap::new(), ExecutionContext::default());
+        vm.enable_trace();
+        let result = vm.execute().unwrap();
+
+        let steps: Vec<(usize, OpCode)> = result.trace.iter().map(|s| (s.pc, s.opcode)).collect();
+        assert_eq!(
+            steps,
+            vec![
+                (0, OpCode::Push),
+                (9, OpCode::Push),
+                (18, OpCode::Mul),
+                (19, OpCode::Return),
+            ]
+        );
+        assert_eq!(result.trace[2].stack, vec![2, 3]);
+        assert_eq!(result.trace[3].stack, vec![6]);
+        assert_eq!(result.trace[0].gas_remaining, DEFAULT_GAS_LIMIT);
+        assert_eq!(result.trace[1].gas_remaining, DEFAULT_GAS_LIMIT - 2);
+    }
+
+    #[test]
+    fn test_trace_off_by_default() {
+        let mut code = make_push(1);
+        code.push(OpCode::Return as u8);
+
+        let mut vm = VM::new(code, HashMap::new(), ExecutionContext::default());
+        let result = vm.execute().unwrap();
+
+        assert!(result.trace.is_empty());
+    }
+
+    #[test]
+    fn test_self_destruct_sends_balance() {
+        let mut code = make_push(0xbeef);
+        code.push(OpCode::SelfDestruct as u8);
+        code.extend(make_push(1));
+        code.push(OpCode::Return as u8);
+
+        let context = ExecutionContext {
+            balance: 500,
+            ..ExecutionContext::default()
+        };
+        let mut vm = VM::new(code, HashMap::new(), context);
+        let result = vm.execute().unwrap();
+
+        let beneficiary = format!("{:016x}", 0xbeef);
+        assert_eq!(result.self_destruct, Some(beneficiary.clone()));
+        assert_eq!(result.transfers, vec![(beneficiary, 500)]);
+        assert_eq!(result.return_value, None); // Halted before RETURN
+    }
+
+    #[test]
+    fn test_balance_lookup() {
+        let mut code = make_push(address_hash("alice"));
+        code.push(OpCode::Balance as u8);
+        code.extend(make_push(address_hash("unknown")));
+        code.push(OpCode::Balance as u8);
+        code.push(OpCode::Add as u8);
+        code.push(OpCode::Return as u8);
+
+        let mut lookup = BalanceLookup::new(Arc::new(|addr: &str| match addr {
+            "alice" => 1_000,
+            _ => 5,
+        }));
+        lookup.add_address("alice");
+
+        let mut vm = VM::new(code, HashMap::new(), ExecutionContext::default());
+        vm.set_balance_lookup(lookup);
+        let result = vm.execute().unwrap();
+
+        // Unregistered hashes resolve to 0
+        assert_eq!(result.return_value, Some(1_000));
+    }
+
     #[test]
     fn test_out_of_gas() {
         let mut code = Vec::new();
@@ -0,0 +1,112 @@
+//! Amount display
+//!
+//! Amounts are stored as whole base units (`u64`). A chain's
+//! `coin_decimals` says how many of those digits sit after the decimal
+//! point when shown to people, so with 8 decimals an amount of 50 reads as
+//! `0.00000050`. Consensus never looks at the decimals.
+
+use thiserror::Error;
+
+/// Most decimals a chain may use (10^19 is the largest power of ten in a u64)
+pub const MAX_COIN_DECIMALS: u8 = 19;
+
+/// Errors parsing a display amount
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("Invalid amount: {0:?}")]
+    Invalid(String),
+    #[error("Too many decimal places: {0} (max: {1})")]
+    TooPrecise(usize, u8),
+    #[error("Amount out of range: {0}")]
+    Overflow(String),
+}
+
+/// Format base units with `decimals` digits after the point, keeping
+/// trailing zeros so amounts line up
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+
+    let digits = format!("{:0>width$}", amount, width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+    format!("{}.{}", whole, fraction)
+}
+
+/// Parse a display amount such as `12.5` back into base units
+pub fn parse_amount(text: &str, decimals: u8) -> Result<u64, AmountError> {
+    let invalid = || AmountError::Invalid(text.to_string());
+    let (whole, fraction) = match text.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (text, ""),
+    };
+
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(AmountError::TooPrecise(fraction.len(), decimals));
+    }
+
+    let overflow = || AmountError::Overflow(text.to_string());
+    let scale = 10u64.checked_pow(decimals as u32).ok_or_else(overflow)?;
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| overflow())?
+    };
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        let padding = 10u64.pow((decimals as usize - fraction.len()) as u32);
+        fraction.parse::<u64>().map_err(|_| invalid())? * padding
+    };
+
+    whole
+        .checked_mul(scale)
+        .and_then(|units| units.checked_add(fraction))
+        .ok_or_else(overflow)
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_parse_round_trip() {
+        assert_eq!(format_amount(50, 8), "0.00000050");
+        assert_eq!(format_amount(1_250_000_000, 8), "12.50000000");
+        assert_eq!(format_amount(50, 0), "50");
+        assert_eq!(format_amount(u64::MAX, 19), "1.8446744073709551615");
+
+        for (amount, decimals) in [(50, 8), (0, 8), (1_250_000_000, 8), (7, 2), (u64::MAX, 19)] {
+            let text = format_amount(amount, decimals);
+            assert_eq!(parse_amount(&text, decimals), Ok(amount));
+        }
+
+        assert_eq!(parse_amount("12.5", 8), Ok(1_250_000_000));
+        assert_eq!(parse_amount(".5", 1), Ok(5));
+        assert!(matches!(
+            parse_amount("0.001", 2),
+            Err(AmountError::TooPrecise(3, 2))
+        ));
+        assert!(matches!(
+            parse_amount("1.2.3", 8),
+            Err(AmountError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse_amount("-1", 8),
+            Err(AmountError::Invalid(_))
+        ));
+        assert!(matches!(parse_amount(".", 8), Err(AmountError::Invalid(_))));
+        assert!(matches!(
+            parse_amount("184467440738", 8),
+            Err(AmountError::Overflow(_))
+        ));
+    }
+}
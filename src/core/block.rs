@@ -2,8 +2,8 @@
 //!
 //! A block contains a header with metadata and a list of transactions.
 
-use crate::core::transaction::{Transaction, MAX_TX_SIZE};
-use crate::crypto::{calculate_merkle_root, double_sha256, meets_difficulty};
+use crate::core::transaction::{serialized_len, Transaction, MAX_TX_SIZE};
+use crate::crypto::{calculate_merkle_root, double_sha256, meets_difficulty, PowKind};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -24,6 +24,13 @@ pub const MAX_BLOCK_TXS: usize = 10_000;
 /// Block header size in bytes (80 bytes like Bitcoin)
 pub const BLOCK_HEADER_SIZE: usize = 80;
 
+/// Version of the block hash preimage. Version 1 hashed the concatenated
+/// `Display` strings of the header fields; version 2 hashes
+/// [`BlockHeader::canonical_bytes`]. Chains saved under an older version
+/// cannot be migrated (re-hashing would invalidate every block's proof of
+/// work) and are rejected on load.
+pub const BLOCK_HASH_FORMAT_VERSION: u32 = 2;
+
 // =============================================================================
 // Block Errors
 // =============================================================================
@@ -60,27 +67,57 @@ pub struct BlockHeader {
     pub difficulty: u32,
     /// Nonce used for proof of work
     pub nonce: u64,
+    /// Proof-of-work algorithm the block was mined with
+    #[serde(default, skip_serializing_if = "PowKind::is_sha256d")]
+    pub pow: PowKind,
 }
 
 impl BlockHeader {
-    /// Calculate the hash of the block header
-    pub fn hash(&self) -> String {
-        let data = format!(
-            "{}{}{}{}{}{}",
-            self.version,
-            self.previous_hash,
-            self.merkle_root,
-            self.timestamp.timestamp(),
-            self.difficulty,
-            self.nonce
+    /// Canonical byte layout hashed for the block hash, independent of serde:
+    ///
+    /// | field           | encoding                          |
+    /// |-----------------|-----------------------------------|
+    /// | `version`       | u32 little-endian                 |
+    /// | `previous_hash` | u32 LE length, then UTF-8 bytes   |
+    /// | `merkle_root`   | u32 LE length, then UTF-8 bytes   |
+    /// | `timestamp`     | i64 LE Unix seconds               |
+    /// | `difficulty`    | u32 LE                            |
+    /// | `nonce`         | u64 LE                            |
+    /// | `pow`           | u8 algorithm id, omitted for sha256d |
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            4 + 4 + self.previous_hash.len() + 4 + self.merkle_root.len() + 8 + 4 + 8,
         );
-        hex::encode(double_sha256(data.as_bytes()))
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        for field in [&self.previous_hash, &self.merkle_root] {
+            bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes.extend_from_slice(&self.timestamp.timestamp().to_le_bytes());
+        bytes.extend_from_slice(&self.difficulty.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        if !self.pow.is_sha256d() {
+            bytes.push(self.pow.id());
+        }
+        bytes
+    }
+
+    /// Calculate the hash of the block header (double SHA-256 of
+    /// [`canonical_bytes`](Self::canonical_bytes), hash format
+    /// [`BLOCK_HASH_FORMAT_VERSION`])
+    pub fn hash(&self) -> String {
+        hex::encode(double_sha256(&self.canonical_bytes()))
     }
 
-    /// Check if the hash meets the difficulty target
+    /// Proof-of-work hash under the header's algorithm (the block hash
+    /// itself for sha256d)
+    pub fn pow_hash(&self) -> Vec<u8> {
+        self.pow.algorithm().hash(&self.canonical_bytes())
+    }
+
+    /// Check if the proof-of-work hash meets the difficulty target
     pub fn is_valid_hash(&self) -> bool {
-        let hash = hex::decode(self.hash()).unwrap_or_default();
-        meets_difficulty(&hash, self.difficulty)
+        meets_difficulty(&self.pow_hash(), self.difficulty)
     }
 }
 
@@ -114,6 +151,7 @@ impl Block {
             timestamp: Utc::now(),
             difficulty,
             nonce: 0,
+            pow: PowKind::default(),
         };
 
         let hash = header.hash();
@@ -126,8 +164,27 @@ impl Block {
         }
     }
 
+    /// Create a block without searching for a valid nonce (test support)
+    ///
+    /// The hash is consistent with the header, but the proof of work will
+    /// almost never be valid; pair with `Blockchain::accept_block_for_test`.
+    #[cfg(test)]
+    pub fn new_unmined(
+        index: u64,
+        previous_hash: String,
+        transactions: Vec<Transaction>,
+        difficulty: u32,
+    ) -> Self {
+        Self::new(index, previous_hash, transactions, difficulty)
+    }
+
     /// Create the genesis block
     pub fn genesis(difficulty: u32) -> Self {
+        Self::genesis_with_pow(difficulty, PowKind::default())
+    }
+
+    /// Create the genesis block mined with the given algorithm
+    pub fn genesis_with_pow(difficulty: u32, pow: PowKind) -> Self {
         let coinbase = Transaction::coinbase("genesis", 0, 0);
 
         let merkle_root = Self::calculate_merkle_root(&[coinbase.clone()]);
@@ -139,6 +196,7 @@ impl Block {
             timestamp: Utc::now(),
             difficulty,
             nonce: 0,
+            pow,
         };
 
         let mut block = Self {
@@ -186,8 +244,17 @@ impl Block {
         attempts
     }
 
-    /// Check if the proof of work is valid
+    /// Use `pow` for this block's proof of work (call before mining)
+    pub fn set_pow(&mut self, pow: PowKind) {
+        self.header.pow = pow;
+        self.hash = self.header.hash();
+    }
+
+    /// Check if the proof of work is valid under the header's algorithm
     pub fn is_valid_pow(&self) -> bool {
+        if !self.header.pow.is_sha256d() {
+            return self.header.is_valid_hash();
+        }
         let hash_bytes = hex::decode(&self.hash).unwrap_or_default();
         meets_difficulty(&hash_bytes, self.header.difficulty)
     }
@@ -238,6 +305,16 @@ impl Block {
         self.size() * 4
     }
 
+    /// Exact length in bytes of the serialized block as stored and relayed
+    pub fn serialized_size(&self) -> usize {
+        serialized_len(self)
+    }
+
+    /// Weight of the serialized block (no witness discount, so 4x its bytes)
+    pub fn serialized_weight(&self) -> usize {
+        self.serialized_size() * 4
+    }
+
     /// Validate that the block size is within limits
     pub fn validate_size(&self) -> Result<(), BlockError> {
         let size = self.size();
@@ -245,6 +322,11 @@ impl Block {
             return Err(BlockError::BlockTooLarge(size, MAX_BLOCK_SIZE));
         }
 
+        let weight = self.serialized_weight();
+        if weight > MAX_BLOCK_WEIGHT {
+            return Err(BlockError::BlockTooLarge(weight / 4, MAX_BLOCK_WEIGHT / 4));
+        }
+
         let tx_count = self.transactions.len();
         if tx_count > MAX_BLOCK_TXS {
             return Err(BlockError::TooManyTransactions(tx_count, MAX_BLOCK_TXS));
@@ -335,4 +417,68 @@ mod tests {
         block.header.nonce += 1;
         assert!(!block.verify_hash());
     }
+
+    #[test]
+    fn test_header_hash_uses_canonical_bytes() {
+        use crate::crypto::sha256;
+
+        let header = BlockHeader {
+            version: 1,
+            previous_hash: "ab".repeat(32),
+            merkle_root: "cd".repeat(32),
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            difficulty: 16,
+            nonce: 42,
+            pow: PowKind::Sha256d,
+        };
+
+        let mut expected = vec![1, 0, 0, 0];
+        expected.extend_from_slice(&[64, 0, 0, 0]);
+        expected.extend_from_slice("ab".repeat(32).as_bytes());
+        expected.extend_from_slice(&[64, 0, 0, 0]);
+        expected.extend_from_slice("cd".repeat(32).as_bytes());
+        expected.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        expected.extend_from_slice(&[16, 0, 0, 0]);
+        expected.extend_from_slice(&[42, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(header.canonical_bytes(), expected);
+        assert_eq!(header.hash(), hex::encode(sha256(&sha256(&expected))));
+
+        // Length prefixes keep adjacent fields from running together
+        let mut shifted = header.clone();
+        shifted.previous_hash = "ab".repeat(32) + "c";
+        shifted.merkle_root = "d".to_string() + &"cd".repeat(31);
+        assert_ne!(shifted.hash(), header.hash());
+
+        // Other algorithms are committed with a trailing id byte
+        let mut memory_hard = header.clone();
+        memory_hard.pow = PowKind::MemoryHard;
+        expected.push(PowKind::MemoryHard.id());
+        assert_eq!(memory_hard.canonical_bytes(), expected);
+    }
+
+    #[test]
+    fn test_serialized_size_matches_encoding() {
+        let transactions = (0..5)
+            .map(|i| Transaction::coinbase(&format!("miner{}", i), 50, i))
+            .collect::<Vec<_>>();
+        let block = Block::new(1, "0".repeat(64), transactions, 1);
+
+        let encoded = serde_json::to_vec(&block).unwrap();
+        assert_eq!(block.serialized_size(), encoded.len());
+        assert_eq!(block.serialized_weight(), encoded.len() * 4);
+
+        // Per-transaction sizes plus the header account for nearly all of it
+        let tx_total: usize = block
+            .transactions
+            .iter()
+            .map(|tx| tx.serialized_size())
+            .sum();
+        let header = serde_json::to_vec(&block.header).unwrap().len();
+        let overhead = block.serialized_size() - tx_total - header;
+        assert!(overhead < 128 + block.hash.len());
+
+        let tx = &block.transactions[0];
+        assert_eq!(tx.serialized_size(), tx.to_raw_hex().unwrap().len() / 2);
+        assert!(block.validate_size().is_ok());
+    }
 }
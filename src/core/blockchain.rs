@@ -4,14 +4,23 @@
 //! Features production-grade consensus with fork resolution, orphan handling,
 //! and Median Time Past (MTP) validation.
 
-use crate::core::block::{Block, BlockError};
+use crate::core::amount::format_amount;
+use crate::core::block::{Block, BlockError, BLOCK_HASH_FORMAT_VERSION};
 use crate::core::chain_state::{
-    BlockStatus, ChainStateManager, UndoData, MAX_FUTURE_BLOCK_TIME, MTP_BLOCK_COUNT,
+    BlockStatus, ChainStateManager, ReorgEvent, TieBreak, UndoData, BYTES_PER_MB,
+    DEFAULT_MAX_ORPHAN_BLOCK_MB, DEFAULT_MAX_REORG_DEPTH, MAX_FUTURE_BLOCK_TIME, MAX_ORPHAN_BLOCKS,
+    MTP_BLOCK_COUNT,
+};
+use crate::core::clock::{system_clock, SharedClock};
+use crate::core::transaction::{OutPoint, Transaction, COINBASE_MATURITY, UTXO};
+use crate::crypto::{address_hash, double_sha256_hex, PowKind};
+use crate::storage::{
+    Checkpoint, CheckpointManager, PruneState, Pruner, PrunerConfig, TxIndex,
+    DEFAULT_CHECKPOINT_INTERVAL,
 };
-use crate::core::transaction::{Transaction, COINBASE_MATURITY, UTXO};
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Default mining difficulty (number of leading zero bits)
@@ -29,6 +38,192 @@ pub const TARGET_BLOCK_TIME: i64 = 10;
 /// Maximum difficulty adjustment factor per period (Bitcoin uses 4x)
 pub const MAX_DIFFICULTY_ADJUSTMENT_FACTOR: f64 = 4.0;
 
+/// Lowest difficulty allowed by default
+pub const MIN_DIFFICULTY: u32 = 1;
+
+/// Highest difficulty allowed by default
+pub const MAX_DIFFICULTY: u32 = 32;
+
+/// Most blocks returned by one [`Blockchain::get_block_range`] call
+pub const MAX_BLOCK_RANGE: u64 = 100;
+
+/// Blocks a node may trail its best peer by and still be out of initial
+/// block download
+pub const IBD_MAX_BLOCKS_BEHIND: u64 = 6;
+
+/// Oldest tip, in seconds, a node may have and still be out of initial
+/// block download
+pub const IBD_MAX_TIP_AGE: i64 = 24 * 60 * 60;
+
+/// Recent blocks averaged over when reporting UTXO set growth
+pub const UTXO_GROWTH_WINDOW: u64 = 144;
+
+/// Page size [`Blockchain::utxo_cursor`] uses when asked for 0 entries
+pub const DEFAULT_UTXO_PAGE_SIZE: usize = 1000;
+
+/// Consensus parameters chosen when a chain is created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainParams {
+    /// Difficulty of the genesis block
+    pub genesis_difficulty: u32,
+    /// Target time between blocks in seconds
+    pub target_block_time: i64,
+    /// Number of blocks between difficulty adjustments
+    pub adjustment_interval: u64,
+    /// Coinbase reward before any halving
+    pub block_reward: u64,
+    /// Blocks between reward halvings (0 disables halving)
+    pub halving_interval: u64,
+    /// Trusted block hash: signatures in it and its ancestors are not
+    /// verified (PoW and structure still are)
+    #[serde(default)]
+    pub assume_valid: Option<String>,
+    /// Memory budget for blocks waiting on their parent, in megabytes
+    #[serde(default = "default_max_orphan_block_mb")]
+    pub max_orphan_block_mb: u64,
+    /// Fork choice between equal-work tips
+    #[serde(default)]
+    pub tie_break: TieBreak,
+    /// Difficulty floor for retargeting and block validation
+    #[serde(default = "default_min_difficulty")]
+    pub min_difficulty: u32,
+    /// Difficulty ceiling for retargeting and block validation
+    #[serde(default = "default_max_difficulty")]
+    pub max_difficulty: u32,
+    /// Most blocks a reorganization may disconnect; undo data for deeper
+    /// blocks is discarded
+    #[serde(default = "default_max_reorg_depth")]
+    pub max_reorg_depth: u64,
+    /// Blocks between automatic checkpoints (0 disables them)
+    #[serde(default = "default_checkpoint_interval")]
+    pub checkpoint_interval: u64,
+    /// Digits after the decimal point when amounts are displayed
+    #[serde(default)]
+    pub coin_decimals: u8,
+    /// Proof-of-work algorithm every block must be mined with
+    #[serde(default)]
+    pub pow_algorithm: PowKind,
+    /// Confirmations a coinbase output needs before it can be spent
+    #[serde(default = "default_coinbase_maturity")]
+    pub coinbase_maturity: u64,
+}
+
+fn default_max_orphan_block_mb() -> u64 {
+    DEFAULT_MAX_ORPHAN_BLOCK_MB
+}
+
+fn default_min_difficulty() -> u32 {
+    MIN_DIFFICULTY
+}
+
+fn default_max_difficulty() -> u32 {
+    MAX_DIFFICULTY
+}
+
+fn default_max_reorg_depth() -> u64 {
+    DEFAULT_MAX_REORG_DEPTH
+}
+
+fn default_checkpoint_interval() -> u64 {
+    DEFAULT_CHECKPOINT_INTERVAL
+}
+
+fn default_coinbase_maturity() -> u64 {
+    COINBASE_MATURITY
+}
+
+/// Hash format of chains saved before `hash_format` was persisted
+fn legacy_hash_format() -> u32 {
+    1
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self {
+            genesis_difficulty: DEFAULT_DIFFICULTY,
+            target_block_time: TARGET_BLOCK_TIME,
+            adjustment_interval: DIFFICULTY_ADJUSTMENT_INTERVAL,
+            block_reward: BLOCK_REWARD,
+            halving_interval: 0,
+            assume_valid: None,
+            max_orphan_block_mb: DEFAULT_MAX_ORPHAN_BLOCK_MB,
+            tie_break: TieBreak::FirstSeen,
+            min_difficulty: MIN_DIFFICULTY,
+            max_difficulty: MAX_DIFFICULTY,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            coin_decimals: 0,
+            pow_algorithm: PowKind::Sha256d,
+            coinbase_maturity: COINBASE_MATURITY,
+        }
+    }
+}
+
+impl ChainParams {
+    /// Format an amount in base units for display using `coin_decimals`
+    pub fn format_amount(&self, amount: u64) -> String {
+        format_amount(amount, self.coin_decimals)
+    }
+
+    /// Orphan block memory budget in bytes
+    pub fn max_orphan_block_bytes(&self) -> usize {
+        (self.max_orphan_block_mb as usize).saturating_mul(BYTES_PER_MB)
+    }
+
+    /// Coinbase reward for a block at the given height
+    pub fn block_reward_at(&self, height: u64) -> u64 {
+        if self.halving_interval == 0 {
+            return self.block_reward;
+        }
+
+        let halvings = height / self.halving_interval;
+        if halvings >= u64::BITS as u64 {
+            0
+        } else {
+            self.block_reward >> halvings
+        }
+    }
+}
+
+/// Detailed outcome of `Blockchain::apply_block`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockConnectResult {
+    /// Hash of the applied block
+    pub hash: String,
+    /// Height of the block
+    pub height: u64,
+    /// Status reported by `process_block`
+    pub status: BlockStatus,
+    /// Whether this call joined the block to the active chain
+    pub connected: bool,
+    /// Total fees (inputs minus outputs) of the block's transactions
+    pub fees: u64,
+    /// Number of UTXOs created by the block
+    pub utxos_added: usize,
+    /// Number of UTXOs spent by the block
+    pub utxos_removed: usize,
+    /// Blocks disconnected from the old chain, if the block caused a reorg
+    pub reorg_depth: Option<u64>,
+    /// Transactions to return to the mempool from reorgs the block caused,
+    /// directly or by connecting orphans
+    pub returned_txs: Vec<Transaction>,
+}
+
+/// UTXO state kept in place of the block bodies dropped by pruning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunedBase {
+    /// Which blocks were pruned
+    pub state: PruneState,
+    /// Unspent outputs after connecting the block at `state.pruned_to`
+    pub utxos: Vec<UTXO>,
+    /// Heights of unspent coinbase transactions at that point
+    pub coinbase_heights: HashMap<String, u64>,
+    /// Heights of the transactions that created `utxos`, so their outputs
+    /// keep their confirmation counts
+    #[serde(default)]
+    pub utxo_heights: HashMap<String, u64>,
+}
+
 /// Blockchain-related errors
 #[derive(Error, Debug)]
 pub enum BlockchainError {
@@ -59,6 +254,13 @@ pub struct Blockchain {
     pub blocks: Vec<Block>,
     /// Current mining difficulty
     pub difficulty: u32,
+    /// Consensus parameters (persisted with the chain)
+    #[serde(default)]
+    pub params: ChainParams,
+    /// Block hash format the stored hashes were computed with; files saved
+    /// before the field existed use the legacy format 1
+    #[serde(default = "legacy_hash_format")]
+    pub hash_format: u32,
     /// Unspent transaction outputs
     #[serde(skip)]
     pub utxo_set: HashMap<String, UTXO>,
@@ -72,44 +274,75 @@ pub struct Blockchain {
     /// Used to enforce COINBASE_MATURITY (100 block delay before spending)
     #[serde(skip, default)]
     pub coinbase_heights: HashMap<String, u64>,
+    /// Transaction index: tx_id -> height of the block containing it
+    #[serde(skip, default)]
+    pub tx_heights: HashMap<String, u64>,
+    /// Transactions by ID and address for blocks with stored bodies
+    #[serde(skip, default)]
+    tx_index: TxIndex,
+    /// Address index: total unspent amount per address, kept in step with
+    /// `utxo_set`
+    #[serde(skip, default)]
+    address_balances: AddressBalances,
+    /// UTXO state replacing pruned block bodies (`None` for an archive node)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pruned: Option<PrunedBase>,
+    /// Checkpoints recorded as blocks are buried past the reorg limit
+    #[serde(default)]
+    checkpoints: CheckpointManager,
+    /// Parent hashes whose orphans still need connecting
+    #[serde(skip, default)]
+    orphan_worklist: VecDeque<String>,
+    /// Whether `process_orphans` is currently draining the worklist
+    #[serde(skip, default)]
+    draining_orphans: bool,
+    /// Transactions returned by reorgs during the current `process_block`
+    /// call, including those triggered by connecting orphans
+    #[serde(skip, default)]
+    returned_txs: Vec<Transaction>,
+    /// Time source for timestamp validation and orphan bookkeeping
+    #[serde(skip, default = "system_clock")]
+    clock: SharedClock,
 }
 
 impl Blockchain {
     /// Create a new blockchain with genesis block
     pub fn new() -> Self {
-        let genesis = Block::genesis(DEFAULT_DIFFICULTY);
-        let genesis_work = ChainStateManager::calculate_work(DEFAULT_DIFFICULTY);
-
-        let mut blockchain = Self {
-            blocks: vec![genesis.clone()],
-            difficulty: DEFAULT_DIFFICULTY,
-            utxo_set: HashMap::new(),
-            chain_work: genesis_work,
-            state: ChainStateManager::new(),
-            coinbase_heights: HashMap::new(),
-        };
-
-        // Initialize state
-        blockchain.state.index_block(genesis.hash.clone(), 0);
-        blockchain
-            .state
-            .set_active_tip(&genesis.hash, 0, genesis_work);
-        blockchain.rebuild_utxo_set();
-        blockchain
+        Self::with_params(ChainParams::default())
     }
 
     /// Create a blockchain with custom difficulty
     pub fn with_difficulty(difficulty: u32) -> Self {
-        let genesis = Block::genesis(difficulty);
+        Self::with_params(ChainParams {
+            genesis_difficulty: difficulty,
+            ..ChainParams::default()
+        })
+    }
+
+    /// Create a blockchain with custom consensus parameters
+    pub fn with_params(params: ChainParams) -> Self {
+        let difficulty = params.genesis_difficulty;
+        let genesis = Block::genesis_with_pow(difficulty, params.pow_algorithm);
         let genesis_work = ChainStateManager::calculate_work(difficulty);
 
         let mut blockchain = Self {
             blocks: vec![genesis.clone()],
             difficulty,
+            params,
+            hash_format: BLOCK_HASH_FORMAT_VERSION,
             utxo_set: HashMap::new(),
             chain_work: genesis_work,
             state: ChainStateManager::new(),
             coinbase_heights: HashMap::new(),
+            tx_heights: HashMap::new(),
+            tx_index: TxIndex::new(),
+            address_balances: AddressBalances::default(),
+            pruned: None,
+            checkpoints: CheckpointManager::new(),
+            orphan_worklist: VecDeque::new(),
+            draining_orphans: false,
+            returned_txs: Vec::new(),
+            clock: system_clock(),
         };
 
         blockchain.state.index_block(genesis.hash.clone(), 0);
@@ -120,6 +353,11 @@ impl Blockchain {
         blockchain
     }
 
+    /// Replace the time source (e.g. with a `MockClock` in tests)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
     /// Get the latest block
     pub fn latest_block(&self) -> &Block {
         self.blocks
@@ -127,16 +365,64 @@ impl Blockchain {
             .expect("Blockchain should have at least genesis block")
     }
 
+    /// Hash of the genesis block, which identifies the chain to peers
+    pub fn genesis_hash(&self) -> &str {
+        &self.blocks[0].hash
+    }
+
     /// Get a block by index
     pub fn get_block(&self, index: u64) -> Option<&Block> {
         self.blocks.get(index as usize)
     }
 
+    /// Blocks at heights `from..=to`, clamped to the chain tip and to
+    /// [`MAX_BLOCK_RANGE`] blocks. Empty if `from` is past either bound.
+    pub fn get_block_range(&self, from: u64, to: u64) -> &[Block] {
+        let to = to
+            .min(self.height())
+            .min(from.saturating_add(MAX_BLOCK_RANGE - 1));
+        if from > to {
+            return &[];
+        }
+        &self.blocks[from as usize..=to as usize]
+    }
+
     /// Get a block by hash
     pub fn get_block_by_hash(&self, hash: &str) -> Option<&Block> {
         self.blocks.iter().find(|b| b.hash == hash)
     }
 
+    /// Hashes of the active-chain blocks following the first `locator`
+    /// hash found on the active chain, for answering `getblocks`.
+    ///
+    /// Returns at most `max` hashes, ending early after `stop_hash`. If no
+    /// locator hash is on the active chain the peer shares only the genesis
+    /// block, so the hashes start right after it.
+    pub fn locate_blocks(
+        &self,
+        locator: &[String],
+        stop_hash: Option<&str>,
+        max: usize,
+    ) -> Vec<String> {
+        let fork_height = locator
+            .iter()
+            .find_map(|hash| {
+                let height = *self.state.block_index.get(hash)?;
+                let on_active_chain = self.get_block(height)?.hash == *hash;
+                on_active_chain.then_some(height)
+            })
+            .unwrap_or(0);
+
+        let mut hashes = Vec::new();
+        for block in self.blocks.iter().skip(fork_height as usize + 1).take(max) {
+            hashes.push(block.hash.clone());
+            if stop_hash == Some(block.hash.as_str()) {
+                break;
+            }
+        }
+        hashes
+    }
+
     /// Get blockchain height
     pub fn height(&self) -> u64 {
         self.blocks.len() as u64 - 1
@@ -151,6 +437,9 @@ impl Blockchain {
     pub fn process_block(&mut self, block: Block) -> Result<BlockStatus, BlockchainError> {
         let block_hash = block.hash.clone();
         let parent_hash = block.header.previous_hash.clone();
+        if !self.draining_orphans {
+            self.returned_txs.clear();
+        }
 
         // Check for duplicate
         if self.state.block_index.contains_key(&block_hash) {
@@ -171,9 +460,13 @@ impl Blockchain {
             return self.handle_potential_fork(block, parent_height);
         }
 
-        // Parent not found - this is an orphan
-        let current_time = Utc::now().timestamp() as u64;
-        self.state.add_orphan(block, current_time);
+        // Parent not found - this is an orphan. Oversized or inconsistent
+        // blocks would only waste the orphan budget
+        block.validate_size()?;
+        Self::validate_merkle_root(&block)?;
+        let current_time = self.clock.now() as u64;
+        let max_bytes = self.params.max_orphan_block_bytes();
+        self.state.add_orphan(block, current_time, max_bytes);
         Ok(BlockStatus::AddedAsOrphan)
     }
 
@@ -182,6 +475,25 @@ impl Blockchain {
         // Full validation
         self.validate_block(&block)?;
 
+        self.connect_block(block)
+    }
+
+    /// Accept a block at the tip without checking proof of work (test support)
+    ///
+    /// Runs every other validation rule, so crafted blocks built with
+    /// `Block::new_unmined` can exercise consensus checks without mining.
+    #[cfg(test)]
+    pub fn accept_block_for_test(&mut self, block: Block) -> Result<BlockStatus, BlockchainError> {
+        if self.state.block_index.contains_key(&block.hash) {
+            return Ok(BlockStatus::Duplicate);
+        }
+
+        self.validate_block_rules(&block)?;
+        self.connect_block(block)
+    }
+
+    /// Connect an already validated block to the tip
+    fn connect_block(&mut self, block: Block) -> Result<BlockStatus, BlockchainError> {
         // Create undo data before modifying state
         let undo = self.create_undo_data(&block);
         self.state.store_undo_data(undo);
@@ -205,13 +517,17 @@ impl Blockchain {
         self.state
             .set_active_tip(&block_hash, height, self.chain_work);
 
+        // Forget undo data for blocks too deep to be disconnected
+        self.state.compact_undo_data(self.lowest_reorg_height());
+        self.record_auto_checkpoint();
+
         // Check for difficulty adjustment
-        if self.blocks.len() as u64 % DIFFICULTY_ADJUSTMENT_INTERVAL == 0 {
+        if self.blocks.len() as u64 % self.params.adjustment_interval == 0 {
             self.adjust_difficulty();
         }
 
         // Process any orphans that depend on this block
-        self.process_orphans(&block_hash)?;
+        self.process_orphans(&block_hash);
 
         Ok(BlockStatus::AddedToMainChain)
     }
@@ -228,9 +544,28 @@ impl Blockchain {
         // Work up to parent + this block's work
         let fork_work = self.calculate_work_at_height(parent_height) + block_work;
 
-        // Compare with current chain work
-        if fork_work > self.chain_work {
-            // New chain has more work - reorganize!
+        // Compare with current chain work. On a tie the active tip stays
+        // unless the configured tie-break prefers the fork
+        let wins_tie = fork_work == self.chain_work
+            && self
+                .params
+                .tie_break
+                .prefers(&block.hash, &self.latest_block().hash);
+        // Blocks whose bodies were pruned, or buried deeper than the reorg
+        // limit, can't be disconnected
+        let can_reorg = parent_height + 1 >= self.lowest_reorg_height()
+            && self
+                .pruned_height()
+                .is_none_or(|pruned| parent_height >= pruned);
+        if (fork_work > self.chain_work || wins_tie) && !can_reorg {
+            log::warn!(
+                "Fork at height {} with more work is too deep or reaches into pruned blocks; \
+                 keeping current tip",
+                parent_height + 1
+            );
+        }
+        if (fork_work > self.chain_work || wins_tie) && can_reorg {
+            // New chain has more work (or wins the tie) - reorganize!
             self.reorganize_to_block(block, parent_height + 1)
         } else {
             // Current chain still has more work, but track this as a tip
@@ -254,65 +589,244 @@ impl Blockchain {
         fork_height: u64,
     ) -> Result<BlockStatus, BlockchainError> {
         let disconnected = self.height() - fork_height + 1;
+        let old_tip_hash = self.latest_block().hash.clone();
+        let old_height = self.height();
+        let old_work = self.chain_work;
 
-        // Disconnect blocks from current chain
-        let mut returned_txs = Vec::new();
+        // Disconnect blocks from current chain, tip first
+        let mut disconnected_blocks = Vec::new();
         while self.height() >= fork_height {
             if let Some(disconnected_block) = self.blocks.pop() {
                 // Restore UTXOs using undo data (clone to avoid borrow issues)
                 if let Some(undo) = self.state.get_undo_data(&disconnected_block.hash).cloned() {
                     self.apply_undo_data(&undo);
                 }
-                // Return non-coinbase transactions to mempool (caller should handle)
-                for tx in disconnected_block.transactions {
-                    if !tx.is_coinbase {
-                        returned_txs.push(tx);
-                    }
-                }
+                disconnected_blocks.push(disconnected_block);
+            }
+        }
+
+        // Work of the remaining chain; connecting adds the new blocks' work
+        self.chain_work = self.calculate_work_at_height(self.height());
+
+        // Connect the new block; if it turns out invalid, put the old
+        // chain back as it was
+        if let Err(e) = self.add_block_to_tip(new_block) {
+            for block in disconnected_blocks.into_iter().rev() {
+                self.update_utxo_set(&block);
+                self.blocks.push(block);
             }
+            self.chain_work = old_work;
+            log::warn!(
+                "Reorg to invalid block abandoned, keeping tip {}",
+                old_tip_hash
+            );
+            return Err(e);
         }
 
-        // Connect the new block
-        self.add_block_to_tip(new_block)?;
+        // Non-coinbase transactions go back to the caller's mempool, unless
+        // the new chain confirmed or double-spent them
+        let disconnected_txs: Vec<Transaction> = disconnected_blocks
+            .into_iter()
+            .rev()
+            .flat_map(|block| block.transactions)
+            .filter(|tx| !tx.is_coinbase)
+            .collect();
+        let disconnected_count = disconnected_txs.len();
+        let returned_txs = self.still_valid_after_reorg(disconnected_txs);
+        if returned_txs.len() < disconnected_count {
+            log::info!(
+                "Reorg dropped {} transaction(s) confirmed or double-spent on the new chain",
+                disconnected_count - returned_txs.len()
+            );
+        }
 
-        Ok(BlockStatus::CausedReorg {
+        let event = ReorgEvent {
+            time: self.clock.now() as u64,
+            old_tip_hash,
+            old_height,
+            old_work,
+            new_tip_hash: self.latest_block().hash.clone(),
+            new_height: self.height(),
+            new_work: self.chain_work,
             disconnected,
-            connected: 1,
-        })
+            connected: self.height() + 1 - fork_height,
+            returned_txs: returned_txs.iter().map(|tx| tx.id.clone()).collect(),
+        };
+        log::warn!(
+            "Chain reorganization: {} (height {}, work {}) -> {} (height {}, work {}); \
+             {} block(s) disconnected, {} connected, {} transaction(s) returned to the mempool",
+            event.old_tip_hash,
+            event.old_height,
+            event.old_work,
+            event.new_tip_hash,
+            event.new_height,
+            event.new_work,
+            event.disconnected,
+            event.connected,
+            event.returned_txs.len()
+        );
+        for tx_id in &event.returned_txs {
+            log::info!("Reorg returned transaction {}", tx_id);
+        }
+
+        self.returned_txs.extend(returned_txs.iter().cloned());
+        let status = BlockStatus::CausedReorg {
+            disconnected: event.disconnected,
+            connected: event.connected,
+            returned_txs,
+        };
+        self.state.record_reorg(event);
+
+        Ok(status)
+    }
+
+    /// Keep the disconnected transactions (in chain order) that could still
+    /// be mined: not confirmed on the active chain, and spending only
+    /// unspent outputs or outputs of earlier kept transactions, each at
+    /// most once. Token and contract transactions aren't backed by UTXOs,
+    /// so only their confirmation is checked.
+    fn still_valid_after_reorg(&self, txs: Vec<Transaction>) -> Vec<Transaction> {
+        let mut kept_outputs: HashMap<String, usize> = HashMap::new();
+        let mut spent: HashSet<(String, u32)> = HashSet::new();
+        let mut kept = Vec::new();
+
+        for tx in txs {
+            if self.tx_exists(&tx.id) {
+                continue;
+            }
+            let is_smart = tx.is_token_transaction() || tx.is_contract_transaction();
+            let spendable = is_smart
+                || tx.inputs.iter().all(|input| {
+                    let outpoint = (input.tx_id.clone(), input.output_index);
+                    let exists = self.find_utxo(&input.tx_id, input.output_index).is_some()
+                        || kept_outputs
+                            .get(&input.tx_id)
+                            .is_some_and(|&count| (input.output_index as usize) < count);
+                    exists && !spent.contains(&outpoint)
+                });
+            if !spendable {
+                log::info!("Dropping reorged transaction {}", tx.id);
+                continue;
+            }
+
+            if !is_smart {
+                spent.extend(
+                    tx.inputs
+                        .iter()
+                        .map(|input| (input.tx_id.clone(), input.output_index)),
+                );
+            }
+            kept_outputs.insert(tx.id.clone(), tx.outputs.len());
+            kept.push(tx);
+        }
+        kept
+    }
+
+    /// Lowest active-chain height a reorganization may still disconnect
+    pub fn lowest_reorg_height(&self) -> u64 {
+        let by_depth = (self.height() + 1).saturating_sub(self.params.max_reorg_depth);
+        // A checkpointed block is never disconnected, even after a restart
+        self.checkpoints
+            .get_highest()
+            .map_or(by_depth, |cp| by_depth.max(cp.height + 1))
+    }
+
+    // =========================================================================
+    // Checkpoints
+    // =========================================================================
+
+    /// Record a checkpoint for the block that just became too deep to
+    /// reorganize, if its height is a multiple of `checkpoint_interval`
+    fn record_auto_checkpoint(&mut self) {
+        let interval = self.params.checkpoint_interval;
+        let Some(height) = self.height().checked_sub(self.params.max_reorg_depth) else {
+            return;
+        };
+        if interval == 0 || height == 0 || height % interval != 0 {
+            return;
+        }
+        if let Some(block) = self.get_block(height) {
+            let checkpoint =
+                Checkpoint::with_timestamp(height, &block.hash, block.header.timestamp.timestamp());
+            log::info!("Recorded checkpoint at height {}", height);
+            self.checkpoints.add_checkpoint(checkpoint);
+        }
+    }
+
+    /// The checkpoint recorded at `height`, if any
+    pub fn checkpoint_at(&self, height: u64) -> Option<&Checkpoint> {
+        self.checkpoints.get_checkpoint(height)
+    }
+
+    /// All recorded checkpoints, lowest first
+    pub fn checkpoints(&self) -> Vec<&Checkpoint> {
+        self.checkpoints.all()
+    }
+
+    /// The last `limit` reorganizations, newest first
+    pub fn recent_reorgs(&self, limit: usize) -> Vec<&ReorgEvent> {
+        self.state.recent_reorgs(limit)
+    }
+
+    /// Undo data recorded when a block was connected: the outputs it spent
+    /// and the transactions it added. External indexes can use it to roll
+    /// back a disconnected block.
+    pub fn get_undo_data(&self, block_hash: &str) -> Option<&UndoData> {
+        self.state.get_undo_data(block_hash)
     }
 
     /// Apply undo data to restore UTXO state
     fn apply_undo_data(&mut self, undo: &UndoData) {
         // Remove outputs added by the disconnected block
+        self.tx_index.remove_transactions(&undo.added_tx_ids);
         for tx_id in &undo.added_tx_ids {
+            self.tx_heights.remove(tx_id);
+
             // Remove all outputs from this transaction
             let keys_to_remove: Vec<String> = self
                 .utxo_set
-                .keys()
-                .filter(|k| k.starts_with(tx_id))
-                .cloned()
+                .values()
+                .filter(|utxo| utxo.tx_id == *tx_id)
+                .map(UTXO::key)
                 .collect();
             for key in keys_to_remove {
-                self.utxo_set.remove(&key);
+                self.remove_utxo(&key);
             }
         }
 
         // Restore spent outputs
-        for (outpoint, output) in &undo.spent_outputs {
-            let parts: Vec<&str> = outpoint.split(':').collect();
-            if parts.len() == 2 {
-                self.utxo_set.insert(
-                    outpoint.clone(),
-                    UTXO {
-                        tx_id: parts[0].to_string(),
-                        output_index: parts[1].parse().unwrap_or(0),
+        for (key, output) in &undo.spent_outputs {
+            match OutPoint::from_key(key) {
+                Some(outpoint) => {
+                    self.insert_utxo(UTXO {
+                        tx_id: outpoint.txid,
+                        output_index: outpoint.vout,
                         output: output.clone(),
-                    },
-                );
+                    });
+                }
+                None => log::error!("Malformed outpoint in undo data: {}", key),
             }
         }
     }
 
+    /// Add an unspent output, keeping the address index in step
+    fn insert_utxo(&mut self, utxo: UTXO) {
+        self.address_balances
+            .credit(&utxo.output.recipient, utxo.output.amount);
+        if let Some(old) = self.utxo_set.insert(utxo.key(), utxo) {
+            self.address_balances
+                .debit(&old.output.recipient, old.output.amount);
+        }
+    }
+
+    /// Remove an unspent output, keeping the address index in step
+    fn remove_utxo(&mut self, key: &str) -> Option<UTXO> {
+        let utxo = self.utxo_set.remove(key)?;
+        self.address_balances
+            .debit(&utxo.output.recipient, utxo.output.amount);
+        Some(utxo)
+    }
+
     /// Create undo data for a block (before adding it)
     fn create_undo_data(&self, block: &Block) -> UndoData {
         let mut undo = UndoData::new(block.hash.clone());
@@ -324,7 +838,7 @@ impl Blockchain {
             // Record spent outputs for restoration
             if !tx.is_coinbase {
                 for input in &tx.inputs {
-                    let outpoint = format!("{}:{}", input.tx_id, input.output_index);
+                    let outpoint = OutPoint::key_for(&input.tx_id, input.output_index);
                     if let Some(utxo) = self.utxo_set.get(&outpoint) {
                         undo.record_spent(outpoint, utxo.output.clone());
                     }
@@ -345,23 +859,51 @@ impl Blockchain {
     }
 
     /// Process orphan blocks that might now be connectable
-    fn process_orphans(&mut self, parent_hash: &str) -> Result<(), BlockchainError> {
-        let orphans = self.state.get_orphans_by_parent(parent_hash);
+    ///
+    /// Connecting an orphan re-enters this function through `process_block`;
+    /// nested calls only queue the new parent hash, and the outermost call
+    /// drains the queue iteratively so long orphan chains can't blow the stack.
+    ///
+    /// The block that triggered this has already been connected, so hitting
+    /// the per-call bound is logged rather than reported as a failure.
+    fn process_orphans(&mut self, parent_hash: &str) {
+        self.orphan_worklist.push_back(parent_hash.to_string());
+        if self.draining_orphans {
+            return;
+        }
+
+        self.draining_orphans = true;
+        self.drain_orphan_worklist();
+        self.draining_orphans = false;
+    }
+
+    /// Connect queued orphans until none are left, bounded by the orphan pool
+    /// size. Parents left over when the bound is hit stay queued and are
+    /// drained by the next call.
+    fn drain_orphan_worklist(&mut self) {
+        let mut connected = 0;
+
+        while let Some(parent_hash) = self.orphan_worklist.pop_front() {
+            for orphan in self.state.get_orphans_by_parent(&parent_hash) {
+                if connected == MAX_ORPHAN_BLOCKS {
+                    log::warn!(
+                        "Connected {} orphans in one call; deferring the rest",
+                        MAX_ORPHAN_BLOCKS
+                    );
+                    self.orphan_worklist.push_front(parent_hash);
+                    return;
+                }
+                connected += 1;
 
-        for orphan in orphans {
-            let orphan_hash = orphan.hash.clone();
-            self.state.remove_orphan(&orphan_hash);
+                let orphan_hash = orphan.hash.clone();
+                self.state.remove_orphan(&orphan_hash);
 
-            // Try to process this orphan (may trigger more orphan processing)
-            match self.process_block(orphan) {
-                Ok(_) => {}
-                Err(e) => {
+                // Children of this orphan are queued rather than recursed into
+                if let Err(e) = self.process_block(orphan) {
                     log::warn!("Failed to process orphan block: {:?}", e);
                 }
             }
         }
-
-        Ok(())
     }
 
     // =========================================================================
@@ -390,7 +932,7 @@ impl Blockchain {
     fn validate_timestamp(&self, block: &Block) -> Result<(), BlockchainError> {
         let block_time = block.header.timestamp.timestamp();
         let mtp = self.get_median_time_past();
-        let current_time = Utc::now().timestamp();
+        let current_time = self.clock.now();
 
         // Block time must be greater than MTP (or equal for short chains during testing)
         // For chains shorter than MTP_BLOCK_COUNT, we allow equal timestamps
@@ -426,11 +968,12 @@ impl Blockchain {
 
     /// Adjust mining difficulty based on block times (Bitcoin-style algorithm)
     fn adjust_difficulty(&mut self) {
-        if self.blocks.len() < DIFFICULTY_ADJUSTMENT_INTERVAL as usize {
+        let interval = self.params.adjustment_interval;
+        if self.blocks.len() < interval as usize {
             return;
         }
 
-        let last_adjusted_index = self.blocks.len() - DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        let last_adjusted_index = self.blocks.len() - interval as usize;
         let last_adjusted_block = &self.blocks[last_adjusted_index];
         let latest_block = self.latest_block();
 
@@ -440,7 +983,7 @@ impl Blockchain {
             .signed_duration_since(last_adjusted_block.header.timestamp)
             .num_seconds();
 
-        let expected_time = TARGET_BLOCK_TIME * DIFFICULTY_ADJUSTMENT_INTERVAL as i64;
+        let expected_time = self.params.target_block_time * interval as i64;
 
         // Calculate adjustment ratio, clamped to max factor
         let ratio = (time_taken as f64 / expected_time as f64).clamp(
@@ -455,12 +998,14 @@ impl Blockchain {
             // Blocks were too fast, increase difficulty
             let increase = ((self.difficulty as f64 / ratio) - self.difficulty as f64)
                 .min(max_change as f64) as u32;
-            (self.difficulty + increase).min(32)
+            (self.difficulty + increase).min(self.params.max_difficulty)
         } else if ratio > 1.0 {
             // Blocks were too slow, decrease difficulty
             let decrease = (self.difficulty as f64 - (self.difficulty as f64 / ratio))
                 .min(max_change as f64) as u32;
-            self.difficulty.saturating_sub(decrease).max(1)
+            self.difficulty
+                .saturating_sub(decrease)
+                .max(self.params.min_difficulty)
         } else {
             self.difficulty
         };
@@ -477,29 +1022,217 @@ impl Blockchain {
         self.difficulty = new_difficulty;
     }
 
+    /// Coinbase reward for the next block
+    pub fn next_block_reward(&self) -> u64 {
+        self.params.block_reward_at(self.height() + 1)
+    }
+
     /// Get the current target difficulty for the next block
     pub fn get_next_difficulty(&self) -> u32 {
         // Check if we need an adjustment at the next block
-        if (self.blocks.len() as u64 + 1) % DIFFICULTY_ADJUSTMENT_INTERVAL == 0 {
+        if (self.blocks.len() as u64 + 1) % self.params.adjustment_interval == 0 {
             // Would need adjustment, but return current for now
             // Actual adjustment happens after block is mined
         }
         self.difficulty
     }
 
+    /// Estimate the network hashrate (hashes per second) from the work done
+    /// and the time elapsed over the last `window_blocks` blocks.
+    /// Returns 0.0 if the window is empty or spans zero (or negative) time.
+    pub fn estimated_hashrate(&self, window_blocks: u64) -> f64 {
+        let window = window_blocks.min(self.height()) as usize;
+        if window == 0 {
+            return 0.0;
+        }
+
+        let end = self.blocks.len() - 1;
+        let start = end - window;
+
+        let time_span = self.blocks[end]
+            .header
+            .timestamp
+            .signed_duration_since(self.blocks[start].header.timestamp)
+            .num_seconds();
+
+        if time_span <= 0 {
+            return 0.0;
+        }
+
+        let work: u128 = self.blocks[start + 1..=end]
+            .iter()
+            .map(|b| ChainStateManager::calculate_work(b.header.difficulty))
+            .sum();
+
+        work as f64 / time_span as f64
+    }
+
+    /// Fraction (0.0 to 1.0) of the best peer's chain we have verified
+    pub fn verification_progress(&self, best_peer_height: u64) -> f64 {
+        let ours = self.height() + 1;
+        let theirs = best_peer_height + 1;
+        if ours >= theirs {
+            return 1.0;
+        }
+        ours as f64 / theirs as f64
+    }
+
+    /// Whether we are still catching up: more than
+    /// [`IBD_MAX_BLOCKS_BEHIND`] blocks behind the best peer, or with a tip
+    /// older than [`IBD_MAX_TIP_AGE`]
+    pub fn is_initial_block_download(&self, best_peer_height: u64) -> bool {
+        let blocks_behind = best_peer_height.saturating_sub(self.height());
+        let tip_age = self.clock.now() - self.latest_block().header.timestamp.timestamp();
+        blocks_behind > IBD_MAX_BLOCKS_BEHIND || tip_age > IBD_MAX_TIP_AGE
+    }
+
     // =========================================================================
     // ORIGINAL METHODS (Updated)
     // =========================================================================
 
     /// Add a new block to the chain (legacy method, use process_block for network blocks)
-    pub fn add_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+    ///
+    /// Returns the transactions that reorgs caused by the block (directly or
+    /// by connecting orphans) took out of the chain, for the mempool.
+    pub fn add_block(&mut self, block: Block) -> Result<Vec<Transaction>, BlockchainError> {
         match self.process_block(block)? {
             BlockStatus::Invalid(msg) => Err(BlockchainError::InvalidBlock(msg)),
             BlockStatus::AddedAsOrphan => Err(BlockchainError::OrphanBlock(
                 "Block added as orphan".to_string(),
             )),
-            _ => Ok(()),
+            _ => Ok(self.take_returned_transactions()),
+        }
+    }
+
+    /// Transactions returned by reorgs during the last `process_block`
+    /// call, including reorgs caused by connecting orphans
+    pub fn take_returned_transactions(&mut self) -> Vec<Transaction> {
+        std::mem::take(&mut self.returned_txs)
+    }
+
+    /// Process a block and report what connecting it changed
+    ///
+    /// Fees and UTXO deltas are derived from the undo data recorded while
+    /// connecting, so they are only non-zero when this call joined the block
+    /// to the active chain (directly or through a reorg). A block that was
+    /// already known reports `Duplicate` with nothing connected.
+    pub fn apply_block(&mut self, block: Block) -> Result<BlockConnectResult, BlockchainError> {
+        let hash = block.hash.clone();
+        let height = block.index;
+        let status = self.process_block(block)?;
+        let returned_txs = self.take_returned_transactions();
+
+        let connected = status != BlockStatus::Duplicate
+            && self.get_block(height).is_some_and(|b| b.hash == hash);
+        let reorg_depth = match status {
+            BlockStatus::CausedReorg { disconnected, .. } => Some(disconnected),
+            _ => None,
+        };
+
+        let mut result = BlockConnectResult {
+            hash,
+            height,
+            status,
+            connected,
+            fees: 0,
+            utxos_added: 0,
+            utxos_removed: 0,
+            reorg_depth,
+            returned_txs,
+        };
+        if !connected {
+            return Ok(result);
+        }
+
+        let block = &self.blocks[height as usize];
+        let spent: HashMap<&str, u64> = self
+            .state
+            .get_undo_data(&result.hash)
+            .map(|undo| {
+                undo.spent_outputs
+                    .iter()
+                    .map(|(outpoint, output)| (outpoint.as_str(), output.amount))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Outputs created earlier in the same block can be spent by later txs
+        let mut created: HashMap<String, u64> = HashMap::new();
+        for tx in &block.transactions {
+            if !tx.is_coinbase {
+                let mut input_total = 0u64;
+                for input in &tx.inputs {
+                    let outpoint = OutPoint::key_for(&input.tx_id, input.output_index);
+                    let amount = spent
+                        .get(outpoint.as_str())
+                        .copied()
+                        .or_else(|| created.get(&outpoint).copied());
+                    if let Some(amount) = amount {
+                        input_total += amount;
+                        result.utxos_removed += 1;
+                    }
+                }
+                result.fees += input_total.saturating_sub(tx.total_output());
+            }
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                created.insert(OutPoint::key_for(&tx.id, index as u32), output.amount);
+            }
+            result.utxos_added += tx.outputs.len();
+        }
+
+        Ok(result)
+    }
+
+    /// Fee a transaction pays when spending the current UTXO set: inputs
+    /// minus outputs, whatever its `fee` field claims.
+    ///
+    /// Returns `None` if an input is not unspent or the outputs exceed the
+    /// inputs. A coinbase pays no fee.
+    pub fn compute_fee(&self, tx: &Transaction) -> Option<u64> {
+        if tx.is_coinbase {
+            return Some(0);
+        }
+
+        let mut input_total = 0u64;
+        for input in &tx.inputs {
+            let utxo = self.find_utxo(&input.tx_id, input.output_index)?;
+            input_total = input_total.checked_add(utxo.output.amount)?;
+        }
+        input_total.checked_sub(tx.total_output())
+    }
+
+    /// Total fees (inputs minus outputs) paid by transactions that would
+    /// extend the current tip, in order.
+    ///
+    /// Inputs resolve against the UTXO set or outputs created earlier in the
+    /// list; unknown inputs count as zero.
+    pub fn transaction_fees(&self, transactions: &[Transaction]) -> u64 {
+        let mut created: HashMap<String, u64> = HashMap::new();
+        let mut fees = 0u64;
+
+        for tx in transactions {
+            if !tx.is_coinbase {
+                let input_total: u64 = tx
+                    .inputs
+                    .iter()
+                    .filter_map(|input| {
+                        let outpoint = OutPoint::key_for(&input.tx_id, input.output_index);
+                        self.utxo_set
+                            .get(&outpoint)
+                            .map(|utxo| utxo.output.amount)
+                            .or_else(|| created.get(&outpoint).copied())
+                    })
+                    .sum();
+                fees += input_total.saturating_sub(tx.total_output());
+            }
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                created.insert(OutPoint::key_for(&tx.id, index as u32), output.amount);
+            }
         }
+
+        fees
     }
 
     /// Create and mine a new block
@@ -509,7 +1242,8 @@ impl Blockchain {
         miner_address: &str,
     ) -> Result<Block, BlockchainError> {
         // Create coinbase transaction
-        let coinbase = Transaction::coinbase(miner_address, BLOCK_REWARD, self.height() + 1);
+        let coinbase =
+            Transaction::coinbase(miner_address, self.next_block_reward(), self.height() + 1);
 
         // Add coinbase as first transaction
         let mut all_transactions = vec![coinbase];
@@ -522,6 +1256,7 @@ impl Blockchain {
             all_transactions,
             self.difficulty,
         );
+        block.set_pow(self.params.pow_algorithm);
 
         // Mine the block
         block.mine();
@@ -532,15 +1267,98 @@ impl Blockchain {
         Ok(block)
     }
 
+    /// Mine a block that competes with the last `depth` blocks and carries
+    /// more work than all of them, for exercising reorg handling on dev
+    /// nodes. The block is returned unconnected; `process_block` on it
+    /// triggers the reorg.
+    ///
+    /// Side branches are only ever one block long here, so the extra work
+    /// comes from a higher difficulty; mining time doubles with each step.
+    pub fn mine_competing_block(
+        &self,
+        depth: u64,
+        miner_address: &str,
+    ) -> Result<Block, BlockchainError> {
+        let fork_height = (self.height() + 1).saturating_sub(depth);
+        if depth == 0 || fork_height == 0 {
+            return Err(BlockchainError::ReorgFailed(format!(
+                "Depth must be between 1 and the chain height ({})",
+                self.height()
+            )));
+        }
+        if fork_height < self.lowest_reorg_height() {
+            return Err(BlockchainError::ReorgFailed(format!(
+                "Depth {} exceeds the reorg limit of {} blocks",
+                depth, self.params.max_reorg_depth
+            )));
+        }
+
+        // Smallest difficulty whose work beats the replaced blocks combined
+        let replaced_work: u128 = self.blocks[fork_height as usize..]
+            .iter()
+            .map(|b| ChainStateManager::calculate_work(b.header.difficulty))
+            .sum();
+        let difficulty = u128::BITS - replaced_work.leading_zeros();
+        if difficulty > self.params.max_difficulty {
+            return Err(BlockchainError::ReorgFailed(format!(
+                "Outworking {} blocks needs difficulty {} (max: {})",
+                depth, difficulty, self.params.max_difficulty
+            )));
+        }
+
+        let parent = &self.blocks[fork_height as usize - 1];
+        let coinbase = Transaction::coinbase(
+            miner_address,
+            self.params.block_reward_at(fork_height),
+            fork_height,
+        );
+        let mut block = Block::new(fork_height, parent.hash.clone(), vec![coinbase], difficulty);
+        block.set_pow(self.params.pow_algorithm);
+        // Stay ahead of the median time past of the chain being replaced
+        block.header.timestamp = block
+            .header
+            .timestamp
+            .max(self.latest_block().header.timestamp + chrono::Duration::seconds(1));
+        block.mine();
+
+        Ok(block)
+    }
+
     /// Validate block header only (quick validation)
     fn validate_block_header(&self, block: &Block) -> Result<(), BlockchainError> {
-        // Check proof of work
+        self.validate_pow(block)?;
+        self.validate_header_rules(block)
+    }
+
+    /// Check the block's proof of work and that its difficulty stays within
+    /// the chain's configured range
+    fn validate_pow(&self, block: &Block) -> Result<(), BlockchainError> {
+        let difficulty = block.header.difficulty;
+        if difficulty < self.params.min_difficulty || difficulty > self.params.max_difficulty {
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Difficulty {} outside allowed range [{}, {}]",
+                difficulty, self.params.min_difficulty, self.params.max_difficulty
+            )));
+        }
+
+        if block.header.pow != self.params.pow_algorithm {
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Block mined with {} but the chain requires {}",
+                block.header.pow, self.params.pow_algorithm
+            )));
+        }
+
         if !block.is_valid_pow() {
             return Err(BlockchainError::InvalidBlock(
                 "Invalid proof of work".to_string(),
             ));
         }
 
+        Ok(())
+    }
+
+    /// Validate header fields other than proof of work
+    fn validate_header_rules(&self, block: &Block) -> Result<(), BlockchainError> {
         // Verify block hash
         if !block.verify_hash() {
             return Err(BlockchainError::InvalidBlock(
@@ -556,6 +1374,45 @@ impl Blockchain {
 
     /// Validate a block before adding (full validation)
     fn validate_block(&self, block: &Block) -> Result<(), BlockchainError> {
+        self.validate_pow(block)?;
+        self.validate_block_rules(block)
+    }
+
+    /// Height of the assumevalid block, once it is known (connected, on a
+    /// side chain, or waiting as an orphan)
+    pub fn assume_valid_height(&self) -> Option<u64> {
+        let hash = self.params.assume_valid.as_ref()?;
+        self.state
+            .block_index
+            .get(hash)
+            .copied()
+            .or_else(|| self.state.orphan_pool.get(hash).map(|o| o.block.index))
+    }
+
+    /// Whether `block` is the assumevalid block or one of its ancestors.
+    ///
+    /// Blocks being connected extend the tip, so the assumevalid chain above
+    /// it can only be known through the orphan pool; walk back from the
+    /// assumevalid hash through orphans until reaching `block`'s height.
+    fn is_assume_valid_ancestor(&self, block: &Block) -> bool {
+        let Some(mut hash) = self.params.assume_valid.as_ref() else {
+            return false;
+        };
+        loop {
+            if *hash == block.hash {
+                return true;
+            }
+            match self.state.orphan_pool.get(hash) {
+                Some(orphan) if orphan.block.index > block.index => {
+                    hash = &orphan.block.header.previous_hash;
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    /// Validate everything about a block except its proof of work
+    fn validate_block_rules(&self, block: &Block) -> Result<(), BlockchainError> {
         let latest = self.latest_block();
 
         // Check block index
@@ -575,26 +1432,97 @@ impl Blockchain {
         }
 
         // Validate header
-        self.validate_block_header(block)?;
+        self.validate_header_rules(block)?;
+
+        // Enforce size, weight and transaction count limits
+        block.validate_size()?;
+
+        Self::validate_merkle_root(block)?;
+
+        // The coinbase may claim at most the subsidy plus the block's fees
+        if let Some(coinbase) = block.coinbase_tx() {
+            let claimed = coinbase.total_output();
+            let allowed = self.params.block_reward_at(block.index)
+                + self.transaction_fees(&block.transactions[1..]);
+            if claimed > allowed {
+                return Err(BlockchainError::InvalidBlock(format!(
+                    "Coinbase pays {} but subsidy plus fees is {}",
+                    claimed, allowed
+                )));
+            }
+        }
 
-        // Verify merkle root
-        if !block.verify_merkle_root() {
-            return Err(BlockchainError::InvalidBlock(
-                "Invalid merkle root".to_string(),
+        for tx in block.transactions.iter().skip(1) {
+            self.check_coinbase_maturity(tx)?;
+        }
+
+        // Validate all transactions, skipping signatures on the assumevalid
+        // chain
+        Self::validate_transactions(block, self.is_assume_valid_ancestor(block))
+    }
+
+    /// Check the header's merkle root commits to the block's transactions
+    fn validate_merkle_root(block: &Block) -> Result<(), BlockchainError> {
+        if !block.verify_merkle_root() {
+            return Err(BlockchainError::InvalidBlock(
+                "Invalid merkle root".to_string(),
             ));
         }
+        Ok(())
+    }
 
-        // Validate all transactions
+    /// Check every transaction is well formed and, unless
+    /// `skip_signatures`, correctly signed
+    fn validate_transactions(block: &Block, skip_signatures: bool) -> Result<(), BlockchainError> {
         for tx in &block.transactions {
-            if !tx
-                .is_valid()
-                .map_err(|e| BlockchainError::InvalidBlock(e.to_string()))?
-            {
+            let valid = if skip_signatures {
+                tx.is_well_formed()
+            } else {
+                tx.is_valid()
+                    .map_err(|e| BlockchainError::InvalidBlock(e.to_string()))?
+            };
+            if !valid {
                 return Err(BlockchainError::InvalidBlock(
                     "Invalid transaction".to_string(),
                 ));
             }
         }
+        Ok(())
+    }
+
+    /// Re-verify one block of the active chain: its link to the previous
+    /// block, proof of work, hash, merkle root and transaction signatures.
+    ///
+    /// A cheap spot check for integrity monitoring; unlike block acceptance
+    /// it doesn't replay the UTXO set. Pruned blocks only have their header
+    /// checked.
+    pub fn verify_block_at_height(&self, height: u64) -> Result<(), BlockchainError> {
+        let block = self
+            .get_block(height)
+            .ok_or_else(|| BlockchainError::BlockNotFound(format!("height {}", height)))?;
+
+        if let Some(previous) = height.checked_sub(1).and_then(|h| self.get_block(h)) {
+            if block.header.previous_hash != previous.hash {
+                return Err(BlockchainError::InvalidBlock(
+                    "Invalid previous hash".to_string(),
+                ));
+            }
+        }
+
+        self.validate_pow(block)?;
+        if !block.verify_hash() {
+            return Err(BlockchainError::InvalidBlock(
+                "Invalid block hash".to_string(),
+            ));
+        }
+
+        if self.has_block_data(height) {
+            Self::validate_merkle_root(block)?;
+            // The genesis coinbase is fixed by the chain, never validated
+            if height > 0 {
+                Self::validate_transactions(block, false)?;
+            }
+        }
 
         Ok(())
     }
@@ -611,7 +1539,7 @@ impl Blockchain {
             }
 
             // Check proof of work
-            if !current.is_valid_pow() {
+            if current.header.pow != self.params.pow_algorithm || !current.is_valid_pow() {
                 return false;
             }
 
@@ -620,8 +1548,8 @@ impl Blockchain {
                 return false;
             }
 
-            // Verify merkle root
-            if !current.verify_merkle_root() {
+            // Verify merkle root (pruned blocks only keep their header)
+            if self.has_block_data(i as u64) && !current.verify_merkle_root() {
                 return false;
             }
         }
@@ -630,20 +1558,139 @@ impl Blockchain {
     }
 
     /// Rebuild the UTXO set from the blockchain
+    ///
+    /// A pruned chain starts from its saved UTXO state and only replays the
+    /// blocks above it.
     pub fn rebuild_utxo_set(&mut self) {
-        self.utxo_set.clear();
-        self.coinbase_heights.clear();
+        self.load_pruned_base();
 
         // Clone blocks to avoid borrow checker issues
         let blocks = self.blocks.clone();
         for block in &blocks {
+            if self.has_block_data(block.index) {
+                self.process_block_utxos(block);
+            }
+        }
+    }
+
+    /// Reset the UTXO indexes to the state saved when the chain was pruned
+    /// (empty for an archive node)
+    fn load_pruned_base(&mut self) {
+        self.utxo_set.clear();
+        self.address_balances = AddressBalances::default();
+        self.coinbase_heights.clear();
+        self.tx_heights.clear();
+        self.tx_index = TxIndex::new();
+
+        if let Some(base) = self.pruned.take() {
+            for utxo in &base.utxos {
+                self.insert_utxo(utxo.clone());
+                self.tx_index.track_output(utxo);
+            }
+            self.coinbase_heights = base.coinbase_heights.clone();
+            self.tx_heights = base.utxo_heights.clone();
+            self.pruned = Some(base);
+        }
+    }
+
+    // =========================================================================
+    // Pruning
+    // =========================================================================
+
+    /// Convert an archive chain to a pruned one in place.
+    ///
+    /// Drops the transactions of the blocks the storage [`Pruner`] selects
+    /// when keeping the last `keep_recent` (everything below
+    /// `tip - keep_recent` except genesis), keeping headers, the UTXO set and
+    /// the heights of transactions with unspent outputs. Pruned blocks are no
+    /// longer served to peers and can't be disconnected by a reorg, and fully
+    /// spent transactions in them are no longer indexed. Returns the number
+    /// of blocks newly pruned.
+    pub fn convert_to_pruned(&mut self, keep_recent: u64) -> u64 {
+        let mut pruner = Pruner::new(PrunerConfig {
+            enabled: true,
+            keep_blocks: keep_recent,
+            ..PrunerConfig::default()
+        });
+        let replay_from = match &self.pruned {
+            Some(base) => {
+                pruner.state = base.state.clone();
+                base.state.lowest_block
+            }
+            None => 0,
+        };
+        let Some(range) = pruner.calculate_prune_range(self.height()) else {
+            return 0;
+        };
+
+        // UTXO state as of the last block being pruned
+        self.load_pruned_base();
+        let pruned_blocks = self.blocks[replay_from as usize..range.end as usize].to_vec();
+        for block in &pruned_blocks {
             self.process_block_utxos(block);
         }
+
+        for height in range.iter() {
+            let block = &mut self.blocks[height as usize];
+            pruner.record_prune(height, &block.hash, block.size() as u64);
+            block.transactions.clear();
+        }
+        let utxo_heights = self
+            .utxo_set
+            .values()
+            .filter_map(|utxo| {
+                let height = *self.tx_heights.get(&utxo.tx_id)?;
+                Some((utxo.tx_id.clone(), height))
+            })
+            .collect();
+        self.pruned = Some(PrunedBase {
+            state: pruner.state,
+            utxos: self.utxo_set.values().cloned().collect(),
+            coinbase_heights: self.coinbase_heights.clone(),
+            utxo_heights,
+        });
+        self.rebuild_utxo_set();
+
+        log::info!(
+            "Pruned block bodies {}..{} ({} blocks)",
+            range.start,
+            range.end,
+            range.count()
+        );
+        range.count()
+    }
+
+    /// Which blocks have been pruned, if the chain is pruned
+    pub fn prune_state(&self) -> Option<&PruneState> {
+        self.pruned.as_ref().map(|base| &base.state)
+    }
+
+    /// Highest block whose body was pruned, if the chain is pruned
+    pub fn pruned_height(&self) -> Option<u64> {
+        self.pruned.as_ref().map(|base| base.state.pruned_to)
+    }
+
+    /// Whether the chain has been converted to a pruned node
+    pub fn is_pruned(&self) -> bool {
+        self.pruned.is_some()
+    }
+
+    /// Whether the full block (not just its header) at `height` is available
+    pub fn has_block_data(&self, height: u64) -> bool {
+        height <= self.height()
+            && self
+                .pruned
+                .as_ref()
+                .is_none_or(|base| base.state.has_block(height))
     }
 
     /// Process a block's transactions for UTXO updates
     fn process_block_utxos(&mut self, block: &Block) {
-        for tx in &block.transactions {
+        for (position, tx) in block.transactions.iter().enumerate() {
+            self.tx_heights.insert(tx.id.clone(), block.index);
+            self.tx_index
+                .add_transaction(tx, &block.hash, block.index, position as u32);
+
             // Track coinbase transaction heights for maturity checks
             if tx.is_coinbase {
                 self.coinbase_heights.insert(tx.id.clone(), block.index);
@@ -652,8 +1699,8 @@ impl Blockchain {
             // Remove spent outputs (inputs)
             for input in &tx.inputs {
                 if !tx.is_coinbase {
-                    let key = format!("{}:{}", input.tx_id, input.output_index);
-                    self.utxo_set.remove(&key);
+                    let key = OutPoint::key_for(&input.tx_id, input.output_index);
+                    self.remove_utxo(&key);
                     // Also remove from coinbase tracking if spending a coinbase
                     self.coinbase_heights.remove(&input.tx_id);
                 }
@@ -661,15 +1708,11 @@ impl Blockchain {
 
             // Add new outputs
             for (index, output) in tx.outputs.iter().enumerate() {
-                let key = format!("{}:{}", tx.id, index);
-                self.utxo_set.insert(
-                    key,
-                    UTXO {
-                        tx_id: tx.id.clone(),
-                        output_index: index as u32,
-                        output: output.clone(),
-                    },
-                );
+                self.insert_utxo(UTXO {
+                    tx_id: tx.id.clone(),
+                    output_index: index as u32,
+                    output: output.clone(),
+                });
             }
         }
     }
@@ -688,31 +1731,152 @@ impl Blockchain {
             .collect()
     }
 
-    /// Get balance for an address (includes immature coinbase)
+    /// Get balance for an address (includes immature coinbase), from the
+    /// address index
     pub fn get_balance(&self, address: &str) -> u64 {
-        self.get_utxos_for_address(address)
-            .iter()
-            .map(|utxo| utxo.output.amount)
-            .sum()
+        self.address_balances.get(address)
+    }
+
+    /// Snapshot of the address index (cheap: shared until the chain next
+    /// changes)
+    pub fn address_balances(&self) -> AddressBalances {
+        self.address_balances.clone()
+    }
+
+    /// Number of unspent outputs at the current tip
+    pub fn utxo_count(&self) -> usize {
+        self.utxo_set.len()
+    }
+
+    /// Average change in UTXO set size per block over the last `blocks`
+    /// blocks (outputs created minus outputs spent). Pruned blocks are
+    /// skipped; returns 0.0 when none are available.
+    pub fn utxo_growth_per_block(&self, blocks: u64) -> f64 {
+        let tip = self.height();
+        let start = tip.saturating_sub(blocks.saturating_sub(1)).max(1);
+
+        let mut counted = 0u64;
+        let mut growth = 0i64;
+        for height in start..=tip {
+            if !self.has_block_data(height) {
+                continue;
+            }
+            let Some(block) = self.get_block(height) else {
+                continue;
+            };
+            for tx in &block.transactions {
+                growth += tx.outputs.len() as i64;
+                if !tx.is_coinbase {
+                    growth -= tx.inputs.len() as i64;
+                }
+            }
+            counted += 1;
+        }
+
+        if counted == 0 {
+            0.0
+        } else {
+            growth as f64 / counted as f64
+        }
     }
 
     /// Find a UTXO by transaction ID and output index
     pub fn find_utxo(&self, tx_id: &str, output_index: u32) -> Option<&UTXO> {
-        let key = format!("{}:{}", tx_id, output_index);
+        let key = OutPoint::key_for(tx_id, output_index);
         self.utxo_set.get(&key)
     }
 
+    /// Page through the UTXO set in outpoint-key order.
+    ///
+    /// Returns up to `limit` UTXOs whose keys sort after `start`, plus the
+    /// cursor to pass as `start` for the next page (`None` once exhausted).
+    /// A `limit` of 0 means [`DEFAULT_UTXO_PAGE_SIZE`].
+    /// The cursor is just the last key returned, so entries added or removed
+    /// between calls never cause skips or repeats of the remaining keys.
+    pub fn utxo_cursor(&self, start: Option<String>, limit: usize) -> (Vec<UTXO>, Option<String>) {
+        let limit = if limit == 0 {
+            DEFAULT_UTXO_PAGE_SIZE
+        } else {
+            limit
+        };
+        let mut keys: Vec<&String> = self
+            .utxo_set
+            .keys()
+            .filter(|key| start.as_ref().is_none_or(|start| *key > start))
+            .collect();
+
+        let has_more = keys.len() > limit;
+        if has_more {
+            keys.select_nth_unstable(limit);
+            keys.truncate(limit);
+        }
+        keys.sort_unstable();
+
+        let cursor = if has_more {
+            keys.last().map(|key| key.to_string())
+        } else {
+            None
+        };
+        let page = keys.iter().map(|key| self.utxo_set[*key].clone()).collect();
+        (page, cursor)
+    }
+
+    /// Whether a transaction is confirmed on the active chain (an index
+    /// lookup, no block scan). Combine with the mempool via
+    /// `Mempool::is_known` to cover unconfirmed transactions.
+    pub fn tx_exists(&self, tx_id: &str) -> bool {
+        self.tx_heights.contains_key(tx_id)
+    }
+
+    /// Get the number of confirmations for a transaction (0 if not in the chain)
+    pub fn confirmations(&self, tx_id: &str) -> u64 {
+        self.tx_heights
+            .get(tx_id)
+            .map(|&height| self.height().saturating_sub(height) + 1)
+            .unwrap_or(0)
+    }
+
+    /// Look up a confirmed transaction together with the height, hash and
+    /// timestamp of the block containing it
+    pub fn get_tx_with_location(
+        &self,
+        tx_id: &str,
+    ) -> Option<(&Transaction, u64, &str, chrono::DateTime<chrono::Utc>)> {
+        let height = *self.tx_heights.get(tx_id)?;
+        let block = self.get_block(height)?;
+        let tx = block.transactions.iter().find(|tx| tx.id == tx_id)?;
+        Some((tx, block.index, block.hash.as_str(), block.header.timestamp))
+    }
+
+    /// Resolve the amount of an outpoint, whether it is still unspent or
+    /// already spent (looked up in the block that created it)
+    pub fn input_value(&self, tx_id: &str, output_index: u32) -> Option<u64> {
+        if let Some(utxo) = self.find_utxo(tx_id, output_index) {
+            return Some(utxo.output.amount);
+        }
+
+        let height = *self.tx_heights.get(tx_id)?;
+        self.get_block(height)?
+            .transactions
+            .iter()
+            .find(|tx| tx.id == tx_id)?
+            .outputs
+            .get(output_index as usize)
+            .map(|output| output.amount)
+    }
+
     // =========================================================================
     // Coinbase Maturity (Production-grade - Bitcoin uses 100 blocks)
     // =========================================================================
 
     /// Check if a coinbase transaction is mature enough to spend
-    /// Coinbase outputs require COINBASE_MATURITY (100) confirmations
+    /// Coinbase outputs require `params.coinbase_maturity` (default 100)
+    /// confirmations
     pub fn is_coinbase_mature(&self, tx_id: &str) -> bool {
         if let Some(&coinbase_height) = self.coinbase_heights.get(tx_id) {
             let current_height = self.height();
             let confirmations = current_height.saturating_sub(coinbase_height);
-            confirmations >= COINBASE_MATURITY
+            confirmations >= self.params.coinbase_maturity
         } else {
             // Not a coinbase transaction, always spendable
             true
@@ -724,16 +1888,38 @@ impl Blockchain {
         if let Some(&coinbase_height) = self.coinbase_heights.get(tx_id) {
             let current_height = self.height();
             let confirmations = current_height.saturating_sub(coinbase_height);
-            if confirmations >= COINBASE_MATURITY {
+            if confirmations >= self.params.coinbase_maturity {
                 0
             } else {
-                COINBASE_MATURITY - confirmations
+                self.params.coinbase_maturity - confirmations
             }
         } else {
             0
         }
     }
 
+    /// Reject a transaction that spends an immature coinbase output.
+    ///
+    /// Token and contract transactions are exempt (see
+    /// [`Transaction::subject_to_coinbase_maturity`]).
+    pub fn check_coinbase_maturity(&self, tx: &Transaction) -> Result<(), BlockchainError> {
+        if !tx.subject_to_coinbase_maturity() {
+            return Ok(());
+        }
+
+        match tx
+            .inputs
+            .iter()
+            .find(|input| !self.is_coinbase_mature(&input.tx_id))
+        {
+            Some(input) => Err(BlockchainError::CoinbaseNotMature(
+                input.tx_id.clone(),
+                self.coinbase_blocks_until_mature(&input.tx_id),
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// Get only spendable UTXOs for an address (excludes immature coinbase)
     pub fn get_spendable_utxos_for_address(&self, address: &str) -> Vec<UTXO> {
         self.utxo_set
@@ -756,6 +1942,15 @@ impl Blockchain {
         self.get_balance(address) - self.get_spendable_balance(address)
     }
 
+    /// Get spendable balance buried under at least `min_confirmations` blocks
+    pub fn get_confirmed_balance(&self, address: &str, min_confirmations: u64) -> u64 {
+        self.get_spendable_utxos_for_address(address)
+            .iter()
+            .filter(|utxo| self.confirmations(&utxo.tx_id) >= min_confirmations)
+            .map(|utxo| utxo.output.amount)
+            .sum()
+    }
+
     /// Burn (remove) coins from an address as gas fees
     /// This modifies the UTXO set directly to deduct the amount
     /// Returns the actual amount burned (may be less if insufficient funds)
@@ -778,7 +1973,7 @@ impl Blockchain {
                 } else {
                     // Reduce UTXO amount
                     let new_amount = utxo.output.amount - remaining;
-                    updates.push((key.clone(), utxo.clone(), new_amount));
+                    updates.push((utxo.clone(), new_amount));
                     remaining = 0;
                 }
             }
@@ -786,13 +1981,13 @@ impl Blockchain {
 
         // Apply removals
         for key in keys_to_remove {
-            self.utxo_set.remove(&key);
+            self.remove_utxo(&key);
         }
 
         // Apply updates (reduce UTXO amount)
-        for (key, mut utxo, new_amount) in updates {
+        for (mut utxo, new_amount) in updates {
             utxo.output.amount = new_amount;
-            self.utxo_set.insert(key, utxo);
+            self.insert_utxo(utxo);
         }
 
         amount - remaining
@@ -816,6 +2011,42 @@ impl Blockchain {
         transactions
     }
 
+    /// Confirmed transactions involving `address` in blocks `from..=to`,
+    /// oldest first, with what the address received and spent in each.
+    ///
+    /// Answered from the transaction index, so only blocks whose bodies are
+    /// still stored are covered; see [`pruned_heights`](Self::pruned_heights).
+    pub fn address_history(&self, address: &str, from: u64, to: u64) -> Vec<AddressTx> {
+        let mut history: Vec<AddressTx> = Vec::new();
+        for entry in self.tx_index.get_by_address_range(address, from, to) {
+            // A transaction's sends and receives are indexed together
+            let tx = match history.last_mut() {
+                Some(last) if last.tx_id == entry.tx_id => last,
+                _ => {
+                    history.push(AddressTx {
+                        tx_id: entry.tx_id.clone(),
+                        block_height: entry.block_height,
+                        received: 0,
+                        sent: 0,
+                    });
+                    history.last_mut().expect("just pushed")
+                }
+            };
+            if entry.is_receive {
+                tx.received += entry.amount;
+            } else {
+                tx.sent += entry.amount;
+            }
+        }
+        history
+    }
+
+    /// The part of `from..=to` whose block bodies have been pruned, if any
+    pub fn pruned_heights(&self, from: u64, to: u64) -> Option<(u64, u64)> {
+        let lowest = self.pruned.as_ref()?.state.lowest_block;
+        (from < lowest && from <= to).then(|| (from, to.min(lowest - 1)))
+    }
+
     /// Get chain statistics
     pub fn stats(&self) -> ChainStats {
         let total_transactions: usize = self.blocks.iter().map(|b| b.transactions.len()).sum();
@@ -832,6 +2063,41 @@ impl Blockchain {
             orphan_count: self.state.orphan_pool.len(),
         }
     }
+
+    /// Commitment to the UTXO set: the hash of every unspent output in
+    /// outpoint order, so two nodes with the same set get the same hash
+    pub fn utxo_set_hash(&self) -> String {
+        let mut keys: Vec<&String> = self.utxo_set.keys().collect();
+        keys.sort();
+
+        let mut data = Vec::new();
+        for key in keys {
+            let utxo = &self.utxo_set[key];
+            data.extend_from_slice(key.as_bytes());
+            data.extend_from_slice(&utxo.output.amount.to_le_bytes());
+            data.extend_from_slice(utxo.output.recipient.as_bytes());
+            data.push(0);
+        }
+        double_sha256_hex(&data)
+    }
+
+    /// Summary of the UTXO set at the current tip
+    pub fn utxo_set_info(&self) -> UtxoSetInfo {
+        let addresses: HashSet<&str> = self
+            .utxo_set
+            .values()
+            .map(|utxo| utxo.output.recipient.as_str())
+            .collect();
+
+        UtxoSetInfo {
+            height: self.height(),
+            best_hash: self.latest_block().hash.clone(),
+            utxos: self.utxo_set.len(),
+            total_amount: self.utxo_set.values().map(|u| u.output.amount).sum(),
+            addresses: addresses.len(),
+            hash: self.utxo_set_hash(),
+        }
+    }
 }
 
 impl Default for Blockchain {
@@ -853,9 +2119,117 @@ pub struct ChainStats {
     pub orphan_count: usize,
 }
 
+/// Unspent amount held by each address, also reachable by the address hash
+/// contracts see on the stack. Clones share the maps until one is modified.
+#[derive(Debug, Clone, Default)]
+pub struct AddressBalances {
+    by_address: Arc<HashMap<String, u64>>,
+    by_hash: Arc<HashMap<u64, String>>,
+}
+
+impl AddressBalances {
+    /// Unspent amount held by `address`
+    pub fn get(&self, address: &str) -> u64 {
+        self.by_address.get(address).copied().unwrap_or(0)
+    }
+
+    /// Unspent amount held by the address with this [`address_hash`]
+    pub fn get_by_hash(&self, hash: u64) -> u64 {
+        self.by_hash
+            .get(&hash)
+            .map_or(0, |address| self.get(address))
+    }
+
+    /// Number of addresses holding coins
+    pub fn len(&self) -> usize {
+        self.by_address.len()
+    }
+
+    /// Whether no address holds coins
+    pub fn is_empty(&self) -> bool {
+        self.by_address.is_empty()
+    }
+
+    fn credit(&mut self, address: &str, amount: u64) {
+        let balances = Arc::make_mut(&mut self.by_address);
+        if !balances.contains_key(address) {
+            Arc::make_mut(&mut self.by_hash).insert(address_hash(address), address.to_string());
+        }
+        *balances.entry(address.to_string()).or_insert(0) += amount;
+    }
+
+    fn debit(&mut self, address: &str, amount: u64) {
+        let balances = Arc::make_mut(&mut self.by_address);
+        let Some(balance) = balances.get_mut(address) else {
+            return;
+        };
+        *balance = balance.saturating_sub(amount);
+        if *balance == 0 {
+            balances.remove(address);
+            Arc::make_mut(&mut self.by_hash).remove(&address_hash(address));
+        }
+    }
+}
+
+/// One confirmed transaction's effect on an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressTx {
+    pub tx_id: String,
+    pub block_height: u64,
+    /// Paid to the address by the transaction's outputs
+    pub received: u64,
+    /// Spent from the address by the transaction's inputs
+    pub sent: u64,
+}
+
+/// UTXO set statistics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtxoSetInfo {
+    /// Height the set was taken at
+    pub height: u64,
+    /// Tip hash the set was taken at
+    pub best_hash: String,
+    /// Number of unspent outputs
+    pub utxos: usize,
+    /// Coins held in unspent outputs
+    pub total_amount: u64,
+    /// Distinct recipients holding unspent outputs
+    pub addresses: usize,
+    /// See [`Blockchain::utxo_set_hash`]
+    pub hash: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::transaction::TransactionBuilder;
+    use crate::crypto::KeyPair;
+
+    /// A chain with spendable coinbases and one block rewarding a fresh key:
+    /// returns the chain, the key and the reward's output
+    fn funded_chain() -> (Blockchain, KeyPair, UTXO) {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        (blockchain, key_pair, utxo)
+    }
+
+    /// Spend `utxo` to `outputs`, signed by `key_pair`
+    fn spend(utxo: &UTXO, outputs: &[(&str, u64)], key_pair: &KeyPair) -> Transaction {
+        outputs
+            .iter()
+            .fold(
+                TransactionBuilder::new().add_input(utxo),
+                |builder, (to, amount)| builder.add_output(to, *amount),
+            )
+            .build_and_sign(key_pair)
+            .unwrap()
+    }
 
     #[test]
     fn test_new_blockchain() {
@@ -866,6 +2240,41 @@ mod tests {
         assert!(blockchain.chain_work > 0);
     }
 
+    #[test]
+    fn test_verify_block_at_height() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        for _ in 0..3 {
+            blockchain.mine_block(vec![], "miner").unwrap();
+        }
+        for height in 0..=3 {
+            assert!(blockchain.verify_block_at_height(height).is_ok());
+        }
+        assert!(matches!(
+            blockchain.verify_block_at_height(4),
+            Err(BlockchainError::BlockNotFound(_))
+        ));
+
+        // Corrupt the merkle root but keep the header hash and PoW valid
+        let block = &mut blockchain.blocks[2];
+        block.header.merkle_root = "00".repeat(32);
+        block.mine();
+        match blockchain.verify_block_at_height(2) {
+            Err(BlockchainError::InvalidBlock(reason)) => {
+                assert_eq!(reason, "Invalid merkle root")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // The next block no longer links to it; earlier ones are unaffected
+        match blockchain.verify_block_at_height(3) {
+            Err(BlockchainError::InvalidBlock(reason)) => {
+                assert_eq!(reason, "Invalid previous hash")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(blockchain.verify_block_at_height(1).is_ok());
+    }
+
     #[test]
     fn test_mine_block() {
         let mut blockchain = Blockchain::with_difficulty(4);
@@ -953,4 +2362,960 @@ mod tests {
         let status = blockchain.process_block(block).unwrap();
         assert_eq!(status, BlockStatus::Duplicate);
     }
+
+    /// Mine a block on top of the chain with an explicit timestamp
+    fn mine_block_at(blockchain: &mut Blockchain, timestamp: chrono::DateTime<chrono::Utc>) {
+        let height = blockchain.height() + 1;
+        let mut block = Block::new(
+            height,
+            blockchain.latest_block().hash.clone(),
+            vec![Transaction::coinbase("miner", BLOCK_REWARD, height)],
+            blockchain.difficulty,
+        );
+        block.header.timestamp = timestamp;
+        block.mine();
+        blockchain.add_block(block).unwrap();
+    }
+
+    #[test]
+    fn test_long_orphan_chain_connects_iteratively() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let mut source = blockchain.clone();
+        let genesis_time = source.latest_block().header.timestamp;
+
+        for i in 1..=60 {
+            mine_block_at(
+                &mut source,
+                genesis_time + chrono::Duration::seconds(10 * i),
+            );
+        }
+
+        // Deliver everything but the first block, newest first: all orphans
+        for block in source.blocks[2..].iter().rev() {
+            let status = blockchain.process_block(block.clone()).unwrap();
+            assert_eq!(status, BlockStatus::AddedAsOrphan);
+        }
+        assert_eq!(blockchain.state.orphan_pool.len(), 59);
+
+        // The missing link connects the whole chain
+        let status = blockchain.process_block(source.blocks[1].clone()).unwrap();
+        assert_eq!(status, BlockStatus::AddedToMainChain);
+        assert_eq!(blockchain.height(), 60);
+        assert_eq!(blockchain.latest_block().hash, source.latest_block().hash);
+        assert!(blockchain.state.orphan_pool.is_empty());
+        assert!(blockchain.orphan_worklist.is_empty());
+    }
+
+    #[test]
+    fn test_estimated_hashrate() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let genesis_time = blockchain.latest_block().header.timestamp;
+
+        // No blocks beyond genesis yet
+        assert_eq!(blockchain.estimated_hashrate(10), 0.0);
+
+        // 5 blocks, 10 seconds apart, each worth 2^4 = 16 hashes
+        for i in 1..=5 {
+            mine_block_at(
+                &mut blockchain,
+                genesis_time + chrono::Duration::seconds(10 * i),
+            );
+        }
+
+        // 80 hashes over 50 seconds
+        let hashrate = blockchain.estimated_hashrate(5);
+        assert!((hashrate - 1.6).abs() < 1e-9);
+
+        // Window larger than the chain is clamped to the chain height
+        assert!((blockchain.estimated_hashrate(100) - 1.6).abs() < 1e-9);
+
+        // Last 2 blocks: 32 hashes over 20 seconds
+        assert!((blockchain.estimated_hashrate(2) - 1.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimated_hashrate_zero_time_span() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let genesis_time = blockchain.latest_block().header.timestamp;
+
+        mine_block_at(&mut blockchain, genesis_time);
+        mine_block_at(&mut blockchain, genesis_time);
+
+        assert_eq!(blockchain.estimated_hashrate(2), 0.0);
+        assert_eq!(blockchain.estimated_hashrate(0), 0.0);
+    }
+
+    #[test]
+    fn test_input_value_unspent_and_spent() {
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let address = key_pair.address();
+        let coinbase_id = utxo.tx_id.clone();
+
+        // Unspent: resolved from the UTXO set
+        assert_eq!(blockchain.input_value(&coinbase_id, 0), Some(BLOCK_REWARD));
+
+        let tx = spend(&utxo, &[("recipient", 30), (&address, 20)], &key_pair);
+        blockchain.mine_block(vec![tx], "miner").unwrap();
+
+        // Spent: resolved from the block that created it
+        assert!(blockchain.find_utxo(&coinbase_id, 0).is_none());
+        assert_eq!(blockchain.input_value(&coinbase_id, 0), Some(BLOCK_REWARD));
+
+        // Unknown outpoints resolve to nothing
+        assert_eq!(blockchain.input_value(&coinbase_id, 5), None);
+        assert_eq!(blockchain.input_value("missing", 0), None);
+    }
+
+    #[test]
+    fn test_token_transactions_skip_coinbase_maturity() {
+        use crate::core::transaction::{
+            TokenOperationType, TransactionBuilder, TransactionInput, SEQUENCE_FINAL,
+        };
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let key_pair = KeyPair::generate();
+        let address = key_pair.address();
+
+        let block = blockchain.mine_block(vec![], &address).unwrap();
+        let coinbase_id = block.transactions[0].id.clone();
+        assert_eq!(blockchain.height(), 1);
+        assert!(!blockchain.is_coinbase_mature(&coinbase_id));
+
+        // Even an identity input naming the fresh coinbase spends nothing
+        let transfer = Transaction::with_token_data(
+            vec![TransactionInput {
+                tx_id: coinbase_id.clone(),
+                output_index: 0,
+                signature: String::new(),
+                public_key: address.clone(),
+                sequence: SEQUENCE_FINAL,
+            }],
+            vec![],
+            TokenOperationType::Transfer {
+                token_address: "0xtoken".to_string(),
+                to: "recipient".to_string(),
+                amount: 10,
+            },
+        );
+        assert!(!transfer.subject_to_coinbase_maturity());
+        assert!(blockchain.check_coinbase_maturity(&transfer).is_ok());
+
+        let utxo = blockchain.find_utxo(&coinbase_id, 0).unwrap().clone();
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 30)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert!(spend.subject_to_coinbase_maturity());
+        match blockchain.check_coinbase_maturity(&spend) {
+            Err(BlockchainError::CoinbaseNotMature(id, blocks)) => {
+                assert_eq!(id, coinbase_id);
+                assert_eq!(blocks, COINBASE_MATURITY);
+            }
+            other => panic!("expected immature coinbase, got {:?}", other),
+        }
+
+        // A block carrying the spend is rejected too
+        let mut block = Block::new(
+            2,
+            block.hash.clone(),
+            vec![Transaction::coinbase("miner", BLOCK_REWARD, 2), spend],
+            blockchain.difficulty,
+        );
+        block.mine();
+        assert!(matches!(
+            blockchain.add_block(block),
+            Err(BlockchainError::CoinbaseNotMature(id, _)) if id == coinbase_id
+        ));
+        assert_eq!(blockchain.height(), 1);
+    }
+
+    #[test]
+    fn test_locate_blocks() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+        for _ in 0..8 {
+            blockchain.mine_block(vec![], "miner").unwrap();
+        }
+        let hash = |height: u64| blockchain.get_block(height).unwrap().hash.clone();
+
+        // The first known hash wins; unknown ones are skipped
+        let locator = vec!["unknown".to_string(), hash(5), hash(2)];
+        assert_eq!(
+            blockchain.locate_blocks(&locator, None, 10),
+            vec![hash(6), hash(7), hash(8)]
+        );
+
+        // Capped by max and by the stop hash
+        let locator = vec![hash(2)];
+        assert_eq!(
+            blockchain.locate_blocks(&locator, None, 2),
+            vec![hash(3), hash(4)]
+        );
+        assert_eq!(
+            blockchain.locate_blocks(&locator, Some(&hash(4)), 10),
+            vec![hash(3), hash(4)]
+        );
+
+        // A locator we know nothing of continues from genesis
+        let from_genesis = blockchain.locate_blocks(&["unknown".to_string()], None, 3);
+        assert_eq!(from_genesis, vec![hash(1), hash(2), hash(3)]);
+
+        // A peer at our tip gets nothing
+        assert!(blockchain.locate_blocks(&[hash(8)], None, 10).is_empty());
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        let block = blockchain.mine_block(vec![], "miner").unwrap();
+        let tx_id = block.transactions[0].id.clone();
+        assert_eq!(blockchain.confirmations(&tx_id), 1);
+
+        blockchain.mine_block(vec![], "miner").unwrap();
+        assert_eq!(blockchain.confirmations(&tx_id), 2);
+        assert_eq!(blockchain.confirmations("missing"), 0);
+    }
+
+    #[test]
+    fn test_blocks_must_use_the_chain_pow_algorithm() {
+        let mut memory_chain = Blockchain::with_params(ChainParams {
+            genesis_difficulty: 4,
+            pow_algorithm: PowKind::MemoryHard,
+            ..ChainParams::default()
+        });
+        let block = memory_chain.mine_block(vec![], "miner").unwrap();
+        assert_eq!(block.header.pow, PowKind::MemoryHard);
+        assert!(block.is_valid_pow());
+        assert!(memory_chain.is_valid());
+
+        // Valid memory-hard work is still rejected by a sha256d node
+        let mut sha_chain = Blockchain::with_difficulty(4);
+        let mut foreign = Block::new(
+            1,
+            sha_chain.latest_block().hash.clone(),
+            vec![Transaction::coinbase("miner", BLOCK_REWARD, 1)],
+            4,
+        );
+        foreign.set_pow(PowKind::MemoryHard);
+        foreign.mine();
+        assert!(foreign.is_valid_pow());
+        match sha_chain.add_block(foreign) {
+            Err(BlockchainError::InvalidBlock(msg)) => assert!(msg.contains("requires sha256d")),
+            other => panic!("expected an algorithm mismatch, got {:?}", other),
+        }
+        assert_eq!(sha_chain.height(), 0);
+
+        // And the other way around
+        let mut plain = Block::new(
+            2,
+            memory_chain.latest_block().hash.clone(),
+            vec![Transaction::coinbase("miner", BLOCK_REWARD, 2)],
+            memory_chain.difficulty,
+        );
+        plain.mine();
+        assert!(plain.is_valid_pow());
+        assert!(matches!(
+            memory_chain.add_block(plain),
+            Err(BlockchainError::InvalidBlock(_))
+        ));
+        assert_eq!(memory_chain.height(), 1);
+    }
+
+    #[test]
+    fn test_accept_crafted_block_without_pow() {
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let miner = key_pair.address();
+        let spend = spend(
+            &utxo,
+            &[("recipient", 30), (&miner, BLOCK_REWARD - 30)],
+            &key_pair,
+        );
+
+        // Difficulty far beyond anything a nonce of zero can satisfy
+        let crafted = Block::new_unmined(
+            blockchain.height() + 1,
+            blockchain.latest_block().hash.clone(),
+            vec![
+                Transaction::coinbase("other_miner", BLOCK_REWARD, blockchain.height() + 1),
+                spend.clone(),
+            ],
+            64,
+        );
+        assert!(!crafted.is_valid_pow());
+        assert!(blockchain.add_block(crafted.clone()).is_err());
+
+        // Every rule except PoW still applies
+        let mut tampered = crafted.clone();
+        tampered.transactions.pop();
+        assert!(blockchain.accept_block_for_test(tampered).is_err());
+
+        let mut wrong_parent = crafted.clone();
+        wrong_parent.header.previous_hash = "0".repeat(64);
+        assert!(blockchain.accept_block_for_test(wrong_parent).is_err());
+
+        let status = blockchain.accept_block_for_test(crafted).unwrap();
+        assert_eq!(status, BlockStatus::AddedToMainChain);
+        assert_eq!(blockchain.height(), 2);
+        assert_eq!(blockchain.get_balance("recipient"), 30);
+        assert!(blockchain.find_utxo(&spend.id, 0).is_some());
+        assert!(blockchain.find_utxo(&utxo.tx_id, 0).is_none());
+    }
+
+    #[test]
+    fn test_future_block_time_uses_clock() {
+        use crate::core::clock::MockClock;
+        use std::sync::Arc;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let genesis_time = blockchain.latest_block().header.timestamp;
+        let clock = Arc::new(MockClock::new(genesis_time.timestamp()));
+        blockchain.set_clock(clock.clone());
+
+        let mut block = Block::new(
+            1,
+            blockchain.latest_block().hash.clone(),
+            vec![Transaction::coinbase("miner", BLOCK_REWARD, 1)],
+            blockchain.difficulty,
+        );
+        block.header.timestamp =
+            genesis_time + chrono::Duration::seconds(MAX_FUTURE_BLOCK_TIME + 60);
+        block.mine();
+
+        assert!(matches!(
+            blockchain.add_block(block.clone()),
+            Err(BlockchainError::InvalidTimestamp(_))
+        ));
+
+        clock.advance(120);
+        assert!(blockchain.add_block(block).is_ok());
+        assert_eq!(blockchain.height(), 1);
+    }
+
+    #[test]
+    fn test_utxo_growth_per_block() {
+        let mut blockchain = Blockchain::with_difficulty(1);
+        assert_eq!(blockchain.utxo_growth_per_block(UTXO_GROWTH_WINDOW), 0.0);
+
+        for _ in 0..3 {
+            blockchain.mine_block(vec![], "miner").unwrap();
+        }
+        // Each empty block adds just its coinbase output
+        assert_eq!(blockchain.utxo_growth_per_block(UTXO_GROWTH_WINDOW), 1.0);
+        assert_eq!(blockchain.utxo_growth_per_block(1), 1.0);
+        assert_eq!(blockchain.utxo_count(), blockchain.utxo_set.len());
+    }
+
+    #[test]
+    fn test_verification_progress() {
+        use crate::core::clock::MockClock;
+        use std::sync::Arc;
+
+        let mut blockchain = Blockchain::with_difficulty(1);
+        for _ in 0..4 {
+            blockchain.mine_block(vec![], "miner").unwrap();
+        }
+        let tip_time = blockchain.latest_block().header.timestamp.timestamp();
+        let clock = Arc::new(MockClock::new(tip_time + 60));
+        blockchain.set_clock(clock.clone());
+
+        // Behind the best peer
+        let progress = blockchain.verification_progress(9);
+        assert!(progress < 1.0);
+        assert_eq!(progress, 0.5);
+        assert!(blockchain.is_initial_block_download(20));
+
+        // Caught up (or within a few blocks with a recent tip)
+        assert_eq!(blockchain.verification_progress(4), 1.0);
+        assert_eq!(blockchain.verification_progress(0), 1.0);
+        assert!(!blockchain.is_initial_block_download(4));
+        assert!(!blockchain.is_initial_block_download(4 + IBD_MAX_BLOCKS_BEHIND));
+
+        // A stale tip means we are still downloading
+        clock.advance(IBD_MAX_TIP_AGE + 1);
+        assert!(blockchain.is_initial_block_download(4));
+    }
+
+    #[test]
+    fn test_custom_chain_params() {
+        let params = ChainParams {
+            genesis_difficulty: 2,
+            target_block_time: 30,
+            adjustment_interval: 5,
+            block_reward: 100,
+            halving_interval: 2,
+            ..ChainParams::default()
+        };
+        let mut blockchain = Blockchain::with_params(params.clone());
+        assert_eq!(blockchain.difficulty, 2);
+        assert_eq!(blockchain.params, params);
+
+        for expected in [100, 50, 50, 25] {
+            let block = blockchain.mine_block(vec![], "miner").unwrap();
+            assert_eq!(block.mining_reward(), expected);
+        }
+        assert_eq!(params.block_reward_at(200), 0);
+        assert_eq!(
+            ChainParams::default().block_reward_at(1_000_000),
+            BLOCK_REWARD
+        );
+    }
+
+    #[test]
+    fn test_difficulty_floor() {
+        use crate::core::clock::MockClock;
+        use std::sync::Arc;
+
+        let mut blockchain = Blockchain::with_params(ChainParams {
+            genesis_difficulty: 5,
+            target_block_time: 600,
+            adjustment_interval: 2,
+            min_difficulty: 4,
+            ..ChainParams::default()
+        });
+        let clock = Arc::new(MockClock::new(
+            blockchain.latest_block().header.timestamp.timestamp(),
+        ));
+        blockchain.set_clock(clock.clone());
+
+        let next_block = |blockchain: &Blockchain, difficulty: u32| {
+            let latest = blockchain.latest_block();
+            let mut block = Block::new(
+                latest.index + 1,
+                latest.hash.clone(),
+                vec![Transaction::coinbase(
+                    "miner",
+                    BLOCK_REWARD,
+                    latest.index + 1,
+                )],
+                difficulty,
+            );
+            block.header.timestamp = latest.header.timestamp + chrono::Duration::hours(1);
+            block.mine();
+            block
+        };
+
+        // Every block takes six times the target, pushing difficulty down
+        for _ in 0..12 {
+            clock.advance(3600);
+            let block = next_block(&blockchain, blockchain.difficulty);
+            blockchain.add_block(block).unwrap();
+            assert!(blockchain.difficulty >= 4);
+        }
+        assert_eq!(blockchain.difficulty, 4);
+
+        // Blocks below the floor are invalid
+        clock.advance(3600);
+        let easy = next_block(&blockchain, 3);
+        assert!(matches!(
+            blockchain.add_block(easy),
+            Err(BlockchainError::InvalidBlock(_))
+        ));
+    }
+
+    #[test]
+    fn test_undo_data_compaction() {
+        let mut blockchain = Blockchain::with_params(ChainParams {
+            genesis_difficulty: 4,
+            max_reorg_depth: 5,
+            ..ChainParams::default()
+        });
+        // One block per second keeps timestamps above the median time past
+        let mut hashes = Vec::new();
+        for height in 1..=12 {
+            let latest = blockchain.latest_block();
+            let mut block = Block::new(
+                height,
+                latest.hash.clone(),
+                vec![Transaction::coinbase("miner", BLOCK_REWARD, height)],
+                blockchain.difficulty,
+            );
+            block.header.timestamp = latest.header.timestamp + chrono::Duration::seconds(1);
+            block.mine();
+            hashes.push(block.hash.clone());
+            blockchain.add_block(block).unwrap();
+        }
+
+        // Tip is 12: only blocks 8..=12 can still be disconnected
+        assert_eq!(blockchain.lowest_reorg_height(), 8);
+        assert_eq!(blockchain.state.undo_data.len(), 5);
+        for (height, hash) in (1..).zip(&hashes) {
+            assert_eq!(blockchain.get_undo_data(hash).is_some(), height >= 8);
+        }
+
+        // A heavier fork below the limit is refused
+        let mut fork = Block::new(
+            7,
+            hashes[5].clone(),
+            vec![Transaction::coinbase("rival", BLOCK_REWARD, 7)],
+            blockchain.difficulty + 4,
+        );
+        fork.header.timestamp =
+            blockchain.latest_block().header.timestamp + chrono::Duration::seconds(1);
+        fork.mine();
+        blockchain.process_block(fork).unwrap();
+        assert_eq!(blockchain.latest_block().hash, hashes[11]);
+    }
+
+    #[test]
+    fn test_auto_checkpoints() {
+        let mut blockchain = Blockchain::with_params(ChainParams {
+            genesis_difficulty: 4,
+            max_reorg_depth: 2,
+            checkpoint_interval: 4,
+            ..ChainParams::default()
+        });
+        for height in 1..=11 {
+            let latest = blockchain.latest_block();
+            let mut block = Block::new(
+                height,
+                latest.hash.clone(),
+                vec![Transaction::coinbase("miner", BLOCK_REWARD, height)],
+                blockchain.difficulty,
+            );
+            block.header.timestamp = latest.header.timestamp + chrono::Duration::seconds(1);
+            block.mine();
+            blockchain.add_block(block).unwrap();
+        }
+
+        // Blocks 4 and 8 are buried past the reorg limit; 12 doesn't exist yet
+        let heights: Vec<u64> = blockchain
+            .checkpoints()
+            .iter()
+            .map(|cp| cp.height)
+            .collect();
+        assert_eq!(heights, vec![4, 8]);
+        for height in [4, 8] {
+            let checkpoint = blockchain.checkpoint_at(height).unwrap();
+            assert_eq!(checkpoint.hash, blockchain.get_block(height).unwrap().hash);
+        }
+        assert!(blockchain.checkpoint_at(10).is_none());
+
+        // Checkpoints are persisted with the chain
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let restored: Blockchain = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.checkpoints().len(), 2);
+        assert_eq!(
+            restored.checkpoint_at(8).unwrap().hash,
+            blockchain.checkpoint_at(8).unwrap().hash
+        );
+    }
+
+    #[test]
+    fn test_reorg_event_log() {
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let block_1 = blockchain.latest_block().clone();
+        let spend = spend(&utxo, &[("recipient", 50)], &key_pair);
+        let old_tip = blockchain.mine_block(vec![spend.clone()], "miner").unwrap();
+        let old_work = blockchain.chain_work;
+        assert!(blockchain.recent_reorgs(10).is_empty());
+
+        // A competing block at height 2 with more work replaces the tip
+        let mut fork = Block::new(
+            2,
+            block_1.hash.clone(),
+            vec![Transaction::coinbase("rival", BLOCK_REWARD, 2)],
+            blockchain.difficulty + 2,
+        );
+        fork.mine();
+        let status = blockchain.process_block(fork.clone()).unwrap();
+        assert_eq!(
+            status,
+            BlockStatus::CausedReorg {
+                disconnected: 1,
+                connected: 1,
+                returned_txs: vec![spend.clone()],
+            }
+        );
+
+        let events = blockchain.recent_reorgs(10);
+        assert_eq!(events.len(), 1);
+        let event = events[0];
+        assert_eq!(event.old_tip_hash, old_tip.hash);
+        assert_eq!(event.old_height, 2);
+        assert_eq!(event.old_work, old_work);
+        assert_eq!(event.new_tip_hash, fork.hash);
+        assert_eq!(event.new_height, 2);
+        assert_eq!(event.new_work, blockchain.chain_work);
+        assert_eq!(
+            event.new_work,
+            blockchain.calculate_work_at_height(blockchain.height())
+        );
+        assert!(event.new_work > event.old_work);
+        assert_eq!(event.returned_txs, vec![spend.id]);
+    }
+
+    #[test]
+    fn test_failed_reorg_restores_old_chain() {
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let block_1 = blockchain.latest_block().clone();
+        let spend = spend(&utxo, &[("recipient", 50)], &key_pair);
+        let old_tip = blockchain.mine_block(vec![spend.clone()], "miner").unwrap();
+        let old_work = blockchain.chain_work;
+        let mut old_utxos: Vec<String> = blockchain.utxo_set.keys().cloned().collect();
+        old_utxos.sort();
+
+        // More work, but its coinbase claims far more than the reward
+        let mut fork = Block::new(
+            2,
+            block_1.hash.clone(),
+            vec![Transaction::coinbase("rival", BLOCK_REWARD * 100, 2)],
+            blockchain.difficulty + 2,
+        );
+        fork.mine();
+        assert!(blockchain.process_block(fork).is_err());
+
+        assert_eq!(blockchain.height(), 2);
+        assert_eq!(blockchain.latest_block().hash, old_tip.hash);
+        assert_eq!(blockchain.chain_work, old_work);
+        let mut utxos: Vec<String> = blockchain.utxo_set.keys().cloned().collect();
+        utxos.sort();
+        assert_eq!(utxos, old_utxos);
+        assert_eq!(blockchain.get_balance("recipient"), 50);
+        assert!(blockchain.tx_exists(&spend.id));
+        assert!(blockchain.recent_reorgs(10).is_empty());
+        assert!(blockchain.is_valid());
+
+        // The restored tip still extends normally
+        blockchain.mine_block(vec![], "miner").unwrap();
+        assert_eq!(blockchain.height(), 3);
+    }
+
+    #[test]
+    fn test_address_index_matches_utxo_set() {
+        use crate::crypto::address_hash;
+
+        // Sum every output per recipient the slow way
+        let assert_index = |blockchain: &Blockchain| {
+            let mut scanned: HashMap<String, u64> = HashMap::new();
+            for utxo in blockchain.utxo_set.values() {
+                *scanned.entry(utxo.output.recipient.clone()).or_insert(0) += utxo.output.amount;
+            }
+            let index = blockchain.address_balances();
+            assert_eq!(index.len(), scanned.len());
+            for (address, amount) in &scanned {
+                assert_eq!(blockchain.get_balance(address), *amount);
+                assert_eq!(index.get_by_hash(address_hash(address)), *amount);
+            }
+        };
+
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let block_1 = blockchain.latest_block().clone();
+        let payment = spend(&utxo, &[("recipient", 50)], &key_pair);
+        blockchain.mine_block(vec![payment], "miner").unwrap();
+        assert_index(&blockchain);
+        assert_eq!(blockchain.get_balance("recipient"), 50);
+
+        // Disconnecting the spend moves the coins back to the key's address
+        let mut fork = Block::new(
+            2,
+            block_1.hash.clone(),
+            vec![Transaction::coinbase("rival", BLOCK_REWARD, 2)],
+            blockchain.difficulty + 2,
+        );
+        fork.mine();
+        blockchain.process_block(fork).unwrap();
+        assert_index(&blockchain);
+        assert_eq!(blockchain.get_balance("recipient"), 0);
+        assert_eq!(blockchain.get_balance(&key_pair.address()), BLOCK_REWARD);
+
+        // Burns shrink or drop outputs; a drained address leaves the index
+        assert_eq!(blockchain.burn_from_address("rival", 10), 10);
+        assert_index(&blockchain);
+        blockchain.burn_from_address("rival", BLOCK_REWARD);
+        assert_index(&blockchain);
+        assert_eq!(
+            blockchain
+                .address_balances()
+                .get_by_hash(address_hash("rival")),
+            0
+        );
+    }
+
+    #[test]
+    fn test_equal_work_fork_tie_break() {
+        // Build a one-block chain plus a competing block at the same height
+        let setup = |tie_break: TieBreak| {
+            let mut blockchain = Blockchain::with_params(ChainParams {
+                genesis_difficulty: 4,
+                tie_break,
+                ..ChainParams::default()
+            });
+            let first = blockchain.mine_block(vec![], "first").unwrap();
+            let mut rival = Block::new(
+                1,
+                blockchain.blocks[0].hash.clone(),
+                vec![Transaction::coinbase("rival", BLOCK_REWARD, 1)],
+                first.header.difficulty,
+            );
+            rival.mine();
+            (blockchain, first, rival)
+        };
+
+        // Default policy: the first-seen tip stays active
+        let (mut blockchain, first, rival) = setup(TieBreak::default());
+        let work = blockchain.chain_work;
+        assert_eq!(
+            blockchain.process_block(rival.clone()).unwrap(),
+            BlockStatus::AddedToMainChain
+        );
+        assert_eq!(blockchain.latest_block().hash, first.hash);
+        assert_eq!(blockchain.chain_work, work);
+        assert!(blockchain.recent_reorgs(10).is_empty());
+
+        // Lowest hash: the tip is the same whichever block arrived first
+        let (mut blockchain, first, rival) = setup(TieBreak::LowestHash);
+        blockchain.process_block(rival.clone()).unwrap();
+        let lowest = first.hash.clone().min(rival.hash.clone());
+        assert_eq!(blockchain.latest_block().hash, lowest);
+        assert_eq!(blockchain.chain_work, work);
+    }
+
+    #[test]
+    fn test_convert_to_pruned_keeps_balances() {
+        let (mut blockchain, alice, utxo) = funded_chain();
+        let bob = KeyPair::generate();
+        let pay_bob = spend(
+            &utxo,
+            &[(&bob.address(), 20), (&alice.address(), 30)],
+            &alice,
+        );
+        blockchain
+            .mine_block(vec![pay_bob.clone()], "miner")
+            .unwrap();
+        for _ in 0..4 {
+            blockchain.mine_block(vec![], &alice.address()).unwrap();
+        }
+
+        let balances = |chain: &Blockchain| {
+            [&alice.address(), &bob.address(), "miner"].map(|address| {
+                (
+                    chain.get_balance(address),
+                    chain.get_spendable_balance(address),
+                    chain.get_immature_balance(address),
+                    chain.get_confirmed_balance(address, 3),
+                )
+            })
+        };
+        let confirmations = blockchain.confirmations(&pay_bob.id);
+        assert_eq!(confirmations, 5);
+        assert_eq!(blockchain.get_confirmed_balance(&bob.address(), 3), 20);
+        let before = balances(&blockchain);
+        let utxo_count = blockchain.utxo_set.len();
+
+        // Height 6, keep 2: bodies of blocks 1..=3 are dropped
+        assert_eq!(blockchain.convert_to_pruned(2), 3);
+        assert_eq!(blockchain.pruned_height(), Some(3));
+        assert!(!blockchain.has_block_data(3));
+        assert!(blockchain.has_block_data(4));
+        assert!(blockchain.blocks[1..4]
+            .iter()
+            .all(|b| b.transactions.is_empty()));
+        assert_eq!(blockchain.blocks.len(), 7);
+        assert!(blockchain.is_valid());
+        assert_eq!(balances(&blockchain), before);
+        assert_eq!(blockchain.confirmations(&pay_bob.id), confirmations);
+        assert!(blockchain.tx_exists(&pay_bob.id));
+        assert_eq!(blockchain.utxo_set.len(), utxo_count);
+        assert_eq!(blockchain.convert_to_pruned(2), 0);
+
+        // The pruned state survives a save/load round trip
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let mut reloaded: Blockchain = serde_json::from_str(&json).unwrap();
+        reloaded.rebuild_utxo_set();
+        assert_eq!(reloaded.pruned_height(), Some(3));
+        assert_eq!(balances(&reloaded), before);
+        assert_eq!(reloaded.confirmations(&pay_bob.id), confirmations);
+
+        // Outputs created in pruned blocks are still spendable
+        let bob_utxo = blockchain.get_utxos_for_address(&bob.address())[0].clone();
+        let pay_alice = spend(&bob_utxo, &[(&alice.address(), 20)], &bob);
+        blockchain.mine_block(vec![pay_alice], "miner").unwrap();
+        assert_eq!(blockchain.get_balance(&bob.address()), 0);
+    }
+
+    #[test]
+    fn test_apply_block_reports_fees_and_utxo_deltas() {
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let miner = key_pair.address();
+
+        // 50 in, 45 out: 5 coins of fees
+        let spend = spend(&utxo, &[("recipient", 30), (&miner, 15)], &key_pair);
+        let mut block = Block::new(
+            2,
+            blockchain.latest_block().hash.clone(),
+            vec![Transaction::coinbase("miner", BLOCK_REWARD, 2), spend],
+            blockchain.difficulty,
+        );
+        block.mine();
+
+        let utxos_before = blockchain.utxo_set.len();
+        let result = blockchain.apply_block(block.clone()).unwrap();
+
+        assert_eq!(result.hash, block.hash);
+        assert_eq!(result.height, 2);
+        assert_eq!(result.status, BlockStatus::AddedToMainChain);
+        assert!(result.connected);
+        assert_eq!(result.fees, 5);
+        assert_eq!(result.utxos_added, 3);
+        assert_eq!(result.utxos_removed, 1);
+        assert_eq!(result.reorg_depth, None);
+        assert_eq!(
+            blockchain.utxo_set.len(),
+            utxos_before + result.utxos_added - result.utxos_removed
+        );
+
+        // Re-applying is a no-op
+        let duplicate = blockchain.apply_block(block).unwrap();
+        assert_eq!(duplicate.status, BlockStatus::Duplicate);
+        assert!(!duplicate.connected);
+        assert_eq!(duplicate.fees, 0);
+        assert_eq!(duplicate.utxos_added, 0);
+        assert_eq!(duplicate.utxos_removed, 0);
+    }
+
+    #[test]
+    fn test_assume_valid_skips_signatures_on_its_chain() {
+        let (mut blockchain, key_pair, utxo) = funded_chain();
+        let miner = key_pair.address();
+
+        // Correctly shaped spend whose signature no longer matches its content
+        let bad_spend = |utxo: &UTXO| {
+            let mut tx = spend(utxo, &[("recipient", 30)], &key_pair);
+            tx.outputs[0].amount = 29;
+            tx.id = tx.calculate_hash();
+            assert!(tx.is_well_formed());
+            assert!(!tx.is_valid().unwrap());
+            tx
+        };
+        let mined = |index: u64, prev: &str, txs: Vec<Transaction>, difficulty: u32| {
+            let mut block = Block::new(index, prev.to_string(), txs, difficulty);
+            block.mine();
+            block
+        };
+
+        let first = blockchain.latest_block().clone();
+        let difficulty = blockchain.difficulty;
+
+        let block2 = mined(
+            2,
+            &first.hash,
+            vec![
+                Transaction::coinbase("miner", BLOCK_REWARD, 2),
+                bad_spend(&utxo),
+            ],
+            difficulty,
+        );
+        let block3 = mined(
+            3,
+            &block2.hash,
+            vec![Transaction::coinbase(&miner, BLOCK_REWARD, 3)],
+            difficulty,
+        );
+
+        // Without assumevalid the bad signature is rejected
+        let mut strict = blockchain.clone();
+        assert!(strict.process_block(block2.clone()).is_err());
+
+        blockchain.params.assume_valid = Some(block3.hash.clone());
+        assert_eq!(
+            blockchain.process_block(block3.clone()).unwrap(),
+            BlockStatus::AddedAsOrphan
+        );
+        assert_eq!(blockchain.assume_valid_height(), Some(3));
+
+        // A competing block at the same height is off the assumevalid chain
+        let rival2 = mined(
+            2,
+            &first.hash,
+            vec![
+                Transaction::coinbase(&miner, BLOCK_REWARD, 2),
+                bad_spend(&utxo),
+            ],
+            difficulty,
+        );
+        assert_ne!(rival2.hash, block2.hash);
+        assert!(blockchain.process_block(rival2).is_err());
+        assert_eq!(blockchain.height(), 1);
+
+        blockchain.process_block(block2).unwrap();
+        assert_eq!(blockchain.height(), 3);
+        assert_eq!(blockchain.latest_block().hash, block3.hash);
+
+        // Above the assumevalid height signatures are checked again
+        let utxo = blockchain
+            .find_utxo(&block3.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let block4 = mined(
+            4,
+            &block3.hash,
+            vec![
+                Transaction::coinbase("miner", BLOCK_REWARD, 4),
+                bad_spend(&utxo),
+            ],
+            difficulty,
+        );
+        assert!(blockchain.process_block(block4).is_err());
+        assert_eq!(blockchain.height(), 3);
+    }
+
+    #[test]
+    fn test_utxo_cursor_covers_set_once() {
+        use crate::core::transaction::TransactionOutput;
+        use std::collections::HashSet;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+        blockchain.utxo_set.clear();
+        for i in 0..1000u32 {
+            let utxo = UTXO {
+                tx_id: format!("tx{:04}", i / 2),
+                output_index: i % 2,
+                output: TransactionOutput {
+                    amount: 1,
+                    recipient: "owner".to_string(),
+                },
+            };
+            blockchain.utxo_set.insert(utxo.key(), utxo);
+        }
+
+        let mut seen = HashSet::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let (page, next) = blockchain.utxo_cursor(cursor, 64);
+            assert!(page.len() <= 64);
+            for utxo in page {
+                assert!(seen.insert(utxo.key()));
+            }
+            pages += 1;
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 16);
+        assert_eq!(seen.len(), 1000);
+
+        // A zero limit falls back to the default page size rather than
+        // returning an empty, exhausted-looking page
+        let (page, next) = blockchain.utxo_cursor(None, 0);
+        assert_eq!(page.len(), 1000);
+        assert!(next.is_none());
+        let (page, next) = blockchain.utxo_cursor(Some(page[9].key()), 0);
+        assert_eq!(page.len(), 990);
+        assert!(next.is_none());
+
+        // Keys deleted between calls (including the cursor itself) are skipped
+        let (first, cursor) = blockchain.utxo_cursor(None, 10);
+        let cursor = cursor.unwrap();
+        blockchain.utxo_set.remove(&cursor);
+        blockchain.utxo_set.remove("tx0005:1");
+        let (second, _) = blockchain.utxo_cursor(Some(cursor), 10);
+        assert_eq!(first.len(), 10);
+        assert_eq!(second[0].tx_id, "tx0005");
+        assert_eq!(second[0].output_index, 0);
+        assert_eq!(second[1].tx_id, "tx0006");
+    }
 }
@@ -4,13 +4,19 @@
 //! This is a critical component for making the blockchain behave like Bitcoin.
 
 use crate::core::block::Block;
-use crate::core::transaction::TransactionOutput;
+use crate::core::transaction::{Transaction, TransactionOutput};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 /// Maximum number of orphan blocks to keep in memory
 pub const MAX_ORPHAN_BLOCKS: usize = 100;
 
+/// Default memory budget for orphan blocks, in megabytes
+pub const DEFAULT_MAX_ORPHAN_BLOCK_MB: u64 = 16;
+
+/// Bytes per megabyte for memory budgets
+pub const BYTES_PER_MB: usize = 1_000_000;
+
 /// Maximum time (in seconds) an orphan block can stay in the pool
 pub const ORPHAN_BLOCK_EXPIRE_TIME: u64 = 3600; // 1 hour
 
@@ -20,6 +26,35 @@ pub const MTP_BLOCK_COUNT: usize = 11;
 /// Maximum allowed time drift into the future (2 hours in seconds)
 pub const MAX_FUTURE_BLOCK_TIME: i64 = 7200;
 
+/// Number of reorg events kept in memory
+pub const MAX_REORG_EVENTS: usize = 100;
+
+/// Deepest reorganization accepted by default, in blocks disconnected
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = 100;
+
+/// How to choose between two chains with exactly equal work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// Keep the tip that was seen first (the active tip stays active)
+    #[default]
+    FirstSeen,
+    /// Switch to whichever tip has the lower block hash, so every node
+    /// settles on the same tip regardless of arrival order
+    LowestHash,
+}
+
+impl TieBreak {
+    /// Whether a competing tip with the same work as the active tip should
+    /// replace it
+    pub fn prefers(&self, candidate_hash: &str, active_hash: &str) -> bool {
+        match self {
+            TieBreak::FirstSeen => false,
+            TieBreak::LowestHash => candidate_hash < active_hash,
+        }
+    }
+}
+
 /// Represents a chain tip (end of a chain branch)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChainTip {
@@ -53,21 +88,25 @@ pub struct OrphanBlock {
     pub parent_hash: String,
     /// Timestamp when this orphan was received
     pub received_at: u64,
+    /// Estimated size of the block in bytes
+    pub size: usize,
 }
 
 impl OrphanBlock {
     pub fn new(block: Block, received_at: u64) -> Self {
         let parent_hash = block.header.previous_hash.clone();
+        let size = block.size();
         Self {
             block,
             parent_hash,
             received_at,
+            size,
         }
     }
 
     /// Check if this orphan has expired
     pub fn is_expired(&self, current_time: u64) -> bool {
-        current_time - self.received_at > ORPHAN_BLOCK_EXPIRE_TIME
+        current_time.saturating_sub(self.received_at) > ORPHAN_BLOCK_EXPIRE_TIME
     }
 }
 
@@ -102,6 +141,27 @@ impl UndoData {
     }
 }
 
+/// Record of a chain reorganization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgEvent {
+    /// When the reorg happened (Unix timestamp)
+    pub time: u64,
+    /// Tip before the reorg
+    pub old_tip_hash: String,
+    pub old_height: u64,
+    pub old_work: u128,
+    /// Tip after the reorg
+    pub new_tip_hash: String,
+    pub new_height: u64,
+    pub new_work: u128,
+    /// Blocks removed from the old chain
+    pub disconnected: u64,
+    /// Blocks added from the new chain
+    pub connected: u64,
+    /// Non-coinbase transactions from disconnected blocks (to return to the mempool)
+    pub returned_txs: Vec<String>,
+}
+
 /// Manages chain state including orphans, tips, and undo data
 #[derive(Debug, Clone, Default)]
 pub struct ChainStateManager {
@@ -109,6 +169,10 @@ pub struct ChainStateManager {
     pub orphan_pool: HashMap<String, OrphanBlock>,
     /// Map from parent hash to orphan hashes (for quick lookup when parent arrives)
     pub orphans_by_parent: HashMap<String, Vec<String>>,
+    /// Orphan hashes in arrival order (oldest first)
+    orphan_order: VecDeque<String>,
+    /// Total estimated size of the orphan pool in bytes
+    orphan_bytes: usize,
     /// All known chain tips
     pub chain_tips: Vec<ChainTip>,
     /// Undo data for recent blocks (for reorganization)
@@ -117,6 +181,8 @@ pub struct ChainStateManager {
     pub block_index: HashMap<String, u64>,
     /// Height to block hash mapping
     pub height_index: HashMap<u64, String>,
+    /// Most recent reorganizations, oldest first
+    pub reorg_log: VecDeque<ReorgEvent>,
 }
 
 impl ChainStateManager {
@@ -124,15 +190,19 @@ impl ChainStateManager {
         Self {
             orphan_pool: HashMap::new(),
             orphans_by_parent: HashMap::new(),
+            orphan_order: VecDeque::new(),
+            orphan_bytes: 0,
             chain_tips: Vec::new(),
             undo_data: HashMap::new(),
             block_index: HashMap::new(),
             height_index: HashMap::new(),
+            reorg_log: VecDeque::new(),
         }
     }
 
-    /// Add an orphan block to the pool
-    pub fn add_orphan(&mut self, block: Block, current_time: u64) -> bool {
+    /// Add an orphan block to the pool, evicting the oldest orphans to stay
+    /// within `max_bytes`
+    pub fn add_orphan(&mut self, block: Block, current_time: u64, max_bytes: usize) -> bool {
         // Check if we already have this orphan or if pool is full
         if self.orphan_pool.len() >= MAX_ORPHAN_BLOCKS {
             self.prune_orphans(current_time);
@@ -146,6 +216,18 @@ impl ChainStateManager {
         }
 
         let orphan = OrphanBlock::new(block, current_time);
+        if orphan.size > max_bytes {
+            return false;
+        }
+        while self.orphan_bytes + orphan.size > max_bytes {
+            match self.orphan_order.front().cloned() {
+                Some(oldest) => self.remove_orphan(&oldest),
+                None => break,
+            }
+        }
+
+        self.orphan_bytes += orphan.size;
+        self.orphan_order.push_back(block_hash.clone());
         self.orphan_pool.insert(block_hash.clone(), orphan);
 
         // Index by parent hash
@@ -174,6 +256,9 @@ impl ChainStateManager {
     /// Remove an orphan block (when it gets connected)
     pub fn remove_orphan(&mut self, block_hash: &str) {
         if let Some(orphan) = self.orphan_pool.remove(block_hash) {
+            self.orphan_bytes -= orphan.size;
+            self.orphan_order.retain(|h| h != block_hash);
+
             // Remove from parent index
             if let Some(siblings) = self.orphans_by_parent.get_mut(&orphan.parent_hash) {
                 siblings.retain(|h| h != block_hash);
@@ -184,6 +269,11 @@ impl ChainStateManager {
         }
     }
 
+    /// Total estimated size of the orphan pool in bytes
+    pub fn orphan_bytes(&self) -> usize {
+        self.orphan_bytes
+    }
+
     /// Remove expired orphans
     pub fn prune_orphans(&mut self, current_time: u64) {
         let expired: Vec<String> = self
@@ -214,6 +304,20 @@ impl ChainStateManager {
         self.undo_data.get(block_hash)
     }
 
+    /// Drop undo data for blocks below `min_height` (too deep to ever be
+    /// disconnected), including blocks of abandoned branches. Returns the
+    /// number of entries removed.
+    pub fn compact_undo_data(&mut self, min_height: u64) -> usize {
+        let before = self.undo_data.len();
+        let block_index = &self.block_index;
+        self.undo_data.retain(|hash, _| {
+            block_index
+                .get(hash)
+                .is_some_and(|&height| height >= min_height)
+        });
+        before - self.undo_data.len()
+    }
+
     /// Update the active chain tip
     pub fn set_active_tip(&mut self, block_hash: &str, height: u64, chain_work: u128) {
         // Deactivate all tips
@@ -259,6 +363,19 @@ impl ChainStateManager {
         1u128 << difficulty.min(127) as u128
     }
 
+    /// Record a reorg, dropping the oldest once `MAX_REORG_EVENTS` are kept
+    pub fn record_reorg(&mut self, event: ReorgEvent) {
+        if self.reorg_log.len() >= MAX_REORG_EVENTS {
+            self.reorg_log.pop_front();
+        }
+        self.reorg_log.push_back(event);
+    }
+
+    /// The last `limit` reorgs, newest first
+    pub fn recent_reorgs(&self, limit: usize) -> Vec<&ReorgEvent> {
+        self.reorg_log.iter().rev().take(limit).collect()
+    }
+
     /// Check if we have a block at the given height
     pub fn has_block_at_height(&self, height: u64) -> bool {
         self.height_index.contains_key(&height)
@@ -278,7 +395,14 @@ pub enum BlockStatus {
     /// Block was added as an orphan (waiting for parent)
     AddedAsOrphan,
     /// Block caused a chain reorganization
-    CausedReorg { disconnected: u64, connected: u64 },
+    CausedReorg {
+        disconnected: u64,
+        connected: u64,
+        /// Transactions from disconnected blocks that are still valid on
+        /// the new chain, in chain order, for the caller to re-add to its
+        /// mempool
+        returned_txs: Vec<Transaction>,
+    },
     /// Block is a duplicate
     Duplicate,
     /// Block is invalid
@@ -310,6 +434,53 @@ mod tests {
         assert!(work_16 > work_8);
     }
 
+    #[test]
+    fn test_orphan_blocks_respect_memory_budget() {
+        use crate::core::transaction::{ContractOperationType, Transaction};
+
+        let mut manager = ChainStateManager::new();
+        let budget = BYTES_PER_MB;
+
+        // ~100KB blocks: a 1MB budget holds nine of them
+        let blocks: Vec<Block> = (0..12u64)
+            .map(|i| {
+                let deploy = Transaction::with_contract_data(
+                    vec![],
+                    vec![],
+                    ContractOperationType::Deploy {
+                        bytecode: vec![i as u8; 100_000],
+                        constructor_args: vec![],
+                    },
+                );
+                Block::new(i + 2, format!("parent{}", i), vec![deploy], 1)
+            })
+            .collect();
+
+        for (time, block) in blocks.iter().enumerate() {
+            assert!(manager.add_orphan(block.clone(), time as u64, budget));
+            assert!(manager.orphan_bytes() <= budget);
+        }
+
+        let kept = budget / blocks[0].size();
+        assert_eq!(manager.orphan_pool.len(), kept);
+        for (i, block) in blocks.iter().enumerate() {
+            // The oldest orphans made room for the newest
+            let expect_kept = i >= blocks.len() - kept;
+            assert_eq!(manager.orphan_pool.contains_key(&block.hash), expect_kept);
+        }
+        assert_eq!(
+            manager.orphan_bytes(),
+            manager.orphan_pool.values().map(|o| o.size).sum::<usize>()
+        );
+
+        // Removing orphans releases their bytes
+        let hashes: Vec<String> = manager.orphan_pool.keys().cloned().collect();
+        for hash in hashes {
+            manager.remove_orphan(&hash);
+        }
+        assert_eq!(manager.orphan_bytes(), 0);
+    }
+
     #[test]
     fn test_chain_tip_management() {
         let mut manager = ChainStateManager::new();
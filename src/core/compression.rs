@@ -255,6 +255,7 @@ impl BlockCompressor {
             timestamp,
             difficulty,
             nonce: compressed.nonce,
+            pow: prev.map(|p| p.header.pow).unwrap_or_default(),
         })
     }
 
@@ -0,0 +1,102 @@
+//! In-process event bus
+//!
+//! Code that changes chain or mempool state publishes a [`ChainEvent`]
+//! instead of notifying each interested subsystem by hand. Consumers (the
+//! websocket broadcaster, P2P relay, indexers) subscribe and react on their
+//! own tasks.
+
+use crate::core::block::Block;
+use crate::core::chain_state::ReorgEvent;
+use crate::core::transaction::Transaction;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before the slowest one starts lagging
+pub const EVENT_BUS_CAPACITY: usize = 256;
+
+/// A change to chain or mempool state
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    /// A block was connected to the active chain
+    BlockConnected { block: Block, reward: u64 },
+    /// A transaction was accepted into the mempool
+    TxAccepted { tx: Transaction },
+    /// The active chain switched to a competing branch
+    Reorg(ReorgEvent),
+}
+
+/// Fan-out channel for [`ChainEvent`]s
+#[derive(Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<ChainEvent>,
+}
+
+impl EventBus {
+    /// Create a bus with the default capacity
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    /// Publish an event to every subscriber, returning how many received it
+    pub fn publish(&self, event: ChainEvent) -> usize {
+        // Publishing with no subscribers is not an error
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Subscribe to events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ChainEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Number of active subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_block_connected_fans_out() {
+        let bus = EventBus::new();
+        assert_eq!(
+            bus.publish(ChainEvent::TxAccepted {
+                tx: Transaction::coinbase("nobody", 50, 1),
+            }),
+            0
+        );
+
+        let mut indexer = bus.subscribe();
+        let mut websocket = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 2);
+
+        let block = Block::genesis(4);
+        let delivered = bus.publish(ChainEvent::BlockConnected {
+            block: block.clone(),
+            reward: 50,
+        });
+        assert_eq!(delivered, 2);
+
+        for rx in [&mut indexer, &mut websocket] {
+            match rx.recv().await.unwrap() {
+                ChainEvent::BlockConnected { block: got, reward } => {
+                    assert_eq!(got.hash, block.hash);
+                    assert_eq!(reward, 50);
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+}
@@ -1,42 +1,62 @@
 //! Core blockchain components
 //!
 //! This module contains the fundamental building blocks:
+//! - Amounts (display with configurable decimals)
 //! - Transactions (UTXO model with locktime, RBF, replay protection)
 //! - Blocks (with proof of work and size limits)
 //! - Blockchain (chain management with coinbase maturity)
 //! - Chain state (fork resolution, orphans, reorgs)
+//! - Clocks (injectable time source)
+//! - Event bus (domain events for decoupled subscribers)
 //! - SPV support (bloom filters, Merkle proofs)
 //! - Fee estimation
+//! - Relay policy (standardness checks)
 //! - Block compression
 //! - Script system (P2PKH, P2SH, MultiSig, TimeLock)
 
+pub mod amount;
 pub mod block;
 pub mod blockchain;
 pub mod chain_state;
+pub mod clock;
 pub mod compression;
+pub mod events;
 pub mod fee;
+pub mod policy;
 pub mod script;
 pub mod spv;
 pub mod transaction;
 
+pub use amount::{format_amount, parse_amount, AmountError, MAX_COIN_DECIMALS};
 pub use block::{
-    Block, BlockError, BlockHeader, BLOCK_HEADER_SIZE, MAX_BLOCK_SIZE, MAX_BLOCK_TXS,
-    MAX_BLOCK_WEIGHT,
+    Block, BlockError, BlockHeader, BLOCK_HASH_FORMAT_VERSION, BLOCK_HEADER_SIZE, MAX_BLOCK_SIZE,
+    MAX_BLOCK_TXS, MAX_BLOCK_WEIGHT,
 };
 pub use blockchain::{
-    Blockchain, BlockchainError, ChainStats, BLOCK_REWARD, DEFAULT_DIFFICULTY,
-    DIFFICULTY_ADJUSTMENT_INTERVAL, MAX_DIFFICULTY_ADJUSTMENT_FACTOR, TARGET_BLOCK_TIME,
+    AddressBalances, AddressTx, BlockConnectResult, Blockchain, BlockchainError, ChainParams,
+    ChainStats, PrunedBase, UtxoSetInfo, BLOCK_REWARD, DEFAULT_DIFFICULTY, DEFAULT_UTXO_PAGE_SIZE,
+    DIFFICULTY_ADJUSTMENT_INTERVAL, IBD_MAX_BLOCKS_BEHIND, IBD_MAX_TIP_AGE, MAX_BLOCK_RANGE,
+    MAX_DIFFICULTY, MAX_DIFFICULTY_ADJUSTMENT_FACTOR, MIN_DIFFICULTY, TARGET_BLOCK_TIME,
+    UTXO_GROWTH_WINDOW,
 };
 pub use chain_state::{
-    BlockStatus, ChainStateManager, ChainTip, OrphanBlock, UndoData, MAX_FUTURE_BLOCK_TIME,
-    MTP_BLOCK_COUNT,
+    BlockStatus, ChainStateManager, ChainTip, OrphanBlock, ReorgEvent, TieBreak, UndoData,
+    BYTES_PER_MB, DEFAULT_MAX_ORPHAN_BLOCK_MB, DEFAULT_MAX_REORG_DEPTH, MAX_FUTURE_BLOCK_TIME,
+    MAX_REORG_EVENTS, MTP_BLOCK_COUNT,
 };
+pub use clock::{system_clock, Clock, MockClock, SharedClock, SystemClock};
 pub use compression::{BlockCompressor, CompressedBlock, CompressionStats};
+pub use events::{ChainEvent, EventBus, EVENT_BUS_CAPACITY};
 pub use fee::{BlockFeeStats, FeeEstimates, FeeEstimator, FeeRate, Priority};
+pub use policy::{
+    PolicyError, MAX_STANDARD_DATA_SIZE, MAX_STANDARD_RECIPIENT_LEN, MAX_STANDARD_TX_SIGOPS,
+    MAX_STANDARD_TX_SIZE,
+};
 pub use script::{ScriptError, ScriptType, ScriptValidator, SigHashType};
-pub use spv::{BloomFilter, MerkleProof, SpvClient};
+pub use spv::{BatchMerkleProof, BloomFilter, MerkleProof, SpvClient};
 pub use transaction::{
-    ContractOperationType, TokenOperationType, Transaction, TransactionBuilder, TransactionError,
-    TransactionInput, TransactionOutput, COINBASE_MATURITY, DEFAULT_CHAIN_ID, LOCKTIME_THRESHOLD,
-    MAX_TX_SIGOPS, MAX_TX_SIZE, SEQUENCE_FINAL, SEQUENCE_RBF_MAX, TX_VERSION, UTXO,
+    ContractOperationType, OutPoint, TokenOperationType, Transaction, TransactionBuilder,
+    TransactionError, TransactionInput, TransactionOutput, COINBASE_MATURITY, DEFAULT_CHAIN_ID,
+    INPUT_COMMITMENT_VERSION, LOCKTIME_THRESHOLD, MAX_COINBASE_TAG_SIZE, MAX_TX_SIGOPS,
+    MAX_TX_SIZE, SEQUENCE_FINAL, SEQUENCE_RBF_MAX, TX_VERSION, UTXO,
 };
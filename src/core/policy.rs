@@ -0,0 +1,240 @@
+//! Relay policy (standardness)
+//!
+//! These rules decide which transactions the mempool accepts and relays.
+//! They are stricter than consensus: a block may still contain transactions
+//! that fail them, so they are never consulted during block validation.
+
+use crate::core::blockchain::ChainParams;
+use crate::core::transaction::{ContractOperationType, Transaction, TX_VERSION};
+use thiserror::Error;
+
+// =============================================================================
+// Policy Constants
+// =============================================================================
+
+/// Largest transaction relayed, in bytes
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+
+/// Most signature checks a relayed transaction may require
+pub const MAX_STANDARD_TX_SIGOPS: usize = 4_000;
+
+/// Largest contract payload (bytecode plus arguments) relayed, in bytes
+pub const MAX_STANDARD_DATA_SIZE: usize = 24_576;
+
+/// Longest recipient address accepted for relay
+pub const MAX_STANDARD_RECIPIENT_LEN: usize = 64;
+
+// =============================================================================
+// Policy Errors
+// =============================================================================
+
+/// Reasons a transaction is non-standard
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    #[error("Non-standard version: {0}")]
+    Version(u32),
+    #[error("Coinbase transactions are not relayed")]
+    Coinbase,
+    #[error("Transaction too large: {0} bytes (max: {1})")]
+    TooLarge(usize, usize),
+    #[error("Non-standard recipient: {0:?}")]
+    Recipient(String),
+    #[error("Too many signature operations: {0} (max: {1})")]
+    TooManySigops(usize, usize),
+    #[error("Data payload too large: {0} bytes (max: {1})")]
+    DataTooLarge(usize, usize),
+    #[error("Transaction not final: locktime {0}")]
+    NotFinal(u32),
+}
+
+// =============================================================================
+// Standardness
+// =============================================================================
+
+/// Whether a recipient looks like an address: a short alphanumeric string
+/// (Base58 addresses, `0x` contract addresses)
+fn is_standard_recipient(recipient: &str) -> bool {
+    !recipient.is_empty()
+        && recipient.len() <= MAX_STANDARD_RECIPIENT_LEN
+        && recipient.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+impl Transaction {
+    /// Number of signature checks spending this transaction requires
+    /// (one per input, one per signer for multisig inputs)
+    pub fn sigop_count(&self) -> usize {
+        if self.is_coinbase {
+            return 0;
+        }
+
+        self.inputs
+            .iter()
+            .map(|input| {
+                if input.public_key.starts_with("MULTISIG:") {
+                    input.signature.split(',').count()
+                } else {
+                    1
+                }
+            })
+            .sum()
+    }
+
+    /// Bytes of contract data carried by the transaction
+    pub fn data_size(&self) -> usize {
+        match self.contract_data {
+            Some(ContractOperationType::Deploy {
+                ref bytecode,
+                ref constructor_args,
+            }) => bytecode.len() + constructor_args.len() * 8,
+            Some(ContractOperationType::Call { ref args, .. }) => args.len() * 8,
+            None => 0,
+        }
+    }
+
+    /// Check relay policy for inclusion after a tip at `tip_height`/`tip_time`.
+    ///
+    /// The transaction must be final in the next block, expected
+    /// `target_block_time` seconds after the tip.
+    pub fn is_standard(
+        &self,
+        params: &ChainParams,
+        tip_height: u64,
+        tip_time: u64,
+    ) -> Result<(), PolicyError> {
+        if self.version == 0 || self.version > TX_VERSION {
+            return Err(PolicyError::Version(self.version));
+        }
+
+        if self.is_coinbase {
+            return Err(PolicyError::Coinbase);
+        }
+
+        let size = self.estimated_size();
+        if size > MAX_STANDARD_TX_SIZE {
+            return Err(PolicyError::TooLarge(size, MAX_STANDARD_TX_SIZE));
+        }
+
+        if let Some(output) = self
+            .outputs
+            .iter()
+            .find(|output| !is_standard_recipient(&output.recipient))
+        {
+            return Err(PolicyError::Recipient(output.recipient.clone()));
+        }
+
+        let sigops = self.sigop_count();
+        if sigops > MAX_STANDARD_TX_SIGOPS {
+            return Err(PolicyError::TooManySigops(sigops, MAX_STANDARD_TX_SIGOPS));
+        }
+
+        let data_size = self.data_size();
+        if data_size > MAX_STANDARD_DATA_SIZE {
+            return Err(PolicyError::DataTooLarge(data_size, MAX_STANDARD_DATA_SIZE));
+        }
+
+        let next_time = tip_time.saturating_add(params.target_block_time.max(0) as u64);
+        if !self.is_final(tip_height + 1, next_time) {
+            return Err(PolicyError::NotFinal(self.locktime));
+        }
+
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transaction::{TransactionBuilder, TransactionOutput, UTXO};
+    use crate::crypto::KeyPair;
+
+    fn standard_tx(key_pair: &KeyPair) -> TransactionBuilder {
+        let utxo = UTXO {
+            tx_id: "a".repeat(64),
+            output_index: 0,
+            output: TransactionOutput {
+                amount: 50,
+                recipient: key_pair.address(),
+            },
+        };
+        TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&key_pair.address(), 40)
+    }
+
+    #[test]
+    fn test_standard_transaction() {
+        let key_pair = KeyPair::generate();
+        let params = ChainParams::default();
+        let tx = standard_tx(&key_pair).build_and_sign(&key_pair).unwrap();
+
+        assert_eq!(tx.sigop_count(), 1);
+        assert_eq!(tx.is_standard(&params, 10, 1_000), Ok(()));
+    }
+
+    #[test]
+    fn test_non_standard_transactions() {
+        let key_pair = KeyPair::generate();
+        let params = ChainParams::default();
+
+        let odd_recipient = standard_tx(&key_pair)
+            .add_output("not an address", 1)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert_eq!(
+            odd_recipient.is_standard(&params, 10, 1_000),
+            Err(PolicyError::Recipient("not an address".to_string()))
+        );
+
+        let mut future_version = standard_tx(&key_pair).build_and_sign(&key_pair).unwrap();
+        future_version.version = TX_VERSION + 1;
+        assert_eq!(
+            future_version.is_standard(&params, 10, 1_000),
+            Err(PolicyError::Version(TX_VERSION + 1))
+        );
+
+        let mut many_sigops = standard_tx(&key_pair).build_and_sign(&key_pair).unwrap();
+        many_sigops.inputs[0].public_key = format!("MULTISIG:{}", key_pair.address());
+        many_sigops.inputs[0].signature = vec!["pk:sig"; MAX_STANDARD_TX_SIGOPS + 1].join(",");
+        assert_eq!(
+            many_sigops.is_standard(&params, 10, 1_000),
+            Err(PolicyError::TooManySigops(
+                MAX_STANDARD_TX_SIGOPS + 1,
+                MAX_STANDARD_TX_SIGOPS
+            ))
+        );
+
+        let mut big_data = standard_tx(&key_pair).build_and_sign(&key_pair).unwrap();
+        big_data.contract_data = Some(ContractOperationType::Deploy {
+            bytecode: vec![0; MAX_STANDARD_DATA_SIZE + 1],
+            constructor_args: vec![],
+        });
+        assert!(matches!(
+            big_data.is_standard(&params, 10, 1_000),
+            Err(PolicyError::DataTooLarge(..))
+        ));
+
+        let locked = standard_tx(&key_pair)
+            .with_rbf()
+            .locktime(50)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert_eq!(
+            locked.is_standard(&params, 10, 1_000),
+            Err(PolicyError::NotFinal(50))
+        );
+        assert_eq!(locked.is_standard(&params, 49, 1_000), Ok(()));
+
+        let coinbase = Transaction::coinbase(&key_pair.address(), 50, 1);
+        assert_eq!(
+            coinbase.is_standard(&params, 10, 1_000),
+            Err(PolicyError::Coinbase)
+        );
+
+        // Consensus rules are unaffected
+        assert!(odd_recipient.is_valid().unwrap());
+    }
+}
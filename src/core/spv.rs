@@ -6,6 +6,7 @@
 //! - Headers-only sync for light nodes
 
 use crate::core::{Block, Transaction};
+use crate::crypto::sha256;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashSet;
@@ -293,6 +294,212 @@ impl MerkleProof {
         hasher.update(right.as_bytes());
         hex::encode(hasher.finalize())
     }
+
+    /// Create one proof for several transactions in a block. Returns `None`
+    /// if no txids are given or any of them is not in the block.
+    pub fn build_batch(block: &Block, tx_ids: &[String]) -> Option<BatchMerkleProof> {
+        if tx_ids.is_empty() {
+            return None;
+        }
+
+        let leaves: Vec<Vec<u8>> = block
+            .transactions
+            .iter()
+            .map(|tx| hex::decode(&tx.id).unwrap_or_default())
+            .collect();
+        let matches: Vec<bool> = block
+            .transactions
+            .iter()
+            .map(|tx| tx_ids.contains(&tx.id))
+            .collect();
+        let found = matches.iter().filter(|m| **m).count();
+        if found != tx_ids.iter().collect::<HashSet<_>>().len() {
+            return None;
+        }
+
+        let mut proof = BatchMerkleProof {
+            block_hash: block.hash.clone(),
+            block_height: block.index,
+            tx_count: leaves.len() as u32,
+            hashes: Vec::new(),
+            flags: Vec::new(),
+        };
+        let tree = PartialTree {
+            leaves: &leaves,
+            matches: &matches,
+        };
+        tree.traverse(tree.root_height(), 0, &mut proof);
+        Some(proof)
+    }
+}
+
+/// Proof that several transactions are included in one block
+///
+/// A partial Merkle tree in the style of Bitcoin's `MerkleBlock`: the
+/// nodes on the paths to the matched transactions are visited depth-first,
+/// with one flag per node saying whether a match lies beneath it. Subtrees
+/// without matches are pruned to their hash, so shared parts of the paths
+/// are only sent once. Unlike [`MerkleProof`], hashes are combined as bytes
+/// exactly like the block header's `merkle_root`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchMerkleProof {
+    /// Block hash
+    pub block_hash: String,
+    /// Block height
+    pub block_height: u64,
+    /// Total transactions in block
+    pub tx_count: u32,
+    /// Hashes of pruned subtrees and matched transactions, depth-first
+    pub hashes: Vec<String>,
+    /// Whether each visited node is (or is above) a matched transaction
+    pub flags: Vec<bool>,
+}
+
+impl BatchMerkleProof {
+    /// Verify the proof against a block header's merkle root
+    pub fn verify(&self, merkle_root: &str) -> bool {
+        self.extract()
+            .is_some_and(|(root, _)| hex::encode(root) == merkle_root)
+    }
+
+    /// Transactions the proof covers (empty if it is malformed)
+    pub fn matched_tx_ids(&self) -> Vec<String> {
+        self.extract()
+            .map(|(_, matched)| matched)
+            .unwrap_or_default()
+    }
+
+    /// Recompute the merkle root, collecting the matched txids on the way.
+    /// `None` if the proof is malformed or has unused hashes or flags.
+    fn extract(&self) -> Option<(Vec<u8>, Vec<String>)> {
+        if self.tx_count == 0 {
+            return None;
+        }
+        let hashes = self
+            .hashes
+            .iter()
+            .map(hex::decode)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+
+        let mut reader = ProofReader {
+            tx_count: self.tx_count as usize,
+            hashes: &hashes,
+            flags: &self.flags,
+            hash_pos: 0,
+            flag_pos: 0,
+            matched: Vec::new(),
+        };
+        let height = level_height(self.tx_count as usize);
+        let root = reader.read(height, 0)?;
+
+        let consumed = reader.hash_pos == hashes.len() && reader.flag_pos == self.flags.len();
+        consumed.then_some((root, reader.matched))
+    }
+}
+
+/// Height of the merkle tree over `tx_count` leaves
+fn level_height(tx_count: usize) -> u32 {
+    let mut height = 0;
+    while level_width(tx_count, height) > 1 {
+        height += 1;
+    }
+    height
+}
+
+/// Nodes at `height` levels above `tx_count` leaves
+fn level_width(tx_count: usize, height: u32) -> usize {
+    (tx_count + (1 << height) - 1) >> height
+}
+
+/// Hash two children the way block merkle roots are computed
+fn hash_children(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut data = left.to_vec();
+    data.extend_from_slice(right);
+    sha256(&data)
+}
+
+/// Leaves of a block and which of them to prove
+struct PartialTree<'a> {
+    leaves: &'a [Vec<u8>],
+    matches: &'a [bool],
+}
+
+impl PartialTree<'_> {
+    fn root_height(&self) -> u32 {
+        level_height(self.leaves.len())
+    }
+
+    /// Hash of the node at `height` and `pos`; odd levels repeat their last
+    /// node
+    fn hash(&self, height: u32, pos: usize) -> Vec<u8> {
+        if height == 0 {
+            return self.leaves[pos].clone();
+        }
+        let left = self.hash(height - 1, pos * 2);
+        let right = if pos * 2 + 1 < level_width(self.leaves.len(), height - 1) {
+            self.hash(height - 1, pos * 2 + 1)
+        } else {
+            left.clone()
+        };
+        hash_children(&left, &right)
+    }
+
+    fn traverse(&self, height: u32, pos: usize, proof: &mut BatchMerkleProof) {
+        let start = pos << height;
+        let end = ((pos + 1) << height).min(self.leaves.len());
+        let above_match = self.matches[start..end].iter().any(|m| *m);
+        proof.flags.push(above_match);
+
+        if height == 0 || !above_match {
+            proof.hashes.push(hex::encode(self.hash(height, pos)));
+            return;
+        }
+        self.traverse(height - 1, pos * 2, proof);
+        if pos * 2 + 1 < level_width(self.leaves.len(), height - 1) {
+            self.traverse(height - 1, pos * 2 + 1, proof);
+        }
+    }
+}
+
+/// Walks a [`BatchMerkleProof`] in the order it was built
+struct ProofReader<'a> {
+    tx_count: usize,
+    hashes: &'a [Vec<u8>],
+    flags: &'a [bool],
+    hash_pos: usize,
+    flag_pos: usize,
+    matched: Vec<String>,
+}
+
+impl ProofReader<'_> {
+    fn read(&mut self, height: u32, pos: usize) -> Option<Vec<u8>> {
+        let above_match = *self.flags.get(self.flag_pos)?;
+        self.flag_pos += 1;
+
+        if height == 0 || !above_match {
+            let hash = self.hashes.get(self.hash_pos)?.clone();
+            self.hash_pos += 1;
+            if height == 0 && above_match {
+                self.matched.push(hex::encode(&hash));
+            }
+            return Some(hash);
+        }
+
+        let left = self.read(height - 1, pos * 2)?;
+        let right = if pos * 2 + 1 < level_width(self.tx_count, height - 1) {
+            let right = self.read(height - 1, pos * 2 + 1)?;
+            // Identical siblings would let one transaction be proven twice
+            // (CVE-2012-2459)
+            if right == left {
+                return None;
+            }
+            right
+        } else {
+            left.clone()
+        };
+        Some(hash_children(&left, &right))
+    }
 }
 
 // =============================================================================
@@ -513,6 +720,32 @@ mod tests {
         assert!(proof.verify(&expected_root));
     }
 
+    #[test]
+    fn test_batch_merkle_proof() {
+        let txs: Vec<Transaction> = (0..4)
+            .map(|i| Transaction::coinbase(&format!("payee{}", i), 50, i))
+            .collect();
+        let block = Block::new(1, "prev".to_string(), txs, 1);
+
+        let wanted: Vec<String> = [0, 1, 3]
+            .iter()
+            .map(|&i| block.transactions[i].id.clone())
+            .collect();
+        let proof = MerkleProof::build_batch(&block, &wanted).unwrap();
+        assert!(proof.verify(&block.header.merkle_root));
+        assert_eq!(proof.matched_tx_ids(), wanted);
+        // The sibling of the third transaction is the only pruned subtree
+        assert_eq!(proof.hashes.len(), 4);
+
+        let mut tampered = proof.clone();
+        tampered.hashes[0] = block.transactions[2].id.clone();
+        assert!(!tampered.verify(&block.header.merkle_root));
+        assert!(!proof.verify(&block.hash));
+
+        assert!(MerkleProof::build_batch(&block, &["missing".to_string()]).is_none());
+        assert!(MerkleProof::build_batch(&block, &[]).is_none());
+    }
+
     #[test]
     fn test_spv_client() {
         let mut client = SpvClient::new();
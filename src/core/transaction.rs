@@ -17,7 +17,11 @@ use thiserror::Error;
 // =============================================================================
 
 /// Current transaction version
-pub const TX_VERSION: u32 = 2;
+pub const TX_VERSION: u32 = 3;
+
+/// First transaction version whose input signatures commit to the spent
+/// outpoints, their sequences and the input being signed
+pub const INPUT_COMMITMENT_VERSION: u32 = 3;
 
 /// Sequence number that disables locktime
 pub const SEQUENCE_FINAL: u32 = 0xFFFFFFFF;
@@ -42,6 +46,10 @@ pub const MAX_TX_SIZE: usize = 100_000;
 /// Maximum number of signature operations per transaction
 pub const MAX_TX_SIGOPS: usize = 80_000;
 
+/// Maximum length of the miner tag carried by a coinbase input (Bitcoin allows
+/// a 100-byte coinbase script)
+pub const MAX_COINBASE_TAG_SIZE: usize = 100;
+
 // =============================================================================
 // Error Types
 // =============================================================================
@@ -71,6 +79,10 @@ pub enum TransactionError {
     TransactionTooLarge(usize, usize),
     #[error("UTXO not found: {0}:{1}")]
     UtxoNotFound(String, u32),
+    #[error("Invalid raw transaction: {0}")]
+    InvalidRawTransaction(String),
+    #[error("Unsupported operation: {0}")]
+    UnsupportedOperation(String),
 }
 
 // =============================================================================
@@ -109,6 +121,11 @@ impl TransactionInput {
     pub fn is_final(&self) -> bool {
         self.sequence == SEQUENCE_FINAL
     }
+
+    /// The output this input spends
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(&self.tx_id, self.output_index)
+    }
 }
 
 // =============================================================================
@@ -135,6 +152,47 @@ impl TransactionOutput {
 // UTXO
 // =============================================================================
 
+/// Reference to a transaction output
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: String,
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub fn new(txid: &str, vout: u32) -> Self {
+        Self {
+            txid: txid.to_string(),
+            vout,
+        }
+    }
+
+    /// Canonical `txid:vout` key, as used by the UTXO set and undo data
+    pub fn key(&self) -> String {
+        Self::key_for(&self.txid, self.vout)
+    }
+
+    /// Key for an outpoint without building one first
+    pub fn key_for(txid: &str, vout: u32) -> String {
+        format!("{}:{}", txid, vout)
+    }
+
+    /// Parse a key produced by [`OutPoint::key`]
+    pub fn from_key(key: &str) -> Option<Self> {
+        let (txid, vout) = key.rsplit_once(':')?;
+        if txid.is_empty() {
+            return None;
+        }
+        Some(Self::new(txid, vout.parse().ok()?))
+    }
+}
+
+impl std::fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
+}
+
 /// Unspent Transaction Output (UTXO)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UTXO {
@@ -143,12 +201,29 @@ pub struct UTXO {
     pub output: TransactionOutput,
 }
 
+impl UTXO {
+    /// The outpoint identifying this output
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(&self.tx_id, self.output_index)
+    }
+
+    /// Key of this output in the UTXO set
+    pub fn key(&self) -> String {
+        OutPoint::key_for(&self.tx_id, self.output_index)
+    }
+}
+
 // =============================================================================
 // Token Operations (On-Chain ERC-20 style)
 // =============================================================================
 
 /// Token operation types that can be embedded in transactions
+///
+/// Unknown variants and fields fail to decode instead of being dropped, so
+/// an operation added by a newer node is never half-understood here. New
+/// variants must also be gated in [`TokenOperationType::min_tx_version`].
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub enum TokenOperationType {
     /// Deploy a new token - all tokens go to the creator (transaction sender)
     Create {
@@ -187,12 +262,42 @@ pub enum TokenOperationType {
     },
 }
 
+impl TokenOperationType {
+    /// Operation name, as it appears on the wire
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenOperationType::Create { .. } => "Create",
+            TokenOperationType::Transfer { .. } => "Transfer",
+            TokenOperationType::Approve { .. } => "Approve",
+            TokenOperationType::TransferFrom { .. } => "TransferFrom",
+            TokenOperationType::Burn { .. } => "Burn",
+            TokenOperationType::Mint { .. } => "Mint",
+        }
+    }
+
+    /// First transaction version allowed to carry this operation
+    pub fn min_tx_version(&self) -> u32 {
+        match self {
+            TokenOperationType::Create { .. }
+            | TokenOperationType::Transfer { .. }
+            | TokenOperationType::Approve { .. }
+            | TokenOperationType::TransferFrom { .. }
+            | TokenOperationType::Burn { .. }
+            | TokenOperationType::Mint { .. } => 1,
+        }
+    }
+}
+
 // =============================================================================
 // Contract Operations (On-Chain Smart Contracts)
 // =============================================================================
 
 /// Contract operation types that can be embedded in transactions
+///
+/// Decoded as strictly as [`TokenOperationType`]; new variants must be
+/// gated in [`ContractOperationType::min_tx_version`].
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub enum ContractOperationType {
     /// Deploy a new smart contract
     Deploy {
@@ -212,12 +317,29 @@ pub enum ContractOperationType {
     },
 }
 
+impl ContractOperationType {
+    /// Operation name, as it appears on the wire
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContractOperationType::Deploy { .. } => "Deploy",
+            ContractOperationType::Call { .. } => "Call",
+        }
+    }
+
+    /// First transaction version allowed to carry this operation
+    pub fn min_tx_version(&self) -> u32 {
+        match self {
+            ContractOperationType::Deploy { .. } | ContractOperationType::Call { .. } => 1,
+        }
+    }
+}
+
 // =============================================================================
 // Transaction
 // =============================================================================
 
 /// A blockchain transaction with production-grade features
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Transaction {
     /// Transaction version (for future upgrades)
     #[serde(default = "default_version")]
@@ -306,16 +428,35 @@ impl Transaction {
 
     /// Create a coinbase (mining reward) transaction
     pub fn coinbase(recipient: &str, amount: u64, block_height: u64) -> Self {
+        Self::coinbase_with_tag(recipient, amount, block_height, &[])
+    }
+
+    /// Create a coinbase transaction carrying a miner tag.
+    ///
+    /// The tag is stored hex-encoded in the coinbase input's signature field
+    /// (its scriptSig) and truncated to `MAX_COINBASE_TAG_SIZE` bytes.
+    pub fn coinbase_with_tag(recipient: &str, amount: u64, block_height: u64, tag: &[u8]) -> Self {
         let outputs = vec![TransactionOutput {
             amount,
             recipient: recipient.to_string(),
         }];
+        Self::coinbase_with_outputs(outputs, block_height, tag)
+    }
+
+    /// Create a tagged coinbase paying several outputs (for example the
+    /// subsidy and the fees separately)
+    pub fn coinbase_with_outputs(
+        outputs: Vec<TransactionOutput>,
+        block_height: u64,
+        tag: &[u8],
+    ) -> Self {
+        let tag = &tag[..tag.len().min(MAX_COINBASE_TAG_SIZE)];
 
         // Coinbase input contains block height as data
         let inputs = vec![TransactionInput {
             tx_id: "0".repeat(64),
             output_index: block_height as u32,
-            signature: String::new(),
+            signature: hex::encode(tag),
             public_key: String::new(),
             sequence: SEQUENCE_FINAL,
         }];
@@ -398,6 +539,44 @@ impl Transaction {
         self.contract_data.is_some()
     }
 
+    /// Whether coinbase maturity applies to this transaction's inputs.
+    ///
+    /// Token and contract operations carry an identity input naming the
+    /// sender rather than an outpoint, so they spend no coins and may be
+    /// submitted before any coinbase has matured.
+    pub fn subject_to_coinbase_maturity(&self) -> bool {
+        !self.is_coinbase && !self.is_token_transaction() && !self.is_contract_transaction()
+    }
+
+    /// Version gate for embedded operations: an operation must be known at
+    /// the transaction's version, and versions newer than [`TX_VERSION`]
+    /// may define operations this node doesn't understand
+    pub fn check_operations(&self) -> Result<(), TransactionError> {
+        let gate = |name: &str, min_version: u32| {
+            if self.version > TX_VERSION {
+                Err(TransactionError::UnsupportedOperation(format!(
+                    "{} in transaction version {} (max: {})",
+                    name, self.version, TX_VERSION
+                )))
+            } else if self.version < min_version {
+                Err(TransactionError::UnsupportedOperation(format!(
+                    "{} requires transaction version {}, got {}",
+                    name, min_version, self.version
+                )))
+            } else {
+                Ok(())
+            }
+        };
+
+        if let Some(op) = &self.token_data {
+            gate(op.name(), op.min_tx_version())?;
+        }
+        if let Some(op) = &self.contract_data {
+            gate(op.name(), op.min_tx_version())?;
+        }
+        Ok(())
+    }
+
     /// Get the sender address from the first input's public key
     /// For token transactions, this is the address performing the operation
     pub fn sender_address(&self) -> Option<String> {
@@ -426,9 +605,13 @@ impl Transaction {
         hex::encode(sha256(data.as_bytes()))
     }
 
-    /// Get the data to be signed (includes chain_id, token_data, contract_data for replay protection)
-    pub fn signing_data(&self) -> Vec<u8> {
-        let data = format!(
+    /// Get the data signed by the input at `index` (includes chain_id,
+    /// token_data, contract_data for replay protection). From
+    /// [`INPUT_COMMITMENT_VERSION`] it also covers every input's outpoint and
+    /// sequence and the index itself, so a signature can't be moved to
+    /// another input or reused after inputs are added or re-sequenced.
+    pub fn signing_data(&self, index: usize) -> Vec<u8> {
+        let mut data = format!(
             "{}{:?}{:?}{}{}{}{:?}{:?}",
             self.version,
             self.outputs,
@@ -439,19 +622,39 @@ impl Transaction {
             self.token_data,
             self.contract_data
         );
+        if self.version >= INPUT_COMMITMENT_VERSION {
+            for input in &self.inputs {
+                data.push_str(&format!(
+                    "{}:{}:{}",
+                    input.tx_id, input.output_index, input.sequence
+                ));
+            }
+            data.push_str(&index.to_string());
+        }
         sha256(data.as_bytes())
     }
 
     /// Sign all inputs with the provided key pair
     pub fn sign(&mut self, key_pair: &KeyPair) -> Result<(), TransactionError> {
-        let signing_data = self.signing_data();
-        let public_key_hex = key_pair.public_key_hex();
-
-        for input in &mut self.inputs {
-            let signature = key_pair.sign(&signing_data)?;
-            input.signature = hex::encode(&signature);
-            input.public_key = public_key_hex.clone();
+        for index in 0..self.inputs.len() {
+            self.sign_input(index, key_pair)?;
         }
+        Ok(())
+    }
+
+    /// Sign only the input at `index`, leaving the others for their owners
+    /// to sign (e.g. a cosigner funding the same transaction). Signatures
+    /// cover the inputs and outputs but not other inputs' signatures, so
+    /// parties can sign in any order once the inputs are settled.
+    pub fn sign_input(&mut self, index: usize, key_pair: &KeyPair) -> Result<(), TransactionError> {
+        let signing_data = self.signing_data(index);
+        let input = self.inputs.get_mut(index).ok_or_else(|| {
+            TransactionError::InvalidTransaction(format!("No input at index {}", index))
+        })?;
+
+        let signature = key_pair.sign(&signing_data)?;
+        input.signature = hex::encode(&signature);
+        input.public_key = key_pair.public_key_hex();
 
         // Recalculate hash after signing
         self.id = self.calculate_hash();
@@ -464,34 +667,41 @@ impl Transaction {
             return Ok(true);
         }
 
-        let signing_data = self.signing_data();
-
-        for input in &self.inputs {
-            if input.signature.is_empty() || input.public_key.is_empty() {
+        for index in 0..self.inputs.len() {
+            if !self.verify_input(index)? {
                 return Ok(false);
             }
+        }
 
-            // Handle multisig transactions - they have "MULTISIG:{address}" as public_key
-            // and comma-separated "pubkey:sig" pairs in signature field
-            if input.public_key.starts_with("MULTISIG:") {
-                // Verify multisig combined signatures
-                if !self.verify_multisig_input(input)? {
-                    return Ok(false);
-                }
-                continue;
-            }
+        Ok(true)
+    }
 
-            // Regular transaction signature verification
-            let public_key = public_key_from_hex(&input.public_key)?;
-            let signature =
-                hex::decode(&input.signature).map_err(|_| TransactionError::InvalidSignature)?;
+    /// Verify the signature on the input at `index` against its own public
+    /// key (`false` while it is still unsigned)
+    pub fn verify_input(&self, index: usize) -> Result<bool, TransactionError> {
+        let Some(input) = self.inputs.get(index) else {
+            return Ok(false);
+        };
+        if input.signature.is_empty() || input.public_key.is_empty() {
+            return Ok(false);
+        }
 
-            if !verify_signature(&public_key, &signing_data, &signature)? {
-                return Ok(false);
-            }
+        // Handle multisig transactions - they have "MULTISIG:{address}" as public_key
+        // and comma-separated "pubkey:sig" pairs in signature field
+        if input.public_key.starts_with("MULTISIG:") {
+            return self.verify_multisig_input(input);
         }
 
-        Ok(true)
+        // Regular transaction signature verification
+        let public_key = public_key_from_hex(&input.public_key)?;
+        let signature =
+            hex::decode(&input.signature).map_err(|_| TransactionError::InvalidSignature)?;
+
+        Ok(verify_signature(
+            &public_key,
+            &self.signing_data(index),
+            &signature,
+        )?)
     }
 
     /// Verify a multisig input's combined signatures
@@ -635,9 +845,53 @@ impl Transaction {
 
     /// Check if this transaction is valid (basic checks only)
     pub fn is_valid(&self) -> Result<bool, TransactionError> {
+        if !self.is_well_formed() {
+            return Ok(false);
+        }
+
+        // Token and contract transactions carry no signatures to verify
+        if self.token_data.is_some() || self.contract_data.is_some() {
+            return Ok(true);
+        }
+
+        // Verify signatures
+        self.verify_signatures()
+    }
+
+    /// Miner tag embedded in a coinbase input (`None` for other transactions)
+    pub fn coinbase_tag(&self) -> Option<Vec<u8>> {
+        if !self.is_coinbase {
+            return None;
+        }
+        hex::decode(&self.inputs.first()?.signature).ok()
+    }
+
+    /// Coinbase tag as text, if there is one
+    pub fn coinbase_message(&self) -> Option<String> {
+        self.coinbase_tag()
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| String::from_utf8_lossy(&tag).into_owned())
+    }
+
+    /// Structural checks from `is_valid`, without verifying signatures
+    pub fn is_well_formed(&self) -> bool {
         // Check version
         if self.version == 0 {
-            return Ok(false);
+            return false;
+        }
+
+        // Coinbase tags must decode and stay within the size limit
+        if self.is_coinbase
+            && self.inputs.iter().any(|input| {
+                input.signature.len() > MAX_COINBASE_TAG_SIZE * 2
+                    || hex::decode(&input.signature).is_err()
+            })
+        {
+            return false;
+        }
+
+        if self.check_operations().is_err() {
+            return false;
         }
 
         // Token transactions are allowed to have empty outputs
@@ -645,30 +899,32 @@ impl Transaction {
         if self.token_data.is_some() {
             // Token transactions just need valid token data - no signatures required
             // since the identity comes from the input's public_key field
-            return Ok(true);
+            return true;
         }
 
         // Contract transactions are allowed to have empty outputs
         // (they record contract deployments and calls on-chain)
         if self.contract_data.is_some() {
             // Contract transactions just need valid contract data
-            return Ok(true);
+            return true;
         }
 
         // Check that outputs are not empty (for regular transactions)
         if self.outputs.is_empty() {
-            return Ok(false);
+            return false;
         }
 
         // Check that all outputs have positive amounts
-        for output in &self.outputs {
-            if output.amount == 0 {
-                return Ok(false);
-            }
+        if self.outputs.iter().any(|output| output.amount == 0) {
+            return false;
         }
 
-        // Verify signatures
-        self.verify_signatures()
+        // Every spending input must carry a signature and public key
+        self.is_coinbase
+            || self
+                .inputs
+                .iter()
+                .all(|input| !input.signature.is_empty() && !input.public_key.is_empty())
     }
 
     /// Full validation including locktime and chain ID
@@ -740,6 +996,54 @@ impl Transaction {
     pub fn virtual_size(&self) -> usize {
         self.estimated_size()
     }
+
+    // =========================================================================
+    // Raw Encoding
+    // =========================================================================
+
+    /// Exact length in bytes of the serialized transaction (the bytes behind
+    /// `to_raw_hex` and the network encoding)
+    pub fn serialized_size(&self) -> usize {
+        serialized_len(self)
+    }
+
+    /// Encode the full transaction as raw hex (hex of its serialized bytes)
+    pub fn to_raw_hex(&self) -> Result<String, TransactionError> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| TransactionError::InvalidRawTransaction(e.to_string()))?;
+        Ok(hex::encode(bytes))
+    }
+
+    /// Decode a transaction from raw hex produced by `to_raw_hex`
+    pub fn from_raw_hex(raw: &str) -> Result<Self, TransactionError> {
+        let bytes = hex::decode(raw.trim())
+            .map_err(|e| TransactionError::InvalidRawTransaction(e.to_string()))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| TransactionError::InvalidRawTransaction(e.to_string()))
+    }
+}
+
+/// Writer that only counts the bytes written to it
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serialized length of a value without allocating the encoded bytes
+pub(crate) fn serialized_len<T: Serialize>(value: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
 }
 
 // =============================================================================
@@ -753,6 +1057,8 @@ pub struct TransactionBuilder {
     locktime: u32,
     chain_id: u32,
     enable_rbf: bool,
+    sorted: bool,
+    timestamp: Option<DateTime<Utc>>,
 }
 
 impl TransactionBuilder {
@@ -763,6 +1069,8 @@ impl TransactionBuilder {
             locktime: 0,
             chain_id: DEFAULT_CHAIN_ID,
             enable_rbf: false,
+            sorted: false,
+            timestamp: None,
         }
     }
 
@@ -827,6 +1135,22 @@ impl TransactionBuilder {
         self
     }
 
+    /// Order inputs and outputs canonically (BIP-69) when building
+    ///
+    /// Inputs are sorted by outpoint (txid, then output index) and outputs by
+    /// amount, then recipient, so the order no longer reveals how the wallet
+    /// assembled the transaction.
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+
+    /// Use a fixed timestamp instead of the current time
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
     /// Build and sign the transaction
     pub fn build_and_sign(self, key_pair: &KeyPair) -> Result<Transaction, TransactionError> {
         let mut tx = self.build();
@@ -835,8 +1159,20 @@ impl TransactionBuilder {
     }
 
     /// Build without signing
-    pub fn build(self) -> Transaction {
+    pub fn build(mut self) -> Transaction {
+        if self.sorted {
+            self.inputs.sort_by(|a, b| {
+                (a.tx_id.as_str(), a.output_index).cmp(&(b.tx_id.as_str(), b.output_index))
+            });
+            self.outputs.sort_by(|a, b| {
+                (a.amount, a.recipient.as_str()).cmp(&(b.amount, b.recipient.as_str()))
+            });
+        }
+
         let mut tx = Transaction::new(self.inputs, self.outputs);
+        if let Some(timestamp) = self.timestamp {
+            tx.timestamp = timestamp;
+        }
         tx.locktime = self.locktime;
         tx.chain_id = self.chain_id;
         tx.id = tx.calculate_hash();
@@ -892,6 +1228,165 @@ mod tests {
         assert!(tx.is_valid().unwrap());
     }
 
+    #[test]
+    fn test_partial_signing_by_two_parties() {
+        let alice = KeyPair::generate();
+        let bob = KeyPair::generate();
+        let utxo_of = |key_pair: &KeyPair, tx_id: &str| UTXO {
+            tx_id: tx_id.to_string(),
+            output_index: 0,
+            output: TransactionOutput {
+                amount: 100,
+                recipient: key_pair.address(),
+            },
+        };
+
+        let mut tx = TransactionBuilder::new()
+            .add_input(&utxo_of(&alice, "aa"))
+            .add_input(&utxo_of(&bob, "bb"))
+            .add_output("recipient", 190)
+            .build();
+
+        // Alice signs her input; Bob's is still missing
+        tx.sign_input(0, &alice).unwrap();
+        assert!(tx.verify_input(0).unwrap());
+        assert!(!tx.verify_input(1).unwrap());
+        assert!(!tx.verify_signatures().unwrap());
+
+        // Bob adds his without touching Alice's signature
+        let alice_signature = tx.inputs[0].signature.clone();
+        tx.sign_input(1, &bob).unwrap();
+        assert_eq!(tx.inputs[0].signature, alice_signature);
+        assert_eq!(tx.inputs[0].public_key, alice.public_key_hex());
+        assert_eq!(tx.inputs[1].public_key, bob.public_key_hex());
+        assert!(tx.verify_signatures().unwrap());
+        assert!(tx.is_valid().unwrap());
+        assert_eq!(tx.id, tx.calculate_hash());
+
+        // A signature moved onto the wrong key fails that input alone
+        let mut swapped = tx.clone();
+        swapped.inputs[1].public_key = alice.public_key_hex();
+        assert!(swapped.verify_input(0).unwrap());
+        assert!(!swapped.verify_input(1).unwrap());
+        assert!(!swapped.verify_signatures().unwrap());
+
+        assert!(matches!(
+            tx.sign_input(2, &alice),
+            Err(TransactionError::InvalidTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn test_signatures_commit_to_inputs() {
+        let alice = KeyPair::generate();
+        let utxo = |tx_id: &str| UTXO {
+            tx_id: tx_id.to_string(),
+            output_index: 0,
+            output: TransactionOutput {
+                amount: 100,
+                recipient: alice.address(),
+            },
+        };
+        let mut tx = TransactionBuilder::new()
+            .add_input(&utxo("aa"))
+            .add_input(&utxo("bb"))
+            .add_output("recipient", 190)
+            .build();
+        tx.sign_input(0, &alice).unwrap();
+        assert!(tx.verify_input(0).unwrap());
+
+        // Copying input 0's signature onto another input of the same key
+        let mut moved = tx.clone();
+        moved.inputs[1].signature = tx.inputs[0].signature.clone();
+        moved.inputs[1].public_key = alice.public_key_hex();
+        assert!(!moved.verify_input(1).unwrap());
+
+        // Re-sequencing or adding an input invalidates the signature
+        let mut resequenced = tx.clone();
+        resequenced.inputs[0].sequence = 0;
+        assert!(!resequenced.verify_input(0).unwrap());
+        let mut extended = tx.clone();
+        let mut added = tx.inputs[1].clone();
+        added.tx_id = "cc".to_string();
+        extended.inputs.push(added);
+        assert!(!extended.verify_input(0).unwrap());
+
+        // Transactions signed before the commitment still verify as they were
+        let mut legacy = tx.clone();
+        legacy.version = INPUT_COMMITMENT_VERSION - 1;
+        legacy.sign_input(0, &alice).unwrap();
+        legacy.inputs[0].sequence = 0;
+        assert!(legacy.verify_input(0).unwrap());
+    }
+
+    #[test]
+    fn test_high_s_signature_rejected() {
+        use crate::crypto::{is_low_s, normalize_signature, KeyError};
+
+        // secp256k1 group order
+        const ORDER: [u8; 32] = [
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xfe, 0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c,
+            0xd0, 0x36, 0x41, 0x41,
+        ];
+
+        let key_pair = KeyPair::generate();
+        let utxo = UTXO {
+            tx_id: "a".repeat(64),
+            output_index: 0,
+            output: TransactionOutput {
+                amount: 50,
+                recipient: key_pair.address(),
+            },
+        };
+        let tx = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 50)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let low = hex::decode(&tx.inputs[0].signature).unwrap();
+        assert!(is_low_s(&low).unwrap());
+
+        // Replace S with ORDER - S: the same signature, malleated
+        let mut high = low.clone();
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let diff = ORDER[i] as i16 - low[32 + i] as i16 - borrow;
+            borrow = (diff < 0) as i16;
+            high[32 + i] = diff.rem_euclid(256) as u8;
+        }
+        assert!(!is_low_s(&high).unwrap());
+
+        let mut malleated = tx.clone();
+        malleated.inputs[0].signature = hex::encode(&high);
+        assert!(matches!(
+            malleated.verify_signatures(),
+            Err(TransactionError::CryptoError(
+                KeyError::NonCanonicalSignature
+            ))
+        ));
+        assert!(malleated.is_valid().is_err());
+
+        // Normalizing restores the original, valid signature
+        let normalized = normalize_signature(&high).unwrap();
+        assert_eq!(normalized, low);
+        malleated.inputs[0].signature = hex::encode(&normalized);
+        assert!(malleated.verify_signatures().unwrap());
+    }
+
+    #[test]
+    fn test_outpoint_key_roundtrip() {
+        let outpoint = OutPoint::new(&"ab".repeat(32), 7);
+        let key = outpoint.key();
+        assert_eq!(key, format!("{}:7", "ab".repeat(32)));
+        assert_eq!(outpoint.to_string(), key);
+        assert_eq!(OutPoint::from_key(&key), Some(outpoint));
+
+        assert_eq!(OutPoint::from_key("no-separator"), None);
+        assert_eq!(OutPoint::from_key(":3"), None);
+        assert_eq!(OutPoint::from_key("abc:-1"), None);
+    }
+
     #[test]
     fn test_transaction_hash() {
         let tx1 = Transaction::coinbase("addr1", 50, 0);
@@ -1043,4 +1538,96 @@ mod tests {
         let tx3 = Transaction::new(vec![input_final], vec![]);
         assert!(tx2.can_replace(&tx3).is_err());
     }
+
+    #[test]
+    fn test_builder_sorted_is_order_independent() {
+        let utxo = |tx_id: &str, output_index| UTXO {
+            tx_id: tx_id.to_string(),
+            output_index,
+            output: TransactionOutput {
+                amount: 10,
+                recipient: "owner".to_string(),
+            },
+        };
+        let timestamp = Utc::now();
+
+        let a = TransactionBuilder::new()
+            .add_input(&utxo("bb", 0))
+            .add_input(&utxo("aa", 1))
+            .add_input(&utxo("aa", 0))
+            .add_output("carol", 7)
+            .add_output("bob", 3)
+            .add_output("alice", 7)
+            .timestamp(timestamp)
+            .sorted()
+            .build();
+        let b = TransactionBuilder::new()
+            .add_input(&utxo("aa", 0))
+            .add_input(&utxo("bb", 0))
+            .add_input(&utxo("aa", 1))
+            .add_output("alice", 7)
+            .add_output("carol", 7)
+            .add_output("bob", 3)
+            .timestamp(timestamp)
+            .sorted()
+            .build();
+
+        assert_eq!(a.id, b.id);
+        let outpoints: Vec<_> = a
+            .inputs
+            .iter()
+            .map(|i| (i.tx_id.as_str(), i.output_index))
+            .collect();
+        assert_eq!(outpoints, vec![("aa", 0), ("aa", 1), ("bb", 0)]);
+        let recipients: Vec<_> = a.outputs.iter().map(|o| o.recipient.as_str()).collect();
+        assert_eq!(recipients, vec!["bob", "alice", "carol"]);
+
+        // Without sorting, insertion order is kept and the ids differ
+        let unsorted = TransactionBuilder::new()
+            .add_input(&utxo("bb", 0))
+            .add_input(&utxo("aa", 0))
+            .add_output("bob", 3)
+            .timestamp(timestamp)
+            .build();
+        assert_eq!(unsorted.inputs[0].tx_id, "bb");
+    }
+
+    #[test]
+    fn test_unknown_operations_rejected() {
+        let tx = Transaction::with_token_data(
+            vec![],
+            vec![],
+            TokenOperationType::Transfer {
+                token_address: "0xtoken".to_string(),
+                to: "bob".to_string(),
+                amount: 5,
+            },
+        );
+        assert!(tx.check_operations().is_ok());
+        let encoded = serde_json::to_value(&tx).unwrap();
+        let raw = |value: &serde_json::Value| hex::encode(serde_json::to_vec(value).unwrap());
+        assert!(Transaction::from_raw_hex(&raw(&encoded)).is_ok());
+
+        // A variant this node doesn't know
+        let mut unknown = encoded.clone();
+        unknown["token_data"] = serde_json::json!({ "Freeze": { "token_address": "0xtoken" } });
+        assert!(matches!(
+            Transaction::from_raw_hex(&raw(&unknown)),
+            Err(TransactionError::InvalidRawTransaction(_))
+        ));
+
+        // A known variant extended with a field this node doesn't know
+        let mut extended = encoded;
+        extended["token_data"]["Transfer"]["memo"] = serde_json::json!("hi");
+        assert!(Transaction::from_raw_hex(&raw(&extended)).is_err());
+
+        // Versions past ours may redefine operations
+        let mut future = tx.clone();
+        future.version = TX_VERSION + 1;
+        assert!(matches!(
+            future.check_operations(),
+            Err(TransactionError::UnsupportedOperation(_))
+        ));
+        assert!(!future.is_well_formed());
+    }
 }
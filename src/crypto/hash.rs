@@ -28,6 +28,15 @@ pub fn double_sha256_hex(data: &[u8]) -> String {
     hex::encode(double_sha256(data))
 }
 
+/// Hash an address string to the u64 form contracts see on the stack
+pub fn address_hash(addr: &str) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    addr.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// Checks if a hash meets the difficulty target
 /// The hash must have `difficulty` leading zeros
 pub fn meets_difficulty(hash: &[u8], difficulty: u32) -> bool {
@@ -5,11 +5,22 @@
 
 use rand::rngs::OsRng;
 use ripemd::Ripemd160;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
 use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
 
-use super::hash::sha256;
+use super::hash::{double_sha256, sha256};
+
+/// Domain-separation prefix for signed messages, so a message signature
+/// can never be replayed as a transaction signature
+pub const SIGNED_MESSAGE_PREFIX: &str = "Mini Blockchain Signed Message:\n";
+
+/// Version byte of an exported private key (Bitcoin's mainnet WIF prefix)
+pub const WIF_VERSION: u8 = 0x80;
+
+/// Marks an exported key as belonging to a compressed public key
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
 
 /// Errors that can occur during key operations
 #[derive(Error, Debug)]
@@ -20,8 +31,12 @@ pub enum KeyError {
     InvalidPublicKey,
     #[error("Invalid signature")]
     InvalidSignature,
+    #[error("Non-canonical signature (high S value)")]
+    NonCanonicalSignature,
     #[error("Signature verification failed")]
     VerificationFailed,
+    #[error("Invalid WIF key: {0}")]
+    InvalidWif(String),
     #[error("Secp256k1 error: {0}")]
     Secp256k1Error(#[from] secp256k1::Error),
 }
@@ -67,6 +82,52 @@ impl KeyPair {
         hex::encode(self.secret_key.secret_bytes())
     }
 
+    /// Encode the private key in Wallet Import Format:
+    /// Base58Check(version || key || compressed flag)
+    pub fn to_wif(&self) -> String {
+        let mut bytes = vec![WIF_VERSION];
+        bytes.extend_from_slice(&self.secret_key.secret_bytes());
+        bytes.push(WIF_COMPRESSED_FLAG);
+
+        let checksum = double_sha256(&bytes);
+        bytes.extend_from_slice(&checksum[..4]);
+        bs58::encode(bytes).into_string()
+    }
+
+    /// Decode a key exported with [`KeyPair::to_wif`], checking its
+    /// version, length and checksum
+    pub fn from_wif(wif: &str) -> Result<Self, KeyError> {
+        let bytes = bs58::decode(wif.trim())
+            .into_vec()
+            .map_err(|_| KeyError::InvalidWif("not Base58".to_string()))?;
+        if bytes.len() != 38 {
+            return Err(KeyError::InvalidWif(format!(
+                "expected 38 bytes, got {}",
+                bytes.len()
+            )));
+        }
+
+        let (payload, checksum) = bytes.split_at(34);
+        if double_sha256(payload)[..4] != *checksum {
+            return Err(KeyError::InvalidWif("checksum mismatch".to_string()));
+        }
+        if payload[0] != WIF_VERSION {
+            return Err(KeyError::InvalidWif(format!(
+                "unknown version byte 0x{:02x}",
+                payload[0]
+            )));
+        }
+        if payload[33] != WIF_COMPRESSED_FLAG {
+            return Err(KeyError::InvalidWif(
+                "uncompressed keys are not supported".to_string(),
+            ));
+        }
+
+        let secret_key =
+            SecretKey::from_slice(&payload[1..33]).map_err(|_| KeyError::InvalidPrivateKey)?;
+        Ok(Self::from_secret_key(secret_key))
+    }
+
     /// Get the public key as a hex string (compressed format)
     pub fn public_key_hex(&self) -> String {
         hex::encode(self.public_key.serialize())
@@ -136,11 +197,33 @@ pub fn sign_message(secret_key: &SecretKey, message_hash: &[u8]) -> Result<Vec<u
     };
 
     let message = Message::from_digest_slice(&hash)?;
-    let signature = secp.sign_ecdsa(&message, secret_key);
+    let mut signature = secp.sign_ecdsa(&message, secret_key);
+    signature.normalize_s();
     Ok(signature.serialize_compact().to_vec())
 }
 
+/// Whether a compact signature is in canonical low-S form
+pub fn is_low_s(signature: &[u8]) -> Result<bool, KeyError> {
+    let sig = secp256k1::ecdsa::Signature::from_compact(signature)
+        .map_err(|_| KeyError::InvalidSignature)?;
+    let mut normalized = sig;
+    normalized.normalize_s();
+    Ok(normalized == sig)
+}
+
+/// Convert a compact signature to its canonical low-S form
+pub fn normalize_signature(signature: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let mut sig = secp256k1::ecdsa::Signature::from_compact(signature)
+        .map_err(|_| KeyError::InvalidSignature)?;
+    sig.normalize_s();
+    Ok(sig.serialize_compact().to_vec())
+}
+
 /// Verify a signature against a public key
+///
+/// High-S signatures are rejected with `NonCanonicalSignature`: each valid
+/// signature has a high-S twin, which would let anyone change a
+/// transaction's ID without invalidating it.
 pub fn verify_signature(
     public_key: &PublicKey,
     message_hash: &[u8],
@@ -158,6 +241,9 @@ pub fn verify_signature(
     let message = Message::from_digest_slice(&hash)?;
     let sig = secp256k1::ecdsa::Signature::from_compact(signature)
         .map_err(|_| KeyError::InvalidSignature)?;
+    if !is_low_s(signature)? {
+        return Err(KeyError::NonCanonicalSignature);
+    }
 
     match secp.verify_ecdsa(&message, &sig, public_key) {
         Ok(()) => Ok(true),
@@ -165,6 +251,60 @@ pub fn verify_signature(
     }
 }
 
+/// Hash a message for address ownership proofs (prefix + length + message)
+pub fn signed_message_hash(message: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(SIGNED_MESSAGE_PREFIX.len() + message.len() + 8);
+    data.extend_from_slice(SIGNED_MESSAGE_PREFIX.as_bytes());
+    data.extend_from_slice(&(message.len() as u64).to_be_bytes());
+    data.extend_from_slice(message.as_bytes());
+    double_sha256(&data)
+}
+
+/// Sign a message to prove ownership of the key's address
+///
+/// Returns a hex-encoded recoverable signature: recovery id (1 byte)
+/// followed by the compact signature (64 bytes).
+pub fn sign_address_message(secret_key: &SecretKey, message: &str) -> Result<String, KeyError> {
+    let secp = Secp256k1::new();
+    let hash = Message::from_digest_slice(&signed_message_hash(message))?;
+
+    let (recovery_id, compact) = secp
+        .sign_ecdsa_recoverable(&hash, secret_key)
+        .serialize_compact();
+
+    let mut signature = Vec::with_capacity(65);
+    signature.push(recovery_id.to_i32() as u8);
+    signature.extend_from_slice(&compact);
+    Ok(hex::encode(signature))
+}
+
+/// Verify that a signed message was produced by the owner of an address
+pub fn verify_address_message(address: &str, message: &str, signature: &str) -> bool {
+    let bytes = match hex::decode(signature) {
+        Ok(bytes) if bytes.len() == 65 => bytes,
+        _ => return false,
+    };
+
+    let recovery_id = match RecoveryId::from_i32(bytes[0] as i32) {
+        Ok(id) => id,
+        Err(_) => return false,
+    };
+    let signature = match RecoverableSignature::from_compact(&bytes[1..], recovery_id) {
+        Ok(sig) => sig,
+        Err(_) => return false,
+    };
+    let hash = match Message::from_digest_slice(&signed_message_hash(message)) {
+        Ok(hash) => hash,
+        Err(_) => return false,
+    };
+
+    let secp = Secp256k1::new();
+    match secp.recover_ecdsa(&hash, &signature) {
+        Ok(public_key) => public_key_to_address(&public_key) == address,
+        Err(_) => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,6 +337,34 @@ mod tests {
         assert_eq!(kp1.address(), kp2.address());
     }
 
+    #[test]
+    fn test_wif_round_trip() {
+        let kp = KeyPair::generate();
+        let wif = kp.to_wif();
+        assert!(wif.starts_with('K') || wif.starts_with('L'));
+
+        let imported = KeyPair::from_wif(&wif).unwrap();
+        assert_eq!(imported.private_key_hex(), kp.private_key_hex());
+        assert_eq!(imported.address(), kp.address());
+
+        // A single changed character breaks the checksum
+        let mut tampered: Vec<char> = wif.chars().collect();
+        tampered[10] = if tampered[10] == 'a' { 'b' } else { 'a' };
+        let tampered: String = tampered.into_iter().collect();
+        assert!(matches!(
+            KeyPair::from_wif(&tampered),
+            Err(KeyError::InvalidWif(_))
+        ));
+        assert!(matches!(
+            KeyPair::from_wif("0OIl"),
+            Err(KeyError::InvalidWif(_))
+        ));
+        assert!(matches!(
+            KeyPair::from_wif(&kp.address()),
+            Err(KeyError::InvalidWif(_))
+        ));
+    }
+
     #[test]
     fn test_address_format() {
         let kp = KeyPair::generate();
@@ -204,4 +372,34 @@ mod tests {
         // Bitcoin-style addresses start with 1 (mainnet)
         assert!(address.starts_with('1'));
     }
+
+    #[test]
+    fn test_address_message_signing() {
+        let kp = KeyPair::generate();
+        let other = KeyPair::generate();
+        let message = "I own this address";
+
+        let signature = sign_address_message(&kp.secret_key, message).unwrap();
+        assert!(verify_address_message(&kp.address(), message, &signature));
+
+        // Wrong address or tampered message fails
+        assert!(!verify_address_message(
+            &other.address(),
+            message,
+            &signature
+        ));
+        assert!(!verify_address_message(
+            &kp.address(),
+            "I own that address",
+            &signature
+        ));
+
+        // Malformed signatures fail
+        assert!(!verify_address_message(&kp.address(), message, "zz"));
+        assert!(!verify_address_message(
+            &kp.address(),
+            message,
+            &signature[2..]
+        ));
+    }
 }
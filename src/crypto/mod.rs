@@ -2,17 +2,24 @@
 //!
 //! This module provides:
 //! - SHA-256 hashing
+//! - Pluggable proof-of-work algorithms
 //! - ECDSA key management (secp256k1)
 //! - Merkle tree calculations
 
 pub mod hash;
 pub mod keys;
 pub mod merkle;
+pub mod pow;
 
-pub use hash::{double_sha256, double_sha256_hex, meets_difficulty, sha256, sha256_hex};
+pub use hash::{
+    address_hash, double_sha256, double_sha256_hex, meets_difficulty, sha256, sha256_hex,
+};
 pub use keys::{
-    public_key_from_hex, public_key_to_address, sign_message, verify_signature, KeyError, KeyPair,
+    is_low_s, normalize_signature, public_key_from_hex, public_key_to_address,
+    sign_address_message, sign_message, signed_message_hash, verify_address_message,
+    verify_signature, KeyError, KeyPair, SIGNED_MESSAGE_PREFIX, WIF_VERSION,
 };
 pub use merkle::{
     build_merkle_tree, calculate_merkle_root, calculate_merkle_root_hex, MerkleProof,
 };
+pub use pow::{MemoryHard, PowAlgorithm, PowKind, Sha256d, MEMORY_HARD_SCRATCHPAD_ENTRIES};
@@ -0,0 +1,175 @@
+//! Proof-of-work algorithms
+//!
+//! A chain picks its algorithm in `ChainParams`; every block header commits
+//! to the one it was mined with, so validators check the same work the
+//! miner did. Double SHA-256 is the default. The memory-hard option fills
+//! a scratchpad and reads it back in a data-dependent order, so each hash
+//! needs the whole scratchpad in memory, which blunts ASIC and GPU
+//! advantages (in the spirit of scrypt).
+
+use super::hash::{double_sha256, meets_difficulty, sha256};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Scratchpad entries (32 bytes each) of the memory-hard algorithm: 128 KiB
+pub const MEMORY_HARD_SCRATCHPAD_ENTRIES: usize = 1 << 12;
+
+/// A hash function used for proof of work
+pub trait PowAlgorithm: Send + Sync {
+    /// Short name shown to users
+    fn name(&self) -> &'static str;
+
+    /// Proof-of-work hash of serialized header bytes
+    fn hash(&self, header: &[u8]) -> Vec<u8>;
+
+    /// Whether the header's hash has `difficulty` leading zero bits
+    fn meets_difficulty(&self, header: &[u8], difficulty: u32) -> bool {
+        meets_difficulty(&self.hash(header), difficulty)
+    }
+}
+
+/// Bitcoin-style double SHA-256
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256d;
+
+impl PowAlgorithm for Sha256d {
+    fn name(&self) -> &'static str {
+        "sha256d"
+    }
+
+    fn hash(&self, header: &[u8]) -> Vec<u8> {
+        double_sha256(header)
+    }
+}
+
+/// Sequential memory-hard hash built from SHA-256
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryHard {
+    /// Scratchpad entries of 32 bytes each
+    pub entries: usize,
+}
+
+impl Default for MemoryHard {
+    fn default() -> Self {
+        Self {
+            entries: MEMORY_HARD_SCRATCHPAD_ENTRIES,
+        }
+    }
+}
+
+impl PowAlgorithm for MemoryHard {
+    fn name(&self) -> &'static str {
+        "memory-hard"
+    }
+
+    fn hash(&self, header: &[u8]) -> Vec<u8> {
+        let entries = self.entries.max(1);
+
+        // Fill the scratchpad with a hash chain
+        let mut x = sha256(header);
+        let mut scratchpad = Vec::with_capacity(entries);
+        for _ in 0..entries {
+            let next = sha256(&x);
+            scratchpad.push(std::mem::replace(&mut x, next));
+        }
+
+        // Visit it in an order that depends on the running state
+        for _ in 0..entries {
+            let mut index = [0u8; 8];
+            index.copy_from_slice(&x[..8]);
+            let j = (u64::from_le_bytes(index) % entries as u64) as usize;
+
+            let mixed: Vec<u8> = x.iter().zip(&scratchpad[j]).map(|(a, b)| a ^ b).collect();
+            x = sha256(&mixed);
+            scratchpad[j].clone_from(&x);
+        }
+
+        sha256(&x)
+    }
+}
+
+/// Which proof-of-work algorithm a chain (and each of its blocks) uses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowKind {
+    /// Double SHA-256
+    #[default]
+    Sha256d,
+    /// [`MemoryHard`] with the default scratchpad
+    MemoryHard,
+}
+
+static SHA256D: Sha256d = Sha256d;
+static MEMORY_HARD: MemoryHard = MemoryHard {
+    entries: MEMORY_HARD_SCRATCHPAD_ENTRIES,
+};
+
+impl PowKind {
+    /// The algorithm implementation
+    pub fn algorithm(&self) -> &'static dyn PowAlgorithm {
+        match self {
+            PowKind::Sha256d => &SHA256D,
+            PowKind::MemoryHard => &MEMORY_HARD,
+        }
+    }
+
+    /// Byte committed in block headers that use a non-default algorithm
+    pub fn id(&self) -> u8 {
+        match self {
+            PowKind::Sha256d => 0,
+            PowKind::MemoryHard => 1,
+        }
+    }
+
+    /// Whether this is the default algorithm (for serde)
+    pub fn is_sha256d(&self) -> bool {
+        *self == PowKind::Sha256d
+    }
+}
+
+impl fmt::Display for PowKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.algorithm().name())
+    }
+}
+
+impl std::str::FromStr for PowKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "sha256d" => Ok(Self::Sha256d),
+            "memory-hard" => Ok(Self::MemoryHard),
+            other => Err(format!(
+                "Unknown proof-of-work algorithm '{}' (expected sha256d or memory-hard)",
+                other
+            )),
+        }
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_algorithms_differ_and_are_deterministic() {
+        let header = b"header bytes";
+        let sha = PowKind::Sha256d.algorithm().hash(header);
+        let memory_hard = PowKind::MemoryHard.algorithm().hash(header);
+
+        assert_eq!(sha, double_sha256(header));
+        assert_eq!(memory_hard.len(), 32);
+        assert_eq!(memory_hard, PowKind::MemoryHard.algorithm().hash(header));
+        assert_ne!(memory_hard, sha);
+        assert_ne!(memory_hard, PowKind::MemoryHard.algorithm().hash(b"other"));
+
+        assert_eq!("memory-hard".parse(), Ok(PowKind::MemoryHard));
+        assert_eq!("SHA256D".parse(), Ok(PowKind::Sha256d));
+        assert!("scrypt".parse::<PowKind>().is_err());
+    }
+}
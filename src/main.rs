@@ -3,13 +3,19 @@
 //! A command-line interface for interacting with the blockchain.
 
 use clap::{Parser, Subcommand};
-use mini_blockchain::api::{create_router, ApiState, WsBroadcaster};
-use mini_blockchain::cli::{self, AppState};
-use mini_blockchain::contract::{Compiler, ContractManager};
-use mini_blockchain::core::Blockchain;
-use mini_blockchain::mining::Mempool;
+use mini_blockchain::api::{
+    create_router, restore_mempool, save_state, spawn_autosave, spawn_event_forwarder, ApiState,
+    WsBroadcaster, DEFAULT_AUTOSAVE_INTERVAL,
+};
+use mini_blockchain::cli::{self, AppState, ExportFormat, ExportKind};
+use mini_blockchain::contract::{Compiler, ContractManager, GasDestination, DEFAULT_MAX_GAS_LIMIT};
+use mini_blockchain::core::{Blockchain, ChainParams, EventBus};
+use mini_blockchain::crypto::PowKind;
+use mini_blockchain::mining::{BlockAssembly, Mempool, DEFAULT_MAX_ORPHAN_TX_MB};
 use mini_blockchain::multisig::MultisigManager;
-use mini_blockchain::network::{Node, NodeConfig, PeerManager};
+use mini_blockchain::network::{
+    magic_for_chain_id, DandelionConfig, Node, NodeConfig, PeerManager, BAN_LIST_FILE,
+};
 use mini_blockchain::storage::{Storage, StorageConfig};
 use mini_blockchain::token::TokenManager;
 use mini_blockchain::wallet::WalletManager;
@@ -28,6 +34,10 @@ struct Cli {
     #[arg(short, long, default_value = ".blockchain_data")]
     data_dir: PathBuf,
 
+    /// Named wallet collection to use instead of the default one
+    #[arg(long, global = true)]
+    wallet: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -39,6 +49,58 @@ enum Commands {
         /// Mining difficulty (number of leading zero bits)
         #[arg(short, long)]
         difficulty: Option<u32>,
+
+        /// Target time between blocks in seconds
+        #[arg(long)]
+        target_block_time: Option<i64>,
+
+        /// Blocks between difficulty adjustments
+        #[arg(long)]
+        adjustment_interval: Option<u64>,
+
+        /// Coinbase reward per block
+        #[arg(long)]
+        block_reward: Option<u64>,
+
+        /// Blocks between reward halvings (0 disables halving)
+        #[arg(long)]
+        halving_interval: Option<u64>,
+
+        /// Skip signature checks for this block hash and its ancestors
+        #[arg(long)]
+        assume_valid: Option<String>,
+
+        /// Memory budget for orphan blocks, in megabytes
+        #[arg(long)]
+        max_orphan_block_mb: Option<u64>,
+
+        /// Lowest difficulty retargeting may reach
+        #[arg(long)]
+        min_difficulty: Option<u32>,
+
+        /// Highest difficulty retargeting may reach
+        #[arg(long)]
+        max_difficulty: Option<u32>,
+
+        /// Deepest reorganization accepted, in blocks
+        #[arg(long)]
+        max_reorg_depth: Option<u64>,
+
+        /// Blocks between automatic checkpoints (0 disables them)
+        #[arg(long)]
+        checkpoint_interval: Option<u64>,
+
+        /// Digits after the decimal point when displaying amounts
+        #[arg(long)]
+        coin_decimals: Option<u8>,
+
+        /// Proof-of-work algorithm: sha256d or memory-hard
+        #[arg(long)]
+        pow_algorithm: Option<PowKind>,
+
+        /// Confirmations a coinbase output needs before it can be spent
+        #[arg(long)]
+        coinbase_maturity: Option<u64>,
     },
 
     /// Mine new blocks
@@ -50,6 +112,14 @@ enum Commands {
         /// Number of blocks to mine
         #[arg(short, long, default_value = "1")]
         count: u32,
+
+        /// Pick transactions to maximize total fees instead of greedily by fee rate
+        #[arg(long)]
+        knapsack: bool,
+
+        /// Message to embed in the coinbase of mined blocks
+        #[arg(long)]
+        tag: Option<String>,
     },
 
     /// Wallet operations
@@ -90,6 +160,14 @@ enum Commands {
         /// Output file path
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Output format: json or csv
+        #[arg(long, default_value = "json")]
+        format: ExportFormat,
+
+        /// Records to export as CSV: transactions, blocks or utxos
+        #[arg(long, default_value = "transactions")]
+        what: ExportKind,
     },
 
     /// Import blockchain from file
@@ -116,6 +194,18 @@ enum Commands {
         #[command(subcommand)]
         action: ContractCommands,
     },
+
+    /// Raw transaction tools
+    Tx {
+        #[command(subcommand)]
+        action: TxCommands,
+    },
+
+    /// UTXO set tools
+    Utxoset {
+        #[command(subcommand)]
+        action: UtxosetCommands,
+    },
 }
 
 #[derive(Subcommand)]
@@ -136,6 +226,24 @@ enum WalletCommands {
         #[arg(short, long)]
         address: String,
     },
+
+    /// Import a wallet from a WIF-encoded private key
+    Import {
+        /// Private key exported with `wallet export`
+        #[arg(short, long)]
+        key: String,
+
+        /// Optional label for the wallet
+        #[arg(short, long)]
+        label: Option<String>,
+    },
+
+    /// Print a wallet's private key in WIF format
+    Export {
+        /// Wallet address
+        #[arg(short, long)]
+        address: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -149,6 +257,40 @@ enum ChainCommands {
         #[arg(short, long, default_value = "10")]
         count: u32,
     },
+
+    /// Convert an archive chain to a pruned one, dropping old block bodies
+    Prune {
+        /// Number of recent blocks to keep in full
+        #[arg(short, long, default_value = "288")]
+        keep: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum TxCommands {
+    /// Show a transaction by ID
+    Get {
+        /// Transaction ID
+        #[arg(long)]
+        id: String,
+
+        /// Print the raw hex encoding instead of JSON
+        #[arg(long)]
+        hex: bool,
+    },
+
+    /// Decode a raw hex transaction
+    Decode {
+        /// Raw transaction hex
+        #[arg(long)]
+        hex: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum UtxosetCommands {
+    /// Show UTXO set statistics and its hash
+    Info,
 }
 
 #[derive(Subcommand)]
@@ -159,9 +301,62 @@ enum NodeCommands {
         #[arg(short, long, default_value = "8333")]
         port: u16,
 
+        /// Interface to listen on (IPv4 or IPv6, e.g. 127.0.0.1 or ::)
+        #[arg(long, default_value = "0.0.0.0")]
+        bind: std::net::IpAddr,
+
         /// Initial peers to connect to (comma-separated)
         #[arg(long)]
         peers: Option<String>,
+
+        /// Relay local transactions through a Dandelion stem phase
+        #[arg(long)]
+        dandelion: bool,
+
+        /// Seconds allowed to establish an outbound connection
+        #[arg(long, default_value = "10")]
+        connect_timeout: u64,
+
+        /// Seconds allowed for a peer to complete the handshake
+        #[arg(long, default_value = "10")]
+        handshake_timeout: u64,
+
+        /// Chain ID selecting the network magic; nodes only talk to peers
+        /// on the same chain
+        #[arg(long, default_value = "1")]
+        chain_id: u32,
+
+        /// Seconds to collect transactions before announcing them to peers
+        /// (0 announces each one immediately)
+        #[arg(long, default_value = "5")]
+        inv_batch_interval: u64,
+
+        /// Most blocks from peers validated at once
+        #[arg(long, default_value = "4")]
+        max_concurrent_validations: usize,
+
+        /// Peer IPs exempt from connection limits, eviction and bans
+        /// (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        whitelist: Vec<std::net::IpAddr>,
+
+        /// Connection slots reserved for whitelisted peers
+        #[arg(long, default_value = "8")]
+        reserved_slots: usize,
+
+        /// Ignore token and contract transactions instead of relaying them
+        #[arg(long)]
+        payments_only: bool,
+
+        /// Hold locally mined blocks until this many peers are connected
+        /// and synced (0 = announce right away)
+        #[arg(long, default_value = "0")]
+        min_peers_to_announce: usize,
+
+        /// Peers to exchange only blocks with, never transactions
+        /// (comma-separated host:port)
+        #[arg(long, value_delimiter = ',')]
+        block_relay_only: Vec<String>,
     },
 
     /// Connect to a peer (while node is running in another terminal)
@@ -173,6 +368,29 @@ enum NodeCommands {
 
     /// Show node status
     Status,
+
+    /// Ban a peer address (applied when the node next starts)
+    Ban {
+        /// Peer address (host:port)
+        addr: std::net::SocketAddr,
+
+        /// Ban duration in seconds
+        #[arg(long, default_value = "86400")]
+        duration: u64,
+
+        /// Reason recorded with the ban
+        #[arg(long, default_value = "manual ban")]
+        reason: String,
+    },
+
+    /// Remove a peer ban
+    Unban {
+        /// Peer address (host:port)
+        addr: std::net::SocketAddr,
+    },
+
+    /// List banned peers
+    Listbanned,
 }
 
 #[derive(Subcommand)]
@@ -183,6 +401,11 @@ enum ApiCommands {
         #[arg(short, long, default_value = "3000")]
         port: u16,
 
+        /// Interface the REST API and P2P node listen on (IPv4 or IPv6,
+        /// e.g. 127.0.0.1 to accept local connections only)
+        #[arg(long, default_value = "0.0.0.0")]
+        bind: std::net::IpAddr,
+
         /// Enable P2P node on this port (optional)
         #[arg(long)]
         p2p_port: Option<u16>,
@@ -190,6 +413,49 @@ enum ApiCommands {
         /// Initial peers to connect to (comma-separated, requires --p2p-port)
         #[arg(long)]
         peers: Option<String>,
+
+        /// Relay local transactions through a Dandelion stem phase
+        #[arg(long)]
+        dandelion: bool,
+
+        /// Chain ID selecting the P2P network magic; the embedded node only
+        /// talks to peers on the same chain
+        #[arg(long, default_value = "1")]
+        chain_id: u32,
+
+        /// Where contract gas payments go: burn or miner
+        #[arg(long, default_value = "burn")]
+        gas_destination: GasDestination,
+
+        /// Largest gas limit a contract call may request
+        #[arg(long, default_value_t = DEFAULT_MAX_GAS_LIMIT)]
+        max_gas_limit: u64,
+
+        /// Seconds between automatic saves of chain, mempool and managers (0 = only on shutdown)
+        #[arg(long, default_value_t = DEFAULT_AUTOSAVE_INTERVAL.as_secs())]
+        autosave_interval: u64,
+
+        /// Enable testing endpoints that rewrite chain state (e.g. reorg simulation)
+        #[arg(long)]
+        dev_mode: bool,
+
+        /// Hold mined blocks until this many P2P peers are connected and
+        /// synced (0 = announce right away)
+        #[arg(long, default_value = "0")]
+        min_peers_to_announce: usize,
+
+        /// Percent fee-rate discount for transactions with more inputs than
+        /// outputs while the UTXO set is above --max-utxo-set-size (0 = off)
+        #[arg(long, default_value = "0")]
+        consolidation_discount: u64,
+
+        /// UTXO set size above which consolidating transactions get the discount
+        #[arg(long, default_value = "0")]
+        max_utxo_set_size: usize,
+
+        /// Memory budget for orphan transactions, in megabytes
+        #[arg(long, default_value_t = DEFAULT_MAX_ORPHAN_TX_MB)]
+        max_orphan_tx_mb: u64,
     },
 }
 
@@ -200,6 +466,10 @@ enum ContractCommands {
         /// Contract source file (.asm)
         #[arg(short, long)]
         file: PathBuf,
+
+        /// Gas limit for the init code
+        #[arg(long, default_value = "100000")]
+        gas: u64,
     },
 
     /// Call a contract
@@ -235,8 +505,42 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
     // Handle init command separately (doesn't need full state)
-    if let Commands::Init { difficulty } = &cli.command {
-        return cli::cmd_init(&cli.data_dir, *difficulty).map_err(Into::into);
+    if let Commands::Init {
+        difficulty,
+        target_block_time,
+        adjustment_interval,
+        block_reward,
+        halving_interval,
+        assume_valid,
+        max_orphan_block_mb,
+        min_difficulty,
+        max_difficulty,
+        max_reorg_depth,
+        checkpoint_interval,
+        coin_decimals,
+        pow_algorithm,
+        coinbase_maturity,
+    } = &cli.command
+    {
+        let defaults = ChainParams::default();
+        let params = ChainParams {
+            genesis_difficulty: difficulty.unwrap_or(defaults.genesis_difficulty),
+            target_block_time: target_block_time.unwrap_or(defaults.target_block_time),
+            adjustment_interval: adjustment_interval.unwrap_or(defaults.adjustment_interval),
+            block_reward: block_reward.unwrap_or(defaults.block_reward),
+            halving_interval: halving_interval.unwrap_or(defaults.halving_interval),
+            assume_valid: assume_valid.clone(),
+            max_orphan_block_mb: max_orphan_block_mb.unwrap_or(defaults.max_orphan_block_mb),
+            tie_break: defaults.tie_break,
+            min_difficulty: min_difficulty.unwrap_or(defaults.min_difficulty),
+            max_difficulty: max_difficulty.unwrap_or(defaults.max_difficulty),
+            max_reorg_depth: max_reorg_depth.unwrap_or(defaults.max_reorg_depth),
+            checkpoint_interval: checkpoint_interval.unwrap_or(defaults.checkpoint_interval),
+            coin_decimals: coin_decimals.unwrap_or(defaults.coin_decimals),
+            pow_algorithm: pow_algorithm.unwrap_or(defaults.pow_algorithm),
+            coinbase_maturity: coinbase_maturity.unwrap_or(defaults.coinbase_maturity),
+        };
+        return cli::cmd_init(&cli.data_dir, params).map_err(Into::into);
     }
 
     // Handle node commands with tokio runtime
@@ -252,6 +556,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Initialize application state
     let mut state = AppState::new(cli.data_dir.clone())?;
+    if let Some(name) = &cli.wallet {
+        state.wallet_manager = state.wallet_manager.load_collection(name)?;
+    }
 
     // Process commands
     match cli.command {
@@ -259,8 +566,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Node { .. } => unreachable!(),
         Commands::Api { .. } => unreachable!(),
 
-        Commands::Mine { address, count } => {
-            cli::cmd_mine(&mut state, &address, count)?;
+        Commands::Mine {
+            address,
+            count,
+            knapsack,
+            tag,
+        } => {
+            let assembly = if knapsack {
+                BlockAssembly::Knapsack
+            } else {
+                BlockAssembly::Greedy
+            };
+            cli::cmd_mine(&mut state, &address, count, assembly, tag.as_deref())?;
         }
 
         Commands::Wallet { action } => match action {
@@ -273,6 +590,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             WalletCommands::Balance { address } => {
                 cli::cmd_wallet_balance(&state, &address)?;
             }
+            WalletCommands::Import { key, label } => {
+                cli::cmd_wallet_import(&state, &key, label.as_deref())?;
+            }
+            WalletCommands::Export { address } => {
+                cli::cmd_wallet_export(&state, &address)?;
+            }
         },
 
         Commands::Send { from, to, amount } => {
@@ -286,6 +609,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Some(ChainCommands::Blocks { count }) => {
                 cli::cmd_chain_blocks(&state, count)?;
             }
+            Some(ChainCommands::Prune { keep }) => {
+                cli::cmd_chain_prune(&mut state, keep)?;
+            }
         },
 
         Commands::Validate => {
@@ -296,8 +622,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             cli::cmd_mempool(&state)?;
         }
 
-        Commands::Export { output } => {
-            cli::cmd_export(&state, &output)?;
+        Commands::Export {
+            output,
+            format,
+            what,
+        } => {
+            cli::cmd_export(&state, &output, format, what)?;
         }
 
         Commands::Import { input } => {
@@ -307,6 +637,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Contract { action } => {
             run_contract_command(&action, &cli.data_dir)?;
         }
+
+        Commands::Tx { action } => match action {
+            TxCommands::Get { id, hex } => {
+                cli::cmd_tx_get(&state, &id, hex)?;
+            }
+            TxCommands::Decode { hex } => {
+                cli::cmd_tx_decode(&hex)?;
+            }
+        },
+
+        Commands::Utxoset { action } => match action {
+            UtxosetCommands::Info => {
+                cli::cmd_utxoset_info(&state)?;
+            }
+        },
     }
 
     Ok(())
@@ -320,26 +665,64 @@ fn run_node_command(
 
     rt.block_on(async {
         match action {
-            NodeCommands::Start { port, peers } => {
+            NodeCommands::Start {
+                port,
+                bind,
+                peers,
+                dandelion,
+                connect_timeout,
+                handshake_timeout,
+                chain_id,
+                inv_batch_interval,
+                max_concurrent_validations,
+                whitelist,
+                reserved_slots,
+                payments_only,
+                min_peers_to_announce,
+                block_relay_only,
+            } => {
                 let bootstrap_peers: Vec<String> = peers
                     .clone()
                     .map(|p| p.split(',').map(|s| s.trim().to_string()).collect())
                     .unwrap_or_default();
 
                 let config = NodeConfig {
+                    bind_address: *bind,
                     port: *port,
                     bootstrap_peers,
                     data_dir: data_dir.clone(),
+                    dandelion: DandelionConfig {
+                        enabled: *dandelion,
+                        ..Default::default()
+                    },
+                    connect_timeout: std::time::Duration::from_secs(*connect_timeout),
+                    handshake_timeout: std::time::Duration::from_secs(*handshake_timeout),
+                    magic: magic_for_chain_id(*chain_id),
+                    inv_batch_interval: std::time::Duration::from_secs(*inv_batch_interval),
+                    max_concurrent_validations: *max_concurrent_validations,
+                    whitelist: whitelist.clone(),
+                    reserved_slots: *reserved_slots,
+                    relay_smart_txs: !*payments_only,
+                    min_peers_to_announce: *min_peers_to_announce,
+                    block_relay_only_peers: block_relay_only.clone(),
                 };
 
-                println!("🌐 Starting P2P node on port {}...", port);
+                println!(
+                    "🌐 Starting P2P node on {}...",
+                    std::net::SocketAddr::new(*bind, *port)
+                );
 
                 let mut node = Node::new(config).await?;
 
-                // Handle Ctrl+C
+                // Handle Ctrl+C, keeping bans for the next start
+                let peer_manager = node.peer_manager();
+                let ban_list = data_dir.join(BAN_LIST_FILE);
                 tokio::spawn(async move {
                     tokio::signal::ctrl_c().await.ok();
                     println!("\n📴 Shutting down node...");
+                    if let Err(e) = peer_manager.save_bans(&ban_list).await {
+                        eprintln!("⚠️  Failed to save ban list: {}", e);
+                    }
                     std::process::exit(0);
                 });
 
@@ -360,6 +743,49 @@ fn run_node_command(
                     "   Use 'node start --port 8334 --peers 127.0.0.1:8333' to connect to peers"
                 );
             }
+
+            NodeCommands::Ban {
+                addr,
+                duration,
+                reason,
+            } => {
+                let ban_list = data_dir.join(BAN_LIST_FILE);
+                let peer_manager = PeerManager::new(0);
+                peer_manager.load_bans(&ban_list).await?;
+                peer_manager
+                    .ban_peer(addr, std::time::Duration::from_secs(*duration), reason)
+                    .await;
+                peer_manager.save_bans(&ban_list).await?;
+                println!("🚫 Banned {} for {}s ({})", addr, duration, reason);
+            }
+
+            NodeCommands::Unban { addr } => {
+                let ban_list = data_dir.join(BAN_LIST_FILE);
+                let peer_manager = PeerManager::new(0);
+                peer_manager.load_bans(&ban_list).await?;
+                peer_manager.unban_peer(addr).await;
+                peer_manager.save_bans(&ban_list).await?;
+                println!("✅ Unbanned {}", addr);
+            }
+
+            NodeCommands::Listbanned => {
+                let peer_manager = PeerManager::new(0);
+                peer_manager
+                    .load_bans(&data_dir.join(BAN_LIST_FILE))
+                    .await?;
+                let bans = peer_manager.get_banned().await;
+                if bans.is_empty() {
+                    println!("No banned peers");
+                }
+                for ban in bans {
+                    println!(
+                        "🚫 {} - {}s remaining ({})",
+                        ban.addr,
+                        ban.remaining().as_secs(),
+                        ban.reason
+                    );
+                }
+            }
         }
 
         Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
@@ -378,8 +804,19 @@ fn run_api_command(
         match action {
             ApiCommands::Start {
                 port,
+                bind,
                 p2p_port,
                 peers,
+                dandelion,
+                chain_id,
+                gas_destination,
+                max_gas_limit,
+                autosave_interval,
+                dev_mode,
+                min_peers_to_announce,
+                consolidation_discount,
+                max_utxo_set_size,
+                max_orphan_tx_mb,
             } => {
                 // Initialize storage
                 let storage_config = StorageConfig {
@@ -399,22 +836,35 @@ fn run_api_command(
                     Arc::new(RwLock::new(chain))
                 };
 
-                // Initialize components
-                let mempool = Arc::new(RwLock::new(Mempool::new()));
+                // Initialize components, restoring transactions pending at the last save
+                let mut mempool = Mempool::new();
+                mempool.set_consolidation_discount_percent(*consolidation_discount);
+                mempool.set_max_utxo_set_size(*max_utxo_set_size);
+                mempool.set_max_orphan_mb(*max_orphan_tx_mb);
+                let restored = restore_mempool(&mut mempool, &*blockchain.read().await, data_dir)?;
+                if restored > 0 {
+                    println!("📥 Restored {} pending transaction(s)", restored);
+                }
+                let mempool = Arc::new(RwLock::new(mempool));
                 let wallets_dir = data_dir.join("wallets");
                 let wallet_manager = Arc::new(RwLock::new(WalletManager::new(&wallets_dir)?));
 
                 // Load or create contract manager
                 let contracts_file = data_dir.join("contracts.json");
-                let contract_manager = if contracts_file.exists() {
+                let mut contract_manager: ContractManager = if contracts_file.exists() {
                     let data = std::fs::read_to_string(&contracts_file)?;
-                    Arc::new(RwLock::new(serde_json::from_str(&data)?))
+                    serde_json::from_str(&data)?
                 } else {
-                    Arc::new(RwLock::new(ContractManager::new()))
+                    ContractManager::new()
                 };
+                contract_manager.set_gas_destination(*gas_destination);
+                contract_manager.set_max_gas_limit(*max_gas_limit);
+                let contract_manager = Arc::new(RwLock::new(contract_manager));
 
-                // Create WebSocket broadcaster
+                // Create WebSocket broadcaster, fed from the event bus
                 let ws_broadcaster = Arc::new(WsBroadcaster::new());
+                let events = Arc::new(EventBus::new());
+                spawn_event_forwarder(&events, ws_broadcaster.clone());
 
                 // Load or create multisig manager
                 let multisig_file = data_dir.join("multisig.json");
@@ -436,7 +886,10 @@ fn run_api_command(
 
                 // Create PeerManager if P2P is enabled
                 let peer_manager: Option<Arc<PeerManager>> = if p2p_port.is_some() {
-                    Some(Arc::new(PeerManager::new(p2p_port.unwrap())))
+                    Some(Arc::new(
+                        PeerManager::new(p2p_port.unwrap())
+                            .with_min_peers_to_announce(*min_peers_to_announce),
+                    ))
                 } else {
                     None
                 };
@@ -449,21 +902,32 @@ fn run_api_command(
                     wallet_manager,
                     contract_manager,
                     ws_broadcaster,
+                    events: events.clone(),
                     multisig_manager,
                     token_manager,
                     peer_manager: peer_manager.clone(),
+                    dev_mode: *dev_mode,
                 };
 
                 // Clone state for shutdown handler
                 let shutdown_state = state.clone();
                 let shutdown_data_dir = data_dir.clone();
 
+                let autosave = (*autosave_interval > 0).then(|| {
+                    println!("💾 Autosaving every {}s", autosave_interval);
+                    spawn_autosave(
+                        state.clone(),
+                        data_dir.clone(),
+                        std::time::Duration::from_secs(*autosave_interval),
+                    )
+                });
+
                 // Create router
                 let app = create_router(state);
 
                 // Start server
-                let addr = format!("0.0.0.0:{}", port);
-                println!("🚀 REST API server starting on http://localhost:{}", port);
+                let addr = std::net::SocketAddr::new(*bind, *port);
+                println!("🚀 REST API server starting on http://{}", addr);
 
                 // Optionally start P2P node
                 if let Some(p2p_port) = p2p_port {
@@ -473,9 +937,17 @@ fn run_api_command(
                         .unwrap_or_default();
 
                     let config = NodeConfig {
+                        bind_address: *bind,
                         port: *p2p_port,
                         bootstrap_peers: bootstrap_peers.clone(),
                         data_dir: data_dir.clone(),
+                        dandelion: DandelionConfig {
+                            enabled: *dandelion,
+                            ..Default::default()
+                        },
+                        magic: magic_for_chain_id(*chain_id),
+                        min_peers_to_announce: *min_peers_to_announce,
+                        ..Default::default()
                     };
 
                     println!("🌐 P2P node enabled on port {}", p2p_port);
@@ -489,6 +961,7 @@ fn run_api_command(
                     let p2p_mempool = mempool.clone();
                     let p2p_storage = storage.clone();
                     let p2p_peer_manager = peer_manager.clone().unwrap();
+                    let p2p_events = events.clone();
 
                     tokio::spawn(async move {
                         // Create node with shared state - pass the same peer_manager
@@ -499,6 +972,7 @@ fn run_api_command(
                             p2p_storage,
                             p2p_peer_manager,
                         );
+                        node.set_event_bus(p2p_events);
 
                         log::info!("P2P node started with shared blockchain");
                         if let Err(e) = node.start().await {
@@ -509,21 +983,37 @@ fn run_api_command(
 
                 println!();
                 println!("📖 Available endpoints:");
-                println!("   GET  /health                      - Health check");
+                println!("   GET  /health                      - Health and sync status");
                 println!("   GET  /ws                          - WebSocket updates");
                 println!("   GET  /api/chain                   - Blockchain info");
                 println!("   GET  /api/chain/blocks            - List blocks");
+                println!("   GET  /api/chain/blocks/range?from=&to= - Blocks in a height range");
                 println!("   GET  /api/chain/blocks/{{height}}   - Get block");
+                println!("   GET  /api/chain/blocks/{{height}}/undo - Block undo data");
+                println!("   GET  /api/chain/blocks/{{height}}/coinbase - Coinbase maturity");
+                println!("   POST /api/chain/blocks/{{height}}/proofs - Batched Merkle proof");
                 println!("   GET  /api/chain/validate          - Validate chain");
+                println!("   GET  /api/chain/hashrate          - Estimated hashrate");
+                println!("   GET  /api/chain/reorgs            - Recent reorgs");
+                if *dev_mode {
+                    println!("   POST /api/chain/reorg-simulate    - Force a reorg (dev mode)");
+                }
                 println!("   POST /api/mine                    - Mine block");
-                println!("   GET  /api/mempool                 - Pending transactions");
+                println!("   POST /api/mine/batch              - Mine several blocks");
+                println!("   GET  /api/network                 - Network info");
+                println!("   GET  /api/mempool                 - Pending transactions (by fee-rate band)");
+                println!("   GET  /api/mempool/{{id}}            - Pending transaction detail");
+                println!("   GET  /api/mempool/{{id}}/position   - Queue position and estimated block");
                 println!("   GET  /api/transactions/{{id}}       - Get transaction");
+                println!("   GET  /api/transactions/{{id}}/propagation - Peers that relayed a broadcast tx");
                 println!("   GET  /api/wallets                 - List wallets");
                 println!("   POST /api/wallets                 - Create wallet");
                 println!("   GET  /api/wallets/{{addr}}/balance   - Get balance");
+                println!("   GET  /api/address/{{addr}}/history   - Transactions in a height range");
+                println!("   POST /api/verifymessage           - Verify signed message");
                 println!("   GET  /api/contracts               - List contracts");
                 println!("   POST /api/contracts               - Deploy contract");
-                println!("   POST /api/contracts/{{addr}}/call    - Call contract");
+                println!("   POST /api/contracts/{{addr}}/call    - Call contract (?trace=true)");
                 println!("   GET  /api/multisig                - List multisig wallets");
                 println!("   POST /api/multisig                - Create multisig wallet");
                 println!("   POST /api/multisig/{{addr}}/propose  - Propose transaction");
@@ -532,6 +1022,7 @@ fn run_api_command(
                 println!("   POST /api/tokens                  - Create token");
                 println!("   GET  /api/tokens/{{addr}}/balance/{{h}} - Token balance");
                 println!("   POST /api/tokens/{{addr}}/transfer   - Transfer tokens");
+                println!("   GET  /api/tokens/{{addr}}/distribution - Token holder distribution");
                 println!();
 
                 // Handle Ctrl+C with graceful shutdown
@@ -539,32 +1030,19 @@ fn run_api_command(
                     tokio::signal::ctrl_c().await.ok();
                     println!("\n📴 Shutting down API server...");
 
-                    // Save all data before exit
-                    println!("💾 Saving data...");
-
-                    // Save contracts
-                    let contracts = shutdown_state.contract_manager.read().await;
-                    if let Ok(data) = serde_json::to_string_pretty(&*contracts) {
-                        let _ = std::fs::write(shutdown_data_dir.join("contracts.json"), data);
-                    }
-
-                    // Save multisig
-                    let multisig = shutdown_state.multisig_manager.read().await;
-                    if let Ok(data) = serde_json::to_string_pretty(&*multisig) {
-                        let _ = std::fs::write(shutdown_data_dir.join("multisig.json"), data);
+                    // Stop autosaving so it cannot start another write
+                    // during or after the final save
+                    if let Some(autosave) = autosave {
+                        autosave.abort();
+                        let _ = autosave.await;
                     }
 
-                    // Save tokens
-                    let tokens = shutdown_state.token_manager.read().await;
-                    if let Ok(data) = serde_json::to_string_pretty(&*tokens) {
-                        let _ = std::fs::write(shutdown_data_dir.join("tokens.json"), data);
+                    // Save all data before exit
+                    println!("💾 Saving data...");
+                    match save_state(&shutdown_state, &shutdown_data_dir).await {
+                        Ok(()) => println!("✅ Data saved successfully!"),
+                        Err(e) => eprintln!("❌ Failed to save data: {}", e),
                     }
-
-                    // Save blockchain
-                    let blockchain = shutdown_state.blockchain.read().await;
-                    let _ = shutdown_state.storage.save(&blockchain);
-
-                    println!("✅ Data saved successfully!");
                     std::process::exit(0);
                 });
 
@@ -593,7 +1071,7 @@ fn run_contract_command(
     };
 
     match action {
-        ContractCommands::Deploy { file } => {
+        ContractCommands::Deploy { file, gas } => {
             println!("📜 Deploying contract from {:?}...", file);
 
             let source = fs::read_to_string(file)?;
@@ -604,7 +1082,7 @@ fn run_contract_command(
 
             // Use a simple deployer address for CLI
             let deployer = "cli_deployer";
-            let address = manager.deploy(bytecode, deployer, 1)?;
+            let address = manager.deploy_with_gas_limit(bytecode, deployer, 1, Some(*gas))?;
 
             // Save manager
             let data = serde_json::to_string_pretty(&manager)?;
@@ -612,6 +1090,9 @@ fn run_contract_command(
 
             println!("✅ Contract deployed!");
             println!("   Address: {}", address);
+            if let Some(contract) = manager.get(&address) {
+                println!("   Init gas used: {}", contract.init_gas_used);
+            }
         }
 
         ContractCommands::Call { address, args, gas } => {
@@ -634,6 +1115,9 @@ fn run_contract_command(
                 println!("   Return value: {}", ret);
             }
             println!("   Gas used: {}", result.gas_used);
+            if result.gas_refunded > 0 {
+                println!("   Gas refunded: {}", result.gas_refunded);
+            }
             if !result.storage_changes.is_empty() {
                 println!("   Storage changes: {}", result.storage_changes.len());
             }
@@ -7,9 +7,16 @@
 //! - Chain ID validation
 //! - Fee-based prioritization
 //! - Ancestor/descendant limits (Bitcoin-style)
-
-use crate::core::{Blockchain, Transaction, TransactionError, DEFAULT_CHAIN_ID};
-use std::collections::{HashMap, HashSet};
+//! - Orphan pool for transactions whose parents haven't arrived yet
+//! - Block assembly under the weight limit (greedy or fee-maximizing)
+
+use super::policy::SharedPolicy;
+use crate::core::{
+    system_clock, Blockchain, BlockchainError, OutPoint, PolicyError, SharedClock, Transaction,
+    TransactionError, TransactionInput, BYTES_PER_MB, DEFAULT_CHAIN_ID, MAX_BLOCK_TXS,
+    MAX_STANDARD_TX_SIZE,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
 use thiserror::Error;
 
 // =============================================================================
@@ -22,21 +29,61 @@ pub const DEFAULT_MEMPOOL_SIZE: usize = 10000;
 /// Maximum mempool size in bytes (300MB like Bitcoin)
 pub const MAX_MEMPOOL_BYTES: usize = 300_000_000;
 
-/// Minimum fee bump for RBF (in percentage, e.g., 10 = 10% higher)
+/// Default memory budget for orphan transactions, in megabytes
+pub const DEFAULT_MAX_ORPHAN_TX_MB: u64 = 5;
+
+/// Age after which orphan transactions are dropped (20 minutes, like Bitcoin)
+pub const ORPHAN_TX_EXPIRE_SECS: u64 = 20 * 60;
+
+/// Default minimum fee bump for RBF (in percentage, e.g., 10 = 10% higher)
 pub const MIN_RBF_FEE_BUMP_PERCENT: u64 = 10;
 
+/// Mempool usage (percent of byte limit) at which the dynamic fee floor kicks in
+pub const MIN_FEE_FLOOR_USAGE_PERCENT: usize = 50;
+
+/// Fee rate a newcomer must beat an evicted package by (like Bitcoin's
+/// incremental relay fee)
+pub const INCREMENTAL_RELAY_FEE_RATE: u64 = 1;
+
+/// Time for the eviction fee floor to halve once evictions stop (12 hours,
+/// like Bitcoin's rolling minimum fee)
+pub const EVICTION_FLOOR_HALF_LIFE_SECS: u64 = 12 * 60 * 60;
+
 /// Maximum number of ancestor transactions (Bitcoin uses 25)
 pub const MAX_ANCESTORS: usize = 25;
 
 /// Maximum number of descendant transactions (Bitcoin uses 25)
 pub const MAX_DESCENDANTS: usize = 25;
 
+/// Maximum number of transactions submitted together as a package
+pub const MAX_PACKAGE_COUNT: usize = 25;
+
 /// Maximum total size of ancestor transactions in bytes
 pub const MAX_ANCESTOR_SIZE: usize = 101_000;
 
 /// Maximum total size of descendant transactions in bytes
 pub const MAX_DESCENDANT_SIZE: usize = 101_000;
 
+/// Age after which unconfirmed transactions are dropped (2 weeks, like Bitcoin)
+pub const MEMPOOL_EXPIRY_SECS: u64 = 14 * 24 * 60 * 60;
+
+/// Most transactions one expiry pass removes, so a backlog of stale
+/// transactions is worked off over several calls instead of one long stall
+pub const DEFAULT_MAX_EXPIRED_PER_CALL: usize = 1_000;
+
+/// Replaced transactions remembered so their successors can be looked up
+pub const MAX_REPLACEMENT_RECORDS: usize = 10_000;
+
+/// Largest fee-rate discount for transactions that shrink the UTXO set
+pub const MAX_CONSOLIDATION_DISCOUNT_PERCENT: u64 = 90;
+
+/// Weight kept free for the header and coinbase when assembling a block
+pub const BLOCK_WEIGHT_RESERVED: usize = 4_000;
+
+/// Upper bound on knapsack table cells; larger pools are solved at a coarser
+/// weight granularity
+const KNAPSACK_MAX_CELLS: usize = 20_000_000;
+
 // =============================================================================
 // Error Types
 // =============================================================================
@@ -56,6 +103,10 @@ pub enum MempoolError {
     RbfNotSignaled,
     #[error("Insufficient fee for RBF: need {0}, got {1}")]
     InsufficientRbfFee(u64, u64),
+    #[error("Insufficient fee rate for RBF: need more than {0}, got {1}")]
+    InsufficientRbfFeeRate(u64, u64),
+    #[error("Replacement spends conflicting transaction {0}")]
+    ReplacementSpendsConflict(String),
     #[error("Wrong chain ID: expected {0}, got {1}")]
     WrongChainId(u32, u32),
     #[error("Mempool full")]
@@ -70,12 +121,46 @@ pub enum MempoolError {
     DescendantPackageTooLarge(usize, usize),
     #[error("Mempool size limit exceeded: {0} bytes (max: {1})")]
     MempoolSizeExceeded(usize, usize),
+    #[error("Fee rate below mempool minimum: need {0}, got {1}")]
+    FeeRateBelowMinimum(u64, u64),
+    #[error("Non-standard transaction: {0}")]
+    NonStandard(#[from] PolicyError),
+    #[error("Fee mismatch: claimed {0}, pays {1}")]
+    FeeMismatch(u64, u64),
+    #[error("Invalid package: {0}")]
+    InvalidPackage(String),
+    #[error("Rejected by mempool policy: {0}")]
+    PolicyRejected(String),
+    #[error("Spends immature coinbase {0} ({1} more block(s) needed)")]
+    ImmatureCoinbase(String, u64),
+}
+
+// =============================================================================
+// Block Assembly
+// =============================================================================
+
+/// How transactions are chosen when filling a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockAssembly {
+    /// Take transactions by fee rate until the block is full (fast)
+    #[default]
+    Greedy,
+    /// Maximize total fees under the weight limit (0/1 knapsack)
+    Knapsack,
 }
 
 // =============================================================================
 // Mempool Entry
 // =============================================================================
 
+/// A transaction pushed out of the pool by a conflicting replacement (RBF)
+#[derive(Debug, Clone)]
+pub struct ReplacedTransaction {
+    pub tx: Transaction,
+    /// ID of the transaction that replaced it
+    pub replaced_by: String,
+}
+
 /// Entry in the mempool with metadata
 #[derive(Debug, Clone)]
 pub struct MempoolEntry {
@@ -87,6 +172,12 @@ pub struct MempoolEntry {
     pub fee_rate: u64,
     /// Ancestor count (for CPFP)
     pub ancestor_count: u32,
+    /// Fee rate of the transaction together with its in-pool ancestors
+    /// (package fee / package size)
+    pub ancestor_fee_rate: u64,
+    /// Fee rate the entry is ranked by: `fee_rate`, raised by the
+    /// consolidation discount while it applies
+    pub priority_rate: u64,
 }
 
 impl MempoolEntry {
@@ -97,6 +188,8 @@ impl MempoolEntry {
             added_time,
             fee_rate,
             ancestor_count: 0,
+            ancestor_fee_rate: fee_rate,
+            priority_rate: fee_rate,
         }
     }
 }
@@ -106,22 +199,70 @@ impl MempoolEntry {
 // =============================================================================
 
 /// Memory pool for pending transactions with RBF support
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
 pub struct Mempool {
     /// Transactions indexed by ID
     entries: HashMap<String, MempoolEntry>,
-    /// Transaction IDs ordered by fee rate (highest first for mining)
+    /// Transaction IDs ordered by priority rate (highest first for mining)
     by_fee: Vec<String>,
     /// Transaction IDs in order of arrival
     by_time: Vec<String>,
     /// Maximum pool size
     max_size: usize,
+    /// Maximum pool size in bytes
+    max_bytes: usize,
     /// Chain ID for validation
     chain_id: u32,
     /// Current block height (for locktime checks)
     current_height: u64,
     /// Current block time (for locktime checks)
     current_time: u64,
+    /// Full-RBF: allow replacing any unconfirmed tx, signaling or not
+    full_rbf: bool,
+    /// Percent by which a replacement must out-pay everything it evicts
+    rbf_fee_bump_percent: u64,
+    /// Fee rate needed to beat the best package evicted for space
+    eviction_floor: u64,
+    /// When `eviction_floor` was last raised; it halves every
+    /// `EVICTION_FLOOR_HALF_LIFE_SECS` after that
+    eviction_floor_time: u64,
+    /// Transactions spending outputs we haven't seen yet, by ID
+    orphans: HashMap<String, MempoolEntry>,
+    /// Orphan IDs in arrival order (oldest first)
+    orphan_order: VecDeque<String>,
+    /// Total estimated size of the orphan pool in bytes
+    orphan_bytes: usize,
+    /// Memory budget for orphan transactions in bytes
+    max_orphan_bytes: usize,
+    /// Time source for entry timestamps and expiry
+    clock: SharedClock,
+    /// Most transactions removed by one expiry pass
+    max_expired_per_call: usize,
+    /// Transactions replaced via RBF, by ID
+    replaced: HashMap<String, ReplacedTransaction>,
+    /// IDs of the transactions each replacement evicted
+    replaces: HashMap<String, Vec<String>>,
+    /// Replaced IDs, oldest record first
+    replaced_order: VecDeque<String>,
+    /// Operator-supplied admission rules, checked in order
+    policies: Vec<SharedPolicy>,
+    /// Fee-rate discount (percent) for transactions that consolidate
+    /// outputs (0 disables it)
+    consolidation_discount_percent: u64,
+    /// UTXO set size above which the consolidation discount applies
+    max_utxo_set_size: usize,
+    /// Whether the UTXO set was above `max_utxo_set_size` when the pool
+    /// last saw the chain
+    utxo_pressure: bool,
+    /// Entries removed while a package is being staged, kept so a failed
+    /// package can be rolled back
+    removed_log: Option<Vec<MempoolEntry>>,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Mempool {
@@ -132,22 +273,231 @@ impl Mempool {
             by_fee: Vec::new(),
             by_time: Vec::new(),
             max_size: DEFAULT_MEMPOOL_SIZE,
+            max_bytes: MAX_MEMPOOL_BYTES,
             chain_id: DEFAULT_CHAIN_ID,
             current_height: 0,
             current_time: 0,
+            full_rbf: false,
+            rbf_fee_bump_percent: MIN_RBF_FEE_BUMP_PERCENT,
+            eviction_floor: 0,
+            eviction_floor_time: 0,
+            orphans: HashMap::new(),
+            orphan_order: VecDeque::new(),
+            orphan_bytes: 0,
+            max_orphan_bytes: DEFAULT_MAX_ORPHAN_TX_MB as usize * BYTES_PER_MB,
+            clock: system_clock(),
+            max_expired_per_call: DEFAULT_MAX_EXPIRED_PER_CALL,
+            replaced: HashMap::new(),
+            replaces: HashMap::new(),
+            replaced_order: VecDeque::new(),
+            policies: Vec::new(),
+            consolidation_discount_percent: 0,
+            max_utxo_set_size: 0,
+            utxo_pressure: true,
+            removed_log: None,
         }
     }
 
     /// Create a mempool with custom settings
-    pub fn with_config(max_size: usize, chain_id: u32) -> Self {
+    pub fn with_config(max_size: usize, chain_id: u32, rbf_fee_bump_percent: u64) -> Self {
         Self {
             entries: HashMap::new(),
             by_fee: Vec::new(),
             by_time: Vec::new(),
             max_size,
+            max_bytes: MAX_MEMPOOL_BYTES,
             chain_id,
             current_height: 0,
             current_time: 0,
+            full_rbf: false,
+            rbf_fee_bump_percent,
+            eviction_floor: 0,
+            eviction_floor_time: 0,
+            orphans: HashMap::new(),
+            orphan_order: VecDeque::new(),
+            orphan_bytes: 0,
+            max_orphan_bytes: DEFAULT_MAX_ORPHAN_TX_MB as usize * BYTES_PER_MB,
+            clock: system_clock(),
+            max_expired_per_call: DEFAULT_MAX_EXPIRED_PER_CALL,
+            replaced: HashMap::new(),
+            replaces: HashMap::new(),
+            replaced_order: VecDeque::new(),
+            policies: Vec::new(),
+            consolidation_discount_percent: 0,
+            max_utxo_set_size: 0,
+            utxo_pressure: true,
+            removed_log: None,
+        }
+    }
+
+    /// Set the maximum pool size in bytes
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+    }
+
+    /// Set the most transactions one expiry pass removes (at least one)
+    pub fn set_max_expired_per_call(&mut self, max: usize) {
+        self.max_expired_per_call = max.max(1);
+    }
+
+    /// Set the memory budget for orphan transactions in megabytes
+    pub fn set_max_orphan_mb(&mut self, max_orphan_mb: u64) {
+        self.max_orphan_bytes = (max_orphan_mb as usize).saturating_mul(BYTES_PER_MB);
+        self.trim_orphans(0);
+    }
+
+    /// Switch between opt-in RBF (default) and full-RBF.
+    /// The fee bump is enforced either way.
+    pub fn set_full_rbf(&mut self, full_rbf: bool) {
+        self.full_rbf = full_rbf;
+    }
+
+    /// Whether full-RBF replacement is enabled
+    pub fn full_rbf(&self) -> bool {
+        self.full_rbf
+    }
+
+    /// Set the percent by which a replacement's fee must exceed the
+    /// combined fees of the transactions it replaces
+    pub fn set_rbf_fee_bump_percent(&mut self, percent: u64) {
+        self.rbf_fee_bump_percent = percent;
+    }
+
+    /// Required RBF fee bump, in percent
+    pub fn rbf_fee_bump_percent(&self) -> u64 {
+        self.rbf_fee_bump_percent
+    }
+
+    /// Replace the time source (e.g. with a `MockClock` in tests)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Add a custom admission rule, checked after the built-in ones
+    pub fn add_policy(&mut self, policy: SharedPolicy) {
+        self.policies.push(policy);
+    }
+
+    /// Number of custom admission rules
+    pub fn policy_count(&self) -> usize {
+        self.policies.len()
+    }
+
+    /// Run `tx` past every custom admission rule
+    fn check_policies(
+        &self,
+        tx: &Transaction,
+        blockchain: &Blockchain,
+    ) -> Result<(), MempoolError> {
+        for policy in &self.policies {
+            policy
+                .check(tx, blockchain)
+                .map_err(MempoolError::PolicyRejected)?;
+        }
+        Ok(())
+    }
+
+    /// Rank transactions with more inputs than outputs as paying a fee
+    /// rate `percent` higher than they do, for ordering, eviction and the
+    /// relay floor, to encourage dust consolidation. Their reported fee
+    /// rate is unchanged. Capped at [`MAX_CONSOLIDATION_DISCOUNT_PERCENT`];
+    /// 0 disables it.
+    pub fn set_consolidation_discount_percent(&mut self, percent: u64) {
+        self.consolidation_discount_percent = percent.min(MAX_CONSOLIDATION_DISCOUNT_PERCENT);
+        self.rerank();
+    }
+
+    /// Fee-rate discount for consolidating transactions, in percent
+    pub fn consolidation_discount_percent(&self) -> u64 {
+        self.consolidation_discount_percent
+    }
+
+    /// Only grant the consolidation discount while the UTXO set holds more
+    /// than `max` outputs (0 = always). Takes effect the next time the pool
+    /// sees the chain (see [`Mempool::refresh_priorities`]).
+    pub fn set_max_utxo_set_size(&mut self, max: usize) {
+        self.max_utxo_set_size = max;
+    }
+
+    /// UTXO set size above which the consolidation discount applies
+    pub fn max_utxo_set_size(&self) -> usize {
+        self.max_utxo_set_size
+    }
+
+    /// Whether `tx` gets the consolidation discount: it reduces the UTXO
+    /// count while the set is above `max_utxo_set_size`
+    fn is_discounted(&self, tx: &Transaction) -> bool {
+        self.consolidation_discount_percent > 0
+            && self.utxo_pressure
+            && !tx.is_coinbase
+            && tx.inputs.len() > tx.outputs.len()
+    }
+
+    /// `amount` (a fee or fee rate of `tx`) as ranked: raised by the
+    /// consolidation discount if it applies
+    fn with_discount(&self, amount: u64, tx: &Transaction) -> u64 {
+        if !self.is_discounted(tx) {
+            return amount;
+        }
+        amount.saturating_mul(100) / (100 - self.consolidation_discount_percent)
+    }
+
+    /// Re-rank the pool against the current chain, so the consolidation
+    /// discount follows the UTXO set size rather than the size when each
+    /// transaction was admitted. Call after connecting blocks.
+    pub fn refresh_priorities(&mut self, blockchain: &Blockchain) {
+        let pressure = blockchain.utxo_count() > self.max_utxo_set_size;
+        if pressure != self.utxo_pressure {
+            self.utxo_pressure = pressure;
+            self.rerank();
+        }
+    }
+
+    /// Recompute every entry's priority rate and re-sort `by_fee`
+    fn rerank(&mut self) {
+        let rates: Vec<(String, u64)> = self
+            .entries
+            .iter()
+            .map(|(id, entry)| (id.clone(), self.with_discount(entry.fee_rate, &entry.tx)))
+            .collect();
+        for (id, rate) in rates {
+            if let Some(entry) = self.entries.get_mut(&id) {
+                entry.priority_rate = rate;
+            }
+        }
+        let entries = &self.entries;
+        self.by_fee
+            .sort_by_key(|id| std::cmp::Reverse(entries.get(id).map_or(0, |e| e.priority_rate)));
+    }
+
+    /// Dynamic minimum fee rate required to enter the pool.
+    /// Rises as the pool fills up relative to its byte limit, and while the
+    /// pool stays busy sits at least at the fee rate of packages evicted to
+    /// make room. That eviction floor halves every
+    /// `EVICTION_FLOOR_HALF_LIFE_SECS` after the last eviction.
+    pub fn mempool_min_feerate(&self) -> u64 {
+        let usage_percent = self.total_mempool_size() * 100 / self.max_bytes.max(1);
+        if usage_percent < MIN_FEE_FLOOR_USAGE_PERCENT {
+            return 0;
+        }
+
+        let usage_floor = match usage_percent {
+            50..=74 => 1,
+            75..=89 => 5,
+            90..=99 => 10,
+            _ => 20,
+        };
+        usage_floor.max(self.decayed_eviction_floor())
+    }
+
+    /// The eviction floor after halving once per elapsed half-life
+    fn decayed_eviction_floor(&self) -> u64 {
+        let elapsed = (self.clock.now() as u64).saturating_sub(self.eviction_floor_time);
+        let halvings = elapsed / EVICTION_FLOOR_HALF_LIFE_SECS;
+        if halvings >= u64::BITS as u64 {
+            0
+        } else {
+            self.eviction_floor >> halvings
         }
     }
 
@@ -157,12 +507,161 @@ impl Mempool {
         self.current_time = time;
     }
 
-    /// Add a transaction to the pool (with RBF support)
+    /// Add a transaction to the pool (with RBF support), returning every
+    /// conflicting transaction it replaced
+    ///
+    /// A transaction spending outputs that are neither confirmed nor in the
+    /// pool is rejected but kept as an orphan; it is retried once its
+    /// parent is accepted.
     pub fn add_transaction(
         &mut self,
         tx: Transaction,
         blockchain: &Blockchain,
-    ) -> Result<Option<Transaction>, MempoolError> {
+    ) -> Result<Vec<Transaction>, MempoolError> {
+        let tx_id = tx.id.clone();
+        let replaced = self.accept_transaction(tx, blockchain, true, true)?;
+        self.accept_orphans(&tx_id, blockchain);
+        Ok(replaced)
+    }
+
+    /// Add a package of related transactions (e.g. a parent and the child
+    /// paying for it) as a unit, returning any transactions they replaced.
+    ///
+    /// The package may arrive in any order; it is sorted so parents come
+    /// before children. Either every transaction is accepted or the pool is
+    /// left untouched. The dynamic fee floor applies to the package fee
+    /// rate, so a child can pay for a parent that falls short on its own.
+    pub fn add_package(
+        &mut self,
+        txs: Vec<Transaction>,
+        blockchain: &Blockchain,
+    ) -> Result<Vec<Transaction>, MempoolError> {
+        if txs.is_empty() || txs.len() > MAX_PACKAGE_COUNT {
+            return Err(MempoolError::InvalidPackage(format!(
+                "{} transactions (max: {})",
+                txs.len(),
+                MAX_PACKAGE_COUNT
+            )));
+        }
+        let txs = Self::sort_package(txs)?;
+
+        // Insert in place, logging what gets evicted, so a failure part-way
+        // can be undone
+        let floor = (self.eviction_floor, self.eviction_floor_time);
+        self.removed_log = Some(Vec::new());
+        let result = self.stage_package(&txs, blockchain);
+        let removed = self.removed_log.take().unwrap_or_default();
+
+        let replaced = match result {
+            Ok(replaced) => replaced,
+            Err(e) => {
+                self.roll_back_package(&txs, removed, floor);
+                return Err(e);
+            }
+        };
+        for tx in &txs {
+            self.accept_orphans(&tx.id, blockchain);
+        }
+        Ok(replaced)
+    }
+
+    /// Accept every package member, then check the package fee rate
+    /// against the floor the pool had before the package. Members with
+    /// missing inputs fail the package rather than becoming orphans, whose
+    /// budget trimming couldn't be rolled back.
+    fn stage_package(
+        &mut self,
+        txs: &[Transaction],
+        blockchain: &Blockchain,
+    ) -> Result<Vec<Transaction>, MempoolError> {
+        let min_feerate = self.mempool_min_feerate();
+        let mut replaced = Vec::new();
+        for tx in txs {
+            replaced.extend(self.accept_transaction(tx.clone(), blockchain, false, false)?);
+        }
+
+        let (fees, size) = txs
+            .iter()
+            .filter_map(|tx| self.entries.get(&tx.id))
+            .fold((0u64, 0u64), |(fees, size), entry| {
+                (fees + entry.tx.fee, size + entry.tx.virtual_size() as u64)
+            });
+        let package_feerate = fees / size.max(1);
+        if package_feerate < min_feerate {
+            return Err(MempoolError::FeeRateBelowMinimum(
+                min_feerate,
+                package_feerate,
+            ));
+        }
+        Ok(replaced)
+    }
+
+    /// Undo a partly staged package: drop its members and restore the
+    /// entries, replacement records and fee floor it displaced
+    fn roll_back_package(
+        &mut self,
+        txs: &[Transaction],
+        removed: Vec<MempoolEntry>,
+        (eviction_floor, eviction_floor_time): (u64, u64),
+    ) {
+        for tx in txs {
+            self.remove_transaction(&tx.id);
+            for old_id in self.replaces.get(&tx.id).cloned().unwrap_or_default() {
+                self.forget_replacement(&old_id);
+            }
+        }
+        for entry in removed {
+            if !txs.iter().any(|tx| tx.id == entry.tx.id) {
+                self.insert_entry(entry);
+            }
+        }
+        let entries = &self.entries;
+        self.by_time
+            .sort_by_key(|id| entries.get(id).map_or(0, |e| e.added_time));
+        self.eviction_floor = eviction_floor;
+        self.eviction_floor_time = eviction_floor_time;
+    }
+
+    /// Order a package so every transaction follows the package members it
+    /// spends from (stable for unrelated transactions)
+    fn sort_package(txs: Vec<Transaction>) -> Result<Vec<Transaction>, MempoolError> {
+        let mut ids = HashSet::new();
+        if let Some(dup) = txs.iter().find(|tx| !ids.insert(tx.id.as_str())) {
+            return Err(MempoolError::InvalidPackage(format!(
+                "duplicate transaction {}",
+                dup.id
+            )));
+        }
+
+        let mut pending = txs;
+        let mut sorted: Vec<Transaction> = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let waiting: HashSet<String> = pending.iter().map(|tx| tx.id.clone()).collect();
+            let (ready, blocked): (Vec<Transaction>, Vec<Transaction>) =
+                pending.into_iter().partition(|tx| {
+                    tx.inputs
+                        .iter()
+                        .all(|input| !waiting.contains(&input.tx_id))
+                });
+            if ready.is_empty() {
+                return Err(MempoolError::InvalidPackage("dependency cycle".to_string()));
+            }
+            sorted.extend(ready);
+            pending = blocked;
+        }
+        Ok(sorted)
+    }
+
+    /// Validate and insert a single transaction, checking its own fee rate
+    /// against the dynamic floor when `check_floor` is set and keeping it as
+    /// an orphan if its parents are unknown when `keep_orphan` is set
+    fn accept_transaction(
+        &mut self,
+        mut tx: Transaction,
+        blockchain: &Blockchain,
+        check_floor: bool,
+        keep_orphan: bool,
+    ) -> Result<Vec<Transaction>, MempoolError> {
         // Check for duplicate
         if self.entries.contains_key(&tx.id) {
             return Err(MempoolError::DuplicateTransaction);
@@ -187,87 +686,519 @@ impl Mempool {
             return Err(MempoolError::NotFinal(tx.locktime));
         }
 
-        // Check UTXO availability and look for conflicts
-        let mut conflicting_tx: Option<Transaction> = None;
+        // Coinbase outputs can't be spent until they mature
+        if let Err(BlockchainError::CoinbaseNotMature(tx_id, blocks)) =
+            blockchain.check_coinbase_maturity(&tx)
+        {
+            return Err(MempoolError::ImmatureCoinbase(tx_id, blocks));
+        }
+
+        // Relay policy (stricter than consensus)
+        tx.is_standard(&blockchain.params, self.current_height, self.current_time)?;
+        self.check_policies(&tx, blockchain)?;
+
+        // Check UTXO availability (in the chain or from an unconfirmed tx in
+        // the pool); transactions with missing parents wait as orphans
+        let missing_inputs = !tx.is_coinbase
+            && tx
+                .inputs
+                .iter()
+                .any(|input| self.input_value(input, blockchain).is_none());
+        if missing_inputs {
+            if keep_orphan {
+                self.add_orphan(tx);
+            }
+            return Err(MempoolError::InvalidTransaction(
+                "Input UTXO not found".to_string(),
+            ));
+        }
+
+        // The fee is recomputed from the inputs, never trusted: an unset
+        // (zero) fee is filled in, a wrong one is rejected
+        let actual_fee = self
+            .compute_fee(&tx, blockchain)
+            .ok_or_else(|| MempoolError::InvalidTransaction("Outputs exceed inputs".to_string()))?;
+        if tx.fee != 0 && tx.fee != actual_fee {
+            return Err(MempoolError::FeeMismatch(tx.fee, actual_fee));
+        }
+        tx.fee = actual_fee;
+
+        // Check against the dynamic fee floor
+        self.refresh_priorities(blockchain);
+        let min_feerate = self.mempool_min_feerate();
+        let fee_rate = self.with_discount(tx.fee_rate(), &tx);
+        if check_floor && fee_rate < min_feerate {
+            return Err(MempoolError::FeeRateBelowMinimum(min_feerate, fee_rate));
+        }
+
+        // Keep in-pool packages small, so the traversals below stay cheap
+        self.check_package_limits(&tx)?;
+
+        // Look for conflicts
+        let mut conflicts: Vec<Transaction> = Vec::new();
 
         for input in &tx.inputs {
             if !tx.is_coinbase {
-                // Check if UTXO exists in blockchain
-                if blockchain
-                    .find_utxo(&input.tx_id, input.output_index)
-                    .is_none()
-                {
-                    // Check if it's from an unconfirmed tx in mempool
-                    let in_mempool = self.entries.values().any(|e| {
-                        e.tx.id == input.tx_id && e.tx.outputs.len() > input.output_index as usize
-                    });
-
-                    if !in_mempool {
-                        return Err(MempoolError::InvalidTransaction(
-                            "Input UTXO not found".to_string(),
-                        ));
-                    }
-                }
-
                 // Check for conflicts in mempool (same input being spent)
                 if let Some(existing) = self.find_conflicting_tx(&input.tx_id, input.output_index) {
-                    // RBF: check if we can replace
-                    if existing.signals_rbf() {
-                        // Must pay higher fee
-                        let min_fee =
-                            existing.fee + (existing.fee * MIN_RBF_FEE_BUMP_PERCENT / 100);
-                        if tx.fee < min_fee {
-                            return Err(MempoolError::InsufficientRbfFee(min_fee, tx.fee));
-                        }
-                        conflicting_tx = Some(existing.clone());
-                    } else {
+                    // RBF: check if we can replace (any tx under full-RBF)
+                    if !self.full_rbf && !existing.signals_rbf() {
                         return Err(MempoolError::RbfNotSignaled);
                     }
+                    if conflicts.iter().all(|c| c.id != existing.id) {
+                        conflicts.push(existing.clone());
+                    }
                 }
             }
         }
 
-        // Remove conflicting transaction if RBF
-        let replaced = if let Some(ref conflict) = conflicting_tx {
-            self.remove_transaction(&conflict.id);
-            Some(conflict.clone())
-        } else {
-            None
-        };
+        // Replacing a transaction evicts its descendants too, so the
+        // replacement must out-pay all of them together
+        let mut evicted: HashSet<String> = HashSet::new();
+        for conflict in &conflicts {
+            evicted.insert(conflict.id.clone());
+            evicted.extend(self.collect_descendants(&conflict.id, MAX_DESCENDANTS)?);
+        }
+        // Its inputs would vanish with the transactions it evicts
+        if let Some(input) = tx.inputs.iter().find(|i| evicted.contains(&i.tx_id)) {
+            return Err(MempoolError::ReplacementSpendsConflict(input.tx_id.clone()));
+        }
+        if !evicted.is_empty() {
+            let replaced_fees = evicted
+                .iter()
+                .filter_map(|id| self.entries.get(id))
+                .fold(0u64, |total, entry| total.saturating_add(entry.tx.fee));
+            let min_fee = replaced_fees
+                .saturating_add(replaced_fees.saturating_mul(self.rbf_fee_bump_percent) / 100);
+            if tx.fee < min_fee {
+                return Err(MempoolError::InsufficientRbfFee(min_fee, tx.fee));
+            }
+
+            // It must also be a better package to mine than anything it
+            // replaces, measured like eviction: with in-pool ancestors
+            let tx_rate = self.ancestor_priority_rate(&tx);
+            let replaced_rate = conflicts
+                .iter()
+                .map(|conflict| self.ancestor_priority_rate(conflict))
+                .max()
+                .unwrap_or(0);
+            if tx_rate <= replaced_rate {
+                return Err(MempoolError::InsufficientRbfFeeRate(replaced_rate, tx_rate));
+            }
+        }
+
+        // Make room under the byte limit before touching the pool
+        let evictions = self.plan_byte_evictions(&tx, evicted.clone())?;
+
+        // Remove the replaced transactions and their descendants
+        for id in &evicted {
+            self.remove_transaction(id);
+        }
 
-        // Evict low-fee transactions if at capacity
+        for (package, fee_rate) in evictions {
+            self.remove_transactions(&package);
+            self.eviction_floor = self
+                .decayed_eviction_floor()
+                .max(fee_rate + INCREMENTAL_RELAY_FEE_RATE);
+            self.eviction_floor_time = self.clock.now() as u64;
+        }
+
+        // Evict low-fee transactions if at capacity, by ancestor fee rate so
+        // a cheap parent stays while its child pays for it; the newcomer's
+        // own ancestors are never candidates
+        let ancestors = self
+            .collect_ancestors(&tx, MAX_ANCESTORS)
+            .unwrap_or_default();
+        let tx_ancestor_fee_rate = self.ancestor_priority_rate(&tx);
         while self.entries.len() >= self.max_size {
-            if let Some(lowest_id) = self.by_fee.last().cloned() {
-                if let Some(lowest) = self.entries.get(&lowest_id) {
-                    if lowest.tx.fee_rate() < tx.fee_rate() {
-                        self.remove_transaction(&lowest_id);
-                    } else {
-                        return Err(MempoolError::MempoolFull);
-                    }
-                }
-            } else {
+            let Some((lowest_id, lowest_rate)) = self.lowest_ancestor_feerate_leaf(&ancestors)
+            else {
                 break;
+            };
+            if lowest_rate >= tx_ancestor_fee_rate {
+                return Err(MempoolError::MempoolFull);
             }
+            self.remove_transaction(&lowest_id);
         }
 
         // Add transaction
+        let added_time = self.clock.now() as u64;
         let tx_id = tx.id.clone();
-        let added_time = chrono::Utc::now().timestamp() as u64;
-        let entry = MempoolEntry::new(tx, added_time);
+        self.insert_entry(MempoolEntry::new(tx, added_time));
+
+        for conflict in &conflicts {
+            self.record_replacement(conflict.clone(), &tx_id);
+        }
+        Ok(conflicts)
+    }
+
+    /// Remember that `old` was replaced by `new_id`, dropping the oldest
+    /// records beyond `MAX_REPLACEMENT_RECORDS`
+    fn record_replacement(&mut self, old: Transaction, new_id: &str) {
+        let old_id = old.id.clone();
+        self.forget_replacement(&old_id);
+
+        self.replaces
+            .entry(new_id.to_string())
+            .or_default()
+            .push(old_id.clone());
+        self.replaced_order.push_back(old_id.clone());
+        self.replaced.insert(
+            old_id,
+            ReplacedTransaction {
+                tx: old,
+                replaced_by: new_id.to_string(),
+            },
+        );
+
+        while self.replaced_order.len() > MAX_REPLACEMENT_RECORDS {
+            if let Some(id) = self.replaced_order.front().cloned() {
+                self.forget_replacement(&id);
+            }
+        }
+    }
+
+    /// Drop the replacement record of `old_id`, if any
+    fn forget_replacement(&mut self, old_id: &str) {
+        let Some(record) = self.replaced.remove(old_id) else {
+            return;
+        };
+        self.replaced_order.retain(|id| id != old_id);
+        if let Some(evicted) = self.replaces.get_mut(&record.replaced_by) {
+            evicted.retain(|id| id != old_id);
+            if evicted.is_empty() {
+                self.replaces.remove(&record.replaced_by);
+            }
+        }
+    }
+
+    /// The record of a transaction that was replaced, with its successor
+    pub fn get_replaced(&self, tx_id: &str) -> Option<&ReplacedTransaction> {
+        self.replaced.get(tx_id)
+    }
+
+    /// IDs of the transactions `tx_id` replaced
+    pub fn replaces(&self, tx_id: &str) -> &[String] {
+        self.replaces
+            .get(tx_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Amount of an outpoint, unspent in the chain or created by a pooled
+    /// transaction
+    fn input_value(&self, input: &TransactionInput, blockchain: &Blockchain) -> Option<u64> {
+        match blockchain.find_utxo(&input.tx_id, input.output_index) {
+            Some(utxo) => Some(utxo.output.amount),
+            None => self
+                .entries
+                .get(&input.tx_id)?
+                .tx
+                .outputs
+                .get(input.output_index as usize)
+                .map(|output| output.amount),
+        }
+    }
+
+    /// Fee a transaction pays, resolving inputs against the chain and the
+    /// pool (see [`Blockchain::compute_fee`] for confirmed inputs only)
+    fn compute_fee(&self, tx: &Transaction, blockchain: &Blockchain) -> Option<u64> {
+        let mut input_total = 0u64;
+        for input in &tx.inputs {
+            input_total = input_total.checked_add(self.input_value(input, blockchain)?)?;
+        }
+        input_total.checked_sub(tx.total_output())
+    }
+
+    // =========================================================================
+    // Orphan Transactions
+    // =========================================================================
+
+    /// Keep a transaction whose parents are unknown, evicting expired and
+    /// then the oldest orphans to stay within the orphan memory budget.
+    ///
+    /// Only correctly signed, standard-sized transactions for our chain are
+    /// kept, so junk can't push out orphans that will connect.
+    pub fn add_orphan(&mut self, tx: Transaction) -> bool {
+        let size = tx.estimated_size();
+        if size > self.max_orphan_bytes
+            || size > MAX_STANDARD_TX_SIZE
+            || tx.chain_id != self.chain_id
+            || self.orphans.contains_key(&tx.id)
+            || self.entries.contains_key(&tx.id)
+            || !matches!(tx.is_valid(), Ok(true))
+        {
+            return false;
+        }
+
+        self.expire_orphans();
+        self.trim_orphans(size);
+        let added_time = self.clock.now() as u64;
+        self.orphan_bytes += size;
+        self.orphan_order.push_back(tx.id.clone());
+        self.orphans
+            .insert(tx.id.clone(), MempoolEntry::new(tx, added_time));
+        true
+    }
+
+    /// Evict the oldest orphans until `incoming` more bytes fit the budget
+    fn trim_orphans(&mut self, incoming: usize) {
+        while self.orphan_bytes + incoming > self.max_orphan_bytes {
+            match self.orphan_order.front().cloned() {
+                Some(oldest) => {
+                    self.remove_orphan(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Drop orphans older than `ORPHAN_TX_EXPIRE_SECS`, returning how many
+    pub fn expire_orphans(&mut self) -> usize {
+        let now = self.clock.now() as u64;
+        let expired: Vec<String> = self
+            .orphan_order
+            .iter()
+            .take_while(|id| {
+                now.saturating_sub(self.orphans[*id].added_time) > ORPHAN_TX_EXPIRE_SECS
+            })
+            .cloned()
+            .collect();
+        for id in &expired {
+            self.remove_orphan(id);
+        }
+        expired.len()
+    }
+
+    /// Remove an orphan transaction
+    fn remove_orphan(&mut self, tx_id: &str) -> Option<Transaction> {
+        let entry = self.orphans.remove(tx_id)?;
+        self.orphan_bytes -= entry.tx.estimated_size();
+        self.orphan_order.retain(|id| id != tx_id);
+        Some(entry.tx)
+    }
+
+    /// Retry orphans whose parent `parent_id` (or its accepted descendants)
+    /// just entered the pool
+    fn accept_orphans(&mut self, parent_id: &str, blockchain: &Blockchain) {
+        let mut worklist = vec![parent_id.to_string()];
+
+        while let Some(parent) = worklist.pop() {
+            let children: Vec<String> = self
+                .orphan_order
+                .iter()
+                .filter(|id| {
+                    self.orphans[*id]
+                        .tx
+                        .inputs
+                        .iter()
+                        .any(|input| input.tx_id == parent)
+                })
+                .cloned()
+                .collect();
+
+            for child_id in children {
+                if let Some(child) = self.remove_orphan(&child_id) {
+                    if self
+                        .accept_transaction(child, blockchain, true, true)
+                        .is_ok()
+                    {
+                        worklist.push(child_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether a transaction is waiting in the orphan pool
+    pub fn contains_orphan(&self, tx_id: &str) -> bool {
+        self.orphans.contains_key(tx_id)
+    }
+
+    /// Number of orphan transactions
+    pub fn orphan_count(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Total estimated size of the orphan pool in bytes
+    pub fn orphan_bytes(&self) -> usize {
+        self.orphan_bytes
+    }
+
+    /// Choose the packages to evict so `tx` fits under the byte limit.
+    ///
+    /// Packages (an entry plus its descendants) go lowest fee rate first; if
+    /// one pays at least as much as `tx`, the newcomer is rejected instead.
+    /// `freed` holds entries already leaving the pool (e.g. RBF conflicts).
+    /// The newcomer's own in-pool ancestors are never chosen.
+    fn plan_byte_evictions(
+        &self,
+        tx: &Transaction,
+        mut freed: HashSet<String>,
+    ) -> Result<Vec<(Vec<String>, u64)>, MempoolError> {
+        let tx_size = tx.estimated_size();
+        if tx_size > self.max_bytes {
+            return Err(MempoolError::MempoolSizeExceeded(tx_size, self.max_bytes));
+        }
+
+        let mut size = self.total_mempool_size() - self.total_entry_size(&freed);
+        // The newcomer is judged with its in-pool ancestors, as in count-based
+        // eviction and replacement
+        let tx_fee_rate = self.ancestor_priority_rate(tx);
+        let mut evictions = Vec::new();
+
+        // The newcomer's in-pool parents must stay, or it would be admitted
+        // with missing inputs
+        let mut excluded = freed.clone();
+        excluded.extend(self.collect_ancestors(tx, MAX_ANCESTORS)?);
+
+        while size + tx_size > self.max_bytes {
+            let Some((package, fee_rate)) = self.lowest_feerate_package(&excluded) else {
+                break;
+            };
+            if fee_rate >= tx_fee_rate {
+                return Err(MempoolError::MempoolFull);
+            }
+
+            size -= package
+                .iter()
+                .filter_map(|id| self.entries.get(id))
+                .map(|entry| entry.tx.estimated_size())
+                .sum::<usize>();
+            freed.extend(package.iter().cloned());
+            excluded.extend(package.iter().cloned());
+            evictions.push((package, fee_rate));
+        }
+
+        Ok(evictions)
+    }
+
+    /// The entry whose descendant package has the lowest fee rate (fee per
+    /// virtual byte, discounted fees raised), with that rate, ignoring
+    /// entries in `excluded`
+    fn lowest_feerate_package(&self, excluded: &HashSet<String>) -> Option<(Vec<String>, u64)> {
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (id, entry) in &self.entries {
+            for input in &entry.tx.inputs {
+                if self.entries.contains_key(&input.tx_id) {
+                    children
+                        .entry(input.tx_id.as_str())
+                        .or_default()
+                        .push(id.as_str());
+                }
+            }
+        }
+
+        let mut lowest: Option<(Vec<String>, u64)> = None;
+        for id in self.entries.keys().filter(|id| !excluded.contains(*id)) {
+            let mut package = HashSet::new();
+            let mut worklist = vec![id.as_str()];
+            while let Some(current) = worklist.pop() {
+                if !excluded.contains(current) && package.insert(current) {
+                    worklist.extend(children.get(current).into_iter().flatten());
+                }
+            }
+
+            let (fees, size) = package.iter().filter_map(|id| self.entries.get(*id)).fold(
+                (0u64, 0u64),
+                |(fees, size), entry| {
+                    let fee = self.with_discount(entry.tx.fee, &entry.tx);
+                    (fees + fee, size + entry.tx.virtual_size() as u64)
+                },
+            );
+            let fee_rate = fees / size.max(1);
+
+            if lowest.as_ref().is_none_or(|(_, rate)| fee_rate < *rate) {
+                let package = package.into_iter().map(str::to_string).collect();
+                lowest = Some((package, fee_rate));
+            }
+        }
+
+        lowest
+    }
+
+    /// The entry without in-pool children that has the lowest ancestor
+    /// priority rate, ignoring entries in `excluded`. Only leaves are
+    /// candidates, so eviction never strands a child that was paying for
+    /// its parent.
+    fn lowest_ancestor_feerate_leaf(&self, excluded: &HashSet<String>) -> Option<(String, u64)> {
+        let parents: HashSet<&str> = self
+            .entries
+            .values()
+            .flat_map(|entry| entry.tx.inputs.iter().map(|input| input.tx_id.as_str()))
+            .collect();
+
+        self.entries
+            .iter()
+            .filter(|(id, _)| !parents.contains(id.as_str()) && !excluded.contains(*id))
+            .map(|(id, entry)| (id.clone(), self.ancestor_priority_rate(&entry.tx)))
+            .min_by_key(|(_, rate)| *rate)
+    }
 
-        // Insert into fee-sorted list (binary search for position)
-        let fee_rate = entry.fee_rate;
+    /// Fee rate of `tx` together with its in-pool ancestors, with each
+    /// fee raised by the consolidation discount where it applies
+    fn ancestor_priority_rate(&self, tx: &Transaction) -> u64 {
+        let ancestors = self.collect_ancestors(tx, usize::MAX).unwrap_or_default();
+        let (fees, size) = ancestors.iter().filter_map(|id| self.entries.get(id)).fold(
+            (self.with_discount(tx.fee, tx), tx.virtual_size() as u64),
+            |(fees, size), entry| {
+                let fee = self.with_discount(entry.tx.fee, &entry.tx);
+                (fees + fee, size + entry.tx.virtual_size() as u64)
+            },
+        );
+        fees / size.max(1)
+    }
+
+    /// Number of in-pool ancestors of `tx` and the fee rate (fee per virtual
+    /// byte) of `tx` together with them
+    fn ancestor_package(&self, tx: &Transaction) -> (u32, u64) {
+        let ancestors = self.collect_ancestors(tx, usize::MAX).unwrap_or_default();
+        let (fees, size) = ancestors
+            .iter()
+            .filter_map(|id| self.entries.get(id))
+            .fold((tx.fee, tx.virtual_size() as u64), |(fees, size), entry| {
+                (fees + entry.tx.fee, size + entry.tx.virtual_size() as u64)
+            });
+        (ancestors.len() as u32, fees / size.max(1))
+    }
+
+    /// Recompute the ancestor stats of everything descending from `tx_id`
+    /// after that transaction entered or left the pool
+    fn refresh_descendants(&mut self, tx_id: &str) {
+        let descendants = self
+            .collect_descendants(tx_id, usize::MAX)
+            .unwrap_or_default();
+        for id in descendants {
+            let (count, fee_rate) = self.ancestor_package(&self.entries[&id].tx);
+            if let Some(entry) = self.entries.get_mut(&id) {
+                entry.ancestor_count = count;
+                entry.ancestor_fee_rate = fee_rate;
+            }
+        }
+    }
+
+    /// Index an accepted entry in the fee and arrival orderings
+    fn insert_entry(&mut self, mut entry: MempoolEntry) {
+        let tx_id = entry.tx.id.clone();
+        let (ancestor_count, ancestor_fee_rate) = self.ancestor_package(&entry.tx);
+        entry.ancestor_count = ancestor_count;
+        entry.ancestor_fee_rate = ancestor_fee_rate;
+        entry.priority_rate = self.with_discount(entry.fee_rate, &entry.tx);
+
+        // Insert into priority-sorted list (binary search for position)
+        let priority_rate = entry.priority_rate;
         let pos = self
             .by_fee
             .iter()
-            .position(|id| self.entries.get(id).map(|e| e.fee_rate).unwrap_or(0) < fee_rate)
+            .position(|id| {
+                self.entries.get(id).map(|e| e.priority_rate).unwrap_or(0) < priority_rate
+            })
             .unwrap_or(self.by_fee.len());
         self.by_fee.insert(pos, tx_id.clone());
 
         self.by_time.push(tx_id.clone());
-        self.entries.insert(tx_id, entry);
-
-        Ok(replaced)
+        self.entries.insert(tx_id.clone(), entry);
+        self.refresh_descendants(&tx_id);
     }
 
     /// Find a transaction that conflicts (spends same input)
@@ -287,6 +1218,10 @@ impl Mempool {
         if let Some(entry) = self.entries.remove(tx_id) {
             self.by_fee.retain(|id| id != tx_id);
             self.by_time.retain(|id| id != tx_id);
+            self.refresh_descendants(tx_id);
+            if let Some(log) = self.removed_log.as_mut() {
+                log.push(entry.clone());
+            }
             Some(entry.tx)
         } else {
             None
@@ -302,9 +1237,206 @@ impl Mempool {
             .collect()
     }
 
-    /// Get transactions for mining (FIFO order, up to limit) - legacy behavior
-    pub fn get_transactions_fifo(&self, limit: usize) -> Vec<Transaction> {
-        self.by_time
+    /// Entries whose fee rate lies in `min..=max`, highest fee rate first
+    pub fn transactions_in_feerate_range(&self, min: u64, max: u64) -> Vec<&MempoolEntry> {
+        // `by_fee` is sorted by priority, which the consolidation discount
+        // can set apart from the fee rate, so filter and re-sort
+        let mut entries: Vec<&MempoolEntry> = self
+            .by_fee
+            .iter()
+            .filter_map(|id| self.entries.get(id))
+            .filter(|e| (min..=max).contains(&e.fee_rate))
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.fee_rate));
+        entries
+    }
+
+    /// Select transactions for a block whose transactions may use at most
+    /// `max_weight`. Each transaction is mined together with its in-pool
+    /// ancestors, and results are in topological order (parents first).
+    pub fn select_for_block(&self, max_weight: usize, strategy: BlockAssembly) -> Vec<Transaction> {
+        let candidates: Vec<&MempoolEntry> = self
+            .by_fee
+            .iter()
+            .filter_map(|id| self.entries.get(id))
+            .collect();
+        let index: HashMap<&str, usize> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.tx.id.as_str(), i))
+            .collect();
+        let weights: Vec<usize> = candidates
+            .iter()
+            .map(|e| e.tx.serialized_size() * 4)
+            .collect();
+        let fees: Vec<u64> = candidates.iter().map(|e| e.tx.fee).collect();
+
+        // Each candidate's package: its in-pool ancestors, parents first,
+        // followed by the candidate itself
+        let packages: Vec<Vec<usize>> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                let mut package: Vec<usize> = self
+                    .collect_ancestors(&e.tx, usize::MAX)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|id| index.get(id.as_str()).copied())
+                    .collect();
+                package.sort_by_key(|&j| (candidates[j].ancestor_count, j));
+                package.push(i);
+                package
+            })
+            .collect();
+
+        let greedy = Self::select_greedy(&packages, &weights, &fees, max_weight);
+        let selected = match strategy {
+            BlockAssembly::Greedy => greedy,
+            BlockAssembly::Knapsack => {
+                // Overlapping packages are counted in full, which keeps the
+                // union within the weight limit
+                let package_weights: Vec<usize> = packages
+                    .iter()
+                    .map(|p| p.iter().map(|&j| weights[j]).sum())
+                    .collect();
+                let package_fees: Vec<u64> = packages
+                    .iter()
+                    .map(|p| p.iter().map(|&j| fees[j]).sum())
+                    .collect();
+                let optimized = Self::select_knapsack(&package_weights, &package_fees, max_weight);
+                let optimized = Self::package_closure(&packages, &optimized);
+                let total = |picked: &[usize]| picked.iter().map(|&i| fees[i]).sum::<u64>();
+                // Coarse granularity and double-counted package fees can
+                // lose to greedy
+                if total(&optimized) >= total(&greedy) {
+                    optimized
+                } else {
+                    greedy
+                }
+            }
+        };
+
+        selected
+            .into_iter()
+            .map(|i| candidates[i].tx.clone())
+            .collect()
+    }
+
+    /// Ancestor-package selection: repeatedly take the candidate whose
+    /// package (itself plus its ancestors not yet taken) pays the best fee
+    /// rate and still fits. Returns indices parents first.
+    fn select_greedy(
+        packages: &[Vec<usize>],
+        weights: &[usize],
+        fees: &[u64],
+        max_weight: usize,
+    ) -> Vec<usize> {
+        let mut taken = vec![false; weights.len()];
+        let mut skipped = vec![false; weights.len()];
+        let mut used = 0;
+        let mut picked = Vec::new();
+        loop {
+            // (candidate, package fee, package weight, package len)
+            let mut best: Option<(usize, u64, usize, usize)> = None;
+            for (i, package) in packages.iter().enumerate() {
+                if taken[i] || skipped[i] {
+                    continue;
+                }
+                let remaining = package.iter().filter(|&&j| !taken[j]);
+                let (fee, weight, len) = remaining.fold((0, 0, 0), |(f, w, n), &j| {
+                    (f + fees[j], w + weights[j], n + 1)
+                });
+                // The block only fills up, so a package that doesn't fit now
+                // never will
+                if used + weight > max_weight || picked.len() + len > MAX_BLOCK_TXS {
+                    skipped[i] = true;
+                    continue;
+                }
+                let better = best.is_none_or(|(_, best_fee, best_weight, _)| {
+                    fee as u128 * best_weight as u128 > best_fee as u128 * weight as u128
+                });
+                if better {
+                    best = Some((i, fee, weight, len));
+                }
+            }
+
+            let Some((i, _, weight, _)) = best else {
+                break;
+            };
+            used += weight;
+            for &j in &packages[i] {
+                if !taken[j] {
+                    taken[j] = true;
+                    picked.push(j);
+                }
+            }
+        }
+        picked
+    }
+
+    /// Union of the picked packages, parents first, without exceeding
+    /// `MAX_BLOCK_TXS`
+    fn package_closure(packages: &[Vec<usize>], picked: &[usize]) -> Vec<usize> {
+        let mut included = HashSet::new();
+        let mut closure = Vec::new();
+        for &i in picked {
+            let new: Vec<usize> = packages[i]
+                .iter()
+                .copied()
+                .filter(|j| !included.contains(j))
+                .collect();
+            if closure.len() + new.len() > MAX_BLOCK_TXS {
+                continue;
+            }
+            included.extend(new.iter().copied());
+            closure.extend(new);
+        }
+        closure
+    }
+
+    /// 0/1 knapsack over weight, returning picked indices in ascending order
+    fn select_knapsack(weights: &[usize], fees: &[u64], max_weight: usize) -> Vec<usize> {
+        let n = weights.len();
+        if n == 0 {
+            return Vec::new();
+        }
+
+        // Work in weight units so the table stays bounded; rounding item
+        // weights up keeps every solution within the real limit
+        let unit = (max_weight.saturating_mul(n) / KNAPSACK_MAX_CELLS).max(1);
+        let capacity = max_weight / unit;
+        let cost: Vec<usize> = weights.iter().map(|w| w.div_ceil(unit)).collect();
+
+        let mut best = vec![0u64; capacity + 1];
+        let mut take = vec![vec![false; capacity + 1]; n];
+        for i in 0..n {
+            if cost[i] > capacity {
+                continue;
+            }
+            for c in (cost[i]..=capacity).rev() {
+                let with = best[c - cost[i]] + fees[i];
+                if with > best[c] {
+                    best[c] = with;
+                    take[i][c] = true;
+                }
+            }
+        }
+
+        let mut picked = Vec::new();
+        let mut c = capacity;
+        for i in (0..n).rev() {
+            if take[i][c] {
+                picked.push(i);
+                c -= cost[i];
+            }
+        }
+        picked.reverse();
+        picked
+    }
+
+    /// Get transactions for mining (FIFO order, up to limit) - legacy behavior
+    pub fn get_transactions_fifo(&self, limit: usize) -> Vec<Transaction> {
+        self.by_time
             .iter()
             .take(limit)
             .filter_map(|id| self.entries.get(id).map(|e| e.tx.clone()))
@@ -318,24 +1450,41 @@ impl Mempool {
         }
     }
 
-    /// Remove transactions with inputs that are now spent
-    pub fn remove_conflicting(&mut self, blockchain: &Blockchain) {
-        let mut to_remove = Vec::new();
-
-        for (id, entry) in &self.entries {
-            for input in &entry.tx.inputs {
-                if !entry.tx.is_coinbase
-                    && blockchain
-                        .find_utxo(&input.tx_id, input.output_index)
-                        .is_none()
-                {
-                    to_remove.push(id.clone());
-                    break;
-                }
+    /// Remove transactions the chain has confirmed, and ones with an input
+    /// that is neither unspent on the chain nor an output of a pooled
+    /// transaction (double-spent by a block), along with their descendants.
+    /// Returns how many were removed.
+    pub fn remove_conflicting(&mut self, blockchain: &Blockchain) -> usize {
+        self.refresh_priorities(blockchain);
+        let mut removed = 0;
+
+        // Removing a transaction strands its children, so repeat until none
+        // is left to remove
+        loop {
+            let to_remove: Vec<String> = self
+                .entries
+                .iter()
+                .filter(|(id, entry)| {
+                    let tx = &entry.tx;
+                    // Token and contract inputs name a sender, not an outpoint
+                    let spends_coins = !tx.is_coinbase
+                        && !tx.is_token_transaction()
+                        && !tx.is_contract_transaction();
+                    blockchain.tx_exists(id)
+                        || (spends_coins
+                            && tx
+                                .inputs
+                                .iter()
+                                .any(|input| self.input_value(input, blockchain).is_none()))
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            if to_remove.is_empty() {
+                return removed;
             }
+            removed += to_remove.len();
+            self.remove_transactions(&to_remove);
         }
-
-        self.remove_transactions(&to_remove);
     }
 
     /// Get a transaction by ID
@@ -348,11 +1497,59 @@ impl Mempool {
         self.entries.get(id)
     }
 
+    /// Seconds a pooled transaction has been waiting
+    pub fn time_in_pool(&self, id: &str) -> Option<u64> {
+        let entry = self.entries.get(id)?;
+        Some((self.clock.now() as u64).saturating_sub(entry.added_time))
+    }
+
+    /// Pooled transactions whose outputs `id` spends (its direct parents)
+    pub fn depends(&self, id: &str) -> Vec<String> {
+        let Some(entry) = self.entries.get(id) else {
+            return Vec::new();
+        };
+        let mut parents: Vec<String> = entry
+            .tx
+            .inputs
+            .iter()
+            .filter(|input| self.entries.contains_key(&input.tx_id))
+            .map(|input| input.tx_id.clone())
+            .collect();
+        parents.sort();
+        parents.dedup();
+        parents
+    }
+
+    /// Pooled transactions spending outputs of `id` (its direct children)
+    pub fn spent_by(&self, id: &str) -> Vec<String> {
+        let mut children: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.tx.inputs.iter().any(|input| input.tx_id == id))
+            .map(|(child_id, _)| child_id.clone())
+            .collect();
+        children.sort();
+        children
+    }
+
+    /// Where a transaction sits in the mining queue, as `(position, total)`
+    /// with position 1 being the highest fee rate
+    pub fn fee_rank(&self, id: &str) -> Option<(usize, usize)> {
+        let index = self.by_fee.iter().position(|tx_id| tx_id == id)?;
+        Some((index + 1, self.by_fee.len()))
+    }
+
     /// Check if a transaction is in the pool
     pub fn contains(&self, id: &str) -> bool {
         self.entries.contains_key(id)
     }
 
+    /// Whether a transaction is already known, pending here or confirmed on
+    /// `blockchain`, so there's no need to request it
+    pub fn is_known(&self, id: &str, blockchain: &Blockchain) -> bool {
+        self.contains(id) || blockchain.tx_exists(id)
+    }
+
     /// Get the number of pending transactions
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -363,11 +1560,50 @@ impl Mempool {
         self.entries.is_empty()
     }
 
+    /// Drop transactions that have been in the pool longer than `max_age` seconds
+    /// Returns the IDs of the expired transactions
+    ///
+    /// The oldest go first, and among equally old ones the lowest fee rate;
+    /// at most `max_expired_per_call` are removed, the rest wait for the
+    /// next call.
+    pub fn expire_older_than(&mut self, max_age: u64) -> Vec<String> {
+        let now = self.clock.now() as u64;
+        let mut stale: Vec<&MempoolEntry> = self
+            .entries
+            .values()
+            .filter(|e| now.saturating_sub(e.added_time) > max_age)
+            .collect();
+        stale.sort_by_key(|e| (e.added_time, e.fee_rate));
+
+        let expired: Vec<String> = stale
+            .into_iter()
+            .take(self.max_expired_per_call)
+            .map(|e| e.tx.id.clone())
+            .collect();
+
+        self.remove_transactions(&expired);
+        expired
+    }
+
+    /// Drop transactions older than `MEMPOOL_EXPIRY_SECS`, and orphans
+    /// older than `ORPHAN_TX_EXPIRE_SECS`
+    pub fn expire(&mut self) -> Vec<String> {
+        self.expire_orphans();
+        self.expire_older_than(MEMPOOL_EXPIRY_SECS)
+    }
+
     /// Clear all transactions
     pub fn clear(&mut self) {
+        self.eviction_floor = 0;
+        self.orphans.clear();
+        self.orphan_order.clear();
+        self.orphan_bytes = 0;
         self.entries.clear();
         self.by_fee.clear();
         self.by_time.clear();
+        self.replaced.clear();
+        self.replaces.clear();
+        self.replaced_order.clear();
     }
 
     /// Get all transaction IDs (by fee order)
@@ -375,13 +1611,40 @@ impl Mempool {
         self.by_fee.clone()
     }
 
+    /// Outpoints spent by pooled transactions
+    pub fn spent_outpoints(&self) -> HashSet<OutPoint> {
+        self.entries
+            .values()
+            .flat_map(|e| &e.tx.inputs)
+            .map(|input| OutPoint::new(&input.tx_id, input.output_index))
+            .collect()
+    }
+
+    /// Get the total amount paid to an address by pending transactions,
+    /// excluding outputs another pooled transaction already spends
+    pub fn pending_balance(&self, address: &str) -> u64 {
+        let spent = self.spent_outpoints();
+        self.entries
+            .values()
+            .flat_map(|e| {
+                e.tx.outputs
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, output)| (&e.tx.id, index as u32, output))
+            })
+            .filter(|(_, _, o)| o.recipient == address)
+            .filter(|(id, index, _)| !spent.contains(&OutPoint::new(id, *index)))
+            .map(|(_, _, o)| o.amount)
+            .sum()
+    }
+
     /// Get total fees of all transactions
     pub fn total_fees(&self) -> u64 {
         self.entries.values().map(|e| e.tx.fee).sum()
     }
 
     /// Check if a UTXO is spent by any transaction in the mempool
-    fn is_utxo_spent(&self, tx_id: &str, output_index: u32) -> bool {
+    pub fn is_utxo_spent(&self, tx_id: &str, output_index: u32) -> bool {
         for entry in self.entries.values() {
             for input in &entry.tx.inputs {
                 if input.tx_id == tx_id && input.output_index == output_index {
@@ -401,18 +1664,8 @@ impl Mempool {
             tx_count: self.entries.len(),
             total_fees,
             total_size,
-            max_fee_rate: self
-                .by_fee
-                .first()
-                .and_then(|id| self.entries.get(id))
-                .map(|e| e.fee_rate)
-                .unwrap_or(0),
-            min_fee_rate: self
-                .by_fee
-                .last()
-                .and_then(|id| self.entries.get(id))
-                .map(|e| e.fee_rate)
-                .unwrap_or(0),
+            max_fee_rate: self.entries.values().map(|e| e.fee_rate).max().unwrap_or(0),
+            min_fee_rate: self.entries.values().map(|e| e.fee_rate).min().unwrap_or(0),
         }
     }
 
@@ -429,81 +1682,101 @@ impl Mempool {
     pub fn check_mempool_size(&self, new_tx_size: usize) -> Result<(), MempoolError> {
         let current_size = self.total_mempool_size();
         let new_total = current_size + new_tx_size;
-        if new_total > MAX_MEMPOOL_BYTES {
-            return Err(MempoolError::MempoolSizeExceeded(
-                new_total,
-                MAX_MEMPOOL_BYTES,
-            ));
+        if new_total > self.max_bytes {
+            return Err(MempoolError::MempoolSizeExceeded(new_total, self.max_bytes));
         }
         Ok(())
     }
 
     /// Calculate all ancestors of a transaction (transactions this tx depends on)
-    /// Returns (ancestor count, total ancestor size)
-    pub fn calculate_ancestors(&self, tx: &Transaction) -> (usize, usize) {
-        let mut ancestors = HashSet::new();
-        let mut total_size = 0;
-        self.collect_ancestors_recursive(tx, &mut ancestors);
-
-        for ancestor_id in &ancestors {
-            if let Some(entry) = self.entries.get(ancestor_id) {
-                total_size += entry.tx.estimated_size();
-            }
-        }
-
-        (ancestors.len(), total_size)
+    /// Returns (ancestor count, total ancestor size), or `TooManyAncestors`
+    /// as soon as more than `MAX_ANCESTORS` are found
+    pub fn calculate_ancestors(&self, tx: &Transaction) -> Result<(usize, usize), MempoolError> {
+        let ancestors = self.collect_ancestors(tx, MAX_ANCESTORS)?;
+        Ok((ancestors.len(), self.total_entry_size(&ancestors)))
     }
 
-    /// Recursively collect all ancestor transaction IDs
-    fn collect_ancestors_recursive(&self, tx: &Transaction, ancestors: &mut HashSet<String>) {
-        for input in &tx.inputs {
-            // Check if this input spends from a mempool transaction
-            if self.entries.contains_key(&input.tx_id) && !ancestors.contains(&input.tx_id) {
-                ancestors.insert(input.tx_id.clone());
-                // Recursively collect ancestors of the parent
+    /// Collect ancestor transaction IDs with an explicit worklist.
+    ///
+    /// Iterative so a long dependency chain cannot overflow the stack; stops
+    /// once more than `limit` ancestors have been visited.
+    fn collect_ancestors(
+        &self,
+        tx: &Transaction,
+        limit: usize,
+    ) -> Result<HashSet<String>, MempoolError> {
+        let mut ancestors = HashSet::new();
+        let mut worklist = vec![tx];
+
+        while let Some(current) = worklist.pop() {
+            for input in &current.inputs {
+                // Check if this input spends from a mempool transaction
                 if let Some(parent_entry) = self.entries.get(&input.tx_id) {
-                    self.collect_ancestors_recursive(&parent_entry.tx, ancestors);
+                    if ancestors.insert(input.tx_id.clone()) {
+                        if ancestors.len() > limit {
+                            return Err(MempoolError::TooManyAncestors(ancestors.len(), limit));
+                        }
+                        worklist.push(&parent_entry.tx);
+                    }
                 }
             }
         }
+
+        Ok(ancestors)
     }
 
     /// Calculate all descendants of a transaction (transactions that depend on this tx)
-    /// Returns (descendant count, total descendant size)
-    pub fn calculate_descendants(&self, tx_id: &str) -> (usize, usize) {
-        let mut descendants = HashSet::new();
-        let mut total_size = 0;
-        self.collect_descendants_recursive(tx_id, &mut descendants);
+    /// Returns (descendant count, total descendant size), or `TooManyDescendants`
+    /// as soon as more than `MAX_DESCENDANTS` are found
+    pub fn calculate_descendants(&self, tx_id: &str) -> Result<(usize, usize), MempoolError> {
+        let descendants = self.collect_descendants(tx_id, MAX_DESCENDANTS)?;
+        Ok((descendants.len(), self.total_entry_size(&descendants)))
+    }
 
-        for descendant_id in &descendants {
-            if let Some(entry) = self.entries.get(descendant_id) {
-                total_size += entry.tx.estimated_size();
+    /// Collect descendant transaction IDs with an explicit worklist.
+    ///
+    /// Iterative counterpart of `collect_ancestors`, bounded the same way.
+    fn collect_descendants(
+        &self,
+        tx_id: &str,
+        limit: usize,
+    ) -> Result<HashSet<String>, MempoolError> {
+        let mut descendants = HashSet::new();
+        let mut worklist = vec![tx_id.to_string()];
+
+        while let Some(current) = worklist.pop() {
+            for (entry_id, entry) in &self.entries {
+                // Check if this transaction spends from the current one
+                let depends_on_tx = entry.tx.inputs.iter().any(|i| i.tx_id == current);
+                if depends_on_tx && descendants.insert(entry_id.clone()) {
+                    if descendants.len() > limit {
+                        return Err(MempoolError::TooManyDescendants(descendants.len(), limit));
+                    }
+                    worklist.push(entry_id.clone());
+                }
             }
         }
 
-        (descendants.len(), total_size)
+        Ok(descendants)
     }
 
-    /// Recursively collect all descendant transaction IDs
-    fn collect_descendants_recursive(&self, tx_id: &str, descendants: &mut HashSet<String>) {
-        for (entry_id, entry) in &self.entries {
-            // Check if this transaction spends from tx_id
-            let depends_on_tx = entry.tx.inputs.iter().any(|i| i.tx_id == tx_id);
-            if depends_on_tx && !descendants.contains(entry_id) {
-                descendants.insert(entry_id.clone());
-                // Recursively collect descendants
-                self.collect_descendants_recursive(entry_id, descendants);
-            }
-        }
+    /// Total estimated size of the given mempool entries
+    fn total_entry_size(&self, tx_ids: &HashSet<String>) -> usize {
+        tx_ids
+            .iter()
+            .filter_map(|id| self.entries.get(id))
+            .map(|entry| entry.tx.estimated_size())
+            .sum()
     }
 
-    /// Check package limits for a new transaction (ancestor and descendant limits)
+    /// Check package limits for a new transaction (ancestor and descendant
+    /// limits, each counting the transaction itself)
     pub fn check_package_limits(&self, tx: &Transaction) -> Result<(), MempoolError> {
-        // Check ancestor limits
-        let (ancestor_count, ancestor_size) = self.calculate_ancestors(tx);
-        if ancestor_count > MAX_ANCESTORS {
+        // Check ancestor limits (+1 for the new transaction)
+        let (ancestor_count, ancestor_size) = self.calculate_ancestors(tx)?;
+        if ancestor_count + 1 > MAX_ANCESTORS {
             return Err(MempoolError::TooManyAncestors(
-                ancestor_count,
+                ancestor_count + 1,
                 MAX_ANCESTORS,
             ));
         }
@@ -518,7 +1791,7 @@ impl Mempool {
         // would cause the parent to exceed descendant limits
         for input in &tx.inputs {
             if self.entries.contains_key(&input.tx_id) {
-                let (desc_count, desc_size) = self.calculate_descendants(&input.tx_id);
+                let (desc_count, desc_size) = self.calculate_descendants(&input.tx_id)?;
                 // +1 because we're adding a new descendant
                 if desc_count + 1 > MAX_DESCENDANTS {
                     return Err(MempoolError::TooManyDescendants(
@@ -542,8 +1815,12 @@ impl Mempool {
     /// Add a token transaction to the pool (skips UTXO validation)
     ///
     /// Token transactions don't spend UTXOs - they record token operations on-chain.
-    /// This method allows adding them without requiring blockchain reference.
-    pub fn add_token_transaction(&mut self, tx: Transaction) -> Result<(), MempoolError> {
+    /// They still have to pass the custom admission policies.
+    pub fn add_token_transaction(
+        &mut self,
+        tx: Transaction,
+        blockchain: &Blockchain,
+    ) -> Result<(), MempoolError> {
         // Only allow token or contract transactions
         if tx.token_data.is_none() && tx.contract_data.is_none() {
             return Err(MempoolError::InvalidTransaction(
@@ -551,36 +1828,98 @@ impl Mempool {
             ));
         }
 
+        tx.check_operations()
+            .map_err(|e| MempoolError::InvalidTransaction(e.to_string()))?;
+        self.check_policies(&tx, blockchain)?;
+
         // Check for duplicate
         if self.entries.contains_key(&tx.id) {
             return Err(MempoolError::DuplicateTransaction);
         }
 
         // Add transaction
-        let tx_id = tx.id.clone();
-        let added_time = chrono::Utc::now().timestamp() as u64;
-        let entry = MempoolEntry::new(tx, added_time);
+        let added_time = self.clock.now() as u64;
+        self.insert_entry(MempoolEntry::new(tx, added_time));
 
-        // Insert into fee-sorted list
-        let fee_rate = entry.fee_rate;
-        let pos = self
-            .by_fee
-            .iter()
-            .position(|id| self.entries.get(id).map(|e| e.fee_rate).unwrap_or(0) < fee_rate)
-            .unwrap_or(self.by_fee.len());
-        self.by_fee.insert(pos, tx_id.clone());
+        Ok(())
+    }
 
-        self.by_time.push(tx_id.clone());
-        self.entries.insert(tx_id, entry);
+    /// Add a token or contract transaction relayed by a peer.
+    ///
+    /// Unlike [`add_token_transaction`](Self::add_token_transaction), which
+    /// takes records built by this node, the identity input must be signed
+    /// and the transaction must pass relay policy, the fee floor and the
+    /// pool's limits. Smart transactions pay no fee, so they are refused
+    /// while the pool is busy enough to charge one.
+    pub fn add_relayed_smart_transaction(
+        &mut self,
+        tx: Transaction,
+        blockchain: &Blockchain,
+    ) -> Result<(), MempoolError> {
+        if tx.token_data.is_none() && tx.contract_data.is_none() {
+            return Err(MempoolError::InvalidTransaction(
+                "Not a token or contract transaction".to_string(),
+            ));
+        }
+        if tx.chain_id != self.chain_id {
+            return Err(MempoolError::WrongChainId(self.chain_id, tx.chain_id));
+        }
+        if tx.inputs.is_empty() || !tx.verify_signatures()? {
+            return Err(MempoolError::InvalidTransaction(
+                "Identity input is not signed".to_string(),
+            ));
+        }
+        tx.is_standard(&blockchain.params, self.current_height, self.current_time)?;
 
-        Ok(())
+        // No coins go in, so nothing can be paid as a fee
+        if tx.fee != 0 {
+            return Err(MempoolError::FeeMismatch(tx.fee, 0));
+        }
+        let min_feerate = self.mempool_min_feerate();
+        if min_feerate > 0 {
+            return Err(MempoolError::FeeRateBelowMinimum(min_feerate, 0));
+        }
+        if self.entries.len() >= self.max_size {
+            return Err(MempoolError::MempoolFull);
+        }
+        self.check_mempool_size(tx.estimated_size())?;
+
+        self.add_token_transaction(tx, blockchain)
     }
 
     /// Add a contract transaction to the pool (skips UTXO validation)
     ///
     /// Contract transactions record deployments and calls on-chain.
-    pub fn add_contract_transaction(&mut self, tx: Transaction) -> Result<(), MempoolError> {
-        self.add_token_transaction(tx) // Reuse the same logic
+    pub fn add_contract_transaction(
+        &mut self,
+        tx: Transaction,
+        blockchain: &Blockchain,
+    ) -> Result<(), MempoolError> {
+        self.add_token_transaction(tx, blockchain) // Reuse the same logic
+    }
+
+    /// Re-add the transactions a reorg disconnected (see
+    /// `BlockStatus::CausedReorg`), returning how many were accepted.
+    /// Each is validated again like a newly relayed transaction.
+    pub fn add_reorged_transactions(
+        &mut self,
+        txs: Vec<Transaction>,
+        blockchain: &Blockchain,
+    ) -> usize {
+        let mut accepted = 0;
+        for tx in txs {
+            let tx_id = tx.id.clone();
+            let result = if tx.is_token_transaction() || tx.is_contract_transaction() {
+                self.add_token_transaction(tx, blockchain)
+            } else {
+                self.add_transaction(tx, blockchain).map(|_| ())
+            };
+            match result {
+                Ok(()) => accepted += 1,
+                Err(e) => log::debug!("Reorged transaction {} not re-added: {}", tx_id, e),
+            }
+        }
+        accepted
     }
 }
 
@@ -601,6 +1940,20 @@ pub struct MempoolStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::{ChainParams, UTXO};
+
+    /// Block reward large enough to pay fees in the thousands
+    const RICH_REWARD: u64 = 1_000_000;
+
+    /// Chain whose coinbases can be spent right away
+    fn rich_chain() -> Blockchain {
+        Blockchain::with_params(ChainParams {
+            genesis_difficulty: 4,
+            block_reward: RICH_REWARD,
+            coinbase_maturity: 0,
+            ..ChainParams::default()
+        })
+    }
 
     #[test]
     fn test_mempool_add_remove() {
@@ -625,7 +1978,7 @@ mod tests {
 
     #[test]
     fn test_mempool_max_size() {
-        let mempool = Mempool::with_config(100, DEFAULT_CHAIN_ID);
+        let mempool = Mempool::with_config(100, DEFAULT_CHAIN_ID, MIN_RBF_FEE_BUMP_PERCENT);
         assert_eq!(mempool.max_size, 100);
     }
 
@@ -644,4 +1997,1656 @@ mod tests {
         assert_eq!(mempool.current_height, 100);
         assert_eq!(mempool.current_time, 1000000);
     }
+
+    /// Insert a chain of `len` transactions, each spending the previous one
+    fn insert_chain(mempool: &mut Mempool, len: usize) -> Vec<Transaction> {
+        use crate::core::{TransactionInput, TransactionOutput, SEQUENCE_FINAL};
+
+        let mut chain = Vec::with_capacity(len);
+        let mut parent_id = "confirmed_parent".to_string();
+        for i in 0..len {
+            let tx = Transaction::new(
+                vec![TransactionInput {
+                    tx_id: parent_id.clone(),
+                    output_index: 0,
+                    signature: String::new(),
+                    public_key: String::new(),
+                    sequence: SEQUENCE_FINAL,
+                }],
+                vec![TransactionOutput {
+                    amount: 1_000,
+                    recipient: format!("addr{}", i),
+                }],
+            );
+            parent_id = tx.id.clone();
+            mempool
+                .entries
+                .insert(tx.id.clone(), MempoolEntry::new(tx.clone(), 0));
+            chain.push(tx);
+        }
+        chain
+    }
+
+    #[test]
+    fn test_package_traversal_long_chain() {
+        let mut mempool = Mempool::new();
+        let chain = insert_chain(&mut mempool, 5_000);
+
+        // Walking the whole chain would recurse 5000 deep; the worklist
+        // stops at the package limit instead
+        assert!(matches!(
+            mempool.calculate_ancestors(chain.last().unwrap()),
+            Err(MempoolError::TooManyAncestors(n, MAX_ANCESTORS)) if n == MAX_ANCESTORS + 1
+        ));
+        assert!(matches!(
+            mempool.calculate_descendants(&chain[0].id),
+            Err(MempoolError::TooManyDescendants(n, MAX_DESCENDANTS)) if n == MAX_DESCENDANTS + 1
+        ));
+
+        // Within the limits the counts are exact
+        let (count, size) = mempool.calculate_ancestors(&chain[10]).unwrap();
+        assert_eq!(count, 10);
+        let expected: usize = chain[..10].iter().map(|tx| tx.estimated_size()).sum();
+        assert_eq!(size, expected);
+        let (count, _) = mempool.calculate_descendants(&chain[4_990].id).unwrap();
+        assert_eq!(count, 9);
+    }
+
+    #[test]
+    fn test_admission_enforces_ancestor_limit() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let mut utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        // Each link spends the previous one's output
+        let mut mempool = Mempool::new();
+        let link = |utxo: &UTXO| {
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(&key_pair.address(), utxo.output.amount - 1_000)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        for _ in 0..MAX_ANCESTORS {
+            let tx = link(&utxo);
+            mempool.add_transaction(tx.clone(), &blockchain).unwrap();
+            utxo = UTXO {
+                tx_id: tx.id.clone(),
+                output_index: 0,
+                output: tx.outputs[0].clone(),
+            };
+        }
+
+        // The 26th link would have 26 transactions in its package
+        let too_deep = link(&utxo);
+        assert!(matches!(
+            mempool.add_transaction(too_deep.clone(), &blockchain),
+            Err(MempoolError::TooManyAncestors(n, MAX_ANCESTORS)) if n == MAX_ANCESTORS + 1
+        ));
+        assert!(!mempool.contains(&too_deep.id));
+        assert_eq!(mempool.len(), MAX_ANCESTORS);
+    }
+
+    #[test]
+    fn test_reorg_returns_transactions_to_mempool() {
+        use crate::core::{Block, BlockStatus, TransactionBuilder};
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let mut utxos = Vec::new();
+        for _ in 0..2 {
+            let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+            utxos.push(
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+        let paying = |utxo: &crate::core::UTXO, recipient: &str| {
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(recipient, RICH_REWARD - 1_000)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let payment = paying(&utxos[0], "alice");
+        let spent_twice = paying(&utxos[1], "alice");
+        let fork_parent = blockchain.latest_block().hash.clone();
+        let old_tip = blockchain
+            .mine_block(vec![payment.clone(), spent_twice.clone()], "miner")
+            .unwrap();
+
+        // A heavier competing block double-spends the second output
+        let double_spend = paying(&utxos[1], "bob");
+        let mut fork = Block::new(
+            old_tip.index,
+            fork_parent,
+            vec![
+                Transaction::coinbase("rival", RICH_REWARD, old_tip.index),
+                double_spend.clone(),
+            ],
+            blockchain.difficulty + 2,
+        );
+        fork.mine();
+
+        let mut mempool = Mempool::new();
+        let returned_txs = match blockchain.process_block(fork).unwrap() {
+            BlockStatus::CausedReorg { returned_txs, .. } => returned_txs,
+            other => panic!("expected a reorg, got {:?}", other),
+        };
+        assert_eq!(returned_txs, vec![payment.clone()]);
+
+        assert_eq!(
+            mempool.add_reorged_transactions(returned_txs, &blockchain),
+            1
+        );
+        assert!(mempool.contains(&payment.id));
+        assert!(!mempool.contains(&spent_twice.id));
+        assert!(blockchain.tx_exists(&double_spend.id));
+    }
+
+    #[test]
+    fn test_remove_conflicting_keeps_children_of_pooled_parents() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let utxos: Vec<UTXO> = (0..2)
+            .map(|_| {
+                let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone()
+            })
+            .collect();
+        let spend = |utxo: &UTXO, recipient: &str| {
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(recipient, utxo.output.amount - 1_000)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let output_of = |tx: &Transaction| UTXO {
+            tx_id: tx.id.clone(),
+            output_index: 0,
+            output: tx.outputs[0].clone(),
+        };
+
+        // Two parent/child pairs waiting in the pool
+        let mut mempool = Mempool::new();
+        let parent = spend(&utxos[0], &key_pair.address());
+        let child = spend(&output_of(&parent), "alice");
+        let doomed = spend(&utxos[1], &key_pair.address());
+        let doomed_child = spend(&output_of(&doomed), "bob");
+        for tx in [&parent, &child, &doomed, &doomed_child] {
+            mempool.add_transaction(tx.clone(), &blockchain).unwrap();
+        }
+
+        // Nothing has changed on the chain: every input still resolves
+        assert_eq!(mempool.remove_conflicting(&blockchain), 0);
+        assert_eq!(mempool.len(), 4);
+
+        // A block confirms the first parent and double-spends the second
+        let double_spend = spend(&utxos[1], "carol");
+        blockchain
+            .mine_block(vec![parent.clone(), double_spend], "miner")
+            .unwrap();
+        assert_eq!(mempool.remove_conflicting(&blockchain), 3);
+        assert!(mempool.contains(&child.id));
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_custom_policy_rejects_blocked_recipient() {
+        use crate::core::{TokenOperationType, TransactionBuilder};
+        use crate::crypto::KeyPair;
+        use crate::mining::{AcceptAll, MempoolPolicy};
+        use std::sync::Arc;
+
+        #[derive(Debug)]
+        struct Blocklist(Vec<String>);
+
+        impl MempoolPolicy for Blocklist {
+            fn check(&self, tx: &Transaction, _chain: &Blockchain) -> Result<(), String> {
+                match tx.outputs.iter().find(|o| self.0.contains(&o.recipient)) {
+                    Some(output) => Err(format!("{} is blocked", output.recipient)),
+                    None => Ok(()),
+                }
+            }
+        }
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let mut utxos = Vec::new();
+        for _ in 0..2 {
+            let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+            utxos.push(
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+        let paying = |utxo: &crate::core::UTXO, recipient: &str| {
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(recipient, RICH_REWARD - 1_000)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        let mut mempool = Mempool::new();
+        mempool.add_policy(Arc::new(AcceptAll));
+        mempool.add_policy(Arc::new(Blocklist(vec!["mallory".to_string()])));
+        assert_eq!(mempool.policy_count(), 2);
+
+        let blocked = paying(&utxos[0], "mallory");
+        assert!(matches!(
+            mempool.add_transaction(blocked.clone(), &blockchain),
+            Err(MempoolError::PolicyRejected(reason)) if reason == "mallory is blocked"
+        ));
+        assert!(!mempool.contains(&blocked.id));
+
+        let allowed = paying(&utxos[1], "alice");
+        mempool
+            .add_transaction(allowed.clone(), &blockchain)
+            .unwrap();
+        assert!(mempool.contains(&allowed.id));
+
+        // Token and contract records go through the same rules
+        #[derive(Debug)]
+        struct NoSmartTransactions;
+
+        impl MempoolPolicy for NoSmartTransactions {
+            fn check(&self, tx: &Transaction, _chain: &Blockchain) -> Result<(), String> {
+                if tx.token_data.is_some() || tx.contract_data.is_some() {
+                    Err("smart transactions are disabled".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        let burn = Transaction::with_token_data(
+            vec![],
+            vec![],
+            TokenOperationType::Burn {
+                token_address: "token".to_string(),
+                amount: 1,
+            },
+        );
+        mempool.add_policy(Arc::new(NoSmartTransactions));
+        assert!(matches!(
+            mempool.add_token_transaction(burn.clone(), &blockchain),
+            Err(MempoolError::PolicyRejected(_))
+        ));
+        assert!(matches!(
+            mempool.add_contract_transaction(burn.clone(), &blockchain),
+            Err(MempoolError::PolicyRejected(_))
+        ));
+        assert!(!mempool.contains(&burn.id));
+    }
+
+    #[test]
+    fn test_immature_coinbase_spend_rejected() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        blockchain.params.coinbase_maturity = 2;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("alice", RICH_REWARD - 10_000)
+            .build_and_sign(&key_pair)
+            .unwrap();
+
+        let mut mempool = Mempool::new();
+        assert!(matches!(
+            mempool.add_transaction(spend.clone(), &blockchain),
+            Err(MempoolError::ImmatureCoinbase(id, 2)) if id == block.transactions[0].id
+        ));
+
+        for _ in 0..2 {
+            blockchain.mine_block(vec![], "miner").unwrap();
+        }
+        mempool.add_transaction(spend.clone(), &blockchain).unwrap();
+        assert!(mempool.contains(&spend.id));
+    }
+
+    #[test]
+    fn test_relayed_smart_transaction_limits() {
+        use crate::core::{TokenOperationType, TransactionInput, SEQUENCE_FINAL};
+        use crate::crypto::KeyPair;
+
+        let blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let transfer = |amount: u128| {
+            let mut tx = Transaction::with_token_data(
+                vec![TransactionInput {
+                    tx_id: "token_transfer".to_string(),
+                    output_index: 0,
+                    signature: String::new(),
+                    public_key: String::new(),
+                    sequence: SEQUENCE_FINAL,
+                }],
+                vec![],
+                TokenOperationType::Transfer {
+                    token_address: "token".to_string(),
+                    to: "recipient".to_string(),
+                    amount,
+                },
+            );
+            tx.sign(&key_pair).unwrap();
+            tx
+        };
+
+        let mut mempool = Mempool::with_config(1, DEFAULT_CHAIN_ID, MIN_RBF_FEE_BUMP_PERCENT);
+        let mut claims_fee = transfer(1);
+        claims_fee.fee = 500;
+        assert!(matches!(
+            mempool.add_relayed_smart_transaction(claims_fee, &blockchain),
+            Err(MempoolError::FeeMismatch(500, 0))
+        ));
+
+        mempool
+            .add_relayed_smart_transaction(transfer(2), &blockchain)
+            .unwrap();
+        assert!(matches!(
+            mempool.add_relayed_smart_transaction(transfer(3), &blockchain),
+            Err(MempoolError::MempoolFull)
+        ));
+    }
+
+    #[test]
+    fn test_consolidation_discount_in_ordering() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let mut utxos = Vec::new();
+        for _ in 0..3 {
+            let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+            utxos.push(
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+
+        // One input to one output at 10 sat/byte, and a consolidation of
+        // two inputs into one output at a slightly lower 8 sat/byte
+        let payment = TransactionBuilder::new()
+            .add_input(&utxos[0])
+            .add_output("alice", RICH_REWARD - 194 * 10)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let consolidation = TransactionBuilder::new()
+            .add_input(&utxos[1])
+            .add_input(&utxos[2])
+            .add_output(&key_pair.address(), 2 * RICH_REWARD - 344 * 8)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let order = |mempool: &mut Mempool| {
+            mempool
+                .add_transaction(payment.clone(), &blockchain)
+                .unwrap();
+            mempool
+                .add_transaction(consolidation.clone(), &blockchain)
+                .unwrap();
+            mempool
+                .get_transactions(2)
+                .into_iter()
+                .map(|tx| tx.id)
+                .collect::<Vec<_>>()
+        };
+
+        // Without a discount the consolidation ranks by its own fee rate
+        let mut mempool = Mempool::new();
+        assert_eq!(
+            order(&mut mempool),
+            vec![payment.id.clone(), consolidation.id.clone()]
+        );
+        assert_eq!(mempool.get_entry(&payment.id).unwrap().fee_rate, 10);
+        assert_eq!(mempool.get_entry(&consolidation.id).unwrap().fee_rate, 8);
+
+        // A 50% discount doubles its effective rate, putting it first
+        let mut mempool = Mempool::new();
+        mempool.set_consolidation_discount_percent(50);
+        assert_eq!(
+            order(&mut mempool),
+            vec![consolidation.id.clone(), payment.id.clone()]
+        );
+        let entry = mempool.get_entry(&consolidation.id).unwrap();
+        assert_eq!(entry.priority_rate, 16);
+        assert_eq!(entry.fee_rate, 8);
+        assert_eq!(mempool.get_entry(&payment.id).unwrap().priority_rate, 10);
+
+        // Reported rates stay real
+        assert_eq!(mempool.stats().max_fee_rate, 10);
+        assert_eq!(mempool.stats().min_fee_rate, 8);
+        let in_range = mempool.transactions_in_feerate_range(9, 20);
+        assert_eq!(in_range.len(), 1);
+        assert_eq!(in_range[0].tx.id, payment.id);
+
+        // Once the UTXO set is back under the limit the discount lapses
+        mempool.set_max_utxo_set_size(blockchain.utxo_count());
+        mempool.refresh_priorities(&blockchain);
+        assert_eq!(
+            mempool.get_entry(&consolidation.id).unwrap().priority_rate,
+            8
+        );
+        assert_eq!(
+            mempool
+                .get_transactions(2)
+                .into_iter()
+                .map(|tx| tx.id)
+                .collect::<Vec<_>>(),
+            vec![payment.id.clone(), consolidation.id.clone()]
+        );
+
+        // The discount only applies while the UTXO set is above the limit
+        let mut mempool = Mempool::new();
+        mempool.set_consolidation_discount_percent(50);
+        mempool.set_max_utxo_set_size(blockchain.utxo_count());
+        assert_eq!(
+            order(&mut mempool),
+            vec![payment.id.clone(), consolidation.id.clone()]
+        );
+    }
+
+    #[test]
+    fn test_full_rbf_replaces_non_signaling() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let paying_fee = |recipient: &str, fee: u64| {
+            TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output(recipient, RICH_REWARD - fee)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        // Non-signaling original and a higher-fee conflict
+        let original = paying_fee("alice", 1_000);
+        assert!(!original.signals_rbf());
+        let replacement = paying_fee("bob", 2_000);
+
+        // Opt-in mode rejects the replacement
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+        assert!(matches!(
+            mempool.add_transaction(replacement.clone(), &blockchain),
+            Err(MempoolError::RbfNotSignaled)
+        ));
+
+        // Full-RBF still enforces the fee bump...
+        let mut mempool = Mempool::new();
+        mempool.set_full_rbf(true);
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+        let underpaying = paying_fee("carol", 1_050);
+        assert!(matches!(
+            mempool.add_transaction(underpaying, &blockchain),
+            Err(MempoolError::InsufficientRbfFee(1_100, 1_050))
+        ));
+
+        // ...and replaces the original once it's met
+        let replaced = mempool
+            .add_transaction(replacement.clone(), &blockchain)
+            .unwrap();
+        let replaced: Vec<&str> = replaced.iter().map(|tx| tx.id.as_str()).collect();
+        assert_eq!(replaced, vec![original.id.as_str()]);
+        assert!(!mempool.contains(&original.id));
+        assert_eq!(
+            mempool.get_replaced(&original.id).unwrap().replaced_by,
+            replacement.id
+        );
+        assert_eq!(
+            mempool.replaces(&replacement.id),
+            std::slice::from_ref(&original.id)
+        );
+    }
+
+    #[test]
+    fn test_replacement_reports_every_conflict() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let utxos: Vec<_> = (0..2)
+            .map(|_| {
+                let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone()
+            })
+            .collect();
+        let spend = |utxos: &[UTXO], fee: u64| {
+            let mut builder = TransactionBuilder::new();
+            for utxo in utxos {
+                builder = builder.add_input(utxo);
+            }
+            builder
+                .add_output("alice", RICH_REWARD * utxos.len() as u64 - fee)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        let mut mempool = Mempool::new();
+        mempool.set_full_rbf(true);
+        let first = spend(&utxos[..1], 1_000);
+        let second = spend(&utxos[1..], 1_000);
+        mempool.add_transaction(first.clone(), &blockchain).unwrap();
+        mempool
+            .add_transaction(second.clone(), &blockchain)
+            .unwrap();
+
+        // One transaction spending both outputs replaces both spenders
+        let replaced = mempool
+            .add_transaction(spend(&utxos, 3_000), &blockchain)
+            .unwrap();
+        let mut replaced: Vec<&str> = replaced.iter().map(|tx| tx.id.as_str()).collect();
+        replaced.sort_unstable();
+        let mut expected = vec![first.id.as_str(), second.id.as_str()];
+        expected.sort_unstable();
+        assert_eq!(replaced, expected);
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_replacement_needs_a_better_package_rate() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let utxos: Vec<_> = (0..2)
+            .map(|_| {
+                let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone()
+            })
+            .collect();
+        let spend = |utxos: &[UTXO], fee: u64| {
+            let mut builder = TransactionBuilder::new();
+            for utxo in utxos {
+                builder = builder.add_input(utxo);
+            }
+            let total: u64 = utxos.iter().map(|u| u.output.amount).sum();
+            builder
+                .add_output(&key_pair.address(), total - fee)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let size = spend(&utxos[..1], 0).virtual_size() as u64;
+
+        let mut mempool = Mempool::new();
+        mempool.set_full_rbf(true);
+        let original = spend(&utxos[..1], 50 * size);
+        let parent = spend(&utxos[1..], size);
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+        mempool
+            .add_transaction(parent.clone(), &blockchain)
+            .unwrap();
+        let parent_output = UTXO {
+            tx_id: parent.id.clone(),
+            output_index: 0,
+            output: parent.outputs[0].clone(),
+        };
+
+        // Out-pays the original, but dragging in its cheap parent makes
+        // the package a worse one to mine
+        let dragging = spend(&[utxos[0].clone(), parent_output.clone()], 60 * size);
+        assert!(matches!(
+            mempool.add_transaction(dragging, &blockchain),
+            Err(MempoolError::InsufficientRbfFeeRate(50, _))
+        ));
+        assert!(mempool.contains(&original.id));
+
+        // Paying for the parent too makes it a valid replacement
+        let paying = spend(&[utxos[0].clone(), parent_output], 200 * size);
+        mempool
+            .add_transaction(paying.clone(), &blockchain)
+            .unwrap();
+        assert!(!mempool.contains(&original.id));
+        assert!(mempool.contains(&paying.id));
+    }
+
+    #[test]
+    fn test_replacement_cannot_spend_what_it_replaces() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        let mut mempool = Mempool::new();
+        let original = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&key_pair.address(), RICH_REWARD - 10_000)
+            .with_rbf()
+            .build_and_sign(&key_pair)
+            .unwrap();
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+
+        // Double-spends the original's input while also spending its output
+        let original_output = UTXO {
+            tx_id: original.id.clone(),
+            output_index: 0,
+            output: original.outputs[0].clone(),
+        };
+        let replacement = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_input(&original_output)
+            .add_output(&key_pair.address(), RICH_REWARD)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert!(matches!(
+            mempool.add_transaction(replacement.clone(), &blockchain),
+            Err(MempoolError::ReplacementSpendsConflict(id)) if id == original.id
+        ));
+        assert!(mempool.contains(&original.id));
+        assert!(!mempool.contains(&replacement.id));
+    }
+
+    #[test]
+    fn test_fee_is_recomputed() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        // Pays 10, claims 5_000 for priority
+        let mut lying = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&key_pair.address(), 40)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert_eq!(blockchain.compute_fee(&lying), Some(10));
+        lying.fee = 5_000;
+
+        let mut mempool = Mempool::new();
+        assert!(matches!(
+            mempool.add_transaction(lying.clone(), &blockchain),
+            Err(MempoolError::FeeMismatch(5_000, 10))
+        ));
+        assert!(mempool.is_empty());
+
+        // An unset fee is filled in from the inputs
+        let mut honest = lying;
+        honest.fee = 0;
+        mempool
+            .add_transaction(honest.clone(), &blockchain)
+            .unwrap();
+        assert_eq!(mempool.get_transaction(&honest.id).unwrap().fee, 10);
+
+        // A child of a pooled transaction is priced from the pool
+        let child = TransactionBuilder::new()
+            .add_input(&UTXO {
+                tx_id: honest.id.clone(),
+                output_index: 0,
+                output: honest.outputs[0].clone(),
+            })
+            .add_output("recipient", 35)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert_eq!(blockchain.compute_fee(&child), None);
+        mempool.add_transaction(child.clone(), &blockchain).unwrap();
+        assert_eq!(mempool.get_transaction(&child.id).unwrap().fee, 5);
+    }
+
+    #[test]
+    fn test_rbf_bump_covers_replaced_descendants() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let address = key_pair.address();
+        let block = blockchain.mine_block(vec![], &address).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        // Parent pays 1_000 and its child 200: replacing both costs 1_200
+        let parent = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&address, RICH_REWARD - 1_000)
+            .with_rbf()
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let child = TransactionBuilder::new()
+            .add_input(&UTXO {
+                tx_id: parent.id.clone(),
+                output_index: 0,
+                output: parent.outputs[0].clone(),
+            })
+            .add_output(&address, RICH_REWARD - 1_200)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let replacement = |fee: u64| {
+            TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output("recipient", RICH_REWARD - fee)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let pool = |bump: u64| {
+            let mut mempool = Mempool::with_config(DEFAULT_MEMPOOL_SIZE, DEFAULT_CHAIN_ID, bump);
+            mempool
+                .add_transaction(parent.clone(), &blockchain)
+                .unwrap();
+            mempool.add_transaction(child.clone(), &blockchain).unwrap();
+            mempool
+        };
+
+        // 15% over the parent alone doesn't cover the child
+        assert!(matches!(
+            pool(10).add_transaction(replacement(1_150), &blockchain),
+            Err(MempoolError::InsufficientRbfFee(1_320, 1_150))
+        ));
+
+        // A 15% bump over both: too little at 25%, enough at 10%
+        assert!(matches!(
+            pool(25).add_transaction(replacement(1_380), &blockchain),
+            Err(MempoolError::InsufficientRbfFee(1_500, 1_380))
+        ));
+        let mut mempool = pool(10);
+        assert_eq!(mempool.rbf_fee_bump_percent(), 10);
+        let replaced = mempool
+            .add_transaction(replacement(1_380), &blockchain)
+            .unwrap();
+        let replaced: Vec<&str> = replaced.iter().map(|tx| tx.id.as_str()).collect();
+        assert_eq!(replaced, vec![parent.id.as_str()]);
+        assert!(!mempool.contains(&parent.id));
+        assert!(!mempool.contains(&child.id));
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_mempool_min_feerate_rises_when_full() {
+        use crate::core::{TokenOperationType, TransactionBuilder};
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        let mut mempool = Mempool::new();
+        mempool.set_max_bytes(1000);
+        assert_eq!(mempool.mempool_min_feerate(), 0);
+
+        // Fill the pool past 75% of its byte limit
+        for i in 0..4 {
+            let tx = Transaction::with_token_data(
+                vec![],
+                vec![],
+                TokenOperationType::Burn {
+                    token_address: "token".to_string(),
+                    amount: i,
+                },
+            );
+            mempool.add_token_transaction(tx, &blockchain).unwrap();
+        }
+        let floor = mempool.mempool_min_feerate();
+        assert!(floor > 0);
+
+        // A zero-fee transaction is now below the floor
+        let cheap = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", RICH_REWARD)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert!(matches!(
+            mempool.add_transaction(cheap, &blockchain),
+            Err(MempoolError::FeeRateBelowMinimum(min, 0)) if min == floor
+        ));
+
+        // Paying above the floor gets in
+        let paying = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", RICH_REWARD - 10_000)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert!(mempool.add_transaction(paying, &blockchain).is_ok());
+    }
+
+    #[test]
+    fn test_byte_limit_evicts_lowest_feerate() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let mut utxos = Vec::new();
+        for _ in 0..5 {
+            let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+            utxos.push(
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+        let spend = |utxo: &UTXO, fee: u64| {
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output("recipient", RICH_REWARD - fee)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        // Every spend has the same shape, so the same size
+        let size = spend(&utxos[0], 0).virtual_size();
+        let with_rate = |utxo: &UTXO, rate: usize| spend(utxo, (size * rate) as u64);
+
+        // Room for exactly three transactions
+        let mut mempool = Mempool::new();
+        mempool.set_max_bytes(size * 3);
+        let low = with_rate(&utxos[0], 30);
+        let mid = with_rate(&utxos[1], 40);
+        let high = with_rate(&utxos[2], 50);
+        for tx in [&low, &mid, &high] {
+            mempool.add_transaction(tx.clone(), &blockchain).unwrap();
+        }
+
+        // A high-fee newcomer pushes out the cheapest transaction
+        let newcomer = with_rate(&utxos[3], 100);
+        mempool
+            .add_transaction(newcomer.clone(), &blockchain)
+            .unwrap();
+        assert!(!mempool.contains(&low.id));
+        assert!(mempool.contains(&newcomer.id));
+        assert_eq!(mempool.len(), 3);
+        assert!(mempool.total_mempool_size() <= size * 3);
+        assert_eq!(
+            mempool.mempool_min_feerate(),
+            30 + INCREMENTAL_RELAY_FEE_RATE
+        );
+
+        // A newcomer paying less than everything in the pool is turned away
+        let cheap = with_rate(&utxos[4], 35);
+        assert!(matches!(
+            mempool.add_transaction(cheap, &blockchain),
+            Err(MempoolError::MempoolFull)
+        ));
+        assert!(mempool.contains(&mid.id));
+    }
+
+    #[test]
+    fn test_byte_eviction_spares_parents_and_floor_decays() {
+        use crate::core::{MockClock, TransactionBuilder, UTXO};
+        use crate::crypto::KeyPair;
+        use std::sync::Arc;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let mut utxos = Vec::new();
+        for _ in 0..2 {
+            let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+            utxos.push(
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+        let spend = |utxo: &UTXO, rate: u64| {
+            let size = TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(&key_pair.address(), utxo.output.amount)
+                .build_and_sign(&key_pair)
+                .unwrap()
+                .virtual_size() as u64;
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(&key_pair.address(), utxo.output.amount - size * rate)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let parent = spend(&utxos[0], 10);
+        let other = spend(&utxos[1], 20);
+        let child = spend(
+            &UTXO {
+                tx_id: parent.id.clone(),
+                output_index: 0,
+                output: parent.outputs[0].clone(),
+            },
+            100,
+        );
+
+        // Room for the parent and one more transaction
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let mut mempool = Mempool::new();
+        mempool.set_clock(clock.clone());
+        mempool.set_max_bytes(
+            parent.estimated_size() + other.estimated_size() + child.estimated_size() / 2,
+        );
+        for tx in [&parent, &other] {
+            mempool.add_transaction(tx.clone(), &blockchain).unwrap();
+        }
+
+        // A child is judged together with its parent: paying 25 on its own
+        // still leaves the pair below the 20 it would have to evict
+        let weak_child = spend(
+            &UTXO {
+                tx_id: parent.id.clone(),
+                output_index: 0,
+                output: parent.outputs[0].clone(),
+            },
+            25,
+        );
+        assert!(matches!(
+            mempool.add_transaction(weak_child, &blockchain),
+            Err(MempoolError::MempoolFull)
+        ));
+
+        // The parent is the cheapest package, but the child needs it
+        mempool.add_transaction(child.clone(), &blockchain).unwrap();
+        assert!(mempool.contains(&parent.id));
+        assert!(mempool.contains(&child.id));
+        assert!(!mempool.contains(&other.id));
+        assert_eq!(
+            mempool.mempool_min_feerate(),
+            20 + INCREMENTAL_RELAY_FEE_RATE
+        );
+
+        // The eviction floor halves every half-life once evictions stop
+        clock.advance(EVICTION_FLOOR_HALF_LIFE_SECS as i64);
+        assert_eq!(mempool.mempool_min_feerate(), 10);
+        clock.advance(EVICTION_FLOOR_HALF_LIFE_SECS as i64 * 4);
+        assert!(mempool.mempool_min_feerate() < 10);
+    }
+
+    #[test]
+    fn test_count_limit_evicts_by_ancestor_feerate() {
+        use crate::core::{TransactionBuilder, UTXO};
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let mut utxos = Vec::new();
+        for _ in 0..3 {
+            let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+            utxos.push(
+                blockchain
+                    .find_utxo(&block.transactions[0].id, 0)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+        let spend = |utxo: &UTXO, rate: u64| {
+            let size = TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(&key_pair.address(), utxo.output.amount)
+                .build_and_sign(&key_pair)
+                .unwrap()
+                .virtual_size() as u64;
+            TransactionBuilder::new()
+                .add_input(utxo)
+                .add_output(&key_pair.address(), utxo.output.amount - size * rate)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let output_of = |tx: &Transaction| UTXO {
+            tx_id: tx.id.clone(),
+            output_index: 0,
+            output: tx.outputs[0].clone(),
+        };
+
+        // A cheap parent whose child pays for both, next to a standalone
+        // transaction that pays more than the parent alone
+        let mut mempool = Mempool::with_config(3, DEFAULT_CHAIN_ID, MIN_RBF_FEE_BUMP_PERCENT);
+        let parent = spend(&utxos[0], 2);
+        let child = spend(&output_of(&parent), 100);
+        let standalone = spend(&utxos[1], 5);
+        for tx in [&parent, &child, &standalone] {
+            mempool.add_transaction(tx.clone(), &blockchain).unwrap();
+        }
+        assert_eq!(mempool.get_entry(&parent.id).unwrap().ancestor_fee_rate, 2);
+        assert_eq!(mempool.get_entry(&child.id).unwrap().ancestor_count, 1);
+        assert!(mempool.get_entry(&child.id).unwrap().ancestor_fee_rate > 20);
+
+        // The newcomer displaces the standalone transaction, not the parent
+        let newcomer = spend(&utxos[2], 20);
+        mempool
+            .add_transaction(newcomer.clone(), &blockchain)
+            .unwrap();
+        assert!(!mempool.contains(&standalone.id));
+        assert!(mempool.contains(&parent.id));
+        assert!(mempool.contains(&child.id));
+        assert!(mempool.contains(&newcomer.id));
+
+        // Once the parent is mined the child stands on its own fee
+        mempool.remove_transaction(&parent.id);
+        let child_entry = mempool.get_entry(&child.id).unwrap();
+        assert_eq!(child_entry.ancestor_count, 0);
+        assert_eq!(child_entry.ancestor_fee_rate, 100);
+    }
+
+    #[test]
+    fn test_orphan_transactions_respect_memory_budget() {
+        use crate::core::{ContractOperationType, TransactionInput, SEQUENCE_FINAL};
+
+        let orphan = |i: u8| {
+            Transaction::with_contract_data(
+                vec![TransactionInput {
+                    tx_id: format!("{:064}", i),
+                    output_index: 0,
+                    signature: String::new(),
+                    public_key: String::new(),
+                    sequence: SEQUENCE_FINAL,
+                }],
+                vec![],
+                ContractOperationType::Deploy {
+                    bytecode: vec![i; 90_000],
+                    constructor_args: vec![],
+                },
+            )
+        };
+
+        let mut mempool = Mempool::new();
+        mempool.set_max_orphan_mb(1);
+        let orphans: Vec<Transaction> = (0..12).map(orphan).collect();
+        for tx in &orphans {
+            assert!(mempool.add_orphan(tx.clone()));
+            assert!(mempool.orphan_bytes() <= BYTES_PER_MB);
+        }
+
+        // The oldest orphans were evicted to make room
+        let kept = BYTES_PER_MB / orphans[0].estimated_size();
+        assert_eq!(mempool.orphan_count(), kept);
+        assert!(!mempool.contains_orphan(&orphans[0].id));
+        assert!(mempool.contains_orphan(&orphans[11].id));
+        assert!(mempool.is_empty());
+
+        // Shrinking the budget trims immediately
+        mempool.set_max_orphan_mb(0);
+        assert_eq!(mempool.orphan_count(), 0);
+        assert_eq!(mempool.orphan_bytes(), 0);
+    }
+
+    #[test]
+    fn test_orphan_accepted_with_parent() {
+        use crate::core::{TransactionBuilder, UTXO};
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let parent = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&key_pair.address(), 40)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let child = TransactionBuilder::new()
+            .add_input(&UTXO {
+                tx_id: parent.id.clone(),
+                output_index: 0,
+                output: parent.outputs[0].clone(),
+            })
+            .add_output("recipient", 30)
+            .build_and_sign(&key_pair)
+            .unwrap();
+
+        // The child arrives first and waits for its parent
+        let mut mempool = Mempool::new();
+        assert!(mempool.add_transaction(child.clone(), &blockchain).is_err());
+        assert!(mempool.contains_orphan(&child.id));
+
+        mempool
+            .add_transaction(parent.clone(), &blockchain)
+            .unwrap();
+        assert!(mempool.contains(&parent.id));
+        assert!(mempool.contains(&child.id));
+        assert_eq!(mempool.orphan_count(), 0);
+    }
+
+    #[test]
+    fn test_orphans_are_validated_and_expire() {
+        use crate::core::{MockClock, TransactionBuilder, TransactionOutput, UTXO};
+        use crate::crypto::KeyPair;
+        use std::sync::Arc;
+
+        let key_pair = KeyPair::generate();
+        let orphan = |amount| {
+            TransactionBuilder::new()
+                .add_input(&UTXO {
+                    tx_id: "f".repeat(64),
+                    output_index: 0,
+                    output: TransactionOutput {
+                        amount: 100,
+                        recipient: key_pair.address(),
+                    },
+                })
+                .add_output("recipient", amount)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let mut mempool = Mempool::new();
+        mempool.set_clock(clock.clone());
+
+        // Bad signatures and other chains are never stored
+        let mut forged = orphan(10);
+        forged.outputs[0].amount = 90;
+        assert!(!mempool.add_orphan(forged));
+        let mut foreign = orphan(20);
+        foreign.chain_id += 1;
+        assert!(!mempool.add_orphan(foreign));
+        assert_eq!(mempool.orphan_count(), 0);
+
+        let old = orphan(30);
+        assert!(mempool.add_orphan(old.clone()));
+        clock.advance(ORPHAN_TX_EXPIRE_SECS as i64);
+        let recent = orphan(40);
+        assert!(mempool.add_orphan(recent.clone()));
+        assert_eq!(mempool.orphan_count(), 2);
+
+        // The older orphan times out first
+        clock.advance(1);
+        assert_eq!(mempool.expire_orphans(), 1);
+        assert!(!mempool.contains_orphan(&old.id));
+        assert!(mempool.contains_orphan(&recent.id));
+    }
+
+    #[test]
+    fn test_package_accepted_out_of_order() {
+        use crate::core::{TokenOperationType, TransactionBuilder, UTXO};
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        // A zero-fee parent whose child pays for both
+        let parent = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output(&key_pair.address(), RICH_REWARD)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let spend_parent = |amount: u64| {
+            TransactionBuilder::new()
+                .add_input(&UTXO {
+                    tx_id: parent.id.clone(),
+                    output_index: 0,
+                    output: parent.outputs[0].clone(),
+                })
+                .add_output("recipient", amount)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+        let child = spend_parent(RICH_REWARD - 20_000);
+
+        // Raise the fee floor above the parent's own fee rate
+        let mut mempool = Mempool::new();
+        mempool.set_max_bytes(1600);
+        for i in 0..4 {
+            let tx = Transaction::with_token_data(
+                vec![],
+                vec![],
+                TokenOperationType::Burn {
+                    token_address: "token".to_string(),
+                    amount: i,
+                },
+            );
+            mempool.add_token_transaction(tx, &blockchain).unwrap();
+        }
+        assert!(mempool.mempool_min_feerate() > 0);
+        assert!(matches!(
+            mempool.add_transaction(parent.clone(), &blockchain),
+            Err(MempoolError::FeeRateBelowMinimum(_, 0))
+        ));
+        let before = mempool.transaction_ids();
+
+        // All-or-nothing: an invalid child keeps the parent out too
+        let overspend = spend_parent(RICH_REWARD + 1);
+        assert!(mempool
+            .add_package(vec![overspend, parent.clone()], &blockchain)
+            .is_err());
+        assert_eq!(mempool.transaction_ids(), before);
+        assert_eq!(mempool.orphan_count(), 0);
+
+        // The child arrives first; the package is sorted and pays the floor
+        mempool
+            .add_package(vec![child.clone(), parent.clone()], &blockchain)
+            .unwrap();
+        assert!(mempool.contains(&parent.id));
+        assert!(mempool.contains(&child.id));
+        assert_eq!(mempool.get_transaction(&child.id).unwrap().fee, 20_000);
+
+        assert!(matches!(
+            mempool.add_package(vec![], &blockchain),
+            Err(MempoolError::InvalidPackage(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_package_restores_replaced_transactions() {
+        use crate::core::{TransactionBuilder, UTXO};
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let pay = |fee: u64| {
+            TransactionBuilder::new()
+                .add_input(&utxo)
+                .add_output(&key_pair.address(), RICH_REWARD - fee)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        let mut mempool = Mempool::new();
+        mempool.set_full_rbf(true);
+        let original = pay(5_000);
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+
+        // The replacement is valid on its own, but its child overspends
+        let replacement = pay(50_000);
+        let overspend = TransactionBuilder::new()
+            .add_input(&UTXO {
+                tx_id: replacement.id.clone(),
+                output_index: 0,
+                output: replacement.outputs[0].clone(),
+            })
+            .add_output("recipient", RICH_REWARD)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert!(mempool
+            .add_package(vec![overspend, replacement.clone()], &blockchain)
+            .is_err());
+
+        assert!(mempool.contains(&original.id));
+        assert!(!mempool.contains(&replacement.id));
+        assert!(mempool.get_replaced(&original.id).is_none());
+        assert_eq!(mempool.transaction_ids(), vec![original.id.clone()]);
+        assert_eq!(mempool.get_transaction(&original.id).unwrap().fee, 5_000);
+    }
+
+    #[test]
+    fn test_failed_package_leaves_orphans_alone() {
+        use crate::core::{TransactionBuilder, TransactionOutput, UTXO};
+        use crate::crypto::KeyPair;
+
+        let blockchain = rich_chain();
+        let key_pair = KeyPair::generate();
+        let spending_unknown = |parent: &str| {
+            TransactionBuilder::new()
+                .add_input(&UTXO {
+                    tx_id: parent.repeat(64),
+                    output_index: 0,
+                    output: TransactionOutput {
+                        amount: 100,
+                        recipient: key_pair.address(),
+                    },
+                })
+                .add_output("recipient", 50)
+                .build_and_sign(&key_pair)
+                .unwrap()
+        };
+
+        // The orphan budget holds exactly one orphan
+        let mut mempool = Mempool::new();
+        let orphan = spending_unknown("e");
+        mempool.max_orphan_bytes = orphan.estimated_size();
+        assert!(mempool.add_orphan(orphan.clone()));
+
+        // A package member with unknown parents fails the package without
+        // pushing the existing orphan out
+        let member = spending_unknown("f");
+        assert!(mempool
+            .add_package(vec![member.clone()], &blockchain)
+            .is_err());
+        assert!(mempool.contains_orphan(&orphan.id));
+        assert!(!mempool.contains_orphan(&member.id));
+        assert_eq!(mempool.orphan_count(), 1);
+    }
+
+    #[test]
+    fn test_tx_exists() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let mined = &block.transactions[0];
+        let utxo = blockchain.find_utxo(&mined.id, 0).unwrap().clone();
+        let pending = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 40)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(pending.clone(), &blockchain)
+            .unwrap();
+
+        assert!(blockchain.tx_exists(&mined.id));
+        assert!(mempool.is_known(&mined.id, &blockchain));
+        assert!(!blockchain.tx_exists(&pending.id));
+        assert!(mempool.is_known(&pending.id, &blockchain));
+        let random = "ab".repeat(32);
+        assert!(!blockchain.tx_exists(&random));
+        assert!(!mempool.is_known(&random, &blockchain));
+    }
+
+    #[test]
+    fn test_expiry_drops_lowest_feerate_first() {
+        use crate::core::MockClock;
+        use std::sync::Arc;
+
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let mut mempool = Mempool::new();
+        mempool.set_clock(clock.clone());
+        mempool.set_max_expired_per_call(2);
+
+        // Five transactions of the same age, plus an older high-fee one
+        let start = 1_700_000_000;
+        let mut ids = HashMap::new();
+        for (i, (age, rate)) in [(0, 10), (0, 1), (0, 50), (0, 5), (0, 20), (1, 100)]
+            .into_iter()
+            .enumerate()
+        {
+            let mut entry = MempoolEntry::new(Transaction::coinbase("miner", 50, i as u64), 0);
+            entry.added_time = start - age;
+            entry.fee_rate = rate;
+            ids.insert(rate, entry.tx.id.clone());
+            mempool.insert_entry(entry);
+        }
+        clock.advance(MEMPOOL_EXPIRY_SECS as i64 + 1);
+
+        // Oldest first, then cheapest; capped at two per call
+        assert_eq!(mempool.expire(), vec![ids[&100].clone(), ids[&1].clone()]);
+        assert_eq!(mempool.expire(), vec![ids[&5].clone(), ids[&10].clone()]);
+        assert_eq!(mempool.len(), 2);
+        assert!(mempool.contains(&ids[&50]));
+        assert_eq!(mempool.expire(), vec![ids[&20].clone(), ids[&50].clone()]);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_mempool_expiry_with_mock_clock() {
+        use crate::core::{MockClock, TokenOperationType};
+        use std::sync::Arc;
+
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let blockchain = Blockchain::with_difficulty(1);
+        let mut mempool = Mempool::new();
+        mempool.set_clock(clock.clone());
+
+        let burn = |amount| {
+            Transaction::with_token_data(
+                vec![],
+                vec![],
+                TokenOperationType::Burn {
+                    token_address: "token".to_string(),
+                    amount,
+                },
+            )
+        };
+
+        let old = burn(1);
+        mempool
+            .add_token_transaction(old.clone(), &blockchain)
+            .unwrap();
+        clock.advance(MEMPOOL_EXPIRY_SECS as i64 / 2);
+        let recent = burn(2);
+        mempool
+            .add_token_transaction(recent.clone(), &blockchain)
+            .unwrap();
+
+        // Nothing has reached the expiry age yet
+        assert!(mempool.expire().is_empty());
+        assert_eq!(mempool.len(), 2);
+
+        clock.advance(MEMPOOL_EXPIRY_SECS as i64 / 2 + 1);
+        assert_eq!(mempool.expire(), vec![old.id.clone()]);
+        assert!(!mempool.contains(&old.id));
+        assert!(mempool.contains(&recent.id));
+        assert_eq!(
+            mempool.get_entry(&recent.id).unwrap().added_time,
+            1_700_000_000 + MEMPOOL_EXPIRY_SECS / 2
+        );
+    }
+
+    #[test]
+    fn test_knapsack_beats_greedy() {
+        use crate::core::{TransactionInput, TransactionOutput, SEQUENCE_FINAL};
+
+        let tx = |name: &str, outputs: usize, fee: u64| {
+            let mut tx = Transaction::new(
+                vec![TransactionInput {
+                    tx_id: format!("{}_parent", name),
+                    output_index: 0,
+                    signature: String::new(),
+                    public_key: String::new(),
+                    sequence: SEQUENCE_FINAL,
+                }],
+                (0..outputs)
+                    .map(|i| TransactionOutput {
+                        amount: 1_000,
+                        recipient: format!("{}{}", name, i),
+                    })
+                    .collect(),
+            );
+            tx.fee = fee;
+            tx
+        };
+
+        // One large tx with the best fee rate, two small ones that together
+        // pay more but only fit if the large one is left out
+        let large = tx("large", 3, 2_600);
+        let small_a = tx("small_a", 1, 1_500);
+        let small_b = tx("small_b", 1, 1_500);
+        let max_weight = (small_a.serialized_size() + small_b.serialized_size()) * 4;
+        assert!((large.serialized_size() + small_a.serialized_size()) * 4 > max_weight);
+
+        let mut mempool = Mempool::new();
+        for tx in [&large, &small_a, &small_b] {
+            mempool.insert_entry(MempoolEntry::new(tx.clone(), 0));
+        }
+        assert_eq!(mempool.get_transactions(1)[0].id, large.id);
+
+        let fees = |txs: &[Transaction]| txs.iter().map(|tx| tx.fee).sum::<u64>();
+        let greedy = mempool.select_for_block(max_weight, BlockAssembly::default());
+        let knapsack = mempool.select_for_block(max_weight, BlockAssembly::Knapsack);
+
+        assert_eq!(fees(&greedy), 2_600);
+        assert_eq!(fees(&knapsack), 3_000);
+        assert!(knapsack.iter().all(|tx| tx.id != large.id));
+    }
+
+    #[test]
+    fn test_block_assembly_takes_parent_with_child() {
+        use crate::core::{TransactionInput, TransactionOutput, SEQUENCE_FINAL};
+
+        let tx = |parent: &str, name: &str, fee: u64| {
+            let mut tx = Transaction::new(
+                vec![TransactionInput {
+                    tx_id: parent.to_string(),
+                    output_index: 0,
+                    signature: String::new(),
+                    public_key: String::new(),
+                    sequence: SEQUENCE_FINAL,
+                }],
+                vec![TransactionOutput {
+                    amount: 1_000,
+                    recipient: name.to_string(),
+                }],
+            );
+            tx.fee = fee;
+            tx
+        };
+
+        // A low-fee parent whose child pays enough to carry it past a
+        // mid-fee transaction; only two of the three fit
+        let parent = tx(&"a".repeat(64), "parent", 100);
+        let child = tx(&parent.id, "child0", 5_000);
+        let other = tx(&"b".repeat(64), "other0", 2_000);
+        let size = |tx: &Transaction| tx.serialized_size() * 4;
+        let max_weight = (size(&parent) + size(&child)).max(size(&other) + size(&child));
+        assert!(size(&parent) + size(&child) + size(&other) > max_weight);
+
+        let mut mempool = Mempool::new();
+        for tx in [&parent, &child, &other] {
+            mempool.insert_entry(MempoolEntry::new(tx.clone(), 0));
+        }
+        assert_eq!(mempool.get_transactions(2)[1].id, other.id);
+
+        for strategy in [BlockAssembly::Greedy, BlockAssembly::Knapsack] {
+            let ids: Vec<String> = mempool
+                .select_for_block(max_weight, strategy)
+                .into_iter()
+                .map(|tx| tx.id)
+                .collect();
+            assert_eq!(ids, vec![parent.id.clone(), child.id.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_transactions_in_feerate_range() {
+        let mut mempool = Mempool::new();
+        for (i, rate) in [10, 1, 50, 5, 20, 10].into_iter().enumerate() {
+            let mut entry = MempoolEntry::new(Transaction::coinbase("miner", 50, i as u64), 0);
+            entry.fee_rate = rate;
+            mempool.insert_entry(entry);
+        }
+
+        let rates = |min, max| {
+            mempool
+                .transactions_in_feerate_range(min, max)
+                .iter()
+                .map(|e| e.fee_rate)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rates(5, 20), vec![20, 10, 10, 5]);
+        assert_eq!(rates(10, 10), vec![10, 10]);
+        assert_eq!(rates(0, u64::MAX), vec![50, 20, 10, 10, 5, 1]);
+        assert_eq!(rates(51, 100), Vec::<u64>::new());
+        assert_eq!(rates(20, 5), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_fee_rank() {
+        let mut mempool = Mempool::new();
+        let mut ids = Vec::new();
+        for (i, rate) in [10, 1, 50, 5, 20].into_iter().enumerate() {
+            let mut entry = MempoolEntry::new(Transaction::coinbase("miner", 50, i as u64), 0);
+            entry.fee_rate = rate;
+            ids.push(entry.tx.id.clone());
+            mempool.insert_entry(entry);
+        }
+
+        // 50, 20, 10, 5, 1
+        assert_eq!(mempool.fee_rank(&ids[0]), Some((3, 5)));
+        assert_eq!(mempool.fee_rank(&ids[2]), Some((1, 5)));
+        assert_eq!(mempool.fee_rank(&ids[1]), Some((5, 5)));
+        assert_eq!(mempool.fee_rank("missing"), None);
+    }
+
+    #[test]
+    fn test_mempool_rejects_non_standard() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+
+        let tx = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("not an address", 40)
+            .build_and_sign(&key_pair)
+            .unwrap();
+
+        // Still valid by consensus, but not relayed
+        assert!(tx.is_valid().unwrap());
+        let mut mempool = Mempool::new();
+        assert!(matches!(
+            mempool.add_transaction(tx, &blockchain),
+            Err(MempoolError::NonStandard(PolicyError::Recipient(_)))
+        ));
+    }
 }
@@ -2,7 +2,11 @@
 //!
 //! Provides block mining and mempool management.
 
-use crate::core::{Block, Blockchain, BlockchainError, Transaction, BLOCK_REWARD};
+use crate::core::{
+    Block, Blockchain, BlockchainError, Transaction, TransactionOutput, BLOCK_REWARD,
+    MAX_COINBASE_TAG_SIZE,
+};
+use crate::crypto::PowKind;
 use log::info;
 use std::time::Instant;
 
@@ -21,6 +25,17 @@ pub struct MiningStats {
 pub struct Miner {
     /// Miner's address for receiving rewards
     pub address: String,
+    /// Coinbase reward used by `mine_block_detached`
+    pub reward: u64,
+    /// Tag embedded in every coinbase this miner creates
+    pub tag: Vec<u8>,
+    /// When set, fees go to this address in a coinbase output separate from
+    /// the subsidy (which may be the same address)
+    pub fee_address: Option<String>,
+    /// Fees claimed by `mine_block_detached` when splitting the coinbase
+    pub fees: u64,
+    /// Proof-of-work algorithm used by `mine_block_detached`
+    pub pow: PowKind,
 }
 
 impl Miner {
@@ -28,9 +43,63 @@ impl Miner {
     pub fn new(address: &str) -> Self {
         Self {
             address: address.to_string(),
+            reward: BLOCK_REWARD,
+            tag: Vec::new(),
+            fee_address: None,
+            fees: 0,
+            pow: PowKind::default(),
         }
     }
 
+    /// Create a miner that tags its coinbases (truncated to
+    /// `MAX_COINBASE_TAG_SIZE` bytes)
+    pub fn new_with_tag(address: &str, tag: &[u8]) -> Self {
+        let mut miner = Self::new(address);
+        miner.tag = tag[..tag.len().min(MAX_COINBASE_TAG_SIZE)].to_vec();
+        miner
+    }
+
+    /// Set the coinbase reward used by `mine_block_detached`
+    pub fn with_reward(mut self, reward: u64) -> Self {
+        self.reward = reward;
+        self
+    }
+
+    /// Pay fees to `fee_address` in their own coinbase output
+    pub fn with_fee_split(mut self, fee_address: &str) -> Self {
+        self.fee_address = Some(fee_address.to_string());
+        self
+    }
+
+    /// Set the fees claimed by `mine_block_detached` when splitting
+    pub fn with_fees(mut self, fees: u64) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Set the proof-of-work algorithm used by `mine_block_detached`
+    pub fn with_pow(mut self, pow: PowKind) -> Self {
+        self.pow = pow;
+        self
+    }
+
+    /// Build this miner's coinbase: the subsidy, plus a separate fee output
+    /// when splitting is enabled and there are fees to claim
+    fn coinbase(&self, height: u64, subsidy: u64, fees: u64) -> Transaction {
+        let mut outputs = vec![TransactionOutput {
+            amount: subsidy,
+            recipient: self.address.clone(),
+        }];
+        if let Some(fee_address) = self.fee_address.as_ref().filter(|_| fees > 0) {
+            outputs.push(TransactionOutput {
+                amount: fees,
+                recipient: fee_address.clone(),
+            });
+        }
+
+        Transaction::coinbase_with_outputs(outputs, height, &self.tag)
+    }
+
     /// Mine a new block with the given transactions
     pub fn mine_block(
         &self,
@@ -40,7 +109,16 @@ impl Miner {
         let start = Instant::now();
 
         // Create coinbase transaction
-        let coinbase = Transaction::coinbase(&self.address, BLOCK_REWARD, blockchain.height() + 1);
+        let fees = if self.fee_address.is_some() {
+            blockchain.transaction_fees(&transactions)
+        } else {
+            0
+        };
+        let coinbase = self.coinbase(
+            blockchain.height() + 1,
+            blockchain.next_block_reward(),
+            fees,
+        );
 
         // Combine coinbase with other transactions
         let mut all_transactions = vec![coinbase];
@@ -53,6 +131,7 @@ impl Miner {
             all_transactions,
             blockchain.difficulty,
         );
+        block.set_pow(blockchain.params.pow_algorithm);
 
         info!(
             "Mining block {} with difficulty {}...",
@@ -101,7 +180,7 @@ impl Miner {
         let start = Instant::now();
 
         // Create coinbase transaction
-        let coinbase = Transaction::coinbase(&self.address, BLOCK_REWARD, current_height + 1);
+        let coinbase = self.coinbase(current_height + 1, self.reward, self.fees);
 
         // Combine coinbase with other transactions
         let mut all_transactions = vec![coinbase];
@@ -114,6 +193,7 @@ impl Miner {
             all_transactions,
             difficulty,
         );
+        block.set_pow(self.pow);
 
         info!(
             "Mining block {} with difficulty {}...",
@@ -188,4 +268,107 @@ mod tests {
         assert_eq!(results.len(), 3);
         assert_eq!(blockchain.height(), 3);
     }
+
+    #[test]
+    fn test_split_coinbase() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+
+        blockchain.params.coinbase_maturity = 0;
+        let key_pair = KeyPair::generate();
+        let block = blockchain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = blockchain
+            .find_utxo(&block.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        // 50 in, 45 out: 5 coins of fees
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", 45)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        assert_eq!(blockchain.transaction_fees(std::slice::from_ref(&spend)), 5);
+
+        // Claiming more fees than the block pays is rejected
+        let split = |fees: u64| {
+            Transaction::coinbase_with_outputs(
+                vec![
+                    TransactionOutput {
+                        amount: BLOCK_REWARD,
+                        recipient: "pool".to_string(),
+                    },
+                    TransactionOutput {
+                        amount: fees,
+                        recipient: "pool_fees".to_string(),
+                    },
+                ],
+                2,
+                &[],
+            )
+        };
+        let mut greedy = Block::new(
+            2,
+            blockchain.latest_block().hash.clone(),
+            vec![split(6), spend.clone()],
+            blockchain.difficulty,
+        );
+        greedy.mine();
+        assert!(blockchain.add_block(greedy).is_err());
+
+        let miner = Miner::new("pool").with_fee_split("pool_fees");
+        let (block, _) = miner.mine_block(&mut blockchain, vec![spend]).unwrap();
+        let outputs = &block.coinbase_tx().unwrap().outputs;
+        assert_eq!(outputs, &split(5).outputs);
+        assert_eq!(block.mining_reward(), BLOCK_REWARD + 5);
+        assert_eq!(blockchain.get_balance("pool_fees"), 5);
+
+        // Without fees the coinbase has a single output
+        let (block, _) = miner.mine_block(&mut blockchain, vec![]).unwrap();
+        assert_eq!(block.coinbase_tx().unwrap().outputs.len(), 1);
+    }
+
+    #[test]
+    fn test_coinbase_tags() {
+        use crate::api::handlers::{BlockInfo, TransactionResponse};
+
+        let mut blockchain = Blockchain::with_difficulty(4);
+        let alice = Miner::new_with_tag("miner_address", b"/alice pool/");
+        let bob = Miner::new_with_tag("miner_address", b"/bob pool/");
+
+        let tip = blockchain.latest_block().hash.clone();
+        let (alice_block, _) = alice.mine_block_detached(0, tip.clone(), 4, vec![]);
+        let (bob_block, _) = bob.mine_block_detached(0, tip, 4, vec![]);
+
+        let alice_coinbase = alice_block.coinbase_tx().unwrap();
+        let bob_coinbase = bob_block.coinbase_tx().unwrap();
+        assert_ne!(alice_coinbase.id, bob_coinbase.id);
+        assert_eq!(alice_coinbase.coinbase_tag().unwrap(), b"/alice pool/");
+        assert_eq!(
+            bob_coinbase.coinbase_message().as_deref(),
+            Some("/bob pool/")
+        );
+
+        blockchain.add_block(alice_block.clone()).unwrap();
+        let block = blockchain.get_block(1).unwrap();
+        assert_eq!(
+            BlockInfo::from(block).coinbase_message.as_deref(),
+            Some("/alice pool/")
+        );
+        assert_eq!(
+            TransactionResponse::from(&block.transactions[0])
+                .coinbase_message
+                .as_deref(),
+            Some("/alice pool/")
+        );
+
+        // Tags are bounded and untagged coinbases carry no message
+        let long = Miner::new_with_tag("miner_address", &[b'x'; MAX_COINBASE_TAG_SIZE + 10]);
+        assert_eq!(long.tag.len(), MAX_COINBASE_TAG_SIZE);
+        assert_eq!(
+            Transaction::coinbase("miner", 50, 1).coinbase_message(),
+            None
+        );
+    }
 }
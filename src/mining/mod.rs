@@ -2,6 +2,11 @@
 
 pub mod mempool;
 pub mod miner;
+pub mod policy;
 
-pub use mempool::{Mempool, MempoolEntry, MempoolError, MempoolStats};
+pub use mempool::{
+    BlockAssembly, Mempool, MempoolEntry, MempoolError, MempoolStats, ReplacedTransaction,
+    BLOCK_WEIGHT_RESERVED, DEFAULT_MAX_ORPHAN_TX_MB, MEMPOOL_EXPIRY_SECS, ORPHAN_TX_EXPIRE_SECS,
+};
 pub use miner::{Miner, MiningStats};
+pub use policy::{AcceptAll, MempoolPolicy, SharedPolicy};
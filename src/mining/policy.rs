@@ -0,0 +1,29 @@
+//! Custom mempool admission policies
+//!
+//! Operators can plug extra rules into the mempool (e.g. refusing payments
+//! to blocklisted addresses) without forking the crate. Every policy added
+//! to a [`Mempool`](super::Mempool) is consulted after the built-in
+//! standardness checks; the first one to object rejects the transaction.
+
+use crate::core::{Blockchain, Transaction};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// An extra rule a transaction must pass to enter the mempool
+pub trait MempoolPolicy: Debug + Send + Sync {
+    /// `Err` with a reason to refuse `tx`
+    fn check(&self, tx: &Transaction, chain: &Blockchain) -> Result<(), String>;
+}
+
+/// Shared handle to a policy
+pub type SharedPolicy = Arc<dyn MempoolPolicy>;
+
+/// Policy that accepts every transaction
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcceptAll;
+
+impl MempoolPolicy for AcceptAll {
+    fn check(&self, _tx: &Transaction, _chain: &Blockchain) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -257,6 +257,11 @@ impl AddrManager {
         }
     }
 
+    /// Look up an address entry
+    pub fn get(&self, addr: &str) -> Option<&AddrEntry> {
+        self.by_addr.get(addr)
+    }
+
     /// Mark address as connected
     pub fn connected(&mut self, addr: &str) {
         self.connected.insert(addr.to_string());
@@ -293,7 +298,10 @@ impl AddrManager {
             .by_addr
             .iter()
             .filter(|(k, e)| {
-                !self.connected.contains(*k) && !e.is_terrible() && (use_new == !e.in_tried)
+                !self.connected.contains(*k)
+                    && e.addr.is_routable()
+                    && !e.is_terrible()
+                    && (use_new == !e.in_tried)
             })
             .collect();
 
@@ -325,7 +333,7 @@ impl AddrManager {
         let mut addrs: Vec<_> = self
             .by_addr
             .values()
-            .filter(|e| !e.is_terrible())
+            .filter(|e| e.addr.is_routable() && !e.is_terrible())
             .map(|e| e.addr.clone())
             .collect();
 
@@ -487,6 +495,21 @@ mod tests {
         assert_eq!(mgr.tried_count(), 1);
     }
 
+    #[test]
+    fn test_attempt_only_updates_known_addresses() {
+        let mut mgr = AddrManager::new();
+
+        let addr = NetAddr::new("8.8.8.8".to_string(), 8333, ServiceFlags::NODE_NETWORK);
+        mgr.add(addr, None);
+        mgr.attempt("8.8.8.8:8333");
+        assert_eq!(mgr.get("8.8.8.8:8333").unwrap().attempts, 1);
+
+        // Failures against addresses we never learned don't add them
+        mgr.attempt("9.9.9.9:8333");
+        assert!(mgr.get("9.9.9.9:8333").is_none());
+        assert_eq!(mgr.new_count(), 1);
+    }
+
     #[test]
     fn test_select_address() {
         let mut mgr = AddrManager::new();
@@ -513,4 +536,28 @@ mod tests {
         let addrs = mgr.get_addr(5);
         assert_eq!(addrs.len(), 5);
     }
+
+    #[test]
+    fn test_ipv6_addresses_round_trip() {
+        let mut mgr = AddrManager::new();
+
+        let addr =
+            NetAddr::from_addr_str("[2001:db8::1]:8333", ServiceFlags::NODE_NETWORK).unwrap();
+        assert_eq!(addr.ip, "2001:db8::1");
+        assert_eq!(addr.to_addr_string(), "[2001:db8::1]:8333");
+        assert!(mgr.add(addr, None));
+
+        // The stored key is a socket address a node can dial
+        let selected = mgr.select(true).unwrap().to_addr_string();
+        let socket_addr: std::net::SocketAddr = selected.parse().unwrap();
+        assert!(socket_addr.is_ipv6());
+        assert_eq!(socket_addr.port(), 8333);
+        mgr.good(&selected);
+        assert_eq!(mgr.tried_count(), 1);
+
+        for local in ["[::1]:8333", "[fe80::1]:8333", "[fd00::1]:8333"] {
+            let addr = NetAddr::from_addr_str(local, ServiceFlags::NODE_NETWORK).unwrap();
+            assert!(!mgr.add(addr, None));
+        }
+    }
 }
@@ -6,8 +6,9 @@
 //! - Version negotiation
 //! - Reject messages for misbehavior
 
-use crate::core::{Block, Transaction};
+use crate::core::{Block, Transaction, DEFAULT_CHAIN_ID};
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 
 // =============================================================================
 // Protocol Constants
@@ -19,6 +20,10 @@ pub const PROTOCOL_VERSION: u32 = 70001;
 /// Minimum supported protocol version
 pub const MIN_PROTOCOL_VERSION: u32 = 70000;
 
+/// Services this node advertises to peers
+pub const LOCAL_SERVICES: ServiceFlags =
+    ServiceFlags(ServiceFlags::NODE_NETWORK.0 | ServiceFlags::NODE_SMART_TX.0);
+
 /// Magic bytes for message framing (network identification)
 pub const MAGIC_MAINNET: [u8; 4] = [0x4D, 0x49, 0x4E, 0x49]; // "MINI"
 pub const MAGIC_TESTNET: [u8; 4] = [0x54, 0x45, 0x53, 0x54]; // "TEST"
@@ -26,9 +31,28 @@ pub const MAGIC_TESTNET: [u8; 4] = [0x54, 0x45, 0x53, 0x54]; // "TEST"
 /// Default magic (mainnet)
 pub const MAGIC: [u8; 4] = MAGIC_MAINNET;
 
+/// Magic bytes for the network with the given chain ID.
+///
+/// The default chain uses `MAGIC_MAINNET`; every other chain ID maps to a
+/// distinct value, so nodes on different chains reject each other's frames.
+pub fn magic_for_chain_id(chain_id: u32) -> [u8; 4] {
+    let offset = (chain_id ^ DEFAULT_CHAIN_ID).to_be_bytes();
+    let mut magic = MAGIC_MAINNET;
+    for (byte, delta) in magic.iter_mut().zip(offset) {
+        *byte ^= delta;
+    }
+    magic
+}
+
 /// Maximum message size (16 MB)
 pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
 
+/// Maximum size of a control message (version, pings, requests)
+pub const MAX_CONTROL_MESSAGE_SIZE: usize = 8 * 1024;
+
+/// Maximum size of an inventory, address or header list
+pub const MAX_INVENTORY_MESSAGE_SIZE: usize = 2 * 1024 * 1024;
+
 /// Maximum blocks per GetBlocks request
 pub const MAX_BLOCKS_PER_REQUEST: u32 = 500;
 
@@ -38,6 +62,9 @@ pub const MAX_HEADERS_PER_REQUEST: u32 = 2000;
 /// Maximum addresses per Addr message
 pub const MAX_ADDR_PER_MESSAGE: usize = 1000;
 
+/// Maximum items per Inv message
+pub const MAX_INV_PER_MESSAGE: usize = 10_000;
+
 /// Header size: Magic(4) + Command(12) + Length(4) + Checksum(4)
 pub const HEADER_SIZE: usize = 24;
 
@@ -68,25 +95,48 @@ impl NetAddr {
         }
     }
 
-    /// Create from socket address string
+    /// Create from socket address string (`1.2.3.4:8333`, `[::1]:8333` or
+    /// `host:8333`)
     pub fn from_addr_str(addr: &str, services: ServiceFlags) -> Option<Self> {
+        if let Ok(socket_addr) = addr.parse::<SocketAddr>() {
+            return Some(Self::new(
+                socket_addr.ip().to_string(),
+                socket_addr.port(),
+                services,
+            ));
+        }
+
         let parts: Vec<&str> = addr.rsplitn(2, ':').collect();
         if parts.len() == 2 {
             let port = parts[0].parse().ok()?;
-            let ip = parts[1].to_string();
-            Some(Self::new(ip, port, services))
+            let ip = parts[1].trim_start_matches('[').trim_end_matches(']');
+            Some(Self::new(ip.to_string(), port, services))
         } else {
             None
         }
     }
 
-    /// Get as socket address string
+    /// Get as socket address string, bracketing IPv6 addresses
     pub fn to_addr_string(&self) -> String {
-        format!("{}:{}", self.ip, self.port)
+        if self.ip.contains(':') {
+            format!("[{}]:{}", self.ip, self.port)
+        } else {
+            format!("{}:{}", self.ip, self.port)
+        }
     }
 
     /// Check if this address is routable (not local/private)
     pub fn is_routable(&self) -> bool {
+        // Loopback, unspecified, unique local (fc00::/7) and link-local
+        // (fe80::/10) IPv6 addresses stay off the network
+        if let Ok(IpAddr::V6(ip)) = self.ip.parse::<IpAddr>() {
+            let first = ip.segments()[0];
+            return !(ip.is_loopback()
+                || ip.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80);
+        }
+
         // Simple check - exclude localhost and private ranges
         !self.ip.starts_with("127.")
             && !self.ip.starts_with("10.")
@@ -115,6 +165,8 @@ impl ServiceFlags {
     pub const NODE_COMPACT_FILTERS: ServiceFlags = ServiceFlags(1 << 6);
     /// Node serves historical blocks (not pruned)
     pub const NODE_NETWORK_LIMITED: ServiceFlags = ServiceFlags(1 << 10);
+    /// Node relays token and contract transactions
+    pub const NODE_SMART_TX: ServiceFlags = ServiceFlags(1 << 24);
     /// Empty flags
     pub const NONE: ServiceFlags = ServiceFlags(0);
 
@@ -132,6 +184,22 @@ impl ServiceFlags {
     pub fn remove(&mut self, flag: ServiceFlags) {
         self.0 &= !flag.0;
     }
+
+    /// Names of the known flags that are set
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (Self::NODE_NETWORK, "NETWORK"),
+            (Self::NODE_BLOOM, "BLOOM"),
+            (Self::NODE_WITNESS, "WITNESS"),
+            (Self::NODE_COMPACT_FILTERS, "COMPACT_FILTERS"),
+            (Self::NODE_NETWORK_LIMITED, "NETWORK_LIMITED"),
+            (Self::NODE_SMART_TX, "SMART_TX"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.contains(*flag))
+        .map(|(_, name)| name)
+        .collect()
+    }
 }
 
 impl std::ops::BitOr for ServiceFlags {
@@ -159,6 +227,8 @@ pub enum Message {
     NewBlock(Block),
     /// Announce a new transaction
     NewTransaction(Transaction),
+    /// Relay a transaction along a single-peer stem path (Dandelion)
+    StemTransaction { tx: Transaction, hops_remaining: u8 },
 
     /// Request blocks by height range
     GetBlocks { start_height: u64, count: u32 },
@@ -249,6 +319,9 @@ pub struct VersionMessage {
     pub start_height: u64,
     /// Whether to relay transactions
     pub relay: bool,
+    /// Hash of the sender's genesis block (empty if not sent)
+    #[serde(default)]
+    pub genesis_hash: String,
 }
 
 impl VersionMessage {
@@ -263,9 +336,16 @@ impl VersionMessage {
             user_agent: format!("mini-blockchain/{}", env!("CARGO_PKG_VERSION")),
             start_height: height,
             relay: true,
+            genesis_hash: String::new(),
         }
     }
 
+    /// Announce the genesis block we are building on
+    pub fn with_genesis_hash(mut self, genesis_hash: String) -> Self {
+        self.genesis_hash = genesis_hash;
+        self
+    }
+
     /// Check if version is compatible
     pub fn is_compatible(&self) -> bool {
         self.version >= MIN_PROTOCOL_VERSION
@@ -284,6 +364,24 @@ pub struct Handshake {
     pub best_hash: String,
     pub listen_port: u16,
     pub user_agent: String,
+    /// Peers that predate this field relay every transaction
+    #[serde(default = "default_handshake_services")]
+    pub services: ServiceFlags,
+    /// Hash of the sender's genesis block (empty if not sent)
+    #[serde(default)]
+    pub genesis_hash: String,
+    /// Whether the sender wants transactions announced to it; false makes
+    /// the connection block-relay-only
+    #[serde(default = "default_handshake_relay")]
+    pub relay: bool,
+}
+
+fn default_handshake_services() -> ServiceFlags {
+    LOCAL_SERVICES
+}
+
+fn default_handshake_relay() -> bool {
+    true
 }
 
 impl Handshake {
@@ -294,8 +392,29 @@ impl Handshake {
             best_hash,
             listen_port,
             user_agent: format!("mini-blockchain/{}", env!("CARGO_PKG_VERSION")),
+            services: LOCAL_SERVICES,
+            genesis_hash: String::new(),
+            relay: true,
         }
     }
+
+    /// Advertise `services` instead of [`LOCAL_SERVICES`]
+    pub fn with_services(mut self, services: ServiceFlags) -> Self {
+        self.services = services;
+        self
+    }
+
+    /// Announce the genesis block we are building on
+    pub fn with_genesis_hash(mut self, genesis_hash: String) -> Self {
+        self.genesis_hash = genesis_hash;
+        self
+    }
+
+    /// Ask for transaction relay (`true`) or only blocks (`false`)
+    pub fn with_relay(mut self, relay: bool) -> Self {
+        self.relay = relay;
+        self
+    }
 }
 
 // =============================================================================
@@ -415,6 +534,102 @@ impl RejectMessage {
     }
 }
 
+// =============================================================================
+// Message Size Limits
+// =============================================================================
+
+/// Message types that are always small
+const CONTROL_MESSAGES: &[&str] = &[
+    "Version",
+    "VerAck",
+    "Handshake",
+    "GetBlocks",
+    "GetHeaders",
+    "GetPeers",
+    "Ping",
+    "Pong",
+    "GetHeight",
+    "Height",
+    "Reject",
+    "FeeFilter",
+    "SendCmpct",
+    "GetAddr",
+];
+
+/// Message types carrying bounded lists of hashes, addresses or headers
+const INVENTORY_MESSAGES: &[&str] = &[
+    "Headers",
+    "Peers",
+    "Inv",
+    "GetData",
+    "NotFound",
+    "GetBlockTxn",
+    "Addr",
+];
+
+/// Size category of a message type, each with its own limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageSizeClass {
+    /// Handshakes, pings and requests
+    Control,
+    /// Lists of inventory, addresses or headers
+    Inventory,
+    /// Blocks and transactions (and unknown commands)
+    Payload,
+}
+
+impl MessageSizeClass {
+    /// Classify a 12-byte wire command
+    pub fn of_command(command: &[u8]) -> Self {
+        let is = |names: &[&str]| names.iter().any(|name| command_bytes(name) == command);
+        if is(CONTROL_MESSAGES) {
+            MessageSizeClass::Control
+        } else if is(INVENTORY_MESSAGES) {
+            MessageSizeClass::Inventory
+        } else {
+            MessageSizeClass::Payload
+        }
+    }
+}
+
+/// Maximum payload size for each message size class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageSizeLimits {
+    pub control: usize,
+    pub inventory: usize,
+    pub payload: usize,
+}
+
+impl Default for MessageSizeLimits {
+    fn default() -> Self {
+        Self {
+            control: MAX_CONTROL_MESSAGE_SIZE,
+            inventory: MAX_INVENTORY_MESSAGE_SIZE,
+            payload: MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl MessageSizeLimits {
+    /// Limit for messages of the given class
+    pub fn for_class(&self, class: MessageSizeClass) -> usize {
+        match class {
+            MessageSizeClass::Control => self.control,
+            MessageSizeClass::Inventory => self.inventory,
+            MessageSizeClass::Payload => self.payload,
+        }
+    }
+}
+
+/// Null-padded 12-byte wire command for a message type name
+fn command_bytes(name: &str) -> [u8; 12] {
+    let mut cmd = [0u8; 12];
+    let bytes = name.as_bytes();
+    let len = bytes.len().min(12);
+    cmd[..len].copy_from_slice(&bytes[..len]);
+    cmd
+}
+
 // =============================================================================
 // Message Implementation
 // =============================================================================
@@ -438,6 +653,7 @@ impl Message {
             Message::Handshake(_) => "Handshake",
             Message::NewBlock(_) => "NewBlock",
             Message::NewTransaction(_) => "NewTransaction",
+            Message::StemTransaction { .. } => "StemTx",
             Message::GetBlocks { .. } => "GetBlocks",
             Message::Blocks(_) => "Blocks",
             Message::GetHeaders { .. } => "GetHeaders",
@@ -471,12 +687,12 @@ impl Message {
 
     /// Get the 12-byte command name for wire protocol
     pub fn command(&self) -> [u8; 12] {
-        let name = self.type_name();
-        let mut cmd = [0u8; 12];
-        let bytes = name.as_bytes();
-        let len = bytes.len().min(12);
-        cmd[..len].copy_from_slice(&bytes[..len]);
-        cmd
+        command_bytes(self.type_name())
+    }
+
+    /// Size category that bounds this message on the wire
+    pub fn size_class(&self) -> MessageSizeClass {
+        MessageSizeClass::of_command(&self.command())
     }
 
     /// Check if this is a high-bandwidth message
@@ -534,6 +750,7 @@ mod tests {
         assert!(flags.contains(ServiceFlags::NODE_NETWORK));
         assert!(flags.contains(ServiceFlags::NODE_BLOOM));
         assert!(!flags.contains(ServiceFlags::NODE_WITNESS));
+        assert_eq!(flags.names(), vec!["NETWORK", "BLOOM"]);
     }
 
     #[test]
@@ -26,17 +26,28 @@ pub mod upnp;
 pub use addrman::{AddrEntry, AddrManager};
 pub use discovery::{DiscoveryStats, PeerDiscovery, DEFAULT_DNS_SEEDS};
 pub use message::{
-    BlockHeader as NetworkBlockHeader, CompactBlock, Handshake, InvItem, InvType, Message, NetAddr,
-    RejectCode, RejectMessage, ServiceFlags, VersionMessage, HEADER_SIZE, MAGIC,
-    MAX_ADDR_PER_MESSAGE, MAX_MESSAGE_SIZE, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+    magic_for_chain_id, BlockHeader as NetworkBlockHeader, CompactBlock, Handshake, InvItem,
+    InvType, Message, MessageSizeClass, MessageSizeLimits, NetAddr, RejectCode, RejectMessage,
+    ServiceFlags, VersionMessage, HEADER_SIZE, LOCAL_SERVICES, MAGIC, MAGIC_MAINNET, MAGIC_TESTNET,
+    MAX_ADDR_PER_MESSAGE, MAX_CONTROL_MESSAGE_SIZE, MAX_INVENTORY_MESSAGE_SIZE,
+    MAX_INV_PER_MESSAGE, MAX_MESSAGE_SIZE, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+pub use node::{
+    DandelionConfig, InvQueue, Node, NodeConfig, NodeStatus, ValidationLimiter,
+    DEFAULT_INV_BATCH_INTERVAL, DEFAULT_MAX_CONCURRENT_VALIDATIONS, DEFAULT_STEM_HOPS,
+    DEFAULT_STEM_TIMEOUT,
 };
-pub use node::{Node, NodeConfig, NodeStatus};
 pub use parallel_sync::{ParallelSync, ParallelSyncStats, SyncError};
 pub use peer::{
-    BanEntry, Misbehavior, PeerError, PeerHandle, PeerInfo, PeerManager, PeerManagerStats,
-    PeerState, RateLimitStats, RateLimiter, BAN_SCORE, DEFAULT_BAN_DURATION, DEFAULT_PEER_SCORE,
-    DISCONNECT_SCORE, MAX_INBOUND, MAX_OUTBOUND, MAX_PEERS,
+    BanEntry, BanRecord, Misbehavior, PeerError, PeerHandle, PeerInfo, PeerManager,
+    PeerManagerStats, PeerState, RateLimitStats, RateLimiter, TxPropagation, BAN_LIST_FILE,
+    BAN_SCORE, DEFAULT_BAN_DURATION, DEFAULT_PEER_SCORE, DEFAULT_RESERVED_SLOTS, DISCONNECT_SCORE,
+    MAX_INBOUND, MAX_OUTBOUND, MAX_PEERS, MAX_TRACKED_BROADCASTS,
+};
+pub use server::{
+    connect_to_peer, connect_to_peer_with_timeout, frame_stream, misbehavior_for_read_error,
+    perform_handshake, MessageCodec, OversizedMessage, PeerStream, Server, DEFAULT_CONNECT_TIMEOUT,
+    DEFAULT_HANDSHAKE_TIMEOUT,
 };
-pub use server::{connect_to_peer, MessageCodec, Server};
-pub use sync::ChainSync;
+pub use sync::{ChainSync, SyncOutcome};
 pub use upnp::{UpnpError, UpnpManager, UpnpStatus};
@@ -2,49 +2,280 @@
 //!
 //! The main node that orchestrates all networking components.
 
-use crate::core::{Blockchain, Transaction};
+use crate::core::{Blockchain, ChainEvent, EventBus, Transaction};
 use crate::mining::Mempool;
-use crate::network::message::{Handshake, Message};
-use crate::network::peer::{PeerError, PeerManager};
-use crate::network::server::{connect_to_peer, handle_connection, Server};
+use crate::network::addrman::AddrManager;
+use crate::network::message::{
+    Handshake, InvItem, InvType, Message, NetAddr, ServiceFlags, LOCAL_SERVICES, MAGIC,
+    MAX_INV_PER_MESSAGE,
+};
+use crate::network::parallel_sync::{ParallelSync, PARALLEL_DOWNLOAD_THRESHOLD};
+use crate::network::peer::{PeerError, PeerManager, BAN_LIST_FILE, DEFAULT_RESERVED_SLOTS};
+use crate::network::server::{
+    connect_to_peer_with_timeout, frame_stream, handle_connection, perform_handshake,
+    run_connection, PeerStream, Server, DEFAULT_CONNECT_TIMEOUT, DEFAULT_HANDSHAKE_TIMEOUT,
+};
 use crate::network::sync::ChainSync;
 use crate::storage::Storage;
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, OwnedSemaphorePermit, RwLock, Semaphore};
+
+/// Default number of stem hops before a transaction is fluffed
+pub const DEFAULT_STEM_HOPS: u8 = 2;
+
+/// Default time after which a stemmed transaction is broadcast anyway
+pub const DEFAULT_STEM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default delay between transaction inventory announcements to a peer
+pub const DEFAULT_INV_BATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default number of blocks from peers validated at once
+pub const DEFAULT_MAX_CONCURRENT_VALIDATIONS: usize = 4;
+
+/// How often parallel sync requests are checked for timeouts
+const SYNC_TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Dandelion-style transaction relay settings
+///
+/// In the stem phase a locally created transaction is passed to a single
+/// random peer per hop, hiding its origin; the last hop (or the origin, once
+/// `stem_timeout` elapses) broadcasts it to everyone (the fluff phase).
+#[derive(Debug, Clone)]
+pub struct DandelionConfig {
+    /// Use the stem phase for local transactions (default off)
+    pub enabled: bool,
+    /// Hops along the stem before fluffing
+    pub stem_hops: u8,
+    /// Fail-safe delay after which the origin fluffs the transaction itself
+    pub stem_timeout: Duration,
+}
+
+impl Default for DandelionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stem_hops: DEFAULT_STEM_HOPS,
+            stem_timeout: DEFAULT_STEM_TIMEOUT,
+        }
+    }
+}
 
 /// P2P Node configuration
 #[derive(Clone)]
 pub struct NodeConfig {
+    /// Interface to listen on (IPv4 or IPv6); `0.0.0.0` accepts peers on
+    /// every interface, `127.0.0.1` only local ones
+    pub bind_address: IpAddr,
     /// Port to listen on
     pub port: u16,
     /// Initial peers to connect to
     pub bootstrap_peers: Vec<String>,
     /// Data directory for blockchain storage
     pub data_dir: std::path::PathBuf,
+    /// Transaction relay privacy settings
+    pub dandelion: DandelionConfig,
+    /// Time allowed to establish an outbound TCP connection
+    pub connect_timeout: Duration,
+    /// Time allowed for a peer to send its handshake
+    pub handshake_timeout: Duration,
+    /// Network magic bytes; peers with different magic cannot connect
+    /// (see `magic_for_chain_id`)
+    pub magic: [u8; 4],
+    /// How long relayed transactions are collected before one Inv per peer
+    /// announces them (zero announces each one immediately)
+    pub inv_batch_interval: Duration,
+    /// Blocks from peers validated at once; further blocks wait their turn
+    pub max_concurrent_validations: usize,
+    /// Peer IPs that bypass connection limits and are never evicted or banned
+    pub whitelist: Vec<IpAddr>,
+    /// Connection slots only whitelisted peers may use
+    pub reserved_slots: usize,
+    /// Accept and relay token and contract transactions; payment-only
+    /// nodes turn this off and stop advertising `NODE_SMART_TX`
+    pub relay_smart_txs: bool,
+    /// Peers required before locally mined blocks are announced; until
+    /// then (and until synced with them) they are held (0 disables)
+    pub min_peers_to_announce: usize,
+    /// Peers connected to as block-relay-only: blocks are exchanged with
+    /// them but transactions are never announced or accepted
+    pub block_relay_only_peers: Vec<String>,
 }
 
 impl Default for NodeConfig {
     fn default() -> Self {
         Self {
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             port: 8333,
             bootstrap_peers: Vec::new(),
             data_dir: std::path::PathBuf::from(".blockchain_data"),
+            dandelion: DandelionConfig::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            magic: MAGIC,
+            inv_batch_interval: DEFAULT_INV_BATCH_INTERVAL,
+            max_concurrent_validations: DEFAULT_MAX_CONCURRENT_VALIDATIONS,
+            whitelist: Vec::new(),
+            reserved_slots: DEFAULT_RESERVED_SLOTS,
+            relay_smart_txs: true,
+            min_peers_to_announce: 0,
+            block_relay_only_peers: Vec::new(),
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Service flags advertised in our handshake
+    pub fn services(&self) -> ServiceFlags {
+        let mut services = LOCAL_SERVICES;
+        if !self.relay_smart_txs {
+            services.remove(ServiceFlags::NODE_SMART_TX);
+        }
+        services
+    }
+
+    /// Service flags advertised for `chain`: a pruned chain can't serve
+    /// old blocks, so it signals `NODE_NETWORK_LIMITED` instead of
+    /// `NODE_NETWORK`
+    pub fn services_for(&self, chain: &Blockchain) -> ServiceFlags {
+        let mut services = self.services();
+        if chain.is_pruned() {
+            services.remove(ServiceFlags::NODE_NETWORK);
+            services.insert(ServiceFlags::NODE_NETWORK_LIMITED);
+        }
+        services
+    }
+}
+
+/// Bounds how many block validations run at once
+///
+/// Block messages from peers are validated on their own tasks. Once the
+/// limit is reached the message loop waits for a free slot, so further
+/// messages queue in arrival order and a flood of gossiped blocks can't
+/// pile up unbounded work.
+#[derive(Debug, Clone)]
+pub struct ValidationLimiter {
+    slots: Arc<Semaphore>,
+    max: usize,
+}
+
+impl ValidationLimiter {
+    /// Allow `max` validations at once (at least one)
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// Wait for a free slot, held until the returned permit is dropped
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("validation semaphore is never closed")
+    }
+
+    /// Validations currently running
+    pub fn in_flight(&self) -> usize {
+        self.max - self.slots.available_permits()
+    }
+
+    /// Most validations allowed at once
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+/// Transaction announcements waiting for the next Inv to each peer
+///
+/// Batching cuts the number of messages and hides which peer a transaction
+/// was first heard from behind the batch timer.
+#[derive(Debug, Default)]
+pub struct InvQueue {
+    pending: HashMap<SocketAddr, Vec<InvItem>>,
+    queued: HashSet<(SocketAddr, String)>,
+}
+
+impl InvQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an item for `peer`; false if it is already queued
+    pub fn push(&mut self, peer: SocketAddr, item: InvItem) -> bool {
+        if !self.queued.insert((peer, item.hash.clone())) {
+            return false;
+        }
+        self.pending.entry(peer).or_default().push(item);
+        true
+    }
+
+    /// Number of queued announcements across all peers
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Whether nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    /// Take everything queued as Inv messages, at most
+    /// [`MAX_INV_PER_MESSAGE`] items each
+    pub fn drain(&mut self) -> Vec<(SocketAddr, Message)> {
+        self.queued.clear();
+        self.pending
+            .drain()
+            .flat_map(|(peer, items)| {
+                items
+                    .chunks(MAX_INV_PER_MESSAGE)
+                    .map(|chunk| (peer, Message::Inv(chunk.to_vec())))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Send every queued announcement
+async fn flush_inv_queue(queue: &RwLock<InvQueue>, peer_manager: &PeerManager) {
+    let messages = queue.write().await.drain();
+    for (peer, msg) in messages {
+        if let Err(e) = peer_manager.send_to(&peer, msg).await {
+            log::debug!("Failed to announce inventory to {}: {}", peer, e);
         }
     }
 }
 
 /// The main P2P node
+///
+/// Clones are handles sharing the same chain, mempool and peers.
+#[derive(Clone)]
 pub struct Node {
     pub config: NodeConfig,
     pub blockchain: Arc<RwLock<Blockchain>>,
     pub mempool: Arc<RwLock<Mempool>>,
     pub peer_manager: Arc<PeerManager>,
     pub chain_sync: Arc<ChainSync>,
+    /// Multi-peer download used when far behind the best peer
+    pub parallel_sync: Arc<ParallelSync>,
     pub storage: Arc<Storage>,
+    /// Known addresses and their connection history
+    pub addr_manager: Arc<RwLock<AddrManager>>,
     shutdown_tx: Option<mpsc::Sender<()>>,
     /// Message channel sender - set after start() is called
     message_tx: Option<mpsc::Sender<(SocketAddr, Message)>>,
+    /// Bus that blocks and reorgs from peers are published on
+    events: Option<Arc<EventBus>>,
+    /// Transactions waiting to be announced
+    inv_queue: Arc<RwLock<InvQueue>>,
+    /// Bound on concurrent validation of blocks from peers
+    validations: ValidationLimiter,
 }
 
 impl Node {
@@ -69,8 +300,22 @@ impl Node {
         };
 
         let mempool = Arc::new(RwLock::new(Mempool::new()));
-        let peer_manager = Arc::new(PeerManager::new(config.port));
+        let peer_manager = Arc::new(
+            PeerManager::new(config.port)
+                .with_whitelist(config.whitelist.iter().copied(), config.reserved_slots)
+                .with_min_peers_to_announce(config.min_peers_to_announce),
+        );
+        let restored = peer_manager
+            .load_bans(&config.data_dir.join(BAN_LIST_FILE))
+            .await?;
+        if restored > 0 {
+            log::info!("Restored {} peer bans", restored);
+        }
+        let genesis_hash = blockchain.read().await.genesis_hash().to_string();
+        peer_manager.set_genesis_hash(genesis_hash).await;
         let chain_sync = Arc::new(ChainSync::new(blockchain.clone(), peer_manager.clone()));
+        let parallel_sync = Arc::new(ParallelSync::new(blockchain.clone(), peer_manager.clone()));
+        let validations = ValidationLimiter::new(config.max_concurrent_validations);
 
         Ok(Self {
             config,
@@ -78,9 +323,14 @@ impl Node {
             mempool,
             peer_manager,
             chain_sync,
+            parallel_sync,
             storage,
+            addr_manager: Arc::new(RwLock::new(AddrManager::new())),
             shutdown_tx: None,
             message_tx: None,
+            events: None,
+            inv_queue: Arc::new(RwLock::new(InvQueue::new())),
+            validations,
         })
     }
 
@@ -93,8 +343,14 @@ impl Node {
         mempool: Arc<RwLock<Mempool>>,
         storage: Arc<Storage>,
     ) -> Self {
-        let peer_manager = Arc::new(PeerManager::new(config.port));
+        let peer_manager = Arc::new(
+            PeerManager::new(config.port)
+                .with_whitelist(config.whitelist.iter().copied(), config.reserved_slots)
+                .with_min_peers_to_announce(config.min_peers_to_announce),
+        );
         let chain_sync = Arc::new(ChainSync::new(blockchain.clone(), peer_manager.clone()));
+        let parallel_sync = Arc::new(ParallelSync::new(blockchain.clone(), peer_manager.clone()));
+        let validations = ValidationLimiter::new(config.max_concurrent_validations);
 
         Self {
             config,
@@ -102,9 +358,14 @@ impl Node {
             mempool,
             peer_manager,
             chain_sync,
+            parallel_sync,
             storage,
+            addr_manager: Arc::new(RwLock::new(AddrManager::new())),
             shutdown_tx: None,
             message_tx: None,
+            events: None,
+            inv_queue: Arc::new(RwLock::new(InvQueue::new())),
+            validations,
         }
     }
 
@@ -119,6 +380,8 @@ impl Node {
         peer_manager: Arc<PeerManager>,
     ) -> Self {
         let chain_sync = Arc::new(ChainSync::new(blockchain.clone(), peer_manager.clone()));
+        let parallel_sync = Arc::new(ParallelSync::new(blockchain.clone(), peer_manager.clone()));
+        let validations = ValidationLimiter::new(config.max_concurrent_validations);
 
         Self {
             config,
@@ -126,12 +389,48 @@ impl Node {
             mempool,
             peer_manager,
             chain_sync,
+            parallel_sync,
             storage,
+            addr_manager: Arc::new(RwLock::new(AddrManager::new())),
             shutdown_tx: None,
             message_tx: None,
+            events: None,
+            inv_queue: Arc::new(RwLock::new(InvQueue::new())),
+            validations,
+        }
+    }
+
+    /// Publish blocks connected from peers, and any reorgs they cause, on
+    /// `events`
+    pub fn set_event_bus(&mut self, events: Arc<EventBus>) {
+        self.events = Some(events);
+    }
+
+    /// Newest recorded reorg, used to detect reorgs caused by a message
+    async fn newest_reorg(&self) -> Option<crate::core::ReorgEvent> {
+        let chain = self.blockchain.read().await;
+        chain.recent_reorgs(1).first().map(|&event| event.clone())
+    }
+
+    /// Publish a reorg on the event bus if one happened since `before` was
+    /// the newest recorded reorg
+    async fn publish_reorg_since(&self, before: Option<crate::core::ReorgEvent>) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let chain = self.blockchain.read().await;
+        if let Some(latest) = chain.recent_reorgs(1).first() {
+            if before.as_ref() != Some(*latest) {
+                events.publish(ChainEvent::Reorg((*latest).clone()));
+            }
         }
     }
 
+    /// Limiter bounding concurrent validation of blocks from peers
+    pub fn validation_limiter(&self) -> &ValidationLimiter {
+        &self.validations
+    }
+
     /// Get the peer manager (for broadcasting blocks from external sources)
     pub fn peer_manager(&self) -> Arc<PeerManager> {
         self.peer_manager.clone()
@@ -142,9 +441,12 @@ impl Node {
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         self.shutdown_tx = Some(shutdown_tx);
 
+        let genesis_hash = self.blockchain.read().await.genesis_hash().to_string();
+        self.peer_manager.set_genesis_hash(genesis_hash).await;
+
         // Start server
-        let server = Server::bind(self.config.port).await?;
-        log::info!("Node started on port {}", self.config.port);
+        let server = Server::bind(self.config.bind_address, self.config.port).await?;
+        log::info!("Node started on {}", server.local_addr()?);
 
         // Create message channel and store it for use by connect_to
         let (message_tx, mut message_rx) = mpsc::channel::<(SocketAddr, Message)>(1000);
@@ -157,6 +459,9 @@ impl Node {
         let _chain_sync = self.chain_sync.clone();
         let _storage = self.storage.clone();
         let port = self.config.port;
+        let config = self.config.clone();
+        let handshake_timeout = self.config.handshake_timeout;
+        let magic = self.config.magic;
 
         // Spawn connection acceptor
         let accept_peer_manager = peer_manager.clone();
@@ -171,13 +476,23 @@ impl Node {
                         let handshake = {
                             let chain = accept_blockchain.read().await;
                             Handshake::new(chain.height(), chain.latest_block().hash.clone(), port)
+                                .with_services(config.services_for(&chain))
+                                .with_genesis_hash(chain.genesis_hash().to_string())
                         };
 
                         let pm = accept_peer_manager.clone();
                         let tx = accept_message_tx.clone();
                         tokio::spawn(async move {
-                            if let Err(e) =
-                                handle_connection(stream, addr, pm, handshake, tx, false).await
+                            if let Err(e) = handle_connection(
+                                frame_stream(stream, magic),
+                                addr,
+                                pm,
+                                handshake,
+                                tx,
+                                false,
+                                handshake_timeout,
+                            )
+                            .await
                             {
                                 log::warn!("Connection error with {}: {}", addr, e);
                             }
@@ -190,16 +505,34 @@ impl Node {
             }
         });
 
+        // Announce queued transactions on the batch timer
+        if !self.config.inv_batch_interval.is_zero() {
+            let inv_queue = self.inv_queue.clone();
+            let inv_peer_manager = peer_manager.clone();
+            let mut ticker = tokio::time::interval(self.config.inv_batch_interval);
+            tokio::spawn(async move {
+                loop {
+                    ticker.tick().await;
+                    flush_inv_queue(&inv_queue, &inv_peer_manager).await;
+                }
+            });
+        }
+
+        self.spawn_sync_watcher();
+
         // Connect to bootstrap peers
         for peer_addr in &self.config.bootstrap_peers {
             let _ = self.connect_to(peer_addr).await;
         }
+        for peer_addr in &self.config.block_relay_only_peers {
+            let _ = self.connect_block_relay_only(peer_addr).await;
+        }
 
         // Message handling loop
         loop {
             tokio::select! {
                 Some((from, msg)) = message_rx.recv() => {
-                    self.handle_message(from, msg).await;
+                    self.dispatch_message(from, msg).await;
                 }
                 _ = shutdown_rx.recv() => {
                     log::info!("Node shutting down...");
@@ -212,19 +545,32 @@ impl Node {
     }
 
     /// Connect to a peer
+    ///
+    /// Fails if the connection or handshake does not complete within the
+    /// configured timeouts; failures are recorded in the address manager.
     pub async fn connect_to(&self, addr: &str) -> Result<(), PeerError> {
+        self.connect(addr, true).await
+    }
+
+    /// Connect to a peer that is only sent blocks, never transactions
+    pub async fn connect_block_relay_only(&self, addr: &str) -> Result<(), PeerError> {
+        self.connect(addr, false).await
+    }
+
+    async fn connect(&self, addr: &str, relay: bool) -> Result<(), PeerError> {
         log::info!("Connecting to peer: {}", addr);
 
-        let (stream, peer_addr) = connect_to_peer(addr).await?;
+        let result = self.open_connection(addr, relay).await;
 
-        let handshake = {
-            let chain = self.blockchain.read().await;
-            Handshake::new(
-                chain.height(),
-                chain.latest_block().hash.clone(),
-                self.config.port,
-            )
-        };
+        if let Some(net_addr) = NetAddr::from_addr_str(addr, LOCAL_SERVICES) {
+            let mut addr_manager = self.addr_manager.write().await;
+            match result {
+                Ok(_) => addr_manager.good(&net_addr.to_addr_string()),
+                Err(_) => addr_manager.attempt(&net_addr.to_addr_string()),
+            }
+        }
+
+        let (framed, peer_addr, their_handshake) = result?;
 
         // Use the stored message_tx so messages go to the main handler
         // If start() hasn't been called yet, fall back to a dummy channel
@@ -237,8 +583,16 @@ impl Node {
 
         let pm = self.peer_manager.clone();
         tokio::spawn(async move {
-            if let Err(e) =
-                handle_connection(stream, peer_addr, pm, handshake, message_tx, true).await
+            if let Err(e) = run_connection(
+                framed,
+                peer_addr,
+                pm,
+                their_handshake,
+                message_tx,
+                true,
+                !relay,
+            )
+            .await
             {
                 log::warn!("Connection error with {}: {}", peer_addr, e);
             }
@@ -247,15 +601,126 @@ impl Node {
         Ok(())
     }
 
+    /// Connect to a peer and exchange handshakes
+    async fn open_connection(
+        &self,
+        addr: &str,
+        relay: bool,
+    ) -> Result<(PeerStream, SocketAddr, Message), PeerError> {
+        let (stream, peer_addr) =
+            connect_to_peer_with_timeout(addr, self.config.connect_timeout).await?;
+
+        let handshake = {
+            let chain = self.blockchain.read().await;
+            Handshake::new(
+                chain.height(),
+                chain.latest_block().hash.clone(),
+                self.config.port,
+            )
+            .with_services(self.config.services_for(&chain))
+            .with_genesis_hash(chain.genesis_hash().to_string())
+            .with_relay(relay)
+        };
+
+        let (framed, their_handshake) = perform_handshake(
+            frame_stream(stream, self.config.magic),
+            handshake,
+            self.config.handshake_timeout,
+        )
+        .await?;
+
+        Ok((framed, peer_addr, their_handshake))
+    }
+
+    /// Catch up with the best peer: far behind, blocks are downloaded from
+    /// all peers in parallel; otherwise they come from the best peer alone
+    async fn check_sync(&self) {
+        if self.parallel_sync.is_syncing().await {
+            return;
+        }
+        let our_height = self.blockchain.read().await.height();
+        if let Some((_, peer_height)) = self.peer_manager.get_best_peer().await {
+            if peer_height > our_height + PARALLEL_DOWNLOAD_THRESHOLD {
+                if let Err(e) = self.parallel_sync.start_sync(peer_height).await {
+                    log::warn!("Failed to start parallel sync: {:?}", e);
+                }
+                return;
+            }
+        }
+        self.chain_sync.check_sync().await;
+    }
+
+    /// Hand the outstanding downloads of dropped peers to the remaining
+    /// ones, and retry requests that timed out
+    pub fn spawn_sync_watcher(&self) {
+        let parallel_sync = self.parallel_sync.clone();
+        let mut disconnects = self.peer_manager.subscribe_disconnects();
+        let mut ticker = tokio::time::interval(SYNC_TIMEOUT_CHECK_INTERVAL);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    addr = disconnects.recv() => match addr {
+                        Ok(addr) => parallel_sync.handle_peer_disconnect(&addr).await,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = ticker.tick() => {
+                        if parallel_sync.is_syncing().await {
+                            parallel_sync.check_timeouts().await;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Route a message from a peer to its handler
+    ///
+    /// Blocks are validated on their own task once a validation slot is
+    /// free; every other message is handled inline.
+    pub async fn dispatch_message(&self, from: SocketAddr, msg: Message) {
+        if !matches!(msg, Message::NewBlock(_) | Message::Blocks(_)) {
+            self.handle_message(from, msg).await;
+            return;
+        }
+
+        let slot = self.validations.acquire().await;
+        let node = self.clone();
+        tokio::spawn(async move {
+            node.handle_message(from, msg).await;
+            drop(slot);
+        });
+    }
+
     /// Handle incoming messages
     async fn handle_message(&self, from: SocketAddr, msg: Message) {
         log::debug!("Received {} from {}", msg.type_name(), from);
 
+        // Transactions are never exchanged over block-relay-only connections
+        let is_tx_relay = matches!(
+            msg,
+            Message::NewTransaction(_)
+                | Message::StemTransaction { .. }
+                | Message::Inv(_)
+                | Message::GetData(_)
+        );
+        if is_tx_relay && self.peer_manager.is_block_relay_only(&from).await {
+            log::debug!(
+                "Ignoring {} from block-relay-only peer {}",
+                msg.type_name(),
+                from
+            );
+            return;
+        }
+
         match msg {
             Message::Version(version) => {
                 // Handle version message (new protocol)
                 if let Err(e) = self.peer_manager.update_peer_version(&from, &version).await {
                     log::warn!("Version incompatible from {}: {}", from, e);
+                    if matches!(e, PeerError::GenesisMismatch(_)) {
+                        self.peer_manager.remove_peer(&from).await;
+                    }
                     return;
                 }
                 // Send VerAck
@@ -263,7 +728,8 @@ impl Node {
                     log::warn!("Failed to send VerAck to {}: {}", from, e);
                 }
                 // Check if we need to sync
-                self.chain_sync.check_sync().await;
+                self.check_sync().await;
+                self.release_held_blocks().await;
             }
 
             Message::VerAck => {
@@ -271,16 +737,39 @@ impl Node {
             }
 
             Message::Handshake(handshake) => {
+                // Peers on another chain are dropped, but not banned
+                if let Err(e) = self
+                    .peer_manager
+                    .check_genesis(&handshake.genesis_hash)
+                    .await
+                {
+                    log::warn!("Disconnecting {}: {}", from, e);
+                    self.peer_manager.remove_peer(&from).await;
+                    return;
+                }
                 self.peer_manager.update_peer(&from, &handshake).await;
 
                 // Check if we need to sync
-                self.chain_sync.check_sync().await;
+                self.check_sync().await;
+                self.release_held_blocks().await;
             }
 
             Message::NewBlock(block) => {
-                if let Err(e) = self.chain_sync.handle_new_block(block, from).await {
-                    log::warn!("Failed to handle new block: {}", e);
+                let reorg_before = self.newest_reorg().await;
+                let result = self.chain_sync.handle_new_block(block.clone(), from).await;
+                match result {
+                    Ok(outcome) => {
+                        if outcome.added > 0 {
+                            if let Some(events) = &self.events {
+                                let reward = block.mining_reward();
+                                events.publish(ChainEvent::BlockConnected { block, reward });
+                            }
+                        }
+                        self.update_mempool(outcome.returned_txs).await;
+                    }
+                    Err(e) => log::warn!("Failed to handle new block: {}", e),
                 }
+                self.publish_reorg_since(reorg_before).await;
 
                 // Save blockchain
                 let chain = self.blockchain.read().await;
@@ -290,16 +779,19 @@ impl Node {
             }
 
             Message::NewTransaction(tx) => {
-                let chain = self.blockchain.read().await;
-                let mut mempool = self.mempool.write().await;
+                self.peer_manager
+                    .record_tx_announcement(&tx.id, &from)
+                    .await;
 
-                if mempool.add_transaction(tx.clone(), &chain).is_ok() {
+                if self.accept_from_peer(&tx).await {
                     // Relay to other peers
-                    drop(chain);
-                    drop(mempool);
-                    self.peer_manager
-                        .broadcast_except(Message::NewTransaction(tx), &from)
-                        .await;
+                    self.announce_transaction(&tx, Some(&from)).await;
+                }
+            }
+
+            Message::StemTransaction { tx, hops_remaining } => {
+                if self.accept_from_peer(&tx).await {
+                    self.relay_stem(tx, hops_remaining, Some(&from)).await;
                 }
             }
 
@@ -318,9 +810,21 @@ impl Node {
             }
 
             Message::Blocks(blocks) => {
-                if let Err(e) = self.chain_sync.handle_blocks(blocks, from).await {
-                    log::warn!("Failed to handle blocks: {}", e);
+                let reorg_before = self.newest_reorg().await;
+                if self.parallel_sync.is_syncing().await {
+                    match self.parallel_sync.handle_blocks(blocks, from).await {
+                        Ok(returned_txs) => self.update_mempool(returned_txs).await,
+                        Err(e) => log::warn!("Failed to handle blocks: {:?}", e),
+                    }
+                } else {
+                    let result = self.chain_sync.handle_blocks(blocks, from).await;
+                    match result {
+                        Ok(outcome) => self.update_mempool(outcome.returned_txs).await,
+                        Err(e) => log::warn!("Failed to handle blocks: {}", e),
+                    }
                 }
+                self.publish_reorg_since(reorg_before).await;
+                self.release_held_blocks().await;
 
                 // Save blockchain
                 let chain = self.blockchain.read().await;
@@ -393,13 +897,77 @@ impl Node {
             Message::Inv(items) => {
                 // Handle inventory announcements
                 log::debug!("Received {} inventory items from {}", items.len(), from);
-                // TODO: Request missing items via GetData
+
+                // A peer only announces transactions it accepted
+                for item in &items {
+                    if matches!(item.inv_type, InvType::Transaction) {
+                        self.peer_manager
+                            .record_tx_announcement(&item.hash, &from)
+                            .await;
+                    }
+                }
+
+                // Request only the transactions we haven't seen
+                let missing: Vec<InvItem> = {
+                    let chain = self.blockchain.read().await;
+                    let mempool = self.mempool.read().await;
+                    items
+                        .into_iter()
+                        .filter(|item| {
+                            matches!(item.inv_type, InvType::Transaction)
+                                && !mempool.is_known(&item.hash, &chain)
+                        })
+                        .collect()
+                };
+                if !missing.is_empty() {
+                    if let Err(e) = self
+                        .peer_manager
+                        .send_to(&from, Message::GetData(missing))
+                        .await
+                    {
+                        log::warn!("Failed to request transactions: {}", e);
+                    }
+                }
             }
 
             Message::GetData(items) => {
                 // Handle data requests
                 log::debug!("GetData request for {} items from {}", items.len(), from);
-                // TODO: Send requested blocks/transactions
+
+                // Serve announced transactions from the mempool
+                // TODO: Send requested blocks
+                let mut found = Vec::new();
+                let mut missing = Vec::new();
+                {
+                    let mempool = self.mempool.read().await;
+                    for item in items {
+                        if !matches!(item.inv_type, InvType::Transaction) {
+                            continue;
+                        }
+                        match mempool.get_transaction(&item.hash) {
+                            Some(tx) => found.push(tx.clone()),
+                            None => missing.push(item),
+                        }
+                    }
+                }
+                for tx in found {
+                    if let Err(e) = self
+                        .peer_manager
+                        .send_to(&from, Message::NewTransaction(tx))
+                        .await
+                    {
+                        log::warn!("Failed to send transaction: {}", e);
+                    }
+                }
+                if !missing.is_empty() {
+                    if let Err(e) = self
+                        .peer_manager
+                        .send_to(&from, Message::NotFound(missing))
+                        .await
+                    {
+                        log::warn!("Failed to send NotFound: {}", e);
+                    }
+                }
             }
 
             Message::NotFound(_items) => {
@@ -414,6 +982,9 @@ impl Node {
                     reject.code,
                     reject.reason
                 );
+                if let Some(hash) = &reject.data {
+                    self.peer_manager.record_tx_reject(hash, &from).await;
+                }
             }
 
             Message::CompactBlock(_) => {
@@ -486,15 +1057,144 @@ impl Node {
         }
     }
 
-    /// Broadcast a new block to all peers
+    /// Broadcast a block mined by this node to all peers, or hold it
+    /// while `min_peers_to_announce` isn't met (see
+    /// [`PeerManager::announce_mined_block`])
     pub async fn broadcast_block(&self, block: crate::core::Block) {
-        self.peer_manager.broadcast(Message::NewBlock(block)).await;
+        self.peer_manager.announce_mined_block(block).await;
+    }
+
+    /// Announce held mined blocks once enough peers are connected and the
+    /// initial sync has finished
+    pub async fn release_held_blocks(&self) -> usize {
+        if self.chain_sync.is_syncing().await {
+            return 0;
+        }
+        let chain = self.blockchain.read().await;
+        self.peer_manager.release_held_blocks(&chain).await
+    }
+
+    /// Bring the mempool up to date after handling blocks: expire stale
+    /// entries, drop what the blocks confirmed or double-spent, re-rank the
+    /// rest against the new chain and re-admit transactions a reorg took out
+    async fn update_mempool(&self, txs: Vec<Transaction>) {
+        let chain = self.blockchain.read().await;
+        let mut mempool = self.mempool.write().await;
+        let expired = mempool.expire();
+        if !expired.is_empty() {
+            log::debug!("Expired {} stale mempool transaction(s)", expired.len());
+        }
+        let removed = mempool.remove_conflicting(&chain);
+        if removed > 0 {
+            log::debug!(
+                "Removed {} confirmed or conflicting transaction(s)",
+                removed
+            );
+        }
+        if txs.is_empty() {
+            return;
+        }
+        let count = txs.len();
+        let accepted = mempool.add_reorged_transactions(txs, &chain);
+        log::info!(
+            "Returned {} of {} reorged transaction(s) to the mempool",
+            accepted,
+            count
+        );
     }
 
     /// Broadcast a new transaction to all peers
+    ///
+    /// The transaction is announced in the next Inv batch. With Dandelion
+    /// enabled it starts in the stem phase instead and is only broadcast by
+    /// this node once the stem timeout has elapsed.
     pub async fn broadcast_transaction(&self, tx: Transaction) {
+        self.peer_manager.track_broadcast(&tx.id).await;
+
+        let dandelion = &self.config.dandelion;
+        if !dandelion.enabled {
+            self.announce_transaction(&tx, None).await;
+            return;
+        }
+
+        self.relay_stem(tx.clone(), dandelion.stem_hops, None).await;
+
+        // Fail-safe: fluff it ourselves in case the stem is dropped
+        let peer_manager = self.peer_manager.clone();
+        let stem_timeout = dandelion.stem_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(stem_timeout).await;
+            peer_manager
+                .broadcast_transaction(Message::NewTransaction(tx), None)
+                .await;
+        });
+    }
+
+    /// Add a transaction received from a peer to the mempool, returning
+    /// whether it was accepted and should be relayed on
+    async fn accept_from_peer(&self, tx: &Transaction) -> bool {
+        let is_smart = tx.is_token_transaction() || tx.is_contract_transaction();
+        if is_smart && !self.config.relay_smart_txs {
+            log::debug!("Dropping token/contract transaction {}", tx.id);
+            return false;
+        }
+
+        let chain = self.blockchain.read().await;
+        let mut mempool = self.mempool.write().await;
+        if is_smart {
+            mempool
+                .add_relayed_smart_transaction(tx.clone(), &chain)
+                .is_ok()
+        } else {
+            mempool.add_transaction(tx.clone(), &chain).is_ok()
+        }
+    }
+
+    /// Queue a transaction announcement for every peer except `from`,
+    /// sending it right away if batching is disabled. Token and contract
+    /// transactions only go to peers advertising `NODE_SMART_TX`, and
+    /// block-relay-only peers get none.
+    async fn announce_transaction(&self, tx: &Transaction, from: Option<&SocketAddr>) {
+        let services = if tx.is_token_transaction() || tx.is_contract_transaction() {
+            ServiceFlags::NODE_SMART_TX
+        } else {
+            ServiceFlags::NONE
+        };
+        let peers = self.peer_manager.get_tx_relay_peers(services).await;
+        {
+            let mut queue = self.inv_queue.write().await;
+            for peer in peers.into_iter().filter(|peer| Some(peer) != from) {
+                queue.push(peer, InvItem::transaction(tx.id.clone()));
+            }
+        }
+
+        if self.config.inv_batch_interval.is_zero() {
+            self.flush_inventory().await;
+        }
+    }
+
+    /// Send all queued transaction announcements now
+    pub async fn flush_inventory(&self) {
+        flush_inv_queue(&self.inv_queue, &self.peer_manager).await;
+    }
+
+    /// Pass a transaction one hop along the stem, or fluff it at the end
+    async fn relay_stem(&self, tx: Transaction, hops_remaining: u8, from: Option<&SocketAddr>) {
+        if hops_remaining > 0 {
+            if let Some(next) = self.peer_manager.random_peer(from).await {
+                let msg = Message::StemTransaction {
+                    tx,
+                    hops_remaining: hops_remaining - 1,
+                };
+                if let Err(e) = self.peer_manager.send_to(&next, msg).await {
+                    log::warn!("Failed to stem transaction to {}: {}", next, e);
+                }
+                return;
+            }
+        }
+
         self.peer_manager
-            .broadcast(Message::NewTransaction(tx))
+            .broadcast_transaction(Message::NewTransaction(tx), from)
             .await;
     }
 
@@ -542,6 +1242,8 @@ mod tests {
             port: 18333,
             bootstrap_peers: vec![],
             data_dir: temp_dir.path().to_path_buf(),
+            dandelion: DandelionConfig::default(),
+            ..Default::default()
         };
 
         let node = Node::new(config).await.unwrap();
@@ -551,4 +1253,745 @@ mod tests {
         assert_eq!(status.height, 0);
         assert_eq!(status.peers, 0);
     }
+
+    #[tokio::test]
+    async fn test_handshake_timeout_recorded_as_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18335,
+            data_dir: temp_dir.path().to_path_buf(),
+            handshake_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+
+        // A peer that accepts the connection but never says anything
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let result = node.connect_to(&addr).await;
+        assert!(matches!(result, Err(PeerError::Timeout(_))));
+
+        // Only addresses the manager already knows are updated on failure
+        let addr_manager = node.addr_manager.read().await;
+        assert!(addr_manager.get(&addr).is_none());
+        assert_eq!(node.peer_manager.peer_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_fails_across_networks() {
+        use crate::network::message::{magic_for_chain_id, MAGIC_MAINNET};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18336,
+            data_dir: temp_dir.path().to_path_buf(),
+            handshake_timeout: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+        let testnet_magic = magic_for_chain_id(2);
+        assert_ne!(testnet_magic, MAGIC_MAINNET);
+        assert_eq!(
+            magic_for_chain_id(crate::core::DEFAULT_CHAIN_ID),
+            MAGIC_MAINNET
+        );
+
+        // A peer on another chain answers with its own magic
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let handshake = Handshake::new(0, "genesis".to_string(), 0);
+            let _ = perform_handshake(
+                frame_stream(socket, testnet_magic),
+                handshake,
+                Duration::from_secs(2),
+            )
+            .await;
+        });
+
+        let result = node.connect_to(&addr).await;
+        assert!(matches!(
+            result,
+            Err(PeerError::IoError(ref e)) if e.kind() == std::io::ErrorKind::InvalidData
+        ));
+        assert_eq!(node.peer_manager.peer_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_announcements_are_batched() {
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18337,
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+
+        let mut receivers = Vec::new();
+        for i in 1..=2 {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = mpsc::channel(10);
+            node.peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            receivers.push(rx);
+        }
+
+        let txs: Vec<Transaction> = (1..=3)
+            .map(|i| Transaction::coinbase("recipient", 50, i))
+            .collect();
+        for tx in &txs {
+            node.broadcast_transaction(tx.clone()).await;
+        }
+        // Announcing the same transaction twice in a window is a no-op
+        node.broadcast_transaction(txs[0].clone()).await;
+
+        // Nothing goes out until the batch window closes
+        for rx in receivers.iter_mut() {
+            assert!(rx.try_recv().is_err());
+        }
+
+        node.flush_inventory().await;
+        for rx in receivers.iter_mut() {
+            match rx.try_recv() {
+                Ok(Message::Inv(items)) => {
+                    let hashes: HashSet<&str> = items.iter().map(|i| i.hash.as_str()).collect();
+                    assert_eq!(items.len(), txs.len());
+                    assert!(txs.iter().all(|tx| hashes.contains(tx.id.as_str())));
+                }
+                other => panic!("expected one combined inv, got {:?}", other),
+            }
+            assert!(rx.try_recv().is_err());
+        }
+
+        // Oversized batches are split across messages
+        let peer: SocketAddr = "10.9.0.1:8333".parse().unwrap();
+        let mut queue = InvQueue::new();
+        for i in 0..=MAX_INV_PER_MESSAGE {
+            assert!(queue.push(peer, InvItem::transaction(i.to_string())));
+        }
+        let sizes: Vec<usize> = queue
+            .drain()
+            .into_iter()
+            .map(|(_, msg)| match msg {
+                Message::Inv(items) => items.len(),
+                other => panic!("unexpected message {:?}", other),
+            })
+            .collect();
+        assert_eq!(sizes, vec![MAX_INV_PER_MESSAGE, 1]);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_propagation_counts_echoing_peers() {
+        use crate::network::message::{RejectCode, RejectMessage};
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18339,
+            data_dir: temp_dir.path().to_path_buf(),
+            inv_batch_interval: Duration::ZERO,
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+
+        let mut peers = Vec::new();
+        for i in 1..=3 {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = mpsc::channel(10);
+            node.peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            peers.push((addr, rx));
+        }
+
+        let tx = Transaction::coinbase("recipient", 50, 1);
+        node.broadcast_transaction(tx.clone()).await;
+        let propagation = node.peer_manager.tx_propagation(&tx.id).await.unwrap();
+        assert!(propagation.announced_by.is_empty());
+
+        // Two peers echo the announcement back; the third rejects it
+        for (addr, rx) in peers.iter_mut().take(2) {
+            let echoed = match rx.try_recv() {
+                Ok(Message::Inv(items)) => items,
+                other => panic!("expected an inv, got {:?}", other),
+            };
+            node.handle_message(*addr, Message::Inv(echoed)).await;
+        }
+        let reject = RejectMessage::new(
+            "tx",
+            RejectCode::InsufficientFee,
+            "fee",
+            Some(tx.id.clone()),
+        );
+        node.handle_message(peers[2].0, Message::Reject(reject))
+            .await;
+
+        let propagation = node.peer_manager.tx_propagation(&tx.id).await.unwrap();
+        assert_eq!(propagation.announced_by.len(), 2);
+        assert!(propagation.announced_by.contains(&peers[0].0));
+        assert_eq!(propagation.rejected_by.len(), 1);
+
+        // Only connected peers count
+        node.peer_manager.remove_peer(&peers[0].0).await;
+        let propagation = node.peer_manager.tx_propagation(&tx.id).await.unwrap();
+        assert_eq!(propagation.announced_by.len(), 1);
+
+        // Transactions we didn't broadcast aren't tracked
+        assert!(node.peer_manager.tx_propagation("unknown").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_validations_are_bounded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18338,
+            data_dir: temp_dir.path().to_path_buf(),
+            max_concurrent_validations: 2,
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+        let limiter = node.validation_limiter().clone();
+        assert_eq!(limiter.max(), 2);
+
+        let peer: SocketAddr = "10.0.0.1:8333".parse().unwrap();
+        let block = |i: u64| {
+            let genesis = node.blockchain.try_read().unwrap().latest_block().clone();
+            let coinbase = Transaction::coinbase("miner", 50, i);
+            crate::core::Block::new_unmined(1, genesis.hash, vec![coinbase], 1)
+        };
+        let blocks: Vec<_> = (0..3).map(block).collect();
+
+        // Validations stall while the chain is locked, holding their slots
+        let chain = node.blockchain.write().await;
+        for block in &blocks[..2] {
+            node.dispatch_message(peer, Message::NewBlock(block.clone()))
+                .await;
+        }
+        assert_eq!(limiter.in_flight(), 2);
+
+        // The third block waits for a slot rather than piling up
+        let queued = {
+            let node = node.clone();
+            let block = blocks[2].clone();
+            tokio::spawn(async move { node.dispatch_message(peer, Message::NewBlock(block)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+        assert_eq!(limiter.in_flight(), 2);
+
+        drop(chain);
+        queued.await.unwrap();
+        for _ in 0..100 {
+            if limiter.in_flight() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_payment_only_node_drops_token_transactions() {
+        use crate::core::{TokenOperationType, TransactionInput, SEQUENCE_FINAL};
+        use crate::crypto::KeyPair;
+        use crate::network::peer::PeerHandle;
+
+        let unsigned_tx = Transaction::with_token_data(
+            vec![TransactionInput {
+                tx_id: "token_transfer".to_string(),
+                output_index: 0,
+                signature: String::new(),
+                public_key: String::new(),
+                sequence: SEQUENCE_FINAL,
+            }],
+            vec![],
+            TokenOperationType::Transfer {
+                token_address: "token".to_string(),
+                to: "recipient".to_string(),
+                amount: 10,
+            },
+        );
+        let mut token_tx = unsigned_tx.clone();
+        token_tx.sign(&KeyPair::generate()).unwrap();
+
+        for (port, relay_smart_txs) in [(18340, false), (18341, true)] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = NodeConfig {
+                port,
+                data_dir: temp_dir.path().to_path_buf(),
+                inv_batch_interval: Duration::ZERO,
+                relay_smart_txs,
+                ..Default::default()
+            };
+            assert_eq!(
+                config.services().contains(ServiceFlags::NODE_SMART_TX),
+                relay_smart_txs
+            );
+            let node = Node::new(config).await.unwrap();
+
+            // One full peer, one payment-only peer, and the sender
+            let mut receivers = Vec::new();
+            for (i, services) in [LOCAL_SERVICES, ServiceFlags::NODE_NETWORK]
+                .into_iter()
+                .enumerate()
+            {
+                let addr: SocketAddr = format!("10.{}.0.1:8333", i + 1).parse().unwrap();
+                let (tx, rx) = mpsc::channel(10);
+                node.peer_manager
+                    .add_peer(addr, PeerHandle { addr, tx }, true)
+                    .await
+                    .unwrap();
+                let handshake = Handshake::new(0, String::new(), 8333).with_services(services);
+                node.peer_manager.update_peer(&addr, &handshake).await;
+                receivers.push(rx);
+            }
+            let sender: SocketAddr = "10.9.0.1:8333".parse().unwrap();
+
+            node.handle_message(sender, Message::NewTransaction(token_tx.clone()))
+                .await;
+
+            let in_mempool = node.mempool.read().await.contains(&token_tx.id);
+            let full_peer_got_inv = matches!(
+                receivers[0].try_recv(),
+                Ok(Message::Inv(items)) if items[0].hash == token_tx.id
+            );
+            assert_eq!(in_mempool, relay_smart_txs);
+            assert_eq!(full_peer_got_inv, relay_smart_txs);
+            // Payment-only peers never hear about it
+            assert!(receivers[1].try_recv().is_err());
+
+            // Unsigned token transactions are never accepted
+            node.handle_message(sender, Message::NewTransaction(unsigned_tx.clone()))
+                .await;
+            assert!(!node.mempool.read().await.contains(&unsigned_tx.id));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_relay_only_peer_gets_blocks_but_no_transactions() {
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let node = Node::new(NodeConfig {
+            port: 18344,
+            data_dir: temp_dir.path().to_path_buf(),
+            inv_batch_interval: Duration::ZERO,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // A regular peer and one asking for blocks only
+        let mut peers = Vec::new();
+        for (i, relay) in [(1, true), (2, false)] {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = mpsc::channel(10);
+            node.peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, false)
+                .await
+                .unwrap();
+            let handshake = Handshake::new(0, String::new(), 8333).with_relay(relay);
+            node.handle_message(addr, Message::Handshake(handshake))
+                .await;
+            peers.push((addr, rx));
+        }
+        assert!(node.peer_manager.is_block_relay_only(&peers[1].0).await);
+
+        let tx = Transaction::coinbase("recipient", 50, 1);
+        node.broadcast_transaction(tx.clone()).await;
+        assert!(matches!(
+            peers[0].1.try_recv(),
+            Ok(Message::Inv(items)) if items[0].hash == tx.id
+        ));
+        assert!(peers[1].1.try_recv().is_err());
+        node.relay_stem(tx, 1, None).await;
+        assert!(matches!(
+            peers[0].1.try_recv(),
+            Ok(Message::StemTransaction { .. })
+        ));
+        assert!(peers[1].1.try_recv().is_err());
+
+        let block = node
+            .blockchain
+            .write()
+            .await
+            .mine_block(vec![], "miner")
+            .unwrap();
+        node.broadcast_block(block).await;
+        for (_, rx) in peers.iter_mut() {
+            assert!(matches!(rx.try_recv(), Ok(Message::NewBlock(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mined_blocks_held_until_peers_synced() {
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(
+            Storage::new(crate::storage::StorageConfig {
+                data_dir: temp_dir.path().to_path_buf(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let config = NodeConfig {
+            port: 18342,
+            data_dir: temp_dir.path().to_path_buf(),
+            min_peers_to_announce: 2,
+            ..Default::default()
+        };
+        let node = Node::new_with_shared(
+            config,
+            Arc::new(RwLock::new(Blockchain::with_difficulty(1))),
+            Arc::new(RwLock::new(Mempool::new())),
+            storage,
+        );
+
+        // Mining while peerless holds the blocks back
+        for _ in 0..2 {
+            let block = node
+                .blockchain
+                .write()
+                .await
+                .mine_block(vec![], "miner")
+                .unwrap();
+            node.broadcast_block(block).await;
+        }
+        assert_eq!(node.peer_manager.held_block_count().await, 2);
+
+        // The second peer has one block on top of ours
+        let peer_block = {
+            let mut peer_chain = node.blockchain.read().await.clone();
+            peer_chain.mine_block(vec![], "peer").unwrap()
+        };
+
+        let mut peers = Vec::new();
+        for (i, height) in [(1, 2), (2, 3)] {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = mpsc::channel(10);
+            node.peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            let handshake = Handshake::new(height, String::new(), 8333);
+            node.handle_message(addr, Message::Handshake(handshake))
+                .await;
+            peers.push((addr, rx));
+        }
+
+        // Two peers, but one is ahead: we sync from it first
+        assert_eq!(node.peer_manager.held_block_count().await, 2);
+        assert!(peers[0].1.try_recv().is_err());
+        assert!(matches!(
+            peers[1].1.try_recv(),
+            Ok(Message::GetBlocks {
+                start_height: 3,
+                ..
+            })
+        ));
+
+        // Sync complete: the held blocks go out in order
+        node.handle_message(peers[1].0, Message::Blocks(vec![peer_block]))
+            .await;
+        assert_eq!(node.blockchain.read().await.height(), 3);
+        assert_eq!(node.peer_manager.held_block_count().await, 0);
+        for (_, rx) in peers.iter_mut() {
+            for height in 1..=2 {
+                match rx.try_recv() {
+                    Ok(Message::NewBlock(block)) => assert_eq!(block.index, height),
+                    other => panic!("expected held block {}, got {:?}", height, other),
+                }
+            }
+        }
+
+        // Once ready, new blocks are announced immediately
+        let block = node
+            .blockchain
+            .write()
+            .await
+            .mine_block(vec![], "miner")
+            .unwrap();
+        node.broadcast_block(block).await;
+        assert_eq!(node.peer_manager.held_block_count().await, 0);
+        assert!(matches!(peers[0].1.try_recv(), Ok(Message::NewBlock(_))));
+    }
+
+    #[test]
+    fn test_pruned_node_advertises_limited_service() {
+        let config = NodeConfig::default();
+        let mut chain = Blockchain::with_difficulty(1);
+        for _ in 0..4 {
+            chain.mine_block(vec![], "miner").unwrap();
+        }
+        assert!(config
+            .services_for(&chain)
+            .contains(ServiceFlags::NODE_NETWORK));
+
+        chain.convert_to_pruned(1);
+        let services = config.services_for(&chain);
+        assert!(!services.contains(ServiceFlags::NODE_NETWORK));
+        assert!(services.contains(ServiceFlags::NODE_NETWORK_LIMITED));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_sync_hands_dropped_peer_downloads_to_others() {
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(
+            Storage::new(crate::storage::StorageConfig {
+                data_dir: temp_dir.path().to_path_buf(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let config = NodeConfig {
+            port: 18347,
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let chain = Blockchain::with_difficulty(1);
+        let mut source = chain.clone();
+        for _ in 0..4 {
+            source.mine_block(vec![], "peer").unwrap();
+        }
+        // Peers claim a chain far ahead; only its first blocks are delivered
+        let target = PARALLEL_DOWNLOAD_THRESHOLD + 2;
+        let node = Node::new_with_shared(
+            config,
+            Arc::new(RwLock::new(chain)),
+            Arc::new(RwLock::new(Mempool::new())),
+            storage,
+        );
+        node.spawn_sync_watcher();
+
+        let add_peer = |i: u8| {
+            let node = node.clone();
+            async move {
+                let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+                let (tx, rx) = mpsc::channel(64);
+                node.peer_manager
+                    .add_peer(addr, PeerHandle { addr, tx }, true)
+                    .await
+                    .unwrap();
+                (addr, rx)
+            }
+        };
+        let requested = |rx: &mut mpsc::Receiver<Message>| {
+            let mut heights = Vec::new();
+            while let Ok(msg) = rx.try_recv() {
+                if let Message::GetBlocks { start_height, .. } = msg {
+                    heights.push(start_height);
+                }
+            }
+            heights.sort_unstable();
+            heights
+        };
+
+        // Far behind the first peer: its blocks are fetched in parallel
+        let (first, mut first_rx) = add_peer(1).await;
+        node.handle_message(
+            first,
+            Message::Handshake(Handshake::new(target, String::new(), 8333)),
+        )
+        .await;
+        assert!(node.parallel_sync.is_syncing().await);
+        assert_eq!(requested(&mut first_rx), (1..=16).collect::<Vec<_>>());
+
+        node.handle_message(first, Message::Blocks(source.blocks[1..=4].to_vec()))
+            .await;
+        assert_eq!(node.blockchain.read().await.height(), 4);
+        assert_eq!(requested(&mut first_rx), (17..=20).collect::<Vec<_>>());
+
+        // A second peer joins, then the first drops mid-download
+        let (second, mut second_rx) = add_peer(2).await;
+        node.peer_manager
+            .update_peer(&second, &Handshake::new(target, String::new(), 8333))
+            .await;
+        node.peer_manager.remove_peer(&first).await;
+
+        let mut handed_over = Vec::new();
+        while handed_over.len() < 16 {
+            match tokio::time::timeout(Duration::from_secs(5), second_rx.recv()).await {
+                Ok(Some(Message::GetBlocks { start_height, .. })) => handed_over.push(start_height),
+                Ok(_) => {}
+                Err(_) => panic!("only {} block(s) handed over", handed_over.len()),
+            }
+        }
+        handed_over.sort_unstable();
+        assert_eq!(handed_over, (5..=20).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_from_peer_returns_transactions_to_mempool() {
+        use crate::core::TransactionBuilder;
+        use crate::crypto::KeyPair;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(
+            Storage::new(crate::storage::StorageConfig {
+                data_dir: temp_dir.path().to_path_buf(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let key_pair = KeyPair::generate();
+        let mut chain = Blockchain::with_difficulty(1);
+        chain.params.coinbase_maturity = 0;
+        let first = chain.mine_block(vec![], &key_pair.address()).unwrap();
+        let utxo = chain
+            .find_utxo(&first.transactions[0].id, 0)
+            .unwrap()
+            .clone();
+        let spend = TransactionBuilder::new()
+            .add_input(&utxo)
+            .add_output("recipient", utxo.output.amount / 2)
+            .build_and_sign(&key_pair)
+            .unwrap();
+        chain.mine_block(vec![spend.clone()], "miner").unwrap();
+        let fork = chain.mine_competing_block(1, "peer").unwrap();
+
+        let config = NodeConfig {
+            port: 18346,
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let node = Node::new_with_shared(
+            config,
+            Arc::new(RwLock::new(chain)),
+            Arc::new(RwLock::new(Mempool::new())),
+            storage,
+        );
+
+        // The heavier fork disconnects the block holding the spend
+        let peer: SocketAddr = "10.0.0.1:8333".parse().unwrap();
+        node.handle_message(peer, Message::NewBlock(fork.clone()))
+            .await;
+        assert_eq!(node.blockchain.read().await.latest_block().hash, fork.hash);
+        assert!(node.mempool.read().await.contains(&spend.id));
+    }
+
+    #[tokio::test]
+    async fn test_peer_with_other_genesis_is_disconnected() {
+        use crate::network::message::VersionMessage;
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18343,
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+        let ours = node.blockchain.read().await.genesis_hash().to_string();
+        let theirs = Blockchain::with_difficulty(1).genesis_hash().to_string();
+        assert_ne!(ours, theirs);
+
+        let mut addrs = Vec::new();
+        for i in 1..=4 {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, _rx) = mpsc::channel(10);
+            node.peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            addrs.push(addr);
+        }
+
+        let handshake = |genesis: &str| {
+            Message::Handshake(
+                Handshake::new(0, String::new(), 8333).with_genesis_hash(genesis.to_string()),
+            )
+        };
+        let version = |genesis: &str| {
+            Message::Version(
+                VersionMessage::new(LOCAL_SERVICES, 0, String::new(), String::new())
+                    .with_genesis_hash(genesis.to_string()),
+            )
+        };
+        node.handle_message(addrs[0], handshake(&theirs)).await;
+        node.handle_message(addrs[1], version(&theirs)).await;
+        node.handle_message(addrs[2], handshake(&ours)).await;
+        // Older peers don't announce a genesis hash
+        node.handle_message(addrs[3], version("")).await;
+
+        let connected = node.peer_manager.get_peers().await;
+        assert!(!connected.contains(&addrs[0]));
+        assert!(!connected.contains(&addrs[1]));
+        assert!(connected.contains(&addrs[2]));
+        assert!(connected.contains(&addrs[3]));
+
+        // Disconnected, not banned
+        assert!(!node.peer_manager.is_banned(&addrs[0]).await);
+        assert!(node.peer_manager.get_banned().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dandelion_stem_then_fluff() {
+        use crate::network::peer::PeerHandle;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = NodeConfig {
+            port: 18334,
+            bootstrap_peers: vec![],
+            data_dir: temp_dir.path().to_path_buf(),
+            dandelion: DandelionConfig {
+                enabled: true,
+                stem_hops: DEFAULT_STEM_HOPS,
+                stem_timeout: Duration::from_millis(50),
+            },
+            ..Default::default()
+        };
+        let node = Node::new(config).await.unwrap();
+
+        let mut receivers = Vec::new();
+        for i in 1..=3 {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = mpsc::channel(10);
+            node.peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            receivers.push(rx);
+        }
+
+        let tx = Transaction::coinbase("recipient", 50, 1);
+        node.broadcast_transaction(tx.clone()).await;
+
+        // Stem phase: exactly one peer hears about it
+        let mut stemmed = 0;
+        for rx in receivers.iter_mut() {
+            if let Ok(msg) = rx.try_recv() {
+                assert!(matches!(
+                    msg,
+                    Message::StemTransaction { hops_remaining, .. }
+                        if hops_remaining == DEFAULT_STEM_HOPS - 1
+                ));
+                stemmed += 1;
+            }
+        }
+        assert_eq!(stemmed, 1);
+
+        // Fluff phase: after the timeout everyone gets it
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        for rx in receivers.iter_mut() {
+            match rx.try_recv() {
+                Ok(Message::NewTransaction(relayed)) => assert_eq!(relayed.id, tx.id),
+                other => panic!("expected fluffed transaction, got {:?}", other),
+            }
+        }
+    }
 }
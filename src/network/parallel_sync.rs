@@ -5,8 +5,9 @@
 //! - Download scheduler with window management
 //! - Stale tip detection
 //! - Request timeout handling
+//! - Resumption after a peer drops mid-download
 
-use crate::core::{Block, Blockchain};
+use crate::core::{Block, Blockchain, Transaction};
 use crate::network::message::Message;
 use crate::network::peer::{PeerManager, PeerState};
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -31,8 +32,8 @@ const BLOCK_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 /// Stale tip detection threshold
 const STALE_TIP_THRESHOLD: Duration = Duration::from_secs(30 * 60);
 
-/// Maximum block height difference for parallel download
-const PARALLEL_DOWNLOAD_THRESHOLD: u64 = 144; // ~1 day of blocks
+/// Blocks behind the best peer beyond which sync downloads in parallel
+pub const PARALLEL_DOWNLOAD_THRESHOLD: u64 = 144; // ~1 day of blocks
 
 /// Batch size for block requests
 const BATCH_SIZE: u32 = 16;
@@ -151,6 +152,10 @@ pub struct ParallelSync {
     last_tip_update: RwLock<Instant>,
     /// Sync target height
     target_height: RwLock<u64>,
+    /// First height of the current sync session
+    start_height: RwLock<u64>,
+    /// Heights received this session, so a resumed download skips them
+    downloaded: RwLock<HashSet<u64>>,
 }
 
 impl ParallelSync {
@@ -167,6 +172,8 @@ impl ParallelSync {
             syncing: RwLock::new(false),
             last_tip_update: RwLock::new(Instant::now()),
             target_height: RwLock::new(0),
+            start_height: RwLock::new(0),
+            downloaded: RwLock::new(HashSet::new()),
         }
     }
 
@@ -191,7 +198,9 @@ impl ParallelSync {
         // Initialize state
         *self.next_height.write().await = current_height + 1;
         *self.target_height.write().await = target;
+        *self.start_height.write().await = current_height + 1;
         *self.syncing.write().await = true;
+        self.downloaded.write().await.clear();
 
         // Build initial queue
         let mut queue = self.queue.write().await;
@@ -271,6 +280,10 @@ impl ParallelSync {
         let mut queue = self.queue.write().await;
         let mut in_flight = self.in_flight.write().await;
         let mut peer_states = self.peer_states.write().await;
+        let downloaded = self.downloaded.read().await;
+
+        // Heights that arrived as part of an earlier batch need no request
+        queue.retain(|height| !downloaded.contains(height));
 
         for (peer_addr, slots, _) in peer_slots {
             for _ in 0..slots {
@@ -309,10 +322,15 @@ impl ParallelSync {
         }
     }
 
-    /// Handle received blocks
-    pub async fn handle_blocks(&self, blocks: Vec<Block>, from: SocketAddr) -> Result<(), SyncError> {
+    /// Handle received blocks, returning transactions from any blocks a
+    /// reorg disconnected
+    pub async fn handle_blocks(
+        &self,
+        blocks: Vec<Block>,
+        from: SocketAddr,
+    ) -> Result<Vec<Transaction>, SyncError> {
         if blocks.is_empty() {
-            return Ok(());
+            return Ok(Vec::new());
         }
 
         let now = Instant::now();
@@ -321,9 +339,11 @@ impl ParallelSync {
         let mut in_flight = self.in_flight.write().await;
         let mut peer_states = self.peer_states.write().await;
         let mut block_buffer = self.block_buffer.write().await;
+        let mut downloaded = self.downloaded.write().await;
 
         for block in blocks {
             let height = block.index;
+            downloaded.insert(height);
 
             // Remove from in-flight
             if let Some(request) = in_flight.remove(&height) {
@@ -342,9 +362,10 @@ impl ParallelSync {
         drop(in_flight);
         drop(peer_states);
         drop(block_buffer);
+        drop(downloaded);
 
         // Try to process buffered blocks in order
-        self.process_buffer().await?;
+        let returned_txs = self.process_buffer().await?;
 
         // Schedule more downloads
         self.schedule_downloads().await;
@@ -354,18 +375,20 @@ impl ParallelSync {
 
         self.record_tip_update().await;
 
-        Ok(())
+        Ok(returned_txs)
     }
 
     /// Process blocks in order from the buffer
-    async fn process_buffer(&self) -> Result<(), SyncError> {
+    async fn process_buffer(&self) -> Result<Vec<Transaction>, SyncError> {
         let mut next_height = self.next_height.write().await;
         let mut block_buffer = self.block_buffer.write().await;
         let mut blockchain = self.blockchain.write().await;
+        let mut returned_txs = Vec::new();
 
         while let Some(block) = block_buffer.remove(&*next_height) {
             match blockchain.process_block(block) {
                 Ok(_) => {
+                    returned_txs.extend(blockchain.take_returned_transactions());
                     *next_height += 1;
                 }
                 Err(e) => {
@@ -376,7 +399,7 @@ impl ParallelSync {
             }
         }
 
-        Ok(())
+        Ok(returned_txs)
     }
 
     /// Handle request timeout
@@ -404,6 +427,43 @@ impl ParallelSync {
         self.schedule_downloads().await;
     }
 
+    /// Handle a peer dropping mid-sync: its outstanding requests go back to
+    /// the front of the queue for the remaining peers, minus any heights
+    /// that already arrived
+    pub async fn handle_peer_disconnect(&self, peer: &SocketAddr) {
+        self.peer_states.write().await.remove(peer);
+
+        let mut in_flight = self.in_flight.write().await;
+        let downloaded = self.downloaded.read().await;
+        let mut missing: Vec<u64> = in_flight
+            .iter()
+            .filter(|(_, request)| request.peer == *peer)
+            .map(|(height, _)| *height)
+            .collect();
+        for height in &missing {
+            in_flight.remove(height);
+        }
+        missing.retain(|height| !downloaded.contains(height));
+        missing.sort_unstable();
+        drop(downloaded);
+        drop(in_flight);
+
+        if !missing.is_empty() {
+            log::info!(
+                "Peer {} dropped mid-sync, re-queueing {} block(s) from height {}",
+                peer,
+                missing.len(),
+                missing[0]
+            );
+            let mut queue = self.queue.write().await;
+            for height in missing.into_iter().rev() {
+                queue.push_front(height);
+            }
+        }
+
+        self.schedule_downloads().await;
+    }
+
     /// Check and handle timed out requests
     pub async fn check_timeouts(&self) {
         let in_flight = self.in_flight.read().await;
@@ -439,6 +499,8 @@ impl ParallelSync {
         let queue = self.queue.read().await;
         let block_buffer = self.block_buffer.read().await;
         let peer_states = self.peer_states.read().await;
+        let target_height = *self.target_height.read().await;
+        let start_height = *self.start_height.read().await;
 
         ParallelSyncStats {
             in_flight: in_flight.len(),
@@ -446,7 +508,9 @@ impl ParallelSync {
             buffered: block_buffer.len(),
             active_peers: peer_states.len(),
             syncing: *self.syncing.read().await,
-            target_height: *self.target_height.read().await,
+            target_height,
+            downloaded: self.downloaded.read().await.len() as u64,
+            total: (target_height + 1).saturating_sub(start_height),
         }
     }
 }
@@ -469,6 +533,10 @@ pub struct ParallelSyncStats {
     pub active_peers: usize,
     pub syncing: bool,
     pub target_height: u64,
+    /// Blocks received this session
+    pub downloaded: u64,
+    /// Blocks the session needs in total
+    pub total: u64,
 }
 
 // =============================================================================
@@ -517,4 +585,60 @@ mod tests {
         
         assert!(request.is_timed_out());
     }
+
+    #[tokio::test]
+    async fn test_peer_drop_requeues_only_missing_blocks() {
+        use crate::network::message::Handshake;
+        use crate::network::peer::PeerHandle;
+        use tokio::sync::mpsc;
+
+        let chain = Blockchain::with_difficulty(1);
+        let mut source = chain.clone();
+        for _ in 0..8 {
+            source.mine_block(vec![], "miner").unwrap();
+        }
+
+        let peer_manager = Arc::new(PeerManager::new(18340));
+        let mut receivers = HashMap::new();
+        for i in 1..=2 {
+            let addr: SocketAddr = format!("10.{}.0.1:8333", i).parse().unwrap();
+            let (tx, rx) = mpsc::channel(32);
+            peer_manager
+                .add_peer(addr, PeerHandle { addr, tx }, true)
+                .await
+                .unwrap();
+            peer_manager
+                .update_peer(&addr, &Handshake::new(8, String::new(), 8333))
+                .await;
+            receivers.insert(addr, rx);
+        }
+
+        let sync = ParallelSync::new(Arc::new(RwLock::new(chain)), peer_manager.clone());
+        sync.start_sync(8).await.unwrap();
+
+        // Everything fits in one peer's slots, so a single peer owns the batch
+        let first = sync.in_flight.read().await.get(&1).unwrap().peer;
+        let (&second, _) = receivers.iter().find(|(addr, _)| **addr != first).unwrap();
+        receivers.get_mut(&second).unwrap().try_recv().unwrap_err();
+
+        // Half the batch arrives, then the peer drops
+        sync.handle_blocks(source.blocks[1..=4].to_vec(), first)
+            .await
+            .unwrap();
+        peer_manager.remove_peer(&first).await;
+        sync.handle_peer_disconnect(&first).await;
+
+        let mut requested = Vec::new();
+        while let Ok(msg) = receivers.get_mut(&second).unwrap().try_recv() {
+            if let Message::GetBlocks { start_height, .. } = msg {
+                requested.push(start_height);
+            }
+        }
+        requested.sort_unstable();
+        assert_eq!(requested, vec![5, 6, 7, 8]);
+
+        let stats = sync.stats().await;
+        assert_eq!((stats.downloaded, stats.total), (4, 8));
+        assert_eq!(sync.blockchain.read().await.height(), 4);
+    }
 }
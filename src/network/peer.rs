@@ -6,14 +6,17 @@
 //! - Rate limiting (DOS protection)
 //! - Connection management
 
+use crate::core::{Block, Blockchain};
 use crate::network::message::{
     Handshake, Message, ServiceFlags, VersionMessage, MIN_PROTOCOL_VERSION,
 };
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 
 // =============================================================================
 // Constants
@@ -28,6 +31,9 @@ pub const MAX_OUTBOUND: usize = 8;
 /// Maximum inbound connections
 pub const MAX_INBOUND: usize = 117;
 
+/// Connection slots kept for whitelisted peers, on top of the regular limits
+pub const DEFAULT_RESERVED_SLOTS: usize = 8;
+
 /// Default peer score (starts positive)
 pub const DEFAULT_PEER_SCORE: i32 = 100;
 
@@ -40,6 +46,12 @@ pub const BAN_SCORE: i32 = -100;
 /// Default ban duration (24 hours)
 pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
 
+/// File (inside the data directory) holding persisted bans
+pub const BAN_LIST_FILE: &str = "banlist.json";
+
+/// Broadcast transactions whose propagation is tracked at once
+pub const MAX_TRACKED_BROADCASTS: usize = 1_000;
+
 /// Rate limit window (seconds)
 pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
 
@@ -75,10 +87,14 @@ pub enum PeerError {
     Banned(Instant),
     #[error("Incompatible protocol version: {0}")]
     IncompatibleVersion(u32),
+    #[error("Genesis block mismatch: peer is on {0}")]
+    GenesisMismatch(String),
     #[error("Rate limit exceeded")]
     RateLimitExceeded,
     #[error("Peer misbehaving: {0}")]
     Misbehaving(String),
+    #[error("Timed out {0}")]
+    Timeout(String),
 }
 
 // =============================================================================
@@ -257,6 +273,11 @@ pub struct PeerInfo {
     pub last_ping_nonce: Option<u64>,
     /// Time of last ping sent
     pub last_ping_time: Option<Instant>,
+    /// Whitelisted peers use reserved slots and are never evicted or banned
+    pub whitelisted: bool,
+    /// Only blocks are relayed over this connection, never transactions.
+    /// Set when either side asks for it at handshake.
+    pub block_relay_only: bool,
 }
 
 impl PeerInfo {
@@ -279,6 +300,8 @@ impl PeerInfo {
             ping_latency: None,
             last_ping_nonce: None,
             last_ping_time: None,
+            whitelisted: false,
+            block_relay_only: false,
         }
     }
 
@@ -288,15 +311,18 @@ impl PeerInfo {
         self.services = version.services;
         self.height = version.start_height;
         self.user_agent = version.user_agent.clone();
+        self.block_relay_only |= !version.relay;
         self.state = PeerState::Connected;
     }
 
     /// Update from legacy handshake
     pub fn update_from_handshake(&mut self, handshake: &Handshake) {
         self.version = handshake.version;
+        self.services = handshake.services;
         self.height = handshake.height;
         self.best_hash = handshake.best_hash.clone();
         self.user_agent = handshake.user_agent.clone();
+        self.block_relay_only |= !handshake.relay;
         self.state = PeerState::Connected;
     }
 
@@ -394,6 +420,64 @@ impl BanEntry {
     pub fn remaining(&self) -> Duration {
         self.duration.saturating_sub(self.banned_at.elapsed())
     }
+
+    /// Convert to a persistable record with a wall-clock expiry
+    pub fn to_record(&self, now: i64) -> BanRecord {
+        BanRecord {
+            addr: self.addr,
+            banned_until: now + self.remaining().as_secs() as i64,
+            reason: self.reason.clone(),
+        }
+    }
+
+    /// Restore from a persisted record; `None` if it has already expired
+    pub fn from_record(record: &BanRecord, now: i64) -> Option<Self> {
+        if record.banned_until <= now {
+            return None;
+        }
+
+        let remaining = Duration::from_secs((record.banned_until - now) as u64);
+        Some(Self::new(record.addr, remaining, record.reason.clone()))
+    }
+}
+
+/// Serializable form of a `BanEntry`
+///
+/// `Instant` has no meaning across restarts, so the expiry is stored as a
+/// Unix timestamp instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanRecord {
+    /// Banned address
+    pub addr: SocketAddr,
+    /// Unix timestamp at which the ban ends
+    pub banned_until: i64,
+    /// Reason for ban
+    pub reason: String,
+}
+
+// =============================================================================
+// Transaction Propagation
+// =============================================================================
+
+/// Which peers appear to have accepted a transaction we broadcast
+#[derive(Debug, Clone)]
+pub struct TxPropagation {
+    /// Peers that announced the transaction back to us
+    pub announced_by: HashSet<SocketAddr>,
+    /// Peers that rejected it
+    pub rejected_by: HashSet<SocketAddr>,
+    /// When tracking started
+    pub broadcast_at: Instant,
+}
+
+impl TxPropagation {
+    fn new() -> Self {
+        Self {
+            announced_by: HashSet::new(),
+            rejected_by: HashSet::new(),
+            broadcast_at: Instant::now(),
+        }
+    }
 }
 
 // =============================================================================
@@ -427,9 +511,28 @@ pub struct PeerManager {
     known_peers: RwLock<Vec<String>>,
     /// Banned peers
     banned: RwLock<HashMap<SocketAddr, BanEntry>>,
+    /// Addresses in the ban file as of our last load or save, to tell bans
+    /// another writer (the `node ban`/`unban` CLI) added or lifted since
+    /// from our own changes
+    ban_file_addrs: RwLock<HashSet<SocketAddr>>,
     /// Our listening port
-    #[allow(dead_code)]
     listen_port: u16,
+    /// External port mapped through UPnP, if a mapping is active
+    upnp_port: RwLock<Option<u16>>,
+    /// IPs exempt from connection limits, eviction and banning
+    whitelist: HashSet<IpAddr>,
+    /// Connections whitelisted peers may hold beyond the regular limits
+    reserved_slots: usize,
+    /// Peer acknowledgements of our own broadcast transactions, by tx ID
+    broadcasts: RwLock<HashMap<String, TxPropagation>>,
+    /// Peers needed before locally mined blocks are announced (0 = always)
+    min_peers_to_announce: usize,
+    /// Locally mined blocks waiting for enough peers and a finished sync
+    held_blocks: RwLock<Vec<Block>>,
+    /// Our genesis block hash; peers announcing another one are rejected
+    genesis_hash: RwLock<Option<String>>,
+    /// Notifies subscribers of every removed peer
+    disconnects: broadcast::Sender<SocketAddr>,
 }
 
 impl PeerManager {
@@ -439,10 +542,59 @@ impl PeerManager {
             handles: RwLock::new(HashMap::new()),
             known_peers: RwLock::new(Vec::new()),
             banned: RwLock::new(HashMap::new()),
+            ban_file_addrs: RwLock::new(HashSet::new()),
             listen_port,
+            upnp_port: RwLock::new(None),
+            whitelist: HashSet::new(),
+            reserved_slots: DEFAULT_RESERVED_SLOTS,
+            broadcasts: RwLock::new(HashMap::new()),
+            min_peers_to_announce: 0,
+            held_blocks: RwLock::new(Vec::new()),
+            genesis_hash: RwLock::new(None),
+            disconnects: broadcast::channel(64).0,
         }
     }
 
+    /// Hold locally mined blocks until at least `min_peers` peers are
+    /// connected and none of them has a longer chain, so an isolated node
+    /// can't spread a private chain that later forces a deep reorg
+    pub fn with_min_peers_to_announce(mut self, min_peers: usize) -> Self {
+        self.min_peers_to_announce = min_peers;
+        self
+    }
+
+    /// Whitelist `ips`, giving them `reserved_slots` connections that
+    /// regular peers can't take
+    pub fn with_whitelist(
+        mut self,
+        ips: impl IntoIterator<Item = IpAddr>,
+        reserved_slots: usize,
+    ) -> Self {
+        self.whitelist = ips.into_iter().collect();
+        self.reserved_slots = reserved_slots;
+        self
+    }
+
+    /// Whether connections from this address are whitelisted
+    pub fn is_whitelisted(&self, addr: &SocketAddr) -> bool {
+        self.whitelist.contains(&addr.ip())
+    }
+
+    /// Our listening port
+    pub fn listen_port(&self) -> u16 {
+        self.listen_port
+    }
+
+    /// Record the active UPnP port mapping (`None` once it is removed)
+    pub async fn set_upnp_mapping(&self, port: Option<u16>) {
+        *self.upnp_port.write().await = port;
+    }
+
+    /// Externally mapped UPnP port, if any
+    pub async fn upnp_mapping(&self) -> Option<u16> {
+        *self.upnp_port.read().await
+    }
+
     /// Check if address is banned
     pub async fn is_banned(&self, addr: &SocketAddr) -> bool {
         let banned = self.banned.read().await;
@@ -453,8 +605,13 @@ impl PeerManager {
         }
     }
 
-    /// Ban a peer
+    /// Ban a peer (whitelisted peers are never banned)
     pub async fn ban_peer(&self, addr: &SocketAddr, duration: Duration, reason: &str) {
+        if self.is_whitelisted(addr) {
+            log::info!("Not banning whitelisted peer {}: {}", addr, reason);
+            return;
+        }
+
         // Remove from connected peers
         self.remove_peer(addr).await;
 
@@ -483,6 +640,77 @@ impl PeerManager {
         banned.values().cloned().collect()
     }
 
+    /// Export active bans with wall-clock expiry times
+    pub async fn export_bans(&self) -> Vec<BanRecord> {
+        let now = chrono::Utc::now().timestamp();
+        let banned = self.banned.read().await;
+        banned
+            .values()
+            .filter(|entry| !entry.is_expired())
+            .map(|entry| entry.to_record(now))
+            .collect()
+    }
+
+    /// Import persisted bans, skipping any that have already expired.
+    /// Returns the number of bans restored.
+    pub async fn import_bans(&self, records: &[BanRecord]) -> usize {
+        let now = chrono::Utc::now().timestamp();
+        let mut banned = self.banned.write().await;
+        let mut imported = 0;
+
+        for record in records {
+            if let Some(entry) = BanEntry::from_record(record, now) {
+                banned.insert(entry.addr, entry);
+                imported += 1;
+            }
+        }
+
+        imported
+    }
+
+    /// Write active bans to a JSON file.
+    ///
+    /// The file may have been changed by another process since we last
+    /// loaded or saved it, so its changes are merged in first: bans added
+    /// there are kept and bans lifted there are dropped, while our own
+    /// bans and unbans take effect as usual.
+    pub async fn save_bans(&self, path: &Path) -> Result<(), PeerError> {
+        let on_disk = Self::read_ban_file(path)?;
+        let disk_addrs: HashSet<SocketAddr> = on_disk.iter().map(|r| r.addr).collect();
+        let mut known = self.ban_file_addrs.write().await;
+        {
+            let now = chrono::Utc::now().timestamp();
+            let mut banned = self.banned.write().await;
+            banned.retain(|addr, _| disk_addrs.contains(addr) || !known.contains(addr));
+            for record in on_disk.iter().filter(|r| !known.contains(&r.addr)) {
+                if let Some(entry) = BanEntry::from_record(record, now) {
+                    banned.entry(record.addr).or_insert(entry);
+                }
+            }
+        }
+
+        let records = self.export_bans().await;
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_string_pretty(&records)?)?;
+        std::fs::rename(&temp_path, path)?;
+        *known = records.iter().map(|r| r.addr).collect();
+        Ok(())
+    }
+
+    /// Load bans from a JSON file written by `save_bans` (a missing file is empty)
+    pub async fn load_bans(&self, path: &Path) -> Result<usize, PeerError> {
+        let records = Self::read_ban_file(path)?;
+        *self.ban_file_addrs.write().await = records.iter().map(|r| r.addr).collect();
+        Ok(self.import_bans(&records).await)
+    }
+
+    fn read_ban_file(path: &Path) -> Result<Vec<BanRecord>, PeerError> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
     /// Add a new peer
     pub async fn add_peer(
         &self,
@@ -490,6 +718,10 @@ impl PeerManager {
         handle: PeerHandle,
         outbound: bool,
     ) -> Result<(), PeerError> {
+        if self.is_whitelisted(&addr) {
+            return self.add_whitelisted_peer(addr, handle, outbound).await;
+        }
+
         // Check if banned
         if self.is_banned(&addr).await {
             let banned = self.banned.read().await;
@@ -500,15 +732,18 @@ impl PeerManager {
 
         let mut peers = self.peers.write().await;
 
-        // Check connection limits
-        let outbound_count = peers.values().filter(|p| p.outbound).count();
-        let inbound_count = peers.values().filter(|p| !p.outbound).count();
+        // Check connection limits (whitelisted peers sit in reserved slots,
+        // block-relay-only peers don't use up transaction relay slots)
+        let regular = || peers.values().filter(|p| !p.whitelisted);
+        let tx_relay = || regular().filter(|p| !p.block_relay_only);
+        let outbound_count = tx_relay().filter(|p| p.outbound).count();
+        let inbound_count = tx_relay().filter(|p| !p.outbound).count();
+        let regular_count = regular().count();
 
         // Check subnet diversity (max 2 peers per /16 subnet for eclipse protection)
         let subnet = Self::get_subnet(&addr);
-        let same_subnet_count = peers
-            .keys()
-            .filter(|a| Self::get_subnet(a) == subnet)
+        let same_subnet_count = regular()
+            .filter(|p| Self::get_subnet(&p.addr) == subnet)
             .count();
 
         if same_subnet_count >= 2 {
@@ -532,7 +767,7 @@ impl PeerManager {
             }
         }
 
-        if peers.len() >= MAX_PEERS {
+        if regular_count >= MAX_PEERS {
             // Try to evict any peer
             if let Some(evicted) = Self::select_eviction_candidate(&peers, true) {
                 log::info!("Evicting peer {} to make room for {}", evicted, addr);
@@ -560,6 +795,28 @@ impl PeerManager {
         Ok(())
     }
 
+    /// Add a whitelisted peer into a reserved slot, bypassing bans and the
+    /// regular connection limits
+    async fn add_whitelisted_peer(
+        &self,
+        addr: SocketAddr,
+        handle: PeerHandle,
+        outbound: bool,
+    ) -> Result<(), PeerError> {
+        let mut peers = self.peers.write().await;
+        if peers.values().filter(|p| p.whitelisted).count() >= self.reserved_slots {
+            return Err(PeerError::MaxPeersReached);
+        }
+
+        let mut info = PeerInfo::new(addr, outbound);
+        info.whitelisted = true;
+        peers.insert(addr, info);
+        self.handles.write().await.insert(addr, handle);
+
+        log::info!("Added whitelisted peer: {} (outbound: {})", addr, outbound);
+        Ok(())
+    }
+
     /// Get /16 subnet for an address (eclipse attack protection)
     fn get_subnet(addr: &SocketAddr) -> String {
         match addr.ip() {
@@ -582,7 +839,7 @@ impl PeerManager {
     ) -> Option<SocketAddr> {
         peers
             .iter()
-            .filter(|(_, p)| include_outbound || !p.outbound)
+            .filter(|(_, p)| !p.whitelisted && (include_outbound || !p.outbound))
             .min_by(|(_, a), (_, b)| {
                 // First compare by score (lower is worse)
                 a.score
@@ -596,12 +853,21 @@ impl PeerManager {
     /// Remove a peer
     pub async fn remove_peer(&self, addr: &SocketAddr) {
         let mut peers = self.peers.write().await;
-        peers.remove(addr);
+        let removed = peers.remove(addr).is_some();
 
         let mut handles = self.handles.write().await;
         handles.remove(addr);
 
         log::info!("Removed peer: {}", addr);
+        if removed {
+            // Nobody listening is fine
+            let _ = self.disconnects.send(*addr);
+        }
+    }
+
+    /// Subscribe to the addresses of peers as they are removed
+    pub fn subscribe_disconnects(&self) -> broadcast::Receiver<SocketAddr> {
+        self.disconnects.subscribe()
     }
 
     /// Report misbehavior and potentially ban
@@ -612,6 +878,11 @@ impl PeerManager {
     ) -> Result<(), PeerError> {
         let mut peers = self.peers.write().await;
         if let Some(peer) = peers.get_mut(addr) {
+            if peer.whitelisted {
+                log::debug!("Whitelisted peer {} misbehaved ({:?})", addr, behavior);
+                return Ok(());
+            }
+
             let new_score = peer.penalize(behavior);
             log::warn!(
                 "Peer {} misbehaved ({:?}), score: {}",
@@ -644,7 +915,9 @@ impl PeerManager {
         if let Some(peer) = peers.get_mut(addr) {
             let allowed = match msg {
                 Message::NewBlock(_) | Message::Blocks(_) => peer.rate_limiter.check_block(),
-                Message::NewTransaction(_) => peer.rate_limiter.check_transaction(),
+                Message::NewTransaction(_) | Message::StemTransaction { .. } => {
+                    peer.rate_limiter.check_transaction()
+                }
                 _ => peer.rate_limiter.check_message(),
             };
 
@@ -659,6 +932,22 @@ impl PeerManager {
         Ok(())
     }
 
+    /// Record the genesis block hash peers must share with us
+    pub async fn set_genesis_hash(&self, genesis_hash: String) {
+        *self.genesis_hash.write().await = Some(genesis_hash);
+    }
+
+    /// Reject a peer announcing a different genesis block. Peers that don't
+    /// announce one (older versions) are let through.
+    pub async fn check_genesis(&self, their_genesis: &str) -> Result<(), PeerError> {
+        match &*self.genesis_hash.read().await {
+            Some(ours) if !their_genesis.is_empty() && their_genesis != ours => {
+                Err(PeerError::GenesisMismatch(their_genesis.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Update peer from version message
     pub async fn update_peer_version(
         &self,
@@ -668,6 +957,7 @@ impl PeerManager {
         if version.version < MIN_PROTOCOL_VERSION {
             return Err(PeerError::IncompatibleVersion(version.version));
         }
+        self.check_genesis(&version.genesis_hash).await?;
 
         let mut peers = self.peers.write().await;
         if let Some(peer) = peers.get_mut(addr) {
@@ -704,6 +994,40 @@ impl PeerManager {
         peers.keys().cloned().collect()
     }
 
+    /// Connected peers advertising every flag in `services`
+    pub async fn get_peers_with_services(&self, services: ServiceFlags) -> Vec<SocketAddr> {
+        let peers = self.peers.read().await;
+        peers
+            .iter()
+            .filter(|(_, peer)| peer.services.contains(services))
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    /// Connected peers transactions may be relayed to (advertising every
+    /// flag in `services`), leaving out block-relay-only connections
+    pub async fn get_tx_relay_peers(&self, services: ServiceFlags) -> Vec<SocketAddr> {
+        let peers = self.peers.read().await;
+        peers
+            .iter()
+            .filter(|(_, peer)| !peer.block_relay_only && peer.services.contains(services))
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    /// Whether only blocks are relayed to and from a peer
+    pub async fn is_block_relay_only(&self, addr: &SocketAddr) -> bool {
+        let peers = self.peers.read().await;
+        peers.get(addr).is_some_and(|peer| peer.block_relay_only)
+    }
+
+    /// Stop relaying transactions to and from a peer
+    pub async fn set_block_relay_only(&self, addr: &SocketAddr) {
+        if let Some(peer) = self.peers.write().await.get_mut(addr) {
+            peer.block_relay_only = true;
+        }
+    }
+
     /// Get peer info
     pub async fn get_peer_info(&self, addr: &SocketAddr) -> Option<PeerInfo> {
         let peers = self.peers.read().await;
@@ -738,6 +1062,117 @@ impl PeerManager {
         }
     }
 
+    /// Start tracking which peers pick up a transaction we broadcast,
+    /// forgetting the oldest tracked one when at capacity
+    pub async fn track_broadcast(&self, tx_id: &str) {
+        let mut broadcasts = self.broadcasts.write().await;
+        if broadcasts.contains_key(tx_id) {
+            return;
+        }
+
+        if broadcasts.len() >= MAX_TRACKED_BROADCASTS {
+            let oldest = broadcasts
+                .iter()
+                .min_by_key(|(_, propagation)| propagation.broadcast_at)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                broadcasts.remove(&oldest);
+            }
+        }
+        broadcasts.insert(tx_id.to_string(), TxPropagation::new());
+    }
+
+    /// Note that `peer` announced or relayed a transaction, which it only
+    /// does after accepting it (ignored unless the transaction is tracked)
+    pub async fn record_tx_announcement(&self, tx_id: &str, peer: &SocketAddr) {
+        if let Some(propagation) = self.broadcasts.write().await.get_mut(tx_id) {
+            propagation.rejected_by.remove(peer);
+            propagation.announced_by.insert(*peer);
+        }
+    }
+
+    /// Note that `peer` rejected a transaction (ignored unless tracked)
+    pub async fn record_tx_reject(&self, tx_id: &str, peer: &SocketAddr) {
+        if let Some(propagation) = self.broadcasts.write().await.get_mut(tx_id) {
+            propagation.announced_by.remove(peer);
+            propagation.rejected_by.insert(*peer);
+        }
+    }
+
+    /// Propagation of a tracked transaction among currently connected peers
+    pub async fn tx_propagation(&self, tx_id: &str) -> Option<TxPropagation> {
+        let mut propagation = self.broadcasts.read().await.get(tx_id)?.clone();
+        let peers = self.peers.read().await;
+        propagation
+            .announced_by
+            .retain(|peer| peers.contains_key(peer));
+        propagation
+            .rejected_by
+            .retain(|peer| peers.contains_key(peer));
+        Some(propagation)
+    }
+
+    /// Whether a block we mined at `height` may be announced yet
+    async fn ready_to_announce(&self, height: u64) -> bool {
+        if self.min_peers_to_announce == 0 {
+            return true;
+        }
+        self.peer_count().await >= self.min_peers_to_announce
+            && self
+                .get_best_peer()
+                .await
+                .is_none_or(|(_, peer_height)| peer_height <= height)
+    }
+
+    /// Announce a block this node mined, or hold it back while the node
+    /// has too few peers or is behind one. Returns whether it was sent.
+    pub async fn announce_mined_block(&self, block: Block) -> bool {
+        if self.ready_to_announce(block.index).await {
+            self.broadcast(Message::NewBlock(block)).await;
+            return true;
+        }
+
+        log::info!(
+            "Holding mined block {} until {} peers are connected and synced",
+            block.index,
+            self.min_peers_to_announce
+        );
+        self.held_blocks.write().await.push(block);
+        false
+    }
+
+    /// Announce held blocks once the node is ready, returning how many
+    /// were sent. Blocks no longer on `chain`'s active chain are dropped.
+    pub async fn release_held_blocks(&self, chain: &Blockchain) -> usize {
+        if self.held_blocks.read().await.is_empty() || !self.ready_to_announce(chain.height()).await
+        {
+            return 0;
+        }
+
+        let held = std::mem::take(&mut *self.held_blocks.write().await);
+        let mut released = 0;
+        for block in held {
+            let on_active_chain = chain
+                .get_block(block.index)
+                .is_some_and(|active| active.hash == block.hash);
+            if on_active_chain {
+                self.broadcast(Message::NewBlock(block)).await;
+                released += 1;
+            } else {
+                log::info!(
+                    "Dropping held block {}: no longer on the active chain",
+                    block.index
+                );
+            }
+        }
+        released
+    }
+
+    /// Number of mined blocks waiting to be announced
+    pub async fn held_block_count(&self) -> usize {
+        self.held_blocks.read().await.len()
+    }
+
     /// Broadcast a message to all peers
     pub async fn broadcast(&self, msg: Message) {
         let handles = self.handles.read().await;
@@ -760,6 +1195,33 @@ impl PeerManager {
         }
     }
 
+    /// Send a transaction message to every peer relaying transactions,
+    /// optionally excluding one address
+    pub async fn broadcast_transaction(&self, msg: Message, except: Option<&SocketAddr>) {
+        let peers = self.get_tx_relay_peers(ServiceFlags::NONE).await;
+        let handles = self.handles.read().await;
+        for addr in peers.iter().filter(|addr| Some(*addr) != except) {
+            if let Some(handle) = handles.get(addr) {
+                if let Err(e) = handle.send(msg.clone()).await {
+                    log::warn!("Failed to send to {}: {}", addr, e);
+                }
+            }
+        }
+    }
+
+    /// Pick a random peer relaying transactions, optionally excluding one
+    /// address
+    pub async fn random_peer(&self, except: Option<&SocketAddr>) -> Option<SocketAddr> {
+        use rand::seq::IteratorRandom;
+
+        let peers = self.get_tx_relay_peers(ServiceFlags::NONE).await;
+        let handles = self.handles.read().await;
+        peers
+            .into_iter()
+            .filter(|addr| Some(addr) != except && handles.contains_key(addr))
+            .choose(&mut rand::thread_rng())
+    }
+
     /// Send a message to a specific peer
     pub async fn send_to(&self, addr: &SocketAddr, msg: Message) -> Result<(), PeerError> {
         let handles = self.handles.read().await;
@@ -817,3 +1279,153 @@ pub struct PeerManagerStats {
     pub banned_count: usize,
     pub average_score: f64,
 }
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ban_list_survives_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(BAN_LIST_FILE);
+        let active: SocketAddr = "10.0.0.1:8333".parse().unwrap();
+        let expired: SocketAddr = "10.0.0.2:8333".parse().unwrap();
+
+        let manager = PeerManager::new(8333);
+        manager
+            .ban_peer(&active, Duration::from_secs(3600), "misbehaving")
+            .await;
+        manager.save_bans(&path).await.unwrap();
+
+        // Simulate a ban that ran out while the node was down
+        let mut records: Vec<BanRecord> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(records.len(), 1);
+        records.push(BanRecord {
+            addr: expired,
+            banned_until: chrono::Utc::now().timestamp() - 60,
+            reason: "old".to_string(),
+        });
+        std::fs::write(&path, serde_json::to_string(&records).unwrap()).unwrap();
+
+        let restarted = PeerManager::new(8333);
+        assert_eq!(restarted.load_bans(&path).await.unwrap(), 1);
+        assert!(restarted.is_banned(&active).await);
+        assert!(!restarted.is_banned(&expired).await);
+
+        let restored = restarted.get_banned().await;
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].reason, "misbehaving");
+        assert!(restored[0].remaining() > Duration::from_secs(3500));
+    }
+
+    #[tokio::test]
+    async fn test_whitelisted_peer_uses_reserved_slot() {
+        let backup: SocketAddr = "192.168.7.7:40000".parse().unwrap();
+        let manager = PeerManager::new(8333).with_whitelist([backup.ip()], 1);
+        let handle = |addr: SocketAddr| PeerHandle {
+            addr,
+            tx: mpsc::channel(1).0,
+        };
+
+        // Fill every inbound slot with regular peers from distinct subnets
+        let regular: Vec<SocketAddr> = (0..MAX_INBOUND)
+            .map(|i| format!("10.{}.0.1:8333", i).parse().unwrap())
+            .collect();
+        for addr in &regular {
+            manager.add_peer(*addr, handle(*addr), false).await.unwrap();
+        }
+
+        // The backup node still gets in, without evicting anyone
+        manager
+            .add_peer(backup, handle(backup), false)
+            .await
+            .unwrap();
+        assert_eq!(manager.peer_count().await, MAX_INBOUND + 1);
+        assert!(manager.get_peer_info(&backup).await.unwrap().whitelisted);
+
+        // An invalid block drops a regular peer, and a ban keeps it out...
+        assert!(manager
+            .report_misbehavior(&regular[0], Misbehavior::InvalidBlock)
+            .await
+            .is_err());
+        assert!(manager.get_peer_info(&regular[0]).await.is_none());
+        manager
+            .ban_peer(&regular[0], DEFAULT_BAN_DURATION, "invalid block")
+            .await;
+        assert!(manager.is_banned(&regular[0]).await);
+
+        // ...but the whitelisted peer is neither penalized nor banned
+        for _ in 0..3 {
+            manager
+                .report_misbehavior(&backup, Misbehavior::InvalidBlock)
+                .await
+                .unwrap();
+        }
+        manager
+            .ban_peer(&backup, DEFAULT_BAN_DURATION, "invalid block")
+            .await;
+        assert!(!manager.is_banned(&backup).await);
+        assert_eq!(
+            manager.get_peer_info(&backup).await.unwrap().score,
+            DEFAULT_PEER_SCORE
+        );
+
+        // Reserved slots are a limit too
+        let other: SocketAddr = "192.168.7.7:40001".parse().unwrap();
+        assert!(matches!(
+            manager.add_peer(other, handle(other), false).await,
+            Err(PeerError::MaxPeersReached)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_save_bans_merges_changes_made_by_the_cli() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(BAN_LIST_FILE);
+        let old: SocketAddr = "10.0.0.1:8333".parse().unwrap();
+        let node_ban: SocketAddr = "10.0.0.2:8333".parse().unwrap();
+        let cli_ban: SocketAddr = "10.0.0.3:8333".parse().unwrap();
+
+        let cli = PeerManager::new(0);
+        cli.ban_peer(&old, Duration::from_secs(3600), "old").await;
+        cli.save_bans(&path).await.unwrap();
+
+        // The node starts, then bans a peer of its own
+        let node = PeerManager::new(8333);
+        node.load_bans(&path).await.unwrap();
+        node.ban_peer(&node_ban, Duration::from_secs(3600), "misbehaving")
+            .await;
+
+        // Meanwhile `node unban` lifts the old ban and `node ban` adds one
+        let cli = PeerManager::new(0);
+        cli.load_bans(&path).await.unwrap();
+        cli.unban_peer(&old).await;
+        cli.ban_peer(&cli_ban, Duration::from_secs(3600), "manual ban")
+            .await;
+        cli.save_bans(&path).await.unwrap();
+
+        // The node's shutdown save keeps both sides' changes
+        node.save_bans(&path).await.unwrap();
+        let restarted = PeerManager::new(8333);
+        assert_eq!(restarted.load_bans(&path).await.unwrap(), 2);
+        assert!(!restarted.is_banned(&old).await);
+        assert!(restarted.is_banned(&node_ban).await);
+        assert!(restarted.is_banned(&cli_ban).await);
+    }
+
+    #[tokio::test]
+    async fn test_load_missing_ban_list() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = PeerManager::new(8333);
+        let loaded = manager
+            .load_bans(&temp_dir.path().join(BAN_LIST_FILE))
+            .await
+            .unwrap();
+        assert_eq!(loaded, 0);
+    }
+}
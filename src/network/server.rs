@@ -5,17 +5,53 @@
 //! - SHA-256 message checksums for integrity
 //! - Length-prefixed framing with magic bytes
 
-use crate::network::message::{Handshake, Message, MAGIC, HEADER_SIZE, MAX_MESSAGE_SIZE};
-use crate::network::peer::{PeerError, PeerHandle, PeerManager};
+use crate::network::message::{
+    Handshake, Message, MessageSizeClass, MessageSizeLimits, HEADER_SIZE, MAGIC,
+};
+use crate::network::peer::{Misbehavior, PeerError, PeerHandle, PeerManager};
 use bytes::{Buf, BufMut, BytesMut};
 use futures::sink::SinkExt;
 use futures::stream::StreamExt;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
+/// A frame larger than its message type allows
+#[derive(Debug, thiserror::Error)]
+#[error("{command} message too large: {size} bytes (max: {max})")]
+pub struct OversizedMessage {
+    pub command: String,
+    pub class: MessageSizeClass,
+    pub size: usize,
+    pub max: usize,
+}
+
+impl OversizedMessage {
+    fn new(command: &[u8], size: usize, limits: &MessageSizeLimits) -> Self {
+        let class = MessageSizeClass::of_command(command);
+        Self {
+            command: String::from_utf8_lossy(command)
+                .trim_end_matches('\0')
+                .to_string(),
+            class,
+            size,
+            max: limits.for_class(class),
+        }
+    }
+}
+
+/// Misbehavior to charge a peer for a read error, if any.
+///
+/// Oversized control messages can't be legitimate and are penalized; other
+/// oversized frames only close the connection.
+pub fn misbehavior_for_read_error(error: &std::io::Error) -> Option<Misbehavior> {
+    let oversized = error.get_ref()?.downcast_ref::<OversizedMessage>()?;
+    (oversized.class == MessageSizeClass::Control).then_some(Misbehavior::ProtocolViolation)
+}
+
 /// Message codec for length-prefixed framing with checksums
 /// 
 /// Header format (24 bytes):
@@ -26,12 +62,23 @@ use tokio_util::codec::{Decoder, Encoder, Framed};
 pub struct MessageCodec {
     /// Whether to verify checksums (can be disabled for testing)
     pub verify_checksum: bool,
+    /// Network magic written to and required on every frame
+    pub magic: [u8; 4],
+    /// Maximum payload size per message type
+    pub size_limits: MessageSizeLimits,
 }
 
 impl MessageCodec {
     pub fn new() -> Self {
+        Self::with_magic(MAGIC)
+    }
+
+    /// Codec for the network identified by `magic`
+    pub fn with_magic(magic: [u8; 4]) -> Self {
         Self {
             verify_checksum: true,
+            magic,
+            size_limits: MessageSizeLimits::default(),
         }
     }
 }
@@ -50,23 +97,23 @@ impl Encoder<Message> for MessageCodec {
             .to_bytes()
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
 
-        // Check message size limit
-        if data.len() > MAX_MESSAGE_SIZE {
+        // Get command name
+        let command = item.command();
+
+        // Check the size limit for this message type
+        if data.len() > self.size_limits.for_class(item.size_class()) {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                format!("Message too large: {} bytes", data.len()),
+                OversizedMessage::new(&command, data.len(), &self.size_limits),
             ));
         }
 
         // Compute checksum (first 4 bytes of double SHA-256)
         let checksum = Message::compute_checksum(&data);
 
-        // Get command name
-        let command = item.command();
-
         // Header: Magic (4) + Command (12) + Length (4) + Checksum (4) = 24 bytes
         dst.reserve(HEADER_SIZE + data.len());
-        dst.put_slice(&MAGIC);           // 4 bytes
+        dst.put_slice(&self.magic);      // 4 bytes
         dst.put_slice(&command);         // 12 bytes
         dst.put_u32(data.len() as u32);  // 4 bytes
         dst.put_slice(&checksum);        // 4 bytes
@@ -86,25 +133,28 @@ impl Decoder for MessageCodec {
             return Ok(None);
         }
 
-        // Check magic
-        if src[..4] != MAGIC {
+        // Check magic: frames from another network are rejected outright
+        if src[..4] != self.magic {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                "Invalid magic bytes",
+                format!("Invalid magic bytes: {}", hex::encode(&src[..4])),
             ));
         }
 
-        // Extract command (bytes 4-16) - for logging
-        let _command = &src[4..16];
+        // Extract command (bytes 4-16)
+        let command = &src[4..16];
 
         // Get length (bytes 16-20)
         let len = u32::from_be_bytes([src[16], src[17], src[18], src[19]]) as usize;
 
-        // Check message size limit
-        if len > MAX_MESSAGE_SIZE {
+        // Check the size limit for this message type before buffering it
+        let max = self
+            .size_limits
+            .for_class(MessageSizeClass::of_command(command));
+        if len > max {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                format!("Message too large: {} bytes", len),
+                OversizedMessage::new(command, len, &self.size_limits),
             ));
         }
 
@@ -152,10 +202,10 @@ pub struct Server {
 }
 
 impl Server {
-    /// Bind to a port and create the server
-    pub async fn bind(port: u16) -> Result<Self, std::io::Error> {
-        let addr = format!("0.0.0.0:{}", port);
-        let listener = TcpListener::bind(&addr).await?;
+    /// Bind to `ip` (IPv4 or IPv6) on a port and create the server
+    pub async fn bind(ip: IpAddr, port: u16) -> Result<Self, std::io::Error> {
+        let addr = SocketAddr::new(ip, port);
+        let listener = TcpListener::bind(addr).await?;
         log::info!("Server listening on {}", addr);
 
         Ok(Self { listener, port })
@@ -166,16 +216,39 @@ impl Server {
         self.port
     }
 
+    /// Address the listener is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.listener.local_addr()
+    }
+
     /// Accept incoming connections
     pub async fn accept(&self) -> Result<(TcpStream, SocketAddr), std::io::Error> {
         self.listener.accept().await
     }
 }
 
+/// Default time allowed for an outbound TCP connection to be established
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default time allowed for a peer to send its handshake
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A peer connection framed with the message codec
+pub type PeerStream = Framed<TcpStream, MessageCodec>;
+
 /// Connect to a peer
 pub async fn connect_to_peer(addr: &str) -> Result<(TcpStream, SocketAddr), PeerError> {
-    let stream = TcpStream::connect(addr)
+    connect_to_peer_with_timeout(addr, DEFAULT_CONNECT_TIMEOUT).await
+}
+
+/// Connect to a peer, giving up after `timeout`
+pub async fn connect_to_peer_with_timeout(
+    addr: &str,
+    timeout: Duration,
+) -> Result<(TcpStream, SocketAddr), PeerError> {
+    let stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
         .await
+        .map_err(|_| PeerError::Timeout(format!("connecting to {}", addr)))?
         .map_err(|e| PeerError::ConnectionFailed(e.to_string()))?;
 
     let peer_addr = stream
@@ -185,16 +258,73 @@ pub async fn connect_to_peer(addr: &str) -> Result<(TcpStream, SocketAddr), Peer
     Ok((stream, peer_addr))
 }
 
+/// Frame a connection with the codec for the network identified by `magic`
+pub fn frame_stream(stream: TcpStream, magic: [u8; 4]) -> PeerStream {
+    Framed::new(stream, MessageCodec::with_magic(magic))
+}
+
+/// Send our handshake and wait up to `timeout` for the peer's.
+///
+/// The peer's first message must be a `Handshake` or `Version`; it is
+/// returned with the framed stream so the caller can process it. A peer on
+/// another network fails here, since its frames carry different magic.
+pub async fn perform_handshake(
+    mut framed: PeerStream,
+    our_handshake: Handshake,
+    timeout: Duration,
+) -> Result<(PeerStream, Message), PeerError> {
+    framed
+        .send(Message::Handshake(our_handshake))
+        .await
+        .map_err(PeerError::IoError)?;
+
+    match tokio::time::timeout(timeout, framed.next()).await {
+        Err(_) => Err(PeerError::Timeout("waiting for handshake".to_string())),
+        Ok(Some(Ok(msg @ (Message::Handshake(_) | Message::Version(_))))) => Ok((framed, msg)),
+        Ok(Some(Ok(_))) => Err(PeerError::InvalidHandshake),
+        Ok(Some(Err(e))) => Err(PeerError::IoError(e)),
+        Ok(None) => Err(PeerError::Disconnected),
+    }
+}
+
 /// Handle a peer connection (both inbound and outbound)
 pub async fn handle_connection(
-    stream: TcpStream,
+    stream: PeerStream,
     addr: SocketAddr,
     peer_manager: Arc<PeerManager>,
     our_handshake: Handshake,
     message_tx: mpsc::Sender<(SocketAddr, Message)>,
     outbound: bool,
+    handshake_timeout: Duration,
+) -> Result<(), PeerError> {
+    let block_relay_only = !our_handshake.relay;
+    let (framed, their_handshake) =
+        perform_handshake(stream, our_handshake, handshake_timeout).await?;
+    run_connection(
+        framed,
+        addr,
+        peer_manager,
+        their_handshake,
+        message_tx,
+        outbound,
+        block_relay_only,
+    )
+    .await
+}
+
+/// Register a handshaken peer and relay its messages until it disconnects
+///
+/// `block_relay_only` is set when we asked the peer not to relay
+/// transactions; the peer can also ask for it in its own handshake.
+pub async fn run_connection(
+    framed: PeerStream,
+    addr: SocketAddr,
+    peer_manager: Arc<PeerManager>,
+    their_handshake: Message,
+    message_tx: mpsc::Sender<(SocketAddr, Message)>,
+    outbound: bool,
+    block_relay_only: bool,
 ) -> Result<(), PeerError> {
-    let framed = Framed::new(stream, MessageCodec::new());
     let (mut writer, mut reader) = framed.split();
 
     // Create channel for sending to this peer
@@ -203,14 +333,17 @@ pub async fn handle_connection(
 
     // Add peer to manager
     peer_manager.add_peer(addr, handle, outbound).await?;
+    if block_relay_only {
+        peer_manager.set_block_relay_only(&addr).await;
+    }
 
-    // Send our handshake
-    writer
-        .send(Message::Handshake(our_handshake))
-        .await
-        .map_err(PeerError::IoError)?;
+    log::debug!("Handshake completed with {}", addr);
 
-    log::debug!("Sent handshake to {}", addr);
+    // The peer is registered now, so its handshake can be processed
+    if message_tx.send((addr, their_handshake)).await.is_err() {
+        peer_manager.remove_peer(&addr).await;
+        return Ok(());
+    }
 
     // Spawn writer task
     let write_handle = tokio::spawn(async move {
@@ -232,6 +365,9 @@ pub async fn handle_connection(
             }
             Some(Err(e)) => {
                 log::warn!("Error reading from {}: {}", addr, e);
+                if let Some(behavior) = misbehavior_for_read_error(&e) {
+                    let _ = peer_manager.report_misbehavior(&addr, behavior).await;
+                }
                 break;
             }
             None => {
@@ -286,7 +422,10 @@ mod tests {
 
     #[test]
     fn test_checksum_disabled() {
-        let mut codec = MessageCodec { verify_checksum: false };
+        let mut codec = MessageCodec {
+            verify_checksum: false,
+            ..MessageCodec::new()
+        };
         let msg = Message::Ping(12345);
 
         let mut buf = BytesMut::new();
@@ -299,4 +438,54 @@ mod tests {
         let result = codec.decode(&mut buf);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_per_type_size_limits() {
+        use crate::core::{Block, ContractOperationType, Transaction};
+        use crate::network::message::{MAX_CONTROL_MESSAGE_SIZE, MAX_INVENTORY_MESSAGE_SIZE};
+
+        let mut codec = MessageCodec::new();
+
+        // A GetAddr frame claiming a payload far beyond any control message
+        let mut buf = BytesMut::new();
+        buf.put_slice(&MAGIC);
+        buf.put_slice(&Message::GetAddr.command());
+        buf.put_u32(MAX_CONTROL_MESSAGE_SIZE as u32 + 1);
+        buf.put_slice(&[0; 4]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            misbehavior_for_read_error(&err),
+            Some(Misbehavior::ProtocolViolation)
+        ));
+
+        // A multi-megabyte Blocks message is within the payload limit
+        let deploy = Transaction::with_contract_data(
+            vec![],
+            vec![],
+            ContractOperationType::Deploy {
+                bytecode: vec![7; 1_500_000],
+                constructor_args: vec![],
+            },
+        );
+        let block = Block::new(1, "parent".to_string(), vec![deploy], 1);
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Message::Blocks(vec![block]), &mut buf)
+            .unwrap();
+        assert!(buf.len() > MAX_INVENTORY_MESSAGE_SIZE);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Ok(Some(Message::Blocks(blocks))) if blocks.len() == 1
+        ));
+
+        // Oversized payload frames are dropped without a penalty
+        codec.size_limits.payload = 1024;
+        let mut buf = BytesMut::new();
+        buf.put_slice(&MAGIC);
+        buf.put_slice(&Message::Blocks(vec![]).command());
+        buf.put_u32(2048);
+        buf.put_slice(&[0; 4]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(misbehavior_for_read_error(&err).is_none());
+    }
 }
@@ -2,7 +2,7 @@
 //!
 //! Handles syncing the blockchain with connected peers.
 
-use crate::core::{Block, Blockchain, BlockchainError};
+use crate::core::{Block, Blockchain, BlockchainError, Transaction};
 use crate::network::message::Message;
 use crate::network::peer::PeerManager;
 use std::net::SocketAddr;
@@ -12,6 +12,15 @@ use tokio::sync::RwLock;
 /// Blocks to request per batch
 const SYNC_BATCH_SIZE: u32 = 50;
 
+/// Outcome of handling blocks from a peer
+#[derive(Debug, Default)]
+pub struct SyncOutcome {
+    /// Blocks connected to the chain
+    pub added: usize,
+    /// Transactions taken out of the chain by reorgs, for the mempool
+    pub returned_txs: Vec<Transaction>,
+}
+
 /// Chain synchronization manager
 pub struct ChainSync {
     blockchain: Arc<RwLock<Blockchain>>,
@@ -77,19 +86,22 @@ impl ChainSync {
         &self,
         blocks: Vec<Block>,
         from: SocketAddr,
-    ) -> Result<usize, BlockchainError> {
+    ) -> Result<SyncOutcome, BlockchainError> {
         if blocks.is_empty() {
             let mut syncing = self.syncing.write().await;
             *syncing = false;
-            return Ok(0);
+            return Ok(SyncOutcome::default());
         }
 
         let mut chain = self.blockchain.write().await;
-        let mut added = 0;
+        let mut outcome = SyncOutcome::default();
 
         for block in blocks {
-            // Verify block connects to our chain
-            if block.index != chain.height() + 1 {
+            // Blocks we have are skipped; lower ones may be a heavier fork
+            if chain.state.block_index.contains_key(&block.hash) {
+                continue;
+            }
+            if block.index > chain.height() + 1 {
                 log::warn!(
                     "Block {} doesn't connect to chain height {}",
                     block.index,
@@ -99,8 +111,9 @@ impl ChainSync {
             }
 
             match chain.add_block(block) {
-                Ok(()) => {
-                    added += 1;
+                Ok(returned) => {
+                    outcome.added += 1;
+                    outcome.returned_txs.extend(returned);
                 }
                 Err(e) => {
                     log::warn!("Failed to add synced block: {}", e);
@@ -112,10 +125,14 @@ impl ChainSync {
         let current_height = chain.height();
         drop(chain);
 
-        log::info!("Synced {} blocks, height now {}", added, current_height);
+        log::info!(
+            "Synced {} blocks, height now {}",
+            outcome.added,
+            current_height
+        );
 
         // Continue syncing if we added a full batch
-        if added == SYNC_BATCH_SIZE as usize {
+        if outcome.added == SYNC_BATCH_SIZE as usize {
             let msg = Message::GetBlocks {
                 start_height: current_height + 1,
                 count: SYNC_BATCH_SIZE,
@@ -129,7 +146,7 @@ impl ChainSync {
             log::info!("Sync complete");
         }
 
-        Ok(added)
+        Ok(outcome)
     }
 
     /// Handle a new block announcement
@@ -137,13 +154,21 @@ impl ChainSync {
         &self,
         block: Block,
         from: SocketAddr,
-    ) -> Result<bool, BlockchainError> {
+    ) -> Result<SyncOutcome, BlockchainError> {
         let mut chain = self.blockchain.write().await;
 
-        // Check if block connects to our chain
-        if block.index == chain.height() + 1 {
-            if block.header.previous_hash == chain.latest_block().hash {
-                chain.add_block(block.clone())?;
+        // Check if block connects to our chain, at the tip or as a fork
+        let parent_known = chain
+            .state
+            .block_index
+            .contains_key(&block.header.previous_hash);
+        if block.index <= chain.height() + 1 {
+            if parent_known && !chain.state.block_index.contains_key(&block.hash) {
+                let returned_txs = chain.add_block(block.clone())?;
+                // A fork without more work stays on the side
+                if chain.latest_block().hash != block.hash {
+                    return Ok(SyncOutcome::default());
+                }
                 log::info!("Added new block {} from peer", block.index);
 
                 // Relay to other peers
@@ -152,7 +177,10 @@ impl ChainSync {
                     .broadcast_except(Message::NewBlock(block), &from)
                     .await;
 
-                return Ok(true);
+                return Ok(SyncOutcome {
+                    added: 1,
+                    returned_txs,
+                });
             }
         } else if block.index > chain.height() + 1 {
             // We're behind, need to sync
@@ -160,7 +188,7 @@ impl ChainSync {
             self.check_sync().await;
         }
 
-        Ok(false)
+        Ok(SyncOutcome::default())
     }
 
     /// Get blocks for a peer request
@@ -170,6 +198,10 @@ impl ChainSync {
 
         for i in 0..count {
             let height = start_height + i as u64;
+            // Pruned blocks no longer have bodies to serve
+            if !chain.has_block_data(height) {
+                break;
+            }
             if let Some(block) = chain.get_block(height) {
                 blocks.push(block.clone());
             } else {
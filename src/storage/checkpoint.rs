@@ -8,6 +8,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Blocks between automatic checkpoints by default
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1000;
+
 // =============================================================================
 // Checkpoint Entry
 // =============================================================================
@@ -46,7 +49,7 @@ impl Checkpoint {
 // =============================================================================
 
 /// Manages checkpoints for chain validation
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CheckpointManager {
     /// Checkpoints by height
     checkpoints: HashMap<u64, Checkpoint>,
@@ -6,7 +6,7 @@
 //! - Transaction by ID
 //! - Transactions by address
 
-use crate::core::{Block, Transaction};
+use crate::core::{Block, OutPoint, Transaction, UTXO};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -206,12 +206,15 @@ pub struct BlockIndexStats {
 // =============================================================================
 
 /// Index for efficient transaction lookups
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TxIndex {
     /// Transactions by ID
     by_id: HashMap<String, TxIndexEntry>,
     /// Transaction IDs by address
     by_address: HashMap<String, Vec<AddressIndexEntry>>,
+    /// Recipient and amount of every indexed output (`txid:vout` keys),
+    /// used to attribute spends to the address they spend from
+    outputs: HashMap<String, (String, u64)>,
 }
 
 impl TxIndex {
@@ -230,8 +233,26 @@ impl TxIndex {
         let entry = TxIndexEntry::from_tx(tx, block_hash, block_height, tx_index);
         self.by_id.insert(tx.id.clone(), entry);
 
+        // Index by address (inputs = sends, for outputs we know the owner of)
+        if !tx.is_coinbase {
+            for input in &tx.inputs {
+                let key = OutPoint::key_for(&input.tx_id, input.output_index);
+                if let Some((recipient, amount)) = self.outputs.get(&key) {
+                    self.by_address
+                        .entry(recipient.clone())
+                        .or_default()
+                        .push(AddressIndexEntry {
+                            tx_id: tx.id.clone(),
+                            block_height,
+                            is_receive: false,
+                            amount: *amount,
+                        });
+                }
+            }
+        }
+
         // Index by address (outputs = receives)
-        for output in &tx.outputs {
+        for (index, output) in tx.outputs.iter().enumerate() {
             let addr_entry = AddressIndexEntry {
                 tx_id: tx.id.clone(),
                 block_height,
@@ -242,9 +263,23 @@ impl TxIndex {
                 .entry(output.recipient.clone())
                 .or_default()
                 .push(addr_entry);
+            self.outputs.insert(
+                OutPoint::key_for(&tx.id, index as u32),
+                (output.recipient.clone(), output.amount),
+            );
         }
     }
 
+    /// Record the owner of an output whose transaction is not indexed
+    /// (e.g. one restored from a pruned chain's UTXO snapshot), so later
+    /// spends of it are still attributed
+    pub fn track_output(&mut self, utxo: &UTXO) {
+        self.outputs.insert(
+            utxo.key(),
+            (utxo.output.recipient.clone(), utxo.output.amount),
+        );
+    }
+
     /// Get transaction entry by ID
     pub fn get_by_id(&self, tx_id: &str) -> Option<&TxIndexEntry> {
         self.by_id.get(tx_id)
@@ -258,6 +293,24 @@ impl TxIndex {
             .unwrap_or_default()
     }
 
+    /// Get transactions for an address within blocks `from..=to`, in the
+    /// order they were indexed
+    pub fn get_by_address_range(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Vec<&AddressIndexEntry> {
+        self.by_address
+            .get(address)
+            .map(|v| {
+                v.iter()
+                    .filter(|e| (from..=to).contains(&e.block_height))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Check if transaction exists
     pub fn contains(&self, tx_id: &str) -> bool {
         self.by_id.contains_key(tx_id)
@@ -282,9 +335,19 @@ impl TxIndex {
             .map(|(id, _)| id.clone())
             .collect();
 
+        self.remove_transactions(&tx_ids);
+    }
+
+    /// Remove transactions by ID (for reorgs)
+    pub fn remove_transactions(&mut self, tx_ids: &[String]) {
         for tx_id in tx_ids {
-            self.by_id.remove(&tx_id);
+            self.by_id.remove(tx_id);
         }
+        let removed: std::collections::HashSet<&str> = tx_ids.iter().map(String::as_str).collect();
+        self.outputs.retain(|key, _| {
+            key.split_once(':')
+                .is_none_or(|(tx_id, _)| !removed.contains(tx_id))
+        });
 
         // Clean up address index (less efficient, but handles reorgs)
         for entries in self.by_address.values_mut() {
@@ -344,5 +407,20 @@ mod tests {
         let addr_txs = index.get_by_address("recipient");
         assert_eq!(addr_txs.len(), 1);
         assert_eq!(addr_txs[0].amount, 50);
+
+        // Spending the output is indexed as a send from its owner
+        let mut spend = crate::core::Transaction::coinbase("other", 50, 2);
+        spend.is_coinbase = false;
+        spend.inputs[0].tx_id = tx.id.clone();
+        spend.inputs[0].output_index = 0;
+        index.add_transaction(&spend, "block_hash_2", 2, 0);
+
+        let sends = index.get_by_address_range("recipient", 2, 2);
+        assert_eq!(sends.len(), 1);
+        assert!(!sends[0].is_receive);
+        assert_eq!(sends[0].amount, 50);
+
+        index.remove_transactions(&[spend.id.clone()]);
+        assert!(index.get_by_address_range("recipient", 2, 2).is_empty());
     }
 }
@@ -12,7 +12,9 @@ pub mod persistence;
 pub mod pruning;
 pub mod utxo_cache;
 
-pub use checkpoint::{Checkpoint, CheckpointManager, CheckpointResult};
+pub use checkpoint::{
+    Checkpoint, CheckpointManager, CheckpointResult, DEFAULT_CHECKPOINT_INTERVAL,
+};
 pub use index::{
     BlockIndex, BlockIndexEntry, BlockIndexStats, TxIndex, TxIndexEntry, TxIndexStats,
 };
@@ -2,10 +2,11 @@
 //!
 //! Provides save/load functionality for the blockchain.
 
-use crate::core::Blockchain;
+use crate::core::{Blockchain, BLOCK_HASH_FORMAT_VERSION};
 use std::fs;
 use std::io::{self, BufReader, BufWriter};
 use std::path::Path;
+use std::sync::Mutex;
 use thiserror::Error;
 
 /// Storage errors
@@ -17,6 +18,24 @@ pub enum StorageError {
     SerializationError(#[from] serde_json::Error),
     #[error("Invalid data: {0}")]
     InvalidData(String),
+    #[error(
+        "Chain uses block hash format {found} but this node requires {expected}; \
+         re-initialize the data directory or re-sync from peers"
+    )]
+    IncompatibleHashFormat { found: u32, expected: u32 },
+}
+
+/// Reject chains whose stored hashes were computed under a different block
+/// hash format, then rebuild the UTXO set (not serialized)
+fn finish_load(mut blockchain: Blockchain) -> Result<Blockchain, StorageError> {
+    if blockchain.hash_format != BLOCK_HASH_FORMAT_VERSION {
+        return Err(StorageError::IncompatibleHashFormat {
+            found: blockchain.hash_format,
+            expected: BLOCK_HASH_FORMAT_VERSION,
+        });
+    }
+    blockchain.rebuild_utxo_set();
+    Ok(blockchain)
 }
 
 /// Storage configuration
@@ -42,13 +61,18 @@ impl Default for StorageConfig {
 /// Blockchain storage manager
 pub struct Storage {
     config: StorageConfig,
+    /// Held while writing so concurrent saves never share `blockchain.tmp`
+    save_lock: Mutex<()>,
 }
 
 impl Storage {
     /// Create a new storage manager
     pub fn new(config: StorageConfig) -> Result<Self, StorageError> {
         fs::create_dir_all(&config.data_dir)?;
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            save_lock: Mutex::new(()),
+        })
     }
 
     /// Create with default configuration
@@ -71,6 +95,7 @@ impl Storage {
     /// Save the blockchain to disk
     pub fn save(&self, blockchain: &Blockchain) -> Result<(), StorageError> {
         let path = self.blockchain_path();
+        let _guard = self.save_lock.lock().unwrap_or_else(|e| e.into_inner());
 
         // Create backup if enabled
         if self.config.backup_enabled && path.exists() {
@@ -104,12 +129,9 @@ impl Storage {
         let file = fs::File::open(&path)?;
         let reader = BufReader::new(file);
 
-        let mut blockchain: Blockchain = serde_json::from_reader(reader)?;
+        let blockchain: Blockchain = serde_json::from_reader(reader)?;
 
-        // Rebuild UTXO set (not serialized)
-        blockchain.rebuild_utxo_set();
-
-        Ok(blockchain)
+        finish_load(blockchain)
     }
 
     /// Check if a saved blockchain exists
@@ -160,10 +182,9 @@ impl Storage {
         let file = fs::File::open(&backup_path)?;
         let reader = BufReader::new(file);
 
-        let mut blockchain: Blockchain = serde_json::from_reader(reader)?;
-        blockchain.rebuild_utxo_set();
+        let blockchain: Blockchain = serde_json::from_reader(reader)?;
 
-        Ok(blockchain)
+        finish_load(blockchain)
     }
 
     /// List available backups
@@ -219,9 +240,8 @@ pub fn save_to_file(blockchain: &Blockchain, path: &Path) -> Result<(), StorageE
 pub fn load_from_file(path: &Path) -> Result<Blockchain, StorageError> {
     let file = fs::File::open(path)?;
     let reader = BufReader::new(file);
-    let mut blockchain: Blockchain = serde_json::from_reader(reader)?;
-    blockchain.rebuild_utxo_set();
-    Ok(blockchain)
+    let blockchain: Blockchain = serde_json::from_reader(reader)?;
+    finish_load(blockchain)
 }
 
 #[cfg(test)]
@@ -249,6 +269,33 @@ mod tests {
         assert_eq!(loaded.difficulty, blockchain.difficulty);
     }
 
+    #[test]
+    fn test_load_rejects_legacy_hash_format() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("legacy.json");
+
+        // Files written before the format was recorded carry no
+        // `hash_format` and were hashed with the legacy string preimage
+        let mut json = serde_json::to_value(Blockchain::with_difficulty(4)).unwrap();
+        json.as_object_mut().unwrap().remove("hash_format");
+        fs::write(&path, json.to_string()).unwrap();
+
+        match load_from_file(&path) {
+            Err(StorageError::IncompatibleHashFormat { found, expected }) => {
+                assert_eq!(found, 1);
+                assert_eq!(expected, BLOCK_HASH_FORMAT_VERSION);
+            }
+            other => panic!(
+                "expected IncompatibleHashFormat, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+
+        // Current-format files round-trip
+        save_to_file(&Blockchain::with_difficulty(4), &path).unwrap();
+        assert!(load_from_file(&path).is_ok());
+    }
+
     #[test]
     fn test_backup_rotation() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -5,7 +5,7 @@
 //! - Dirty tracking for persistence
 //! - Batch updates for efficiency
 
-use crate::core::transaction::UTXO;
+use crate::core::transaction::{OutPoint, UTXO};
 use std::collections::{HashMap, VecDeque};
 
 // =============================================================================
@@ -87,7 +87,7 @@ impl UtxoCache {
 
     /// Get a UTXO from cache (clones the UTXO to avoid borrow issues)
     pub fn get(&mut self, tx_id: &str, output_index: u32) -> Option<UTXO> {
-        let key = format!("{}:{}", tx_id, output_index);
+        let key = OutPoint::key_for(tx_id, output_index);
 
         let result = self.entries.get(&key).and_then(|entry| {
             if entry.deleted {
@@ -109,7 +109,7 @@ impl UtxoCache {
 
     /// Get a UTXO reference from cache (no LRU update, immutable)
     pub fn peek(&self, tx_id: &str, output_index: u32) -> Option<&UTXO> {
-        let key = format!("{}:{}", tx_id, output_index);
+        let key = OutPoint::key_for(tx_id, output_index);
         self.entries.get(&key).and_then(|entry| {
             if entry.deleted {
                 None
@@ -121,7 +121,7 @@ impl UtxoCache {
 
     /// Insert a UTXO into cache
     pub fn insert(&mut self, utxo: UTXO) {
-        let key = format!("{}:{}", utxo.tx_id, utxo.output_index);
+        let key = utxo.key();
 
         // Evict if at capacity
         if self.entries.len() >= self.max_size {
@@ -140,7 +140,7 @@ impl UtxoCache {
 
     /// Mark a UTXO as spent (soft delete)
     pub fn spend(&mut self, tx_id: &str, output_index: u32) -> bool {
-        let key = format!("{}:{}", tx_id, output_index);
+        let key = OutPoint::key_for(tx_id, output_index);
 
         if let Some(entry) = self.entries.get_mut(&key) {
             if !entry.deleted {
@@ -156,7 +156,7 @@ impl UtxoCache {
 
     /// Remove a UTXO from cache entirely
     pub fn remove(&mut self, tx_id: &str, output_index: u32) -> Option<UTXO> {
-        let key = format!("{}:{}", tx_id, output_index);
+        let key = OutPoint::key_for(tx_id, output_index);
 
         if let Some(entry) = self.entries.remove(&key) {
             self.lru_order.retain(|k| k != &key);
@@ -172,7 +172,7 @@ impl UtxoCache {
 
     /// Check if UTXO exists and is unspent
     pub fn contains(&self, tx_id: &str, output_index: u32) -> bool {
-        let key = format!("{}:{}", tx_id, output_index);
+        let key = OutPoint::key_for(tx_id, output_index);
         self.entries.get(&key).map(|e| !e.deleted).unwrap_or(false)
     }
 
@@ -7,6 +7,25 @@ use crate::token::token::{ApprovalEvent, Token, TokenError, TokenMetadata, Trans
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// A holder's balance and share of circulating supply
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HolderShare {
+    pub address: String,
+    pub balance: u128,
+    /// Percent of circulating supply held
+    pub percent: f64,
+}
+
+/// Supply and holder-distribution statistics for a token
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenDistribution {
+    pub token: String,
+    pub total_supply: u128,
+    pub circulating_supply: u128,
+    pub holder_count: usize,
+    pub top_holders: Vec<HolderShare>,
+}
+
 /// Manages all tokens in the system
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TokenManager {
@@ -222,6 +241,41 @@ impl TokenManager {
         token.mint(caller, to, amount)
     }
 
+    /// Supply and distribution statistics, with the `top_n` largest holders
+    pub fn distribution(
+        &self,
+        token_address: &str,
+        top_n: usize,
+    ) -> Result<TokenDistribution, TokenError> {
+        let token = self
+            .tokens
+            .get(token_address)
+            .ok_or_else(|| TokenError::TokenNotFound(token_address.to_string()))?;
+
+        let circulating = token.circulating_supply();
+        let top_holders = token
+            .top_holders(top_n)
+            .into_iter()
+            .map(|(address, balance)| HolderShare {
+                address,
+                balance,
+                percent: if circulating == 0 {
+                    0.0
+                } else {
+                    balance as f64 * 100.0 / circulating as f64
+                },
+            })
+            .collect();
+
+        Ok(TokenDistribution {
+            token: token.address.clone(),
+            total_supply: token.total_supply(),
+            circulating_supply: circulating,
+            holder_count: token.holder_count(),
+            top_holders,
+        })
+    }
+
     /// Get transfer history for a token
     pub fn get_history(&self, token_address: &str) -> Result<Vec<TransferEvent>, TokenError> {
         let token = self
@@ -372,6 +426,49 @@ mod tests {
         assert_eq!(bob_tokens[0].1, 500);
     }
 
+    #[test]
+    fn test_distribution() {
+        let mut manager = TokenManager::new();
+        let token = manager
+            .create_token("Spread".to_string(), "SPR".to_string(), 0, 1000, "alice", 1)
+            .unwrap();
+
+        manager
+            .transfer(&token.address, "alice", "bob", 300)
+            .unwrap();
+        manager
+            .transfer(&token.address, "alice", "carol", 200)
+            .unwrap();
+        manager
+            .transfer(&token.address, "alice", "dave", 100)
+            .unwrap();
+        manager.burn(&token.address, "alice", 200).unwrap();
+
+        let dist = manager.distribution(&token.address, 3).unwrap();
+        assert_eq!(dist.total_supply, 1000);
+        assert_eq!(dist.circulating_supply, 800);
+        assert_eq!(dist.holder_count, 4);
+
+        let top: Vec<(&str, u128, f64)> = dist
+            .top_holders
+            .iter()
+            .map(|h| (h.address.as_str(), h.balance, h.percent))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                ("bob", 300, 37.5),
+                ("alice", 200, 25.0),
+                ("carol", 200, 25.0)
+            ]
+        );
+
+        assert!(matches!(
+            manager.distribution("0xmissing", 3),
+            Err(TokenError::TokenNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_transfer_nonexistent_token() {
         let mut manager = TokenManager::new();
@@ -32,5 +32,5 @@
 pub mod manager;
 pub mod token;
 
-pub use manager::TokenManager;
+pub use manager::{HolderShare, TokenDistribution, TokenManager};
 pub use token::{ApprovalEvent, Token, TokenError, TokenMetadata, TransferEvent};
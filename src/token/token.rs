@@ -226,6 +226,18 @@ impl Token {
         self.balances.values().filter(|&&b| b > 0).count()
     }
 
+    /// Get the `n` largest holders, richest first (ties by address)
+    pub fn top_holders(&self, n: usize) -> Vec<(String, u128)> {
+        let mut holders: Vec<(String, u128)> = self
+            .holders()
+            .into_iter()
+            .map(|(address, &balance)| (address.clone(), balance))
+            .collect();
+        holders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        holders.truncate(n);
+        holders
+    }
+
     // =========================================================================
     // ERC-20 Mutating Functions
     // =========================================================================
@@ -5,6 +5,7 @@
 use crate::core::{Blockchain, Transaction, TransactionBuilder, TransactionError, UTXO};
 use crate::crypto::KeyPair;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 use thiserror::Error;
@@ -14,6 +15,14 @@ use thiserror::Error;
 pub enum WalletError {
     #[error("Insufficient funds: have {have}, need {need}")]
     InsufficientFunds { have: u64, need: u64 },
+    #[error("Input not in UTXO set: {0}")]
+    UnknownInput(String),
+    #[error("Input not owned by this wallet: {0}")]
+    NotOwned(String),
+    #[error("Input is an immature coinbase: {0}")]
+    ImmatureInput(String),
+    #[error("Input selected more than once: {0}")]
+    DuplicateInput(String),
     #[error("Transaction error: {0}")]
     TransactionError(#[from] TransactionError),
     #[error("IO error: {0}")]
@@ -22,6 +31,8 @@ pub enum WalletError {
     SerializationError(#[from] serde_json::Error),
     #[error("Crypto error: {0}")]
     CryptoError(#[from] crate::crypto::KeyError),
+    #[error("Invalid wallet collection name: {0:?}")]
+    InvalidCollectionName(String),
 }
 
 /// Serializable wallet data for persistence
@@ -66,6 +77,20 @@ impl Wallet {
         })
     }
 
+    /// Import a wallet from a key exported with [`Wallet::export_key`]
+    pub fn import_key(wif: &str) -> Result<Self, WalletError> {
+        Ok(Self {
+            key_pair: KeyPair::from_wif(wif)?,
+            label: None,
+        })
+    }
+
+    /// Export the private key in checksummed Wallet Import Format
+    /// WARNING: Keep this secret!
+    pub fn export_key(&self) -> String {
+        self.key_pair.to_wif()
+    }
+
     /// Get the wallet's address
     pub fn address(&self) -> String {
         self.key_pair.address()
@@ -82,14 +107,10 @@ impl Wallet {
         self.key_pair.private_key_hex()
     }
 
-    /// Sign a message and return hex-encoded signature
+    /// Sign a message proving ownership of this wallet's address
+    /// Returns a hex-encoded signature checkable with `crypto::verify_address_message`
     pub fn sign_message(&self, message: &str) -> String {
-        use sha2::{Digest, Sha256};
-        let hash = Sha256::digest(message.as_bytes());
-        match self.key_pair.sign(&hash) {
-            Ok(sig) => hex::encode(sig),
-            Err(_) => String::new(),
-        }
+        crate::crypto::sign_address_message(&self.key_pair.secret_key, message).unwrap_or_default()
     }
 
     /// Sign raw data bytes and return signature bytes
@@ -113,14 +134,28 @@ impl Wallet {
         recipient: &str,
         amount: u64,
         blockchain: &Blockchain,
+    ) -> Result<Transaction, WalletError> {
+        self.create_transaction_with_fee(recipient, amount, 0, blockchain)
+    }
+
+    /// Create a transaction that leaves `fee` unspent for the miner
+    ///
+    /// An `amount` of zero pays only the fee, returning the rest as change.
+    pub fn create_transaction_with_fee(
+        &self,
+        recipient: &str,
+        amount: u64,
+        fee: u64,
+        blockchain: &Blockchain,
     ) -> Result<Transaction, WalletError> {
         let utxos = self.utxos(blockchain);
         let balance: u64 = utxos.iter().map(|u| u.output.amount).sum();
+        let needed = amount + fee;
 
-        if balance < amount {
+        if balance < needed {
             return Err(WalletError::InsufficientFunds {
                 have: balance,
-                need: amount,
+                need: needed,
             });
         }
 
@@ -132,7 +167,7 @@ impl Wallet {
             selected_utxos.push(utxo.clone());
             selected_amount += utxo.output.amount;
 
-            if selected_amount >= amount {
+            if selected_amount >= needed {
                 break;
             }
         }
@@ -145,16 +180,80 @@ impl Wallet {
         }
 
         // Output to recipient
-        builder = builder.add_output(recipient, amount);
+        if amount > 0 {
+            builder = builder.add_output(recipient, amount);
+        }
 
         // Change back to self
-        let change = selected_amount - amount;
+        let change = selected_amount - needed;
         if change > 0 {
             builder = builder.add_output(&self.address(), change);
         }
 
         // Build and sign
-        let tx = builder.build_and_sign(&self.key_pair)?;
+        let mut tx = builder.build_and_sign(&self.key_pair)?;
+        tx.fee = fee;
+        Ok(tx)
+    }
+
+    /// Create a transaction funded by exactly the `selected` UTXOs (coin control)
+    ///
+    /// Each input must be unspent, owned by this wallet, mature and selected
+    /// only once. Whatever the inputs hold beyond `outputs` and `fee` is
+    /// returned as change.
+    pub fn create_transaction_with_inputs(
+        &self,
+        selected: Vec<UTXO>,
+        outputs: Vec<(String, u64)>,
+        fee: u64,
+        blockchain: &Blockchain,
+    ) -> Result<Transaction, WalletError> {
+        let address = self.address();
+        let mut builder = TransactionBuilder::new();
+        let mut selected_amount = 0u64;
+        let mut seen = HashSet::new();
+
+        for utxo in &selected {
+            let outpoint = utxo.key();
+            if !seen.insert(outpoint.clone()) {
+                return Err(WalletError::DuplicateInput(outpoint));
+            }
+            // Trust the chain's copy of the output, not the caller's
+            let utxo = blockchain
+                .find_utxo(&utxo.tx_id, utxo.output_index)
+                .ok_or_else(|| WalletError::UnknownInput(outpoint.clone()))?;
+            if utxo.output.recipient != address {
+                return Err(WalletError::NotOwned(outpoint));
+            }
+            if !blockchain.is_coinbase_mature(&utxo.tx_id) {
+                return Err(WalletError::ImmatureInput(outpoint));
+            }
+
+            builder = builder.add_input(utxo);
+            selected_amount += utxo.output.amount;
+        }
+
+        let sent: u64 = outputs.iter().map(|(_, amount)| amount).sum();
+        let needed = sent + fee;
+        if selected_amount < needed {
+            return Err(WalletError::InsufficientFunds {
+                have: selected_amount,
+                need: needed,
+            });
+        }
+
+        for (recipient, amount) in &outputs {
+            builder = builder.add_output(recipient, *amount);
+        }
+
+        // Change back to self
+        let change = selected_amount - needed;
+        if change > 0 {
+            builder = builder.add_output(&address, change);
+        }
+
+        let mut tx = builder.build_and_sign(&self.key_pair)?;
+        tx.fee = fee;
         Ok(tx)
     }
 
@@ -219,6 +318,38 @@ impl WalletManager {
         })
     }
 
+    /// Open the named wallet collection, a subdirectory with its own set
+    /// of wallets (created if missing).
+    ///
+    /// Collections are isolated: wallets listed, loaded and signed with
+    /// through the returned manager never include another collection's.
+    pub fn load_collection(&self, name: &str) -> Result<WalletManager, WalletError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(WalletError::InvalidCollectionName(name.to_string()));
+        }
+
+        WalletManager::new(&self.wallets_dir.join(name))
+    }
+
+    /// Names of the wallet collections under this manager
+    pub fn list_collections(&self) -> Result<Vec<String>, WalletError> {
+        let mut names = Vec::new();
+
+        for entry in fs::read_dir(&self.wallets_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
     /// Create and save a new wallet
     pub fn create_wallet(&self, label: Option<&str>) -> Result<Wallet, WalletError> {
         let wallet = match label {
@@ -233,6 +364,18 @@ impl WalletManager {
         Ok(wallet)
     }
 
+    /// Import and save a wallet from a WIF-encoded private key
+    pub fn import_wallet(&self, wif: &str, label: Option<&str>) -> Result<Wallet, WalletError> {
+        let mut wallet = Wallet::import_key(wif)?;
+        wallet.label = label.map(str::to_string);
+
+        let filename = format!("{}.json", wallet.address());
+        let path = self.wallets_dir.join(filename);
+        wallet.save(&path)?;
+
+        Ok(wallet)
+    }
+
     /// List all wallet addresses
     pub fn list_wallets(&self) -> Result<Vec<String>, WalletError> {
         let mut addresses = Vec::new();
@@ -288,6 +431,38 @@ mod tests {
         assert_eq!(wallet1.address(), wallet2.address());
     }
 
+    #[test]
+    fn test_wallet_key_export_import() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = WalletManager::new(temp_dir.path()).unwrap();
+        let original = Wallet::new();
+
+        let imported = manager
+            .import_wallet(&original.export_key(), Some("moved"))
+            .unwrap();
+        assert_eq!(imported.address(), original.address());
+        assert_eq!(imported.label.as_deref(), Some("moved"));
+        assert_eq!(
+            manager.load_wallet(&original.address()).unwrap().address(),
+            original.address()
+        );
+
+        // The imported key signs for the same address
+        let signature = imported.sign_message("hello");
+        assert!(crate::crypto::verify_address_message(
+            &original.address(),
+            "hello",
+            &signature
+        ));
+
+        assert!(matches!(
+            Wallet::import_key(&original.private_key()),
+            Err(WalletError::CryptoError(
+                crate::crypto::KeyError::InvalidWif(_)
+            ))
+        ));
+    }
+
     #[test]
     fn test_wallet_save_load() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -301,6 +476,38 @@ mod tests {
         assert_eq!(wallet1.label, wallet2.label);
     }
 
+    #[test]
+    fn test_wallet_collections_are_isolated() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = WalletManager::new(temp_dir.path()).unwrap();
+
+        let savings = manager.load_collection("savings").unwrap();
+        let trading = manager.load_collection("trading").unwrap();
+        let saved = savings.create_wallet(Some("cold")).unwrap().address();
+        let traded = trading.create_wallet(None).unwrap().address();
+
+        assert_eq!(savings.list_wallets().unwrap(), vec![saved.clone()]);
+        assert_eq!(trading.list_wallets().unwrap(), vec![traded.clone()]);
+        assert!(manager.list_wallets().unwrap().is_empty());
+        assert!(trading.load_wallet(&saved).is_err());
+        assert!(savings.load_wallet(&traded).is_err());
+
+        // Reopening a collection finds its wallets again
+        let reopened = manager.load_collection("savings").unwrap();
+        assert_eq!(reopened.load_wallet(&saved).unwrap().label.unwrap(), "cold");
+        assert_eq!(
+            manager.list_collections().unwrap(),
+            vec!["savings", "trading"]
+        );
+
+        for bad in ["", "..", "a/b"] {
+            assert!(matches!(
+                manager.load_collection(bad),
+                Err(WalletError::InvalidCollectionName(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_transaction_creation() {
         let mut blockchain = crate::core::Blockchain::with_difficulty(4);
@@ -321,4 +528,99 @@ mod tests {
 
         assert!(tx.verify_signatures().unwrap());
     }
+
+    #[test]
+    fn test_create_transaction_with_selected_inputs() {
+        let mut blockchain = crate::core::Blockchain::with_difficulty(4);
+        blockchain.params.coinbase_maturity = 0;
+        let wallet = Wallet::new();
+        let recipient = Wallet::new().address();
+        blockchain.mine_block(vec![], &wallet.address()).unwrap();
+
+        // Split the (immature) reward into several non-coinbase outputs
+        let coinbase = wallet.utxos(&blockchain).remove(0);
+        let split = TransactionBuilder::new()
+            .add_input(&coinbase)
+            .add_output(&wallet.address(), 10)
+            .add_output(&wallet.address(), 15)
+            .add_output(&wallet.address(), 20)
+            .build_and_sign(&wallet.key_pair)
+            .unwrap();
+        blockchain
+            .mine_block(vec![split.clone()], &recipient)
+            .unwrap();
+
+        let pick = |index: u32| blockchain.find_utxo(&split.id, index).unwrap().clone();
+        let tx = wallet
+            .create_transaction_with_inputs(
+                vec![pick(0), pick(2)],
+                vec![(recipient.clone(), 25)],
+                1,
+                &blockchain,
+            )
+            .unwrap();
+
+        let spent: Vec<_> = tx
+            .inputs
+            .iter()
+            .map(|input| (input.tx_id.as_str(), input.output_index))
+            .collect();
+        assert_eq!(spent, vec![(split.id.as_str(), 0), (split.id.as_str(), 2)]);
+        assert_eq!(tx.outputs[0].amount, 25);
+        assert_eq!(tx.outputs[1].recipient, wallet.address());
+        assert_eq!(tx.outputs[1].amount, 4);
+        assert!(tx.verify_signatures().unwrap());
+
+        assert!(matches!(
+            wallet.create_transaction_with_inputs(
+                vec![pick(0)],
+                vec![(recipient.clone(), 10)],
+                1,
+                &blockchain
+            ),
+            Err(WalletError::InsufficientFunds { have: 10, need: 11 })
+        ));
+        // The same outpoint twice must not count its value twice
+        assert!(matches!(
+            wallet.create_transaction_with_inputs(
+                vec![pick(0), pick(0)],
+                vec![(recipient.clone(), 15)],
+                1,
+                &blockchain
+            ),
+            Err(WalletError::DuplicateInput(_))
+        ));
+        assert!(matches!(
+            wallet.create_transaction_with_inputs(vec![coinbase], vec![], 1, &blockchain),
+            Err(WalletError::UnknownInput(_))
+        ));
+        let reward = blockchain.get_utxos_for_address(&recipient).remove(0);
+        assert!(matches!(
+            wallet.create_transaction_with_inputs(vec![reward], vec![], 1, &blockchain),
+            Err(WalletError::NotOwned(_))
+        ));
+    }
+
+    #[test]
+    fn test_wallet_sign_message() {
+        let wallet = Wallet::new();
+        let other = Wallet::new();
+
+        let signature = wallet.sign_message("proof of ownership");
+        assert!(crate::crypto::verify_address_message(
+            &wallet.address(),
+            "proof of ownership",
+            &signature
+        ));
+        assert!(!crate::crypto::verify_address_message(
+            &other.address(),
+            "proof of ownership",
+            &signature
+        ));
+        assert!(!crate::crypto::verify_address_message(
+            &wallet.address(),
+            "proof of 0wnership",
+            &signature
+        ));
+    }
 }
@@ -8,7 +8,12 @@ export interface ChainInfo {
     total_blocks: number;
     total_transactions: number;
     total_coins: number;
+    total_coins_display: string;
+    coin_decimals: number;
     latest_hash: string;
+    best_peer_height: number;
+    verification_progress: number;
+    initial_block_download: boolean;
 }
 
 export interface BlockInfo {
@@ -20,6 +25,9 @@ export interface BlockInfo {
     difficulty: number;
     nonce: number;
     transactions: number;
+    size: number;
+    weight: number;
+    coinbase_message: string | null;
 }
 
 export interface WalletResponse {
@@ -31,8 +39,12 @@ export interface WalletResponse {
 export interface BalanceResponse {
     address: string;
     balance: number;
+    balance_display: string;
     spendable_balance: number;
+    confirmed_balance: number;
+    unconfirmed_balance: number;
     immature_balance: number;
+    min_confirmations: number;
     utxo_count: number;
 }
 
@@ -43,6 +55,11 @@ export interface MineResponse {
     attempts: number;
 }
 
+export interface MineBatchResponse {
+    blocks: MineResponse[];
+    total_reward: number;
+}
+
 export interface MempoolResponse {
     pending_transactions: number;
     transactions: TransactionResponse[];
@@ -54,6 +71,18 @@ export interface TransactionResponse {
     inputs: number;
     outputs: number;
     total_output: number;
+    size: number;
+    vsize: number;
+    coinbase_message: string | null;
+}
+
+export interface TransactionDetailResponse extends TransactionResponse {
+    block_height: number | null;
+    block_hash: string | null;
+    confirmations: number;
+    status: 'confirmed' | 'pending' | 'replaced';
+    replaced_by: string | null;
+    replaces: string[];
 }
 
 export interface ValidationResponse {
@@ -73,11 +102,64 @@ export async function getBlocks(): Promise<BlockInfo[]> {
     return res.json();
 }
 
+export async function getBlockRange(from: number, to: number): Promise<BlockInfo[]> {
+    const res = await fetch(`${API_BASE}/chain/blocks/range?from=${from}&to=${to}`);
+    return res.json();
+}
+
 export async function getBlock(height: number): Promise<BlockInfo> {
     const res = await fetch(`${API_BASE}/chain/blocks/${height}`);
     return res.json();
 }
 
+export interface SpentOutput {
+    outpoint: string;
+    amount: number;
+    recipient: string;
+}
+
+export interface UndoData {
+    height: number;
+    block_hash: string;
+    spent_outputs: SpentOutput[];
+    added_tx_ids: string[];
+}
+
+export async function getBlockUndo(height: number): Promise<UndoData> {
+    const res = await fetch(`${API_BASE}/chain/blocks/${height}/undo`);
+    return res.json();
+}
+
+export interface CoinbaseMaturity {
+    height: number;
+    txid: string;
+    reward: number;
+    is_mature: boolean;
+    blocks_until_mature: number;
+}
+
+export async function getBlockCoinbase(height: number): Promise<CoinbaseMaturity> {
+    const res = await fetch(`${API_BASE}/chain/blocks/${height}/coinbase`);
+    return res.json();
+}
+
+export interface BatchMerkleProof {
+    block_hash: string;
+    block_height: number;
+    tx_count: number;
+    hashes: string[];
+    flags: boolean[];
+}
+
+export async function getBlockProofs(height: number, txids: string[]): Promise<BatchMerkleProof> {
+    const res = await fetch(`${API_BASE}/chain/blocks/${height}/proofs`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ txids })
+    });
+    return res.json();
+}
+
 export async function validateChain(): Promise<ValidationResponse> {
     const res = await fetch(`${API_BASE}/chain/validate`);
     return res.json();
@@ -93,6 +175,15 @@ export async function mineBlock(address: string): Promise<MineResponse> {
     return res.json();
 }
 
+export async function mineBatch(address: string, count: number): Promise<MineBatchResponse> {
+    const res = await fetch(`${API_BASE}/mine/batch`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ count, miner_address: address })
+    });
+    return res.json();
+}
+
 // Wallet endpoints
 export async function getWallets(): Promise<WalletResponse[]> {
     const res = await fetch(`${API_BASE}/wallets`);
@@ -113,18 +204,107 @@ export async function getBalance(address: string): Promise<BalanceResponse> {
     return res.json();
 }
 
+export interface AddressHistoryEntry {
+    tx_id: string;
+    block_height: number;
+    direction: 'in' | 'out' | 'self';
+    received: number;
+    sent: number;
+    net_amount: number;
+}
+
+export interface AddressHistoryResponse {
+    address: string;
+    from: number;
+    to: number;
+    pruned: { from: number; to: number } | null;
+    transactions: AddressHistoryEntry[];
+}
+
+export async function getAddressHistory(address: string, from?: number, to?: number): Promise<AddressHistoryResponse> {
+    const params = new URLSearchParams();
+    if (from !== undefined) params.set('from', String(from));
+    if (to !== undefined) params.set('to', String(to));
+    const query = params.toString();
+    const res = await fetch(`${API_BASE}/address/${address}/history${query ? `?${query}` : ''}`);
+    return res.json();
+}
+
 // Transaction endpoints
-export async function getMempool(): Promise<MempoolResponse> {
-    const res = await fetch(`${API_BASE}/mempool`);
+export async function getMempool(minFeerate?: number, maxFeerate?: number): Promise<MempoolResponse> {
+    const params = new URLSearchParams();
+    if (minFeerate !== undefined) params.set('min_feerate', String(minFeerate));
+    if (maxFeerate !== undefined) params.set('max_feerate', String(maxFeerate));
+    const query = params.toString();
+    const res = await fetch(`${API_BASE}/mempool${query ? `?${query}` : ''}`);
     return res.json();
 }
 
-export async function getTransaction(id: string): Promise<TransactionResponse> {
+export interface MempoolEntryResponse {
+    tx_id: string;
+    fee: number;
+    fee_rate: number;
+    ancestor_fee_rate: number;
+    size: number;
+    vsize: number;
+    time_in_pool: number;
+    ancestor_count: number;
+    ancestor_size: number;
+    descendant_count: number;
+    descendant_size: number;
+    signals_rbf: boolean;
+    depends: string[];
+    spent_by: string[];
+}
+
+export async function getMempoolEntry(id: string): Promise<MempoolEntryResponse> {
+    const res = await fetch(`${API_BASE}/mempool/${id}`);
+    return res.json();
+}
+
+export interface MempoolPositionResponse {
+    tx_id: string;
+    position: number;
+    total: number;
+    fee_rate: number;
+    weight_ahead: number;
+    estimated_blocks: number;
+}
+
+export async function getMempoolPosition(id: string): Promise<MempoolPositionResponse> {
+    const res = await fetch(`${API_BASE}/mempool/${id}/position`);
+    return res.json();
+}
+
+export async function getTransaction(id: string): Promise<TransactionDetailResponse> {
     const res = await fetch(`${API_BASE}/transactions/${id}`);
     return res.json();
 }
 
+export interface TxPropagationResponse {
+    tx_id: string;
+    peers_with_tx: number;
+    peers_rejected: number;
+    connected_peers: number;
+    seconds_since_broadcast: number;
+}
+
+export async function getTxPropagation(id: string): Promise<TxPropagationResponse> {
+    const res = await fetch(`${API_BASE}/transactions/${id}/propagation`);
+    return res.json();
+}
+
 // Health check
+export interface HealthResponse {
+    status: 'ok' | 'syncing';
+    height: number;
+    best_hash: string;
+    peers: number;
+    is_syncing: boolean;
+    blocks_behind_best_peer: number;
+    mempool_size: number;
+}
+
 export async function healthCheck(): Promise<boolean> {
     try {
         const res = await fetch('/health');
@@ -134,6 +314,11 @@ export async function healthCheck(): Promise<boolean> {
     }
 }
 
+export async function getHealth(): Promise<HealthResponse> {
+    const res = await fetch('/health');
+    return res.json();
+}
+
 // Contract types
 export interface ContractInfo {
     address: string;
@@ -145,14 +330,24 @@ export interface ContractInfo {
 export interface DeployResponse {
     address: string;
     code_size: number;
+    init_gas_used: number;
 }
 
 export interface CallResponse {
     success: boolean;
     return_value: number | null;
     gas_used: number;
+    gas_refunded: number;
     gas_cost: number;
     caller_balance: number | null;
+    trace?: TraceStep[];
+}
+
+export interface TraceStep {
+    pc: number;
+    opcode: string;
+    gas_remaining: number;
+    stack: number[];
 }
 
 // Contract endpoints
@@ -161,11 +356,11 @@ export async function listContracts(): Promise<ContractInfo[]> {
     return res.json();
 }
 
-export async function deployContract(source: string): Promise<DeployResponse> {
+export async function deployContract(source: string, gasLimit?: number): Promise<DeployResponse> {
     const res = await fetch(`${API_BASE}/contracts`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ source })
+        body: JSON.stringify({ source, gas_limit: gasLimit })
     });
     if (!res.ok) {
         const error = await res.json();
@@ -186,6 +381,7 @@ export async function callContract(
         gasLimit?: number;
         gasPrice?: number;
         callerAddress?: string;
+        value?: number;
     }
 ): Promise<CallResponse> {
     const res = await fetch(`${API_BASE}/contracts/${address}/call`, {
@@ -195,7 +391,8 @@ export async function callContract(
             args,
             gas_limit: options?.gasLimit,
             gas_price: options?.gasPrice,
-            caller_address: options?.callerAddress
+            caller_address: options?.callerAddress,
+            value: options?.value
         })
     });
     if (!res.ok) {
@@ -369,6 +566,20 @@ export interface TokenBalanceResponse {
     balance: string;
 }
 
+export interface TokenHolderShare {
+    address: string;
+    balance: string;
+    percent: number;
+}
+
+export interface TokenDistribution {
+    token: string;
+    total_supply: string;
+    circulating_supply: string;
+    holder_count: number;
+    top_holders: TokenHolderShare[];
+}
+
 export interface TransferResponse {
     success: boolean;
     from: string;
@@ -534,6 +745,18 @@ export async function getTokenHistory(
     return res.json();
 }
 
+export async function getTokenDistribution(
+    tokenAddress: string,
+    limit = 10
+): Promise<TokenDistribution> {
+    const res = await fetch(`${API_BASE}/tokens/${tokenAddress}/distribution?limit=${limit}`);
+    if (!res.ok) {
+        const error = await res.json();
+        throw new Error(error.error || 'Distribution fetch failed');
+    }
+    return res.json();
+}
+
 export async function transferFromTokens(
     tokenAddress: string,
     spender: string,
@@ -580,6 +803,7 @@ export interface FeeEstimate {
     normal: number;
     low_priority: number;
     economy: number;
+    mempool_min_fee_rate: number;
     unit: string;
 }
 
@@ -596,6 +820,9 @@ export interface StorageStats {
     block_count: number;
     transaction_count: number;
     utxo_count: number;
+    utxo_growth_per_block: number;
+    max_utxo_set_size: number;
+    consolidation_discount_percent: number;
     difficulty: number;
     chain_work: string;
 }
@@ -607,6 +834,20 @@ export interface AdvancedStats {
     mempool_bytes: number;
 }
 
+export interface NetworkInfo {
+    protocol_version: number;
+    min_protocol_version: number;
+    services: number;
+    service_names: string[];
+    network_active: boolean;
+    listen_port: number | null;
+    connections: number;
+    connections_in: number;
+    connections_out: number;
+    upnp_active: boolean;
+    upnp_port: number | null;
+}
+
 // Fee estimation endpoint
 export async function getFeeEstimates(): Promise<FeeEstimate> {
     const res = await fetch(`${API_BASE}/fees`);
@@ -618,3 +859,47 @@ export async function getAdvancedStats(): Promise<AdvancedStats> {
     const res = await fetch(`${API_BASE}/stats`);
     return res.json();
 }
+
+// Network info endpoint
+export async function getNetworkInfo(): Promise<NetworkInfo> {
+    const res = await fetch(`${API_BASE}/network`);
+    return res.json();
+}
+
+export interface ReorgEvent {
+    time: number;
+    old_tip_hash: string;
+    old_height: number;
+    old_work: string;
+    new_tip_hash: string;
+    new_height: number;
+    new_work: string;
+    disconnected: number;
+    connected: number;
+    returned_txs: string[];
+}
+
+// Recent chain reorganizations, newest first
+export async function getReorgs(limit = 10): Promise<ReorgEvent[]> {
+    const res = await fetch(`${API_BASE}/chain/reorgs?limit=${limit}`);
+    return res.json();
+}
+
+export interface ReorgSimulateResponse {
+    old_tip_hash: string;
+    new_tip_hash: string;
+    new_height: number;
+    disconnected: number;
+    connected: number;
+    returned_txs: string[];
+}
+
+// Only available when the server runs with --dev-mode
+export async function simulateReorg(depth: number, minerAddress?: string): Promise<ReorgSimulateResponse> {
+    const res = await fetch(`${API_BASE}/chain/reorg-simulate`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ depth, miner_address: minerAddress }),
+    });
+    return res.json();
+}
@@ -34,6 +34,15 @@ export interface WsEventChainUpdated {
     };
 }
 
+export interface WsEventTxConfirmed {
+    type: 'TxConfirmed';
+    data: {
+        id: string;
+        block_height: number;
+        confirmations: number;
+    };
+}
+
 export interface WsEventConnected {
     type: 'Connected';
     data: {
@@ -45,7 +54,7 @@ export interface WsEventPing {
     type: 'Ping';
 }
 
-export type WsEvent = WsEventBlockMined | WsEventTransactionAdded | WsEventChainUpdated | WsEventConnected | WsEventPing;
+export type WsEvent = WsEventBlockMined | WsEventTransactionAdded | WsEventChainUpdated | WsEventTxConfirmed | WsEventConnected | WsEventPing;
 
 // Connection status
 export type ConnectionStatus = 'connecting' | 'connected' | 'disconnected';
@@ -103,6 +112,9 @@ function handleMessage(event: MessageEvent): void {
             case 'TransactionAdded':
                 console.log('📝 Transaction added:', data.data.transaction.id);
                 break;
+            case 'TxConfirmed':
+                console.log('✔️ Transaction confirmed:', data.data.id, data.data.confirmations);
+                break;
             case 'Ping':
                 // Heartbeat, no action needed
                 break;
@@ -187,6 +199,15 @@ export function disconnectWebSocket(): void {
     reconnectAttempts = 0;
 }
 
+/**
+ * Ask for TxConfirmed updates for a transaction
+ */
+export function watchTransaction(txid: string): void {
+    if (ws && ws.readyState === WebSocket.OPEN) {
+        ws.send(JSON.stringify({ watch_tx: txid }));
+    }
+}
+
 /**
  * Check if WebSocket is connected
  */